@@ -0,0 +1,66 @@
+//! Time to load a large synthetic allocations.json. Compare the default parser with the
+//! `fast-json` one:
+//!
+//! ```bash
+//! cargo bench --bench load_json
+//! cargo bench --bench load_json --features fast-json
+//! ```
+
+use snapviewer::constants::ALLOCATIONS_FILE_NAME;
+use snapviewer::load::read_allocations;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+const ALLOCATIONS: usize = 200_000;
+const RUNS: usize = 5;
+
+/// Allocations freed in order, each moving down a few times before that
+fn synthetic_json() -> String {
+    let mut json = String::from("[");
+    for i in 0..ALLOCATIONS as u64 {
+        let timesteps: Vec<String> = (0..12).map(|k| (i * 4 + k * 3).to_string()).collect();
+        let offsets: Vec<String> = (0..12).map(|k| (i * 512 - k * 16).to_string()).collect();
+        if i > 0 {
+            json.push(',');
+        }
+        write!(
+            json,
+            r#"{{"timesteps":[{}],"offsets":[{}],"size":{},"category":"activation"}}"#,
+            timesteps.join(","),
+            offsets.join(","),
+            512 + i % 4096
+        )
+        .unwrap();
+    }
+    json.push(']');
+    json
+}
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("snapviewer-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let json = synthetic_json();
+    std::fs::write(dir.join(ALLOCATIONS_FILE_NAME), &json).unwrap();
+
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let allocs = read_allocations(&dir).unwrap();
+        best = best.min(start.elapsed());
+        assert_eq!(allocs.len(), ALLOCATIONS);
+    }
+    let parser = if cfg!(feature = "fast-json") {
+        "fast-json"
+    } else {
+        "default"
+    };
+    println!(
+        "{} parser: {} MiB in {:.3} s (best of {}), {:.0} MiB/s",
+        parser,
+        json.len() >> 20,
+        best.as_secs_f64(),
+        RUNS,
+        json.len() as f64 / (1 << 20) as f64 / best.as_secs_f64()
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
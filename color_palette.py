@@ -41,3 +41,6 @@ NIGHT = ColorPalette(
     select_fg="#121212",
     entry_bg="#3a3a3a",
 )
+
+# Theme name -> palette, in the order Ctrl+T cycles through them
+PALETTES = {"cute": CUTE, "default": DEFAULT, "night": NIGHT}
@@ -10,75 +10,731 @@ Features:
 """
 
 import ctypes
+import functools
+import json
 import os
 import platform
+import shutil
 import subprocess
 import sys
 import threading
 import time
 import tkinter as tk
+import zipfile
+from collections import deque
 from ctypes import wintypes
 from datetime import datetime
 from pathlib import Path
 from tkinter import font, messagebox, scrolledtext, ttk
+from urllib.parse import parse_qs, parse_qsl, urlencode, urlparse
+from urllib.error import HTTPError
+from urllib.request import Request, urlopen
 
 import zmq
 from blake3 import blake3 as blake3_hasher
 
-from color_palette import CUTE, DEFAULT, NIGHT, ColorPalette
-from convert_snap import convert_pickle_to_dir
+from color_palette import CUTE, PALETTES, ColorPalette
+from i18n import SUPPORTED_LANGS, set_language, tr
+
+# Process exit codes, shared with the renderer (SnapError in src/error.rs)
+EXIT_BAD_INPUT = 2
+EXIT_IPC = 5
+
+# Files `snapviewer-renderer convert` writes
+ALLOCATIONS_FILE_NAME = "allocations.json"
+ALLOCATIONS_BIN_FILE_NAME = "allocations.bin"
+ALLOCATIONS_ZST_FILE_NAME = "allocations.json.zst"
+ALLOCATIONS_SHARDS_FILE_NAME = "allocations_shards.json"
+DATABASE_FILE_NAME = "elements.db"
+METADATA_FILE_NAME = "metadata.json"
+MANIFEST_FILE_NAME = "convert_manifest.json"
+
+
+CACHE_ROOT = Path.home() / ".snapviewer_cache"
+WINDOW_STATE_FILE = CACHE_ROOT / "window_state.json"
+PREFETCH_LOG_FILE = CACHE_ROOT / "prefetch.log"
+# full-file hashes of pickles by path, reused while their size and mtime are unchanged
+FILE_HASHES_FILE = CACHE_ROOT / "file_hashes.json"
+DOWNLOAD_DIR = CACHE_ROOT / "downloads"
+# next to a download, the ETag and Last-Modified it was served with, to revalidate it
+VALIDATORS_SUFFIX = ".validators.json"
+# Query parameters presigned URLs (S3, GCS, CloudFront, Azure SAS) change on every signing of the same object,
+# left out of download keys; compared lowercased, SIGNING_QUERY_PREFIXES match any parameter they start
+SIGNING_QUERY_PARAMS = {
+    "signature", "expires", "awsaccesskeyid", "googleaccessid", "policy", "key-pair-id",
+    "sig", "se", "st", "sp", "sv", "sr", "spr", "skoid", "sktid", "skt", "ske", "sks", "skv",
+}
+SIGNING_QUERY_PREFIXES = ("x-amz-", "x-goog-")
+MERGED_DIR = CACHE_ROOT / "merged"
+BUNDLE_DIR = CACHE_ROOT / "bundles"
+DEFAULT_GUI_GEOMETRY = "1600x1200"
+DEFAULT_RESOLUTION = (2400, 1000)
+DEFAULT_REPL_MAX_LINES = 5000
+# how often --watch checks whether the pickle was rewritten
+WATCH_POLL_S = 1.0
+CONTENT_HASH_FILE = "content.blake3"
+# which --cache-hash strategy keyed a cache entry, and the key
+CACHE_HASH_FILE = "cache_hash.json"
+CACHE_HASH_STRATEGIES = ["full", "fast", "size-mtime"]
+# bytes of the pickle hashed by --cache-hash fast
+FAST_HASH_BYTES = 128 * 1024 * 1024
+CACHE_OUTPUT_FILES = [ALLOCATIONS_FILE_NAME, ALLOCATIONS_BIN_FILE_NAME, DATABASE_FILE_NAME, METADATA_FILE_NAME]
+# outputs the renderer never writes after converting, so cache entries can share them as hard links;
+# elements.db is copied instead, the REPL's SQL could otherwise change every entry linked to it
+SHAREABLE_OUTPUT_FILES = [ALLOCATIONS_FILE_NAME, ALLOCATIONS_BIN_FILE_NAME, METADATA_FILE_NAME]
+LINK_SCHEME = "snapviewer"
+# a converted directory holds one of these, see the renderer's src/input.rs
+CONVERTED_FILES = [
+    DATABASE_FILE_NAME,
+    ALLOCATIONS_BIN_FILE_NAME,
+    ALLOCATIONS_FILE_NAME,
+    ALLOCATIONS_ZST_FILE_NAME,
+    ALLOCATIONS_SHARDS_FILE_NAME,
+]
 
-VERSION = "0"
 
+def compute_file_hash(path: str) -> str:
+    """
+    Hash of the whole file. Hashing a multi-GB pickle takes a while, so the hash is remembered in
+    FILE_HASHES_FILE and reused while the file's size and mtime stay the same.
+    """
+    stat = os.stat(path)
+    key = os.path.abspath(path)
+    try:
+        hashes = json.loads(FILE_HASHES_FILE.read_text())
+    except (OSError, ValueError):
+        hashes = {}
+    entry = hashes.get(key)
+    if entry and entry["size"] == stat.st_size and entry["mtime_ns"] == stat.st_mtime_ns:
+        return entry["hash"]
 
-_HASH_CAP = 128 * 1024 * 1024  # 128 MB
+    h = blake3_hasher(max_threads=blake3_hasher.AUTO)
+    h.update_mmap(path)
+    hashes[key] = {"size": stat.st_size, "mtime_ns": stat.st_mtime_ns, "hash": h.hexdigest()}
+    try:
+        CACHE_ROOT.mkdir(parents=True, exist_ok=True)
+        FILE_HASHES_FILE.write_text(json.dumps(hashes))
+    except OSError as e:
+        print(f"Could not remember the hash of {path}: {e}")
+    return hashes[key]["hash"]
 
 
-def compute_file_hash(path: str) -> str:
+def compute_cache_hash(path: str, strategy: str) -> str:
+    """
+    Cache key of the pickle at path, by --cache-hash strategy: "full" is compute_file_hash, "fast" hashes
+    only the first FAST_HASH_BYTES and the file size (pickles differing only after that share a key),
+    "size-mtime" hashes the path, size and modification time without reading the file (a copy converts
+    again). Keys of the other strategies are prefixed with the strategy, so they never match a full hash.
+    """
+    if strategy == "full":
+        return compute_file_hash(path)
+    stat = os.stat(path)
+    h = blake3_hasher()
+    if strategy == "fast":
+        with open(path, "rb") as f:
+            remaining = FAST_HASH_BYTES
+            while remaining > 0 and (chunk := f.read(min(remaining, 8 * 1024 * 1024))):
+                h.update(chunk)
+                remaining -= len(chunk)
+        h.update(str(stat.st_size).encode())
+    else:
+        h.update(f"{os.path.abspath(path)}\0{stat.st_size}\0{stat.st_mtime_ns}".encode())
+    return f"{strategy}-{h.hexdigest()}"
+
+
+@functools.cache
+def converter_version(renderer_binary: str) -> str:
+    """
+    Version of the renderer's converter output ("11"), part of the cache key so conversions by another
+    version are not picked up. It is also recorded in each conversion's metadata.json.
+    """
+    result = subprocess.run([renderer_binary, "--version"], capture_output=True, text=True)
+    if result.returncode != 0 or not result.stdout.split():
+        print(f"Error: could not get the version of {renderer_binary}")
+        sys.exit(result.returncode or 1)
+    return result.stdout.split()[-1]
+
+
+def compute_content_hash(cache_dir: Path) -> str:
+    """Hash of the converted outputs, unlike compute_file_hash which hashes the pickle"""
     h = blake3_hasher(max_threads=blake3_hasher.AUTO)
-    with open(path, "rb") as f:
-        h.update(f.read(_HASH_CAP))
+    for name in CACHE_OUTPUT_FILES:
+        path = cache_dir / name
+        if path.exists():
+            h.update(name.encode())
+            h.update_mmap(path)
     return h.hexdigest()
 
 
-def get_or_create_cache(pickle_path: str, device_id: int) -> str:
+def link_shareable_outputs(src_dir: Path, dst_dir: Path, replace: bool):
+    """
+    Hard link the SHAREABLE_OUTPUT_FILES of src_dir into dst_dir, replacing dst_dir's own copies if
+    replace, otherwise only where dst_dir has none. Falls back to copying without hard link support.
+    """
+    for name in SHAREABLE_OUTPUT_FILES:
+        src, dst = src_dir / name, dst_dir / name
+        if not src.exists() or (dst.exists() and (not replace or os.path.samefile(src, dst))):
+            continue
+        tmp = dst.with_name(dst.name + ".link")
+        try:
+            os.link(src, tmp)
+        except OSError:
+            shutil.copy2(src, tmp)
+        os.replace(tmp, dst)
+
 
-    cache_root = Path.home() / ".snapviewer_cache"
-    file_hash = compute_file_hash(pickle_path)
-    cache_key = f"{file_hash}_dev{device_id}_v{VERSION}"
+def reuse_duplicate_conversion(cache_dir: Path, source_hash: str) -> bool:
+    """
+    Before converting: if another cache entry of the same device and converter version was converted from
+    a pickle with the same content (keyed differently by --cache-hash fast or size-mtime, e.g. a copy at
+    another path), fill cache_dir from it instead of converting again.
+    """
+    device_and_version = cache_dir.name.split("_", 1)[1]
+    for other in CACHE_ROOT.glob(f"*_{device_and_version}"):
+        if other == cache_dir or not conversion_complete(other):
+            continue
+        try:
+            if json.loads((other / CACHE_HASH_FILE).read_text()).get("source") != source_hash:
+                continue
+        except (OSError, ValueError):
+            continue
+        try:
+            link_shareable_outputs(other, cache_dir, replace=True)
+            for name in [DATABASE_FILE_NAME, CONTENT_HASH_FILE, MANIFEST_FILE_NAME]:
+                if (other / name).exists():
+                    shutil.copy2(other / name, cache_dir / name)
+        except OSError as e:
+            print(f"Could not reuse duplicate cache entry {other.name}, converting: {e}")
+            return False
+        print(f"Pickle is identical to the one of cache entry {other.name}, reusing its conversion")
+        return True
+    return False
+
+
+def share_duplicate_cache(cache_dir: Path):
+    """
+    After converting: if another cache entry holds identical converted content (e.g. the same snapshot
+    pickled twice, so the pickles differ), replace our shareable outputs with hard links to its files
+    instead of keeping another copy.
+    """
+    content_hash = compute_content_hash(cache_dir)
+    (cache_dir / CONTENT_HASH_FILE).write_text(content_hash)
+
+    for other in CACHE_ROOT.iterdir():
+        hash_file = other / CONTENT_HASH_FILE
+        if other == cache_dir or not hash_file.exists():
+            continue
+        if hash_file.read_text().strip() != content_hash:
+            continue
+        try:
+            link_shareable_outputs(other, cache_dir, replace=True)
+            print(f"Converted content is identical to cache entry {other.name}, sharing its files")
+        except OSError as e:
+            print(f"Could not share files with duplicate cache entry {other.name}: {e}")
+        return
+
+
+def prefetch_devices(renderer_binary: str, pickle_path: str, output_template: str):
+    """
+    Convert the pickle's other devices into the cache from a background thread, so opening them later
+    is a cache hit. The converter runs as its own process, keeping the GUI responsive, and finishes
+    even if the GUI is closed first. Its output goes to PREFETCH_LOG_FILE.
+    """
+    cmd = [renderer_binary, "convert", "-i", pickle_path, "-o", output_template, "--device", "all", "--binary"]
+
+    def run():
+        with open(PREFETCH_LOG_FILE, "w", encoding="utf-8") as log:
+            subprocess.run(cmd, stdout=log, stderr=subprocess.STDOUT)
+
+    threading.Thread(target=run, daemon=True).start()
+    print(f"Prefetching other devices in the background, progress in {PREFETCH_LOG_FILE}")
+
+
+def convert_other_devices(renderer_binary: str, pickle_path: str, cache_dir: str) -> list[str]:
+    """
+    Convert the pickle's other devices into the cache next to cache_dir (the device converted first),
+    for the renderer's --devices. Devices converted before are skipped. Returns their cache directories.
+    """
+    cache_dir = Path(cache_dir)
+    file_hash = cache_dir.name.split("_dev", 1)[0]
+    version = converter_version(renderer_binary)
+    template = str(cache_dir.parent / f"{file_hash}_dev{{device}}_v{version}")
+    cmd = [renderer_binary, "convert", "-i", pickle_path, "-o", template, "--device", "all", "--binary"]
+    result = subprocess.run(cmd)
+    if result.returncode != 0:
+        print(f"Error: converting the other devices of {pickle_path} failed")
+        sys.exit(result.returncode)
+    return sorted(
+        str(other)
+        for other in cache_dir.parent.glob(f"{file_hash}_dev*_v{version}")
+        if other != cache_dir and (other / DATABASE_FILE_NAME).exists()
+    )
+
+
+def is_url(path: str) -> bool:
+    return urlparse(path).scheme in ("http", "https")
+
+
+def download_key(url: str) -> str:
+    """What identifies the object url downloads: the URL without its SIGNING_QUERY_PARAMS, so `?file=run1` and
+    `?file=run2` are different objects while re-signed (e.g. S3 presigned) URLs of one object are the same"""
+    parsed = urlparse(url)
+    query = [
+        (name, value)
+        for name, value in parse_qsl(parsed.query, keep_blank_values=True)
+        if name.lower() not in SIGNING_QUERY_PARAMS and not name.lower().startswith(SIGNING_QUERY_PREFIXES)
+    ]
+    return f"{parsed.netloc}{parsed.path}?{urlencode(sorted(query))}"
+
+
+def download(url: str) -> Path:
+    """
+    Download url into DOWNLOAD_DIR, printing progress, and return the file. Files are keyed by download_key,
+    so a re-signed (e.g. S3 presigned) URL of the same object is not downloaded again.
+    An earlier download is revalidated with a conditional GET against the ETag and Last-Modified it was
+    served with (kept in a VALIDATORS_SUFFIX sidecar), or its size if the server sent neither, and kept
+    if the server is unreachable.
+    """
+    parsed = urlparse(url)
+    key = blake3_hasher(download_key(url).encode()).hexdigest()[:16]
+    path = DOWNLOAD_DIR / f"{key}_{os.path.basename(parsed.path) or 'snapshot'}"
+    validators_file = path.with_name(path.name + VALIDATORS_SUFFIX)
+    try:
+        validators = json.loads(validators_file.read_text()) if path.exists() else {}
+    except (OSError, ValueError):
+        validators = {}
+    request = Request(url)
+    if "etag" in validators:
+        request.add_header("If-None-Match", validators["etag"])
+    if "last_modified" in validators:
+        request.add_header("If-Modified-Since", validators["last_modified"])
+
+    DOWNLOAD_DIR.mkdir(parents=True, exist_ok=True)
+    part = path.with_name(path.name + ".part")
+    try:
+        with urlopen(request) as response:
+            total = int(response.headers.get("Content-Length") or 0)
+            etag, last_modified = response.headers.get("ETag"), response.headers.get("Last-Modified")
+            unvalidated = path.exists() and not etag and not last_modified
+            if unvalidated and total == path.stat().st_size:
+                print(f"Already downloaded (same size): {path}")
+                return path
+            if path.exists():
+                print(f"Changed since it was downloaded: {path}")
+            print(f"Downloading {parsed.scheme}://{parsed.netloc}{parsed.path}")
+            with open(part, "wb") as f:
+                done = 0
+                while chunk := response.read(1 << 20):
+                    f.write(chunk)
+                    done += len(chunk)
+                    progress = f" / {format_bytes(total)} ({done * 100 // total}%)" if total else ""
+                    print(f"\r- {format_bytes(done)}{progress}", end="", flush=True)
+        print()
+    except HTTPError as e:
+        part.unlink(missing_ok=True)
+        if e.code == 304:
+            print(f"Already downloaded, unchanged: {path}")
+            return path
+        print(f"\nError: downloading {url} failed: {e}")
+        exit(EXIT_BAD_INPUT)
+    except OSError as e:
+        # urllib's URLError is an OSError
+        part.unlink(missing_ok=True)
+        if path.exists():
+            print(f"Could not check {url} for changes ({e}), using the earlier download: {path}")
+            return path
+        print(f"\nError: downloading {url} failed: {e}")
+        exit(EXIT_BAD_INPUT)
+    os.replace(part, path)
+    validators = {"etag": etag, "last_modified": last_modified}
+    validators_file.write_text(json.dumps({k: v for k, v in validators.items() if v}))
+    return path
+
+
+def download_converted_dir(url: str) -> str:
+    """--dir with a URL: the URL points to a zip of a converted directory, which is extracted next to it"""
+    archive = download(url)
+    target = archive.with_name(archive.name + ".d")
+    # a revalidated download that changed is newer than what was extracted from it
+    if target.exists() and target.stat().st_mtime < archive.stat().st_mtime:
+        shutil.rmtree(target)
+    return extract_converted_dir(archive, target, url)
+
+
+def is_bundle(path: str) -> bool:
+    """Whether path is a zip of a converted directory, e.g. written by `--export-bundle`, rather than of a pickle"""
+    if not os.path.isfile(path) or not zipfile.is_zipfile(path):
+        return False
+    with zipfile.ZipFile(path) as z:
+        return any(os.path.basename(name) in CONVERTED_FILES for name in z.namelist())
+
+
+def extract_converted_dir(archive: Path, target: Path, source: str) -> str:
+    """Extract a zip of a converted directory into target, unless done before; returns the directory holding its files"""
+    if not target.exists():
+        if not zipfile.is_zipfile(archive):
+            print(f"Error: {source} is not a zip of a converted directory.")
+            exit(EXIT_BAD_INPUT)
+        with zipfile.ZipFile(archive) as z:
+            z.extractall(target.with_name(target.name + ".part"))
+        os.replace(target.with_name(target.name + ".part"), target)
+    # the files may sit in a directory inside the archive
+    for root, _, files in os.walk(target):
+        if any(name in files for name in CONVERTED_FILES):
+            return root
+    print(f"Error: {source} holds no converted snapshot (none of {', '.join(CONVERTED_FILES)}).")
+    shutil.rmtree(target)
+    exit(EXIT_BAD_INPUT)
+
+
+def conversion_complete(cache_dir: Path) -> bool:
+    """Whether `convert` finished writing cache_dir; running it again resumes an interrupted one"""
+    try:
+        completed = json.loads((cache_dir / MANIFEST_FILE_NAME).read_text())["completed"]
+    except (OSError, ValueError, KeyError):
+        # converted before manifests were written
+        return (cache_dir / DATABASE_FILE_NAME).exists()
+    return all(name in completed for name in [METADATA_FILE_NAME, DATABASE_FILE_NAME, ALLOCATIONS_BIN_FILE_NAME])
+
+
+def remove_stale_caches(cache_root: Path, file_hash: str, version: str):
+    """Delete the pickle's cache entries converted by other converter versions, they are never used again"""
+    for stale in cache_root.glob(f"{file_hash}_dev*_v*"):
+        if stale.is_dir() and not stale.name.endswith(f"_v{version}"):
+            print(f"Removing stale cache entry {stale.name}")
+            shutil.rmtree(stale, ignore_errors=True)
+
+
+def merged_cache_dir(
+    renderer_binary: str, paths: list[str], device_id: int, timings: dict, cache_hash: str = "full"
+) -> tuple[list[str], str]:
+    """
+    Converted directories of the ranks in paths (pickles are converted into the cache, directories used
+    as they are), and the cache directory the renderer merges them into for --merge-ranks.
+    """
+    dirs = []
+    for path in paths:
+        if os.path.isdir(path):
+            dirs.append(os.path.abspath(path))
+        elif os.path.exists(path):
+            dirs.append(get_or_create_cache(renderer_binary, path, device_id, timings, cache_hash=cache_hash))
+        else:
+            print(f"Error: rank snapshot '{path}' does not exist.")
+            exit(EXIT_BAD_INPUT)
+    key = blake3_hasher("\0".join(dirs).encode()).hexdigest()[:16]
+    merged = MERGED_DIR / key
+    merged.mkdir(parents=True, exist_ok=True)
+    return dirs, str(merged)
+
+
+# resolved cache entries converted by this process, the only ones it deletes once a watch reload replaces them:
+# entries it found may be open in another GUI, or the source of another entry's reused conversion
+CREATED_CACHE_ENTRIES: set[Path] = set()
+
+
+def get_or_create_cache(
+    renderer_binary: str,
+    pickle_path: str,
+    device_id: int,
+    timings: dict | None = None,
+    prefetch: bool = False,
+    cache_hash: str = "full",
+) -> str:
+    """
+    The pickle is converted by the renderer's `convert` subcommand.
+    timings, if given, receives the seconds spent in each stage for --self-profile.
+    With prefetch, the pickle's other devices are converted in the background afterwards.
+    cache_hash is the --cache-hash strategy keying the cache entry, see compute_cache_hash.
+    """
+    timings = {} if timings is None else timings
+
+    cache_root = CACHE_ROOT
+    start = time.perf_counter()
+    file_hash = compute_cache_hash(pickle_path, cache_hash)
+    timings["hash"] = time.perf_counter() - start
+    version = converter_version(renderer_binary)
+    cache_key = f"{file_hash}_dev{device_id}_v{version}"
     cache_dir = cache_root / cache_key
-    alloc_file = cache_dir / "allocations.json"
-    db_file = cache_dir / "elements.db"
-    if alloc_file.exists() and db_file.exists():
+    remove_stale_caches(cache_root, file_hash, version)
+    if prefetch:
+        # started after this device is converted, both would compete for the CPU otherwise
+        prefetch_template = str(cache_root / f"{file_hash}_dev{{device}}_v{version}")
+    if conversion_complete(cache_dir) and load_metadata(str(cache_dir)).get("converter_version") != version:
+        # e.g. a renderer built from a newer checkout without bumping the version
+        print(f"Cache entry {cache_key} was written by another converter, converting again")
+        shutil.rmtree(cache_dir)
+    if conversion_complete(cache_dir):
         print("Cache hit:")
-        print(f"- version: {VERSION}")
+        print(f"- version: {version}")
         print(f"- path:    {cache_dir}")
+        if prefetch:
+            prefetch_devices(renderer_binary, pickle_path, prefetch_template)
         return str(cache_dir)
-    print(f"Cache miss, converting pickle: {pickle_path}")
     cache_dir.mkdir(parents=True, exist_ok=True)
-    convert_pickle_to_dir(pickle_path, str(cache_dir), device_id)
+    CREATED_CACHE_ENTRIES.add(cache_dir.resolve())
+    # converting reads the whole pickle anyway, so hashing all of it is cheap next to that
+    start = time.perf_counter()
+    source_hash = file_hash if cache_hash == "full" else compute_file_hash(pickle_path)
+    timings["hash"] += time.perf_counter() - start
+    (cache_dir / CACHE_HASH_FILE).write_text(
+        json.dumps({"strategy": cache_hash, "key": file_hash, "source": source_hash})
+    )
+    if reuse_duplicate_conversion(cache_dir, source_hash):
+        if prefetch:
+            prefetch_devices(renderer_binary, pickle_path, prefetch_template)
+        return str(cache_dir)
+    print(f"Cache miss, converting pickle: {pickle_path}")
+    start = time.perf_counter()
+    # allocations.bin loads faster than elements.db's timeline table when reopening the cache
+    cmd = [renderer_binary, "convert", "-i", pickle_path, "-o", str(cache_dir), "--device", str(device_id), "--binary"]
+    result = subprocess.run(cmd)
+    if result.returncode != 0:
+        print(f"Error: converting {pickle_path} failed")
+        # the renderer's exit code tells bad input from a failed conversion
+        sys.exit(result.returncode)
+    timings["convert"] = time.perf_counter() - start
+    start = time.perf_counter()
+    share_duplicate_cache(cache_dir)
+    timings["dedup cache"] = time.perf_counter() - start
+    if prefetch:
+        prefetch_devices(renderer_binary, pickle_path, prefetch_template)
     return str(cache_dir)
 
 
+def write_gui_profile(path: str, timings: dict):
+    """
+    Start the --self-profile summary with the GUI's stages,
+    the renderer keeps them at the top and appends its own.
+    """
+    lines = ["== GUI startup stages =="]
+    lines += [f"{name:<24} {secs:>10.3} s" for name, secs in timings.items()]
+    with open(path, "w", encoding="utf-8") as f:
+        f.write("\n".join(lines) + "\n")
+
+
+def format_bytes(num: float) -> str:
+    for unit in ["", "Ki", "Mi", "Gi", "Ti", "Pi"]:
+        if abs(num) < 1024.0:
+            return f"{num:.2f} {unit}B"
+        num /= 1024.0
+    return f"{num:.2f} EiB"
+
+
+def load_metadata(snapshot_dir: str) -> dict:
+    """Snapshot metadata written by the converter, empty for directories converted without it"""
+    try:
+        with open(os.path.join(snapshot_dir, METADATA_FILE_NAME), "r", encoding="utf-8") as f:
+            return json.load(f)
+    except (OSError, ValueError):
+        return {}
+
+
+def metadata_summary(metadata: dict) -> str:
+    """One-line, self-describing header for screenshots"""
+    parts = []
+    if "torch_version" in metadata:
+        parts.append(f"PyTorch {metadata['torch_version']}")
+    if "device_name" in metadata:
+        parts.append(f"{metadata['device_name']} (device {metadata['device']})")
+    elif "device" in metadata:
+        parts.append(tr("meta-device", device=metadata["device"]))
+    if "total_capacity" in metadata:
+        parts.append(tr("meta-capacity", size=format_bytes(metadata["total_capacity"])))
+    if "reserved_bytes" in metadata:
+        parts.append(tr("meta-reserved", size=format_bytes(metadata["reserved_bytes"])))
+    if "capture_time" in metadata:
+        parts.append(tr("meta-captured", time=metadata["capture_time"]))
+    return "  |  ".join(parts)
+
+
+# First part of the renderer's two-part messages carrying region stats as JSON, see src/region.rs
+REGION_STATS_EVENT = b"region_stats"
+# First part of the renderer's two-part messages carrying loading progress, see src/load_progress.rs
+LOAD_PROGRESS_EVENT = b"load_progress"
+# First part of the renderer's two-part messages carrying its window size as "WIDTH HEIGHT", see src/constants.rs
+WINDOW_SIZE_EVENT = b"window_size"
+# First part of the renderer's two-part messages carrying the directory it reloaded, see src/watch.rs
+RELOADED_EVENT = b"reloaded"
+
+
+def region_summary(stats: dict) -> str:
+    """Message panel text for the stats of a region dragged with Shift in the renderer"""
+    t0, t1 = stats["time_range"]
+    m0, m1 = stats["memory_range"]
+    lines = [
+        tr("region-title", t0=str(t0), t1=str(t1), m0=format_bytes(m0), m1=format_bytes(m1)),
+        tr("region-total", count=stats["count"], size=format_bytes(stats["bytes"])),
+    ]
+    for entry in stats["callstacks"]:
+        lines += ["", tr("region-callstack", count=entry["count"], size=format_bytes(entry["bytes"]))]
+        lines.append(entry["callstack"] or tr("region-no-callstack"))
+    return "\n".join(lines)
+
+
+def monitor_key() -> str:
+    """Identify the current monitor configuration by its screen size"""
+    probe = tk.Tk()
+    probe.withdraw()
+    key = f"{probe.winfo_screenwidth()}x{probe.winfo_screenheight()}"
+    probe.destroy()
+    return key
+
+
+def load_window_state(key: str) -> dict:
+    """Saved window sizes/positions for a monitor configuration, empty if none"""
+    try:
+        with open(WINDOW_STATE_FILE, "r", encoding="utf-8") as f:
+            return json.load(f).get(key, {})
+    except (OSError, ValueError):
+        return {}
+
+
+def save_window_state(key: str, state: dict):
+    try:
+        with open(WINDOW_STATE_FILE, "r", encoding="utf-8") as f:
+            all_states = json.load(f)
+    except (OSError, ValueError):
+        all_states = {}
+    all_states[key] = state
+    try:
+        CACHE_ROOT.mkdir(parents=True, exist_ok=True)
+        with open(WINDOW_STATE_FILE, "w", encoding="utf-8") as f:
+            json.dump(all_states, f, indent=2)
+    except OSError as e:
+        print(f"Could not save window state: {e}")
+
+
+def resolve_input_path(args):
+    """
+    Sort the positional path into --link, --dir or --pickle, like the renderer's `detect_input`:
+    a snapviewer:// link, a converted directory (or a file in one, e.g. its elements.db),
+    or anything else to convert (pickles, zip archives, sample CSVs).
+    """
+    path = args.path
+    if path.startswith(f"{LINK_SCHEME}://"):
+        args.link = path
+    elif is_url(path):
+        # downloaded, then converted like a local pickle
+        args.pickle = path
+    elif os.path.isdir(path):
+        if not any(os.path.exists(os.path.join(path, name)) for name in CONVERTED_FILES):
+            print(f"Error: '{path}' holds no converted snapshot (none of {', '.join(CONVERTED_FILES)}).")
+            exit(EXIT_BAD_INPUT)
+        args.dir = path
+    elif os.path.basename(path) in CONVERTED_FILES:
+        args.dir = os.path.dirname(path) or "."
+    else:
+        args.pickle = path
+
+
+def parse_link(url: str) -> tuple[str, dict]:
+    """
+    Split a `snapviewer://<cache_key>?idx=123&t0=..&t1=..` link into the cache key
+    and its integer parameters (all optional).
+    """
+    parsed = urlparse(url)
+    if parsed.scheme != LINK_SCHEME or not parsed.netloc:
+        raise ValueError(f"not a {LINK_SCHEME}://<cache_key> link: {url}")
+    params = {}
+    for name, values in parse_qs(parsed.query).items():
+        if name in ("idx", "t0", "t1"):
+            params[name] = int(values[-1])
+    return parsed.netloc, params
+
+
+def make_link(snapshot_dir: str, view_query: str) -> str | None:
+    """Link to the current view, None if the snapshot is not in the cache (links refer to cache keys)"""
+    path = Path(snapshot_dir).resolve()
+    if path.parent != CACHE_ROOT.resolve():
+        return None
+    return f"{LINK_SCHEME}://{path.name}?{view_query}"
+
+
+def register_link_handler():
+    """Make the OS open snapviewer:// links with this script"""
+    command = [sys.executable, str(Path(__file__).resolve()), "--link"]
+    system = platform.system()
+    if system == "Windows":
+        import winreg
+
+        with winreg.CreateKey(winreg.HKEY_CURRENT_USER, rf"Software\Classes\{LINK_SCHEME}") as key:
+            winreg.SetValueEx(key, "", 0, winreg.REG_SZ, "URL:SnapViewer link")
+            winreg.SetValueEx(key, "URL Protocol", 0, winreg.REG_SZ, "")
+        with winreg.CreateKey(
+            winreg.HKEY_CURRENT_USER, rf"Software\Classes\{LINK_SCHEME}\shell\open\command"
+        ) as key:
+            winreg.SetValueEx(key, "", 0, winreg.REG_SZ, " ".join(f'"{c}"' for c in command) + ' "%1"')
+    elif system == "Linux":
+        desktop_file = Path.home() / ".local" / "share" / "applications" / "snapviewer-link.desktop"
+        desktop_file.parent.mkdir(parents=True, exist_ok=True)
+        desktop_file.write_text(
+            "[Desktop Entry]\n"
+            "Type=Application\n"
+            "Name=SnapViewer\n"
+            f"Exec={' '.join(command)} %u\n"
+            "NoDisplay=true\n"
+            f"MimeType=x-scheme-handler/{LINK_SCHEME};\n"
+        )
+        subprocess.run(["xdg-mime", "default", desktop_file.name, f"x-scheme-handler/{LINK_SCHEME}"], check=True)
+    else:
+        # macOS only dispatches URL schemes to application bundles
+        print(f"Registering a {LINK_SCHEME}:// handler is not supported on {system}, use `--link <url>` instead.")
+        return
+    print(f"Registered {LINK_SCHEME}:// links to open with {' '.join(command)}")
+
+
 # Global reference to the app instance for callback access
 app_instance = None
 sql_client = None
 renderer_process = None
 
-HELP_MSG = """Execute any SqLite commands.
-Special commands:
-    --help: display this help message
-    --schema: display database schema of the memory snapshot
-    --clear: clear REPL output
-    --find <pattern>: find the message panel (on the left) with a pattern.
-                      case INsensitive, does NOT support regex
-"""
-DATABASE_SCHEMA = """CREATE TABLE allocs (
+DATABASE_SCHEMA = """CREATE TABLE callstacks (
+    id INTEGER PRIMARY KEY,
+    callstack TEXT       -- each unique callstack once
+);
+CREATE UNIQUE INDEX callstacks_by_text ON callstacks (callstack);
+CREATE TABLE alloc_rows (
     idx INTEGER PRIMARY KEY,
     size INTEGER,
     start_time INTEGER,
     end_time INTEGER,
+    callstack_id INTEGER,  -- callstacks.id
+    category TEXT
+);
+CREATE VIEW allocs AS    -- query this one: alloc_rows with their callstack
+    SELECT alloc_rows.*, callstacks.callstack FROM alloc_rows
+    LEFT JOIN callstacks ON callstacks.id = alloc_rows.callstack_id;
+CREATE TABLE timeline (
+    idx INTEGER PRIMARY KEY,
+    timesteps BLOB,  -- little-endian u64 array
+    offsets BLOB     -- little-endian u64 array
+);
+CREATE TABLE lanes (
+    idx INTEGER PRIMARY KEY,
+    stream INTEGER,      -- CUDA stream the block was allocated on
+    lane_offsets BLOB    -- little-endian u64 array, offsets stacking only blocks on the same stream
+);
+CREATE TABLE events (
+    idx INTEGER PRIMARY KEY,
+    timestep INTEGER,    -- x position, same units as start_time / end_time
+    action TEXT,         -- oom, segment_free (e.g. empty_cache) or snapshot
+    size INTEGER,
+    stream INTEGER,
     callstack TEXT
+);
+CREATE TABLE clock (
+    timestep INTEGER PRIMARY KEY,  -- start of an allocator action
+    time INTEGER                   -- when it happened, in metadata's time_unit (us)
+);
+CREATE TABLE reserved (
+    timestep INTEGER PRIMARY KEY,  -- where the memory reserved in segments changes
+    bytes INTEGER                  -- reserved from here on, for the fragmentation mode
+);
+CREATE TABLE segments (
+    idx INTEGER PRIMARY KEY,
+    start_time INTEGER,  -- reserved by the caching allocator from here
+    end_time INTEGER,    -- until here (released, or the end of the trace)
+    size INTEGER
 );"""
 
 
@@ -108,7 +764,21 @@ class ZeroMQReceiver(threading.Thread):
             socks = dict(self.poller.poll(timeout=100))
             if self.socket in socks and socks[self.socket] == zmq.POLLIN:
                 try:
-                    message = self.socket.recv_string(zmq.NOBLOCK)
+                    parts = self.socket.recv_multipart(zmq.NOBLOCK)
+                    message = parts[-1].decode("utf-8", errors="replace")
+                    if len(parts) == 2 and parts[0] == LOAD_PROGRESS_EVENT:
+                        progress = json.loads(message)
+                        self.app.root.after(0, self.app.update_progress, progress["stage"], progress["percent"])
+                        continue
+                    if len(parts) == 2 and parts[0] == WINDOW_SIZE_EVENT:
+                        width, height = message.split()
+                        self.app.renderer_size = (int(width), int(height))
+                        continue
+                    if len(parts) == 2 and parts[0] == RELOADED_EVENT:
+                        self.app.root.after(0, self.app.reloaded, message)
+                        continue
+                    if len(parts) == 2 and parts[0] == REGION_STATS_EVENT:
+                        message = region_summary(json.loads(message))
                     # Use after() for thread-safe UI updates
                     self.app.root.after(0, self.app.update_message, message)
                 except zmq.ZMQError:
@@ -122,6 +792,46 @@ class ZeroMQReceiver(threading.Thread):
         self.context.term()
 
 
+class PickleWatcher(threading.Thread):
+    """
+    Background thread for --watch with a pickle: once the pickle is rewritten (e.g. dumped again by a
+    training job) and holds still, converts it into the cache and has the renderer reload it there.
+    """
+
+    def __init__(self, args, app):
+        super().__init__(daemon=True)
+        self.args = args
+        self.app = app
+
+    def stamp(self):
+        try:
+            stat = os.stat(self.args.pickle)
+        except OSError:
+            return None
+        return stat.st_size, stat.st_mtime_ns
+
+    def run(self):
+        loaded = seen = self.stamp()
+        while True:
+            time.sleep(WATCH_POLL_S)
+            current = self.stamp()
+            # a pickle being dumped keeps growing, it is converted once it has not changed for a poll
+            settled = current == seen
+            seen = current
+            if current is None or not settled or current == loaded:
+                continue
+            loaded = current
+            print(f"{self.args.pickle} changed, converting it again")
+            try:
+                snapshot_dir = get_or_create_cache(
+                    self.args.renderer_binary, self.args.pickle, self.args.device, cache_hash=self.args.cache_hash
+                )
+            except SystemExit:
+                print(f"Converting {self.args.pickle} failed, keeping the snapshot shown until it changes again")
+                continue
+            self.app.root.after(0, self.app.reload, snapshot_dir)
+
+
 class ZeroMQSQLClient:
     """Client for sending SQL commands to renderer via ZeroMQ REQ socket"""
 
@@ -165,6 +875,21 @@ def _replace_scrollbar(st: scrolledtext.ScrolledText, style: str) -> None:
     st.configure(yscrollcommand=new.set)
 
 
+def _style_text_area(st: scrolledtext.ScrolledText, palette: ColorPalette) -> None:
+    """Apply palette colors to a ScrolledText, with an accent-colored ring while it has focus."""
+    st.configure(
+        bg=palette.text_area_bg,
+        fg=palette.text_fg,
+        selectbackground=palette.accent,
+        selectforeground=palette.select_fg,
+        highlightthickness=2,  # Focus indicator for keyboard navigation
+        highlightcolor=palette.accent,
+        highlightbackground=palette.text_area_bg,
+        insertbackground=palette.accent,
+    )
+    st.frame.configure(bg=palette.text_area_bg)
+
+
 class MessagePanel(ttk.Frame):
     """Panel that displays messages from main thread"""
 
@@ -232,9 +957,9 @@ class MessagePanel(ttk.Frame):
             self.mono_font = font.Font(family="Consolas", size=13)
 
         # Title
-        title_label = ttk.Label(self, text="Messages", font=self.title_font)
-        title_label.configure(foreground=self.palette.accent)
-        title_label.pack(anchor="w", pady=(0, 10))
+        self.title_label = ttk.Label(self, text=tr("messages-title"), font=self.title_font)
+        self.title_label.configure(foreground=self.palette.accent)
+        self.title_label.pack(anchor="w", pady=(0, 10))
 
         # Message display
         self.text_widget = scrolledtext.ScrolledText(
@@ -244,23 +969,40 @@ class MessagePanel(ttk.Frame):
             wrap=tk.WORD,
             height=20,
             width=60,
-            bg=self.palette.text_area_bg,
-            fg=self.palette.text_fg,
-            selectbackground=self.palette.accent,
-            selectforeground=self.palette.select_fg,
-            highlightthickness=0,  # Remove highlight border
-            insertbackground=self.palette.accent,
+            takefocus=True,  # reachable with Tab / Ctrl+1 for keyboard scrolling
             padx=10,  # Added horizontal padding
             pady=10,  # Added vertical padding
         )
         self.text_widget.pack(fill=tk.BOTH, expand=True)
         _replace_scrollbar(self.text_widget, "Palette.Vertical.TScrollbar")
-        self.text_widget.frame.configure(bg=self.palette.text_area_bg)
+        _style_text_area(self.text_widget, self.palette)
+
+        # Loading progress of the renderer, packed above the messages while it loads
+        self.progress_label = ttk.Label(self)
+        self.progress_bar = ttk.Progressbar(
+            self, mode="determinate", maximum=100, style="Palette.Horizontal.TProgressbar"
+        )
 
         # Set initial message
-        self.update_content("""This panel will show:
-- On left click, info of the allocation you left clicked on
-- On right click, your current mouse position (x -> timestamp, y -> memory)""")
+        self.update_content(tr("messages-initial"))
+
+    def apply_palette(self, palette: ColorPalette):
+        """Re-color the panel after a theme change"""
+        self.palette = palette
+        self.title_label.configure(foreground=palette.accent)
+        _style_text_area(self.text_widget, palette)
+
+    def show_progress(self, stage: str, percent: int):
+        """Show the renderer's loading stage, hidden again once it reaches 100%"""
+        if percent >= 100:
+            self.progress_label.pack_forget()
+            self.progress_bar.pack_forget()
+            return
+        self.progress_label.configure(text=tr("loading-progress", stage=stage, percent=percent))
+        self.progress_bar["value"] = percent
+        if not self.progress_bar.winfo_manager():
+            self.progress_label.pack(anchor="w", before=self.text_widget)
+            self.progress_bar.pack(fill=tk.X, pady=(0, 10), before=self.text_widget)
 
     def update_content(self, message: str):
         """Update the message content"""
@@ -304,13 +1046,6 @@ class HistoryEntry(ttk.Entry):
 class REPLPanel(ttk.Frame):
     """REPL panel with input and scrollable output"""
 
-    REPL_HINT = (
-        "SQLite REPL - This is a SQLite database storing the allocation data.",
-        "Type `--help` to see available commands.",
-        "Type `--find <pattern>` to search messages.",
-        "Ctrl+D to quit application.",
-    )
-
     def __init__(self, parent, args, palette: ColorPalette):
         super().__init__(parent)
         self.args = args
@@ -376,9 +1111,9 @@ class REPLPanel(ttk.Frame):
             self.mono_font = font.Font(family="Consolas", size=14)
 
         # Title
-        title_label = ttk.Label(self, text="SQLite REPL", font=self.title_font)
-        title_label.configure(foreground=self.palette.accent)
-        title_label.pack(anchor="w", pady=(0, 10))
+        self.title_label = ttk.Label(self, text=tr("repl-title"), font=self.title_font)
+        self.title_label.configure(foreground=self.palette.accent)
+        self.title_label.pack(anchor="w", pady=(0, 10))
 
         # Output area
         self.output_text = scrolledtext.ScrolledText(
@@ -388,26 +1123,21 @@ class REPLPanel(ttk.Frame):
             wrap=tk.WORD,
             height=15,
             width=60,
-            bg=self.palette.text_area_bg,
-            fg=self.palette.text_fg,
-            selectbackground=self.palette.accent,
-            selectforeground=self.palette.select_fg,
-            highlightthickness=0,  # Remove highlight border
-            insertbackground=self.palette.accent,
+            takefocus=True,  # reachable with Tab for keyboard scrolling
             padx=10,  # Added horizontal padding
             pady=10,  # Added vertical padding
         )
         self.output_text.pack(fill=tk.BOTH, expand=True, pady=(0, 10))
         _replace_scrollbar(self.output_text, "Palette.Vertical.TScrollbar")
-        self.output_text.frame.configure(bg=self.palette.text_area_bg)
+        _style_text_area(self.output_text, self.palette)
 
         # Input area
         input_frame = ttk.Frame(self)
         input_frame.pack(fill=tk.X, pady=(0, 10))
 
-        prompt_label = ttk.Label(input_frame, text="> ", font=self.mono_font)
-        prompt_label.configure(foreground=self.palette.accent)
-        prompt_label.pack(side=tk.LEFT)
+        self.prompt_label = ttk.Label(input_frame, text="> ", font=self.mono_font)
+        self.prompt_label.configure(foreground=self.palette.accent)
+        self.prompt_label.pack(side=tk.LEFT)
 
         self.input_entry = HistoryEntry(
             input_frame,
@@ -418,13 +1148,20 @@ class REPLPanel(ttk.Frame):
         self.input_entry.bind("<Return>", self.on_submit)
         self.input_entry.pack(side=tk.LEFT, fill=tk.X, expand=True)
 
-        # Initialize with hint
-        self.output_lines = list(REPLPanel.REPL_HINT)
-        self.update_output()
+        # Initialize with hint; only the last lines are kept so long sessions stay responsive
+        self.output_lines = deque(maxlen=self.args.repl_max_lines)
+        self.reset_output(tr("repl-hint"))
 
         # Focus the input
         self.input_entry.focus_set()
 
+    def apply_palette(self, palette: ColorPalette):
+        """Re-color the panel after a theme change"""
+        self.palette = palette
+        self.title_label.configure(foreground=palette.accent)
+        self.prompt_label.configure(foreground=palette.accent)
+        _style_text_area(self.output_text, palette)
+
     def on_submit(self, event=None):
         """Handle command submission"""
         command = self.input_entry.get().strip()
@@ -437,18 +1174,18 @@ class REPLPanel(ttk.Frame):
             self.input_entry.history_index = len(history)
 
             if command == "--clear":
-                self.output_lines = list(REPLPanel.REPL_HINT)
+                self.reset_output(tr("repl-hint"))
             else:
                 # is input command
                 timestamp = datetime.now().strftime("%H:%M:%S")
-                self.output_lines.append(f"[{timestamp}] > {command}")
+                self.append_output(f"[{timestamp}] > {command}")
                 # split at first whitespace
                 cmdlist = command.split(None, 1)
                 cmd = cmdlist[0]
                 pattern = cmdlist[1] if len(cmdlist) > 1 else None
                 if cmd == "--find":
                     if not pattern:
-                        self.output_lines.append(f"[{timestamp}]\nUsage: --find <pattern>")
+                        self.append_output(f"[{timestamp}]\n{tr('find-usage')}")
                     else:
                         global app_instance
                         if app_instance and hasattr(app_instance, "message_panel"):
@@ -458,37 +1195,63 @@ class REPLPanel(ttk.Frame):
 
                             if found_lines:
                                 result = (
-                                    f"Found {len(found_lines)} matching lines for '{pattern}':\n"
+                                    tr("find-found", count=len(found_lines), pattern=pattern)
+                                    + "\n"
                                     + "\n".join(found_lines)
                                 )
-                                self.output_lines.append(f"[{timestamp}]\n{result}")
+                                self.append_output(f"[{timestamp}]\n{result}")
                             else:
-                                self.output_lines.append(f"[{timestamp}]\nNo matches found for '{pattern}'.")
+                                self.append_output(f"[{timestamp}]\n{tr('find-none', pattern=pattern)}")
                         else:
-                            self.output_lines.append(f"[{timestamp}]\nError: Could not access message panel.")
+                            self.append_output(f"[{timestamp}]\n{tr('find-no-panel')}")
                 elif cmd == "--help":
-                    self.output_lines.append(f"[{timestamp}]\n{HELP_MSG}")
+                    self.append_output(f"[{timestamp}]\n{tr('repl-help')}")
+                elif cmd == "--info":
+                    metadata = load_metadata(self.args.dir)
+                    if metadata:
+                        info = "\n".join(f"{key}: {value}" for key, value in metadata.items())
+                    else:
+                        info = tr("info-none")
+                    self.append_output(f"[{timestamp}]\n{info}")
+                elif cmd == "--link":
+                    view_query = app_instance.sql_client.execute_sql("--view")
+                    link = make_link(self.args.dir, view_query)
+                    self.append_output(f"[{timestamp}]\n{link or tr('link-not-cached')}")
                 elif cmd == "--schema":
-                    self.output_lines.append(f"[{timestamp}]\n{DATABASE_SCHEMA}")
+                    self.append_output(f"[{timestamp}]\n{DATABASE_SCHEMA}")
                 else:
                     output = app_instance.sql_client.execute_sql(command)
-                    self.output_lines.append(f"[{timestamp}]\n{output}")
+                    self.append_output(f"[{timestamp}]\n{output}")
 
-            self.update_output()
 
         # Clear input
         self.input_entry.delete(0, tk.END)
 
-    def update_output(self):
-        """Update the output display"""
-        output_content = "\n".join(self.output_lines)
+    def reset_output(self, text: str):
+        """Replace the output with text"""
+        self.output_lines.clear()
+        self.output_text.configure(state="normal")
+        self.output_text.delete(1.0, tk.END)
+        self.output_text.configure(state="disabled")
+        self.append_output(text)
+
+    def append_output(self, text: str):
+        """Append text to the output, dropping the oldest lines beyond the line limit"""
         # Ensure proper Unicode handling
-        if isinstance(output_content, bytes):
-            output_content = output_content.decode("utf-8", errors="replace")
+        if isinstance(text, bytes):
+            text = text.decode("utf-8", errors="replace")
+        if self.args.screen_reader:
+            print(text)
+        lines = text.splitlines() or [""]
+        lines = lines[-self.output_lines.maxlen :]
+        dropped = max(0, len(self.output_lines) + len(lines) - self.output_lines.maxlen)
+        was_empty = not self.output_lines
+        self.output_lines.extend(lines)
 
         self.output_text.configure(state="normal")
-        self.output_text.delete(1.0, tk.END)
-        self.output_text.insert(1.0, output_content)
+        self.output_text.insert(tk.END, ("" if was_empty else "\n") + "\n".join(lines))
+        if dropped:
+            self.output_text.delete(1.0, f"{dropped + 1}.0")
         self.output_text.configure(state="disabled")
         # Auto-scroll to bottom
         self.output_text.see(tk.END)
@@ -499,6 +1262,10 @@ class SnapViewerApp:
 
     def __init__(self, args, sql_client, palette: ColorPalette = CUTE):
         self.args = args
+        # Updated as the renderer window is resized, saved on close to open it at that size next time
+        self.renderer_size = args.resolution
+        # cache entry --watch last had the renderer reload, see reloaded
+        self.watch_reload = None
         self.sql_client = sql_client
         self.palette = palette
         self.root = tk.Tk()
@@ -513,9 +1280,72 @@ class SnapViewerApp:
 
     def setup_ui(self, path: str):
         """Setup the main UI"""
-        self.root.title(f"SnapViewer - Memory Allocation Viewer & SQLite REPL ( Path: {path} )")
-        self.root.geometry("1600x1200")
+        self.root.title(tr("window-title", path=path))
+        self.root.geometry(self.args.window_state.get("gui_geometry", DEFAULT_GUI_GEOMETRY))
+
+        self._configure_styles()
+
+        # Create main container
+        main_frame = ttk.Frame(self.root, padding="20")
+        main_frame.pack(fill=tk.BOTH, expand=True)
 
+        # Top bar with toggle button
+        top_bar = ttk.Frame(main_frame)
+        top_bar.pack(fill=tk.X, pady=(0, 6))
+        self._repl_visible = True
+        self._toggle_btn = tk.Button(
+            top_bar,
+            text=tr("hide-repl"),
+            command=self._toggle_repl,
+            relief="flat",
+            padx=10,
+            pady=4,
+            cursor="hand2",
+            takefocus=True,
+        )
+        self._style_toggle_btn()
+        # Space activates a focused tk.Button by default, make Return work too
+        self._toggle_btn.bind("<Return>", lambda e: self._toggle_btn.invoke())
+        self._toggle_btn.pack(side=tk.RIGHT)
+
+        # Snapshot metadata header, so screenshots are self-describing
+        self._metadata_label = ttk.Label(top_bar, text=metadata_summary(load_metadata(path)))
+        self._metadata_label.pack(side=tk.LEFT)
+
+        # Panel container (below top bar)
+        self._panel_frame = ttk.Frame(main_frame)
+        self._panel_frame.pack(fill=tk.BOTH, expand=True)
+
+        # Create panels
+        self.message_panel = MessagePanel(self._panel_frame, self.palette)
+        self.repl_panel = REPLPanel(self._panel_frame, self.args, self.palette)
+
+        # Configure panel styling
+        self.message_panel.configure(style="Panel.TFrame")
+        self.repl_panel.configure(style="Panel.TFrame")
+
+        # Pack panels side by side
+        self.message_panel.pack(side=tk.LEFT, fill=tk.BOTH, expand=True, padx=(0, 10))
+
+        # Add separator
+        self._separator = ttk.Separator(self._panel_frame, orient="vertical")
+        self._separator.pack(side=tk.LEFT, fill=tk.Y, padx=10)
+
+        self.repl_panel.pack(side=tk.RIGHT, fill=tk.BOTH, expand=True, padx=(10, 0))
+
+        # Add keyboard shortcuts
+        self.root.bind("<Control-d>", lambda e: self.close())
+        self.root.bind("<Control-q>", lambda e: self.close())
+        self.root.bind("<Control-r>", lambda e: self._toggle_repl())
+        self.root.bind("<Control-t>", lambda e: self.cycle_theme())
+        self.root.bind("<Control-Key-1>", lambda e: self.focus_messages())
+        self.root.bind("<Control-Key-2>", lambda e: self.focus_repl())
+
+        # Handle window close event
+        self.root.protocol("WM_DELETE_WINDOW", self.close)
+
+    def _configure_styles(self):
+        """Apply the current palette to the root window and ttk styles"""
         # Configure colors and styling
         self.root.configure(bg=self.palette.window_bg)
 
@@ -559,6 +1389,20 @@ class SnapViewerApp:
             darkcolor=self.palette.panel_bg,
             lightcolor=self.palette.panel_bg,
         )
+        # Focus ring on the REPL input for keyboard navigation
+        style.map(
+            "REPL.TEntry",
+            bordercolor=[("focus", self.palette.accent)],
+            lightcolor=[("focus", self.palette.accent)],
+        )
+        style.configure(
+            "Palette.Horizontal.TProgressbar",
+            background=self.palette.accent,
+            troughcolor=self.palette.panel_bg,
+            bordercolor=self.palette.panel_bg,
+            lightcolor=self.palette.accent,
+            darkcolor=self.palette.accent,
+        )
         style.map(
             "Palette.Vertical.TScrollbar",
             background=[("active", self.palette.accent), ("!active", self.palette.panel_bg)],
@@ -575,80 +1419,92 @@ class SnapViewerApp:
         # Remove the border from the Panel.TFrame style
         style.configure("Panel.TFrame", background=self.palette.panel_bg, relief="flat", borderwidth=0)
 
-        # Create main container
-        main_frame = ttk.Frame(self.root, padding="20")
-        main_frame.pack(fill=tk.BOTH, expand=True)
-
-        # Top bar with toggle button
-        top_bar = ttk.Frame(main_frame)
-        top_bar.pack(fill=tk.X, pady=(0, 6))
-        self._repl_visible = True
-        self._toggle_btn = tk.Button(
-            top_bar,
-            text="Hide REPL",
-            command=self._toggle_repl,
+    def _style_toggle_btn(self):
+        self._toggle_btn.configure(
             bg=self.palette.panel_bg,
             fg=self.palette.text_fg,
             activebackground=self.palette.accent,
             activeforeground=self.palette.select_fg,
-            relief="flat",
-            padx=10,
-            pady=4,
-            cursor="hand2",
+            highlightthickness=2,  # Focus indicator for keyboard navigation
+            highlightcolor=self.palette.accent,
+            highlightbackground=self.palette.window_bg,
         )
-        self._toggle_btn.pack(side=tk.RIGHT)
 
-        # Panel container (below top bar)
-        self._panel_frame = ttk.Frame(main_frame)
-        self._panel_frame.pack(fill=tk.BOTH, expand=True)
-
-        # Create panels
-        self.message_panel = MessagePanel(self._panel_frame, self.palette)
-        self.repl_panel = REPLPanel(self._panel_frame, self.args, self.palette)
-
-        # Configure panel styling
-        self.message_panel.configure(style="Panel.TFrame")
-        self.repl_panel.configure(style="Panel.TFrame")
-
-        # Pack panels side by side
-        self.message_panel.pack(side=tk.LEFT, fill=tk.BOTH, expand=True, padx=(0, 10))
-
-        # Add separator
-        self._separator = ttk.Separator(self._panel_frame, orient="vertical")
-        self._separator.pack(side=tk.LEFT, fill=tk.Y, padx=10)
-
-        self.repl_panel.pack(side=tk.RIGHT, fill=tk.BOTH, expand=True, padx=(10, 0))
-
-        # Add keyboard shortcuts
-        self.root.bind("<Control-d>", lambda e: self.close())
-        self.root.bind("<Control-q>", lambda e: self.close())
-
-        # Handle window close event
-        self.root.protocol("WM_DELETE_WINDOW", self.close)
+    def cycle_theme(self):
+        """Switch to the next color theme"""
+        names = list(PALETTES)
+        current = next((name for name, p in PALETTES.items() if p is self.palette), names[0])
+        self.palette = PALETTES[names[(names.index(current) + 1) % len(names)]]
+
+        self._configure_styles()
+        self._style_toggle_btn()
+        self.message_panel.apply_palette(self.palette)
+        self.repl_panel.apply_palette(self.palette)
+        # the renderer knows the palettes by the same names
+        name = next(name for name, p in PALETTES.items() if p is self.palette)
+        self.sql_client.execute_sql(f"--theme {name}")
+
+    def focus_messages(self):
+        self.message_panel.text_widget.focus_set()
+
+    def focus_repl(self):
+        if not self._repl_visible:
+            self._toggle_repl()
+        self.repl_panel.input_entry.focus_set()
+
+    def reload(self, snapshot_dir: str):
+        """Have the renderer show the snapshot converted into snapshot_dir in place of the shown one"""
+        self.watch_reload = snapshot_dir
+        self.update_message(self.sql_client.execute_sql(f"--reload {snapshot_dir}"))
+
+    def reloaded(self, snapshot_dir: str):
+        """
+        The renderer shows snapshot_dir now. If --watch asked for it, the cache entry of the pickle's
+        previous contents it replaced is never opened again, so it is deleted rather than left to pile up
+        with every reload, if this process converted it (see CREATED_CACHE_ENTRIES). Reloads typed in the
+        REPL leave the cache alone.
+        """
+        previous, self.args.dir = self.args.dir, snapshot_dir
+        shown = Path(snapshot_dir).resolve()
+        if self.watch_reload is None or shown != Path(self.watch_reload).resolve():
+            return
+        replaced = Path(previous).resolve() if previous else None
+        if replaced and replaced != shown and replaced in CREATED_CACHE_ENTRIES:
+            print(f"Removing replaced cache entry {replaced.name}")
+            shutil.rmtree(replaced, ignore_errors=True)
+            CREATED_CACHE_ENTRIES.discard(replaced)
 
     def update_message(self, message: str):
         """Update the message panel content"""
+        if self.args.screen_reader:
+            print(message)
         self.message_panel.update_content(message)
 
+    def update_progress(self, stage: str, percent: int):
+        """Show the renderer's loading progress above the messages"""
+        self.message_panel.show_progress(stage, percent)
+
     def _toggle_repl(self):
         if self._repl_visible:
             self.repl_panel.pack_forget()
             self._separator.pack_forget()
-            self._toggle_btn.configure(text="Show REPL")
+            self._toggle_btn.configure(text=tr("show-repl"))
             self._repl_visible = False
         else:
             self._separator.pack(side=tk.LEFT, fill=tk.Y, padx=10)
             self.repl_panel.pack(side=tk.RIGHT, fill=tk.BOTH, expand=True, padx=(10, 0))
-            self._toggle_btn.configure(text="Hide REPL")
+            self._toggle_btn.configure(text=tr("hide-repl"))
             self._repl_visible = True
 
     def close(self):
         """Handle window close event"""
-        result = messagebox.askyesno(
-            "Quit SnapViewer", "Are you sure you want to quit SnapViewer?", default=messagebox.NO
-        )
+        result = messagebox.askyesno(tr("quit-title"), tr("quit-confirm"), default=messagebox.NO)
 
         if result:
+            save_window_state(
+                self.args.monitor_key,
+                {"gui_geometry": self.root.geometry(), "renderer_res": list(self.renderer_size)},
+            )
             # Stop the receiver thread
             if self.receiver:
                 self.receiver.stop()
@@ -670,23 +1526,25 @@ def terminate():
     if sql_client:
         sql_client.close()
 
-    # Terminate renderer process
+    # Terminate renderer process, or pass on why it exited
+    code = 0
     if renderer_process:
-        renderer_process.terminate()
-        renderer_process.wait()
-
-    os._exit(0)
+        if renderer_process.poll() is None:
+            renderer_process.terminate()
+            renderer_process.wait()
+        else:
+            code = renderer_process.returncode
 
+    os._exit(code)
 
-def spawn_renderer(args):
-    """Spawn the renderer process"""
-    global renderer_process
 
+def find_renderer_binary(args) -> str:
+    """--bin, or the renderer built in this repository (building it if needed)"""
     if args.bin:
         renderer_binary = args.bin
         if not Path(renderer_binary).exists():
             print(f"Error: Renderer binary not found at {renderer_binary}")
-            sys.exit(1)
+            sys.exit(EXIT_BAD_INPUT)
     else:
         # Find the renderer binary
         # First try the target/release directory
@@ -713,24 +1571,84 @@ def spawn_renderer(args):
                 check=True,
             )
             renderer_binary = str(script_dir / "target" / "release" / f"snapviewer-renderer{exe_suffix}")
+    return renderer_binary
+
+
+def spawn_renderer(args):
+    """Spawn the renderer process"""
+    global renderer_process
 
     cmd = [
-        renderer_binary,
+        args.renderer_binary,
         "--dir",
         args.dir,
         "--res",
         str(args.resolution[0]),
         str(args.resolution[1]),
-        "--resolution-ratio",
-        str(args.resolution_ratio),
         "--pub-port",
         str(args.pub_port),
         "--rep-port",
         str(args.rep_port),
         "--log",
         args.log,
+        "--max-segments",
+        str(args.max_segments),
     ]
 
+    if args.pickle:
+        # the cache directory is named by a hash, title the window after the pickle instead
+        cmd += ["--title", os.path.basename(args.pickle)]
+    if args.resolution_ratio is not None:
+        cmd += ["--resolution-ratio", str(args.resolution_ratio)]
+    if args.self_profile is not None:
+        cmd += ["--self-profile", args.self_profile]
+    if args.color_seed is not None:
+        cmd += ["--color-seed", str(args.color_seed)]
+    if args.color_by != "random":
+        cmd += ["--color-by", args.color_by]
+    if args.palette is not None:
+        cmd += ["--palette", args.palette, "--palette-order", args.palette_order]
+    if args.diff is not None:
+        cmd += ["--diff", args.diff]
+    if args.devices:
+        cmd += ["--devices", *args.devices]
+    if args.merge_dirs:
+        cmd += ["--merge-ranks", *args.merge_dirs]
+    if args.lanes:
+        cmd += ["--lanes"]
+    if args.watch and not args.pickle:
+        # a pickle's cache directory never changes, PickleWatcher converts it again instead
+        cmd += ["--watch"]
+    if args.min_size is not None:
+        cmd += ["--min-size", args.min_size]
+    if args.min_lifetime is not None:
+        cmd += ["--min-lifetime", str(args.min_lifetime)]
+    if args.max_allocs is not None:
+        cmd += ["--max-allocs", str(args.max_allocs), "--max-allocs-by", args.max_allocs_by]
+    if args.heatmap:
+        cmd += ["--heatmap"]
+    if args.segments:
+        cmd += ["--segments"]
+    if args.top_labels is not None:
+        cmd += ["--top-labels", str(args.top_labels)]
+    cmd += ["--theme", args.theme]
+    for flag, color in [
+        ("--background", args.background),
+        ("--tick-color", args.tick_color),
+        ("--selection-color", args.selection_color),
+    ]:
+        if color is not None:
+            cmd += [flag, color]
+    if args.max_fps is not None:
+        cmd += ["--max-fps", str(args.max_fps)]
+    if args.link_cursor is not None:
+        cmd += ["--link-cursor", *map(str, args.link_cursor)]
+    link_params = getattr(args, "link_params", {})
+    if "t0" in link_params and "t1" in link_params:
+        cmd += ["--view", f"{link_params['t0']}..{link_params['t1']}"]
+    if "idx" in link_params:
+        cmd += ["--select", str(link_params["idx"])]
+
     print(f"Starting renderer process: {' '.join(cmd)}")
     renderer_process = subprocess.Popen(cmd)
 
@@ -744,6 +1662,8 @@ def run_gui(args, palette: ColorPalette):
     sql_client.connect()
 
     app_instance = SnapViewerApp(args, sql_client, palette=palette)
+    if args.watch and args.pickle:
+        PickleWatcher(args, app_instance).start()
     app_instance.run()
 
     print("Stopping SnapViewer application...")
@@ -778,9 +1698,10 @@ def main():
         "--res",
         type=positive_int,
         nargs=2,  # Expect exactly 2 arguments for resolution
-        default=[2400, 1000],  # Default as a list
+        default=None,  # Resolved from saved window state, then DEFAULT_RESOLUTION
         metavar=("WIDTH", "HEIGHT"),  # Help text for the arguments
-        help="Specify resolution as two positive integers (WIDTH HEIGHT).",
+        help="Specify resolution as two positive integers (WIDTH HEIGHT). "
+        "Default: last used for this monitor setup, else 2400 1000.",
     )
     parser.add_argument(
         "--pub-port",
@@ -798,8 +1719,8 @@ def main():
         "-rr",
         "--resolution-ratio",
         type=float,
-        default=1.0,
-        help="Resolution ratio for high-DPI displays (e.g., 2.0 for Retina). Default: 1.0",
+        default=None,
+        help="Resolution ratio for high-DPI displays (e.g., 2.0 for Retina). Default: detected by the renderer",
     )
 
     parser.add_argument(
@@ -807,20 +1728,202 @@ def main():
         type=str,
         choices=["cute", "default", "night"],
         default="default",
-        help="Color theme: cute (pink), default (white), night (dark). Default: cute",
+        help="Color theme of the GUI and renderer: cute (pink), default (white), night (dark). Default: default",
+    )
+    parser.add_argument(
+        "--background",
+        type=str,
+        default=None,
+        help="Renderer background color (hex like #002b36) replacing the theme's",
+    )
+    parser.add_argument(
+        "--tick-color",
+        type=str,
+        default=None,
+        help="Renderer tick label color (hex) replacing the theme's",
+    )
+    parser.add_argument(
+        "--selection-color",
+        type=str,
+        default=None,
+        help="Outline color of the selected allocation (hex) replacing the theme's",
+    )
+
+    parser.add_argument(
+        "--max-segments",
+        type=positive_int,
+        default=10_000_000,
+        help="Coarsen the plot geometry if the trace has more quads than this. Default: 10000000",
+    )
+    parser.add_argument(
+        "--max-allocs",
+        type=positive_int,
+        default=None,
+        metavar="N",
+        help="Draw only the N largest allocations, the rest aggregated into grey bands, for a quick look at huge traces",
+    )
+    parser.add_argument(
+        "--min-size",
+        type=str,
+        default=None,
+        metavar="SIZE",
+        help="Load only allocations of at least this size (e.g. 1MiB), for less memory and a faster start on huge snapshots",
+    )
+    parser.add_argument(
+        "--min-lifetime",
+        type=positive_int,
+        default=None,
+        metavar="TIMESTEPS",
+        help="Load only allocations living at least this many timesteps",
+    )
+    parser.add_argument(
+        "--max-allocs-by",
+        choices=["size", "lifetime"],
+        default="size",
+        help="What --max-allocs keeps the largest by. Default: size",
+    )
+    parser.add_argument(
+        "--repl-max-lines",
+        type=positive_int,
+        default=DEFAULT_REPL_MAX_LINES,
+        help=f"Keep at most this many lines of REPL output. Default: {DEFAULT_REPL_MAX_LINES}",
+    )
+    parser.add_argument(
+        "--lang",
+        type=str,
+        choices=SUPPORTED_LANGS,
+        default="en",
+        help="UI language for GUI and REPL strings. Default: en",
+    )
+    parser.add_argument(
+        "--screen-reader",
+        action="store_true",
+        help="Also print renderer messages and REPL output to the terminal, where screen readers can read them "
+        "(Tk widgets are not exposed to the platform accessibility APIs)",
+    )
+
+    parser.add_argument(
+        "--lanes",
+        action="store_true",
+        help="Split the plot into one lane per CUDA stream",
+    )
+    parser.add_argument(
+        "--heatmap",
+        action="store_true",
+        help="Start in heatmap mode (toggle with B): allocation counts per time x memory bin, for huge traces",
+    )
+    parser.add_argument(
+        "--segments",
+        action="store_true",
+        help="Start with reserved segments drawn as bands behind the allocations (toggle with E)",
+    )
+    parser.add_argument(
+        "--top-labels",
+        type=int,
+        metavar="N",
+        help="Label the N largest allocations with their size and top stack frame (toggle with T, 5 by default)",
+    )
+    parser.add_argument(
+        "--diff",
+        type=str,
+        default=None,
+        metavar="DIR",
+        help="Converted snapshot directory to overlay for comparison (blue: this snapshot, orange: DIR). "
+        "`--diff` in the REPL lists allocations present in only one of them.",
+    )
+    parser.add_argument(
+        "--color-seed",
+        type=int,
+        default=None,
+        help="Seed for the random allocation colors, to get the same colors across runs "
+        "(e.g. when comparing two snapshots). Default: random",
+    )
+    parser.add_argument(
+        "--color-by",
+        choices=["random", "size", "lifetime", "category"],
+        default="random",
+        help="What allocation colors encode. `size` (log scale), `lifetime` and `category` "
+        "(parameter, activation, gradient, ... for snapshots recording it) show a legend "
+        "in the bottom right corner. Not available with --diff. Default: random",
+    )
+    parser.add_argument(
+        "--palette",
+        default=None,
+        metavar="FILE",
+        help="Color allocations from a file of hex colors (e.g. #1f77b4, separated by "
+        "newlines, spaces or commas; lines starting with // are comments) instead of random ones",
+    )
+    parser.add_argument(
+        "--palette-order",
+        choices=["round-robin", "hash"],
+        default="round-robin",
+        help="How allocations pick a palette color: in turn, or by a hash of their size so "
+        "same-sized allocations share a color. Default: round-robin",
+    )
+    parser.add_argument(
+        "--max-fps",
+        type=positive_int,
+        default=None,
+        help="Cap the renderer's frame rate. The window is only redrawn when something changes; "
+        "this limits animations like the selection fade. Default: uncapped",
+    )
+    parser.add_argument(
+        "--link-cursor",
+        type=int,
+        nargs=2,
+        default=None,
+        metavar=("PUB_PORT", "SUB_PORT"),
+        help="Show the cursor of another SnapViewer window (started with the two ports swapped) "
+        "at the same timestamp, with the live memory there.",
+    )
+    parser.add_argument(
+        "--self-profile",
+        type=str,
+        nargs="?",
+        const="snapviewer_profile.txt",
+        default=None,
+        metavar="FILE",
+        help="Record startup stage timings and frame times to FILE (default: snapviewer_profile.txt) "
+        "to attach to performance bug reports.",
     )
 
     source_group = parser.add_mutually_exclusive_group(required=True)
+    source_group.add_argument(
+        "path",
+        nargs="?",
+        help="Snapshot to open, whatever it is: a .pickle (or .pickle.gz/.zst, .zip, .csv of samples or profiler memory timeline) to convert, "
+        "a converted directory or a file in one, a snapviewer:// link, or an http(s) URL of a pickle",
+    )
     source_group.add_argument(
         "-d",
         "--dir",
         type=str,
-        help="Directory containing allocations.json and elements.db",
+        help="Directory containing elements.db (and allocations.json for older conversions), "
+        "or a zip of one (e.g. from --export-bundle in the REPL), local or at an http(s) URL",
     )
     source_group.add_argument(
         "--pickle",
         type=str,
-        help="Path to a .pickle snapshot. Preprocessing result is cached under ~/.snapviewer_cache/",
+        help="Path to a .pickle snapshot (gzip or zstd compressed ones too, or a .zip holding one, a .csv of samples from `snapviewer-renderer record`, "
+        "or a memory timeline .json/.json.gz exported by the PyTorch profiler). "
+        "An http(s) URL is downloaded first. Preprocessing result is cached under ~/.snapviewer_cache/",
+    )
+    source_group.add_argument(
+        "--link",
+        type=str,
+        help="Open a snapviewer://<cache_key>?idx=..&t0=..&t1=.. link (made with `--link` in the REPL)",
+    )
+    source_group.add_argument(
+        "--merge-ranks",
+        nargs="+",
+        metavar="PATH",
+        help="Snapshots of a job's ranks, rank 0 first (pickles, converted in the cache first, or converted directories), "
+        "merged into one drawn with a lane per rank; SQL sees every rank's allocations, told apart by their `rank` column",
+    )
+    source_group.add_argument(
+        "--register-link-handler",
+        action="store_true",
+        help="Register this script as the handler for snapviewer:// links, then exit",
     )
 
     parser.add_argument(
@@ -829,22 +1932,103 @@ def main():
         default=0,
         help="Device ID to use when --pickle is provided. Default: 0",
     )
+    parser.add_argument(
+        "--cache-hash",
+        choices=CACHE_HASH_STRATEGIES,
+        default="full",
+        help="How a pickle's cache entry is keyed: full hashes the whole file (remembered while its size and mtime "
+        "stay the same), fast only its first 128 MiB and its size, size-mtime its path, size and modification time "
+        "without reading it. Default: full",
+    )
+    parser.add_argument(
+        "--watch",
+        action="store_true",
+        help="Reload the snapshot when it changes, keeping the visible time range: a --pickle is converted again "
+        "once it is rewritten (e.g. dumped again by a running training job), a --dir once it is converted again",
+    )
+    parser.add_argument(
+        "--prefetch-devices",
+        action="store_true",
+        help="With --pickle, also convert the pickle's other devices into the cache in the background, "
+        "so opening them later is instant",
+    )
+    parser.add_argument(
+        "--all-devices",
+        action="store_true",
+        help="With --pickle, convert and load every device of the pickle: Tab in the renderer or "
+        "`--device <id>` in the REPL switches between them without reconverting",
+    )
 
     args = parser.parse_args()
 
-    # Convert the resolution list to a tuple after parsing
-    args.resolution = tuple(args.res)
+    set_language(args.lang)
+
+    if args.path:
+        resolve_input_path(args)
+
+    if args.register_link_handler:
+        register_link_handler()
+        return
+
+    if args.link:
+        try:
+            cache_key, args.link_params = parse_link(args.link)
+        except ValueError as e:
+            print(f"Error: {e}")
+            exit(EXIT_BAD_INPUT)
+        args.dir = str(CACHE_ROOT / cache_key)
+        if not os.path.exists(args.dir):
+            print(f"Error: snapshot '{cache_key}' is not in the cache, open its pickle with --pickle first.")
+            exit(EXIT_BAD_INPUT)
+
+    # Restore window sizes saved for this monitor setup; an explicit --res wins
+    args.monitor_key = monitor_key()
+    args.window_state = load_window_state(args.monitor_key)
 
+    # Convert the resolution list to a tuple after parsing
+    args.resolution = tuple(args.res or args.window_state.get("renderer_res", DEFAULT_RESOLUTION))
+
+    args.renderer_binary = find_renderer_binary(args)
+
+    timings = {}
+    start = time.perf_counter()
+    if args.pickle and is_url(args.pickle):
+        args.pickle = str(download(args.pickle))
+        timings["download"] = time.perf_counter() - start
+    if args.dir and is_url(args.dir):
+        args.dir = download_converted_dir(args.dir)
+        timings["download"] = time.perf_counter() - start
+    # a bundle from `--export-bundle`, passed as --dir, --pickle or the bare path
+    if args.pickle and is_bundle(args.pickle):
+        args.dir, args.pickle = args.pickle, None
+    if args.dir and os.path.isfile(args.dir) and zipfile.is_zipfile(args.dir):
+        BUNDLE_DIR.mkdir(parents=True, exist_ok=True)
+        args.dir = extract_converted_dir(Path(args.dir), BUNDLE_DIR / compute_file_hash(args.dir), args.dir)
     if args.pickle:
         if not os.path.exists(args.pickle):
             print(f"Error: pickle file '{args.pickle}' does not exist.")
-            exit(1)
-        args.dir = get_or_create_cache(args.pickle, args.device)
+            exit(EXIT_BAD_INPUT)
+        args.dir = get_or_create_cache(
+            args.renderer_binary,
+            args.pickle,
+            args.device,
+            timings,
+            prefetch=args.prefetch_devices,
+            cache_hash=args.cache_hash,
+        )
+    args.merge_dirs = []
+    if args.merge_ranks:
+        args.merge_dirs, args.dir = merged_cache_dir(
+            args.renderer_binary, args.merge_ranks, args.device, timings, args.cache_hash
+        )
+    args.devices = []
+    if args.pickle and args.all_devices:
+        args.devices = convert_other_devices(args.renderer_binary, args.pickle, args.dir)
 
     # Verify that the path exists
     if not os.path.exists(args.dir):
         print(f"Error: The specified path '{args.dir}' does not exist.")
-        exit(1)  # Exit the program with an error code
+        exit(EXIT_BAD_INPUT)
 
     # Check ports are available before spawning anything
     import socket
@@ -853,17 +2037,22 @@ def main():
         with socket.socket(socket.AF_INET, socket.SOCK_STREAM) as s:
             if s.connect_ex(("127.0.0.1", port)) == 0:
                 print(f"Error: port {port} (--{name}-port) is already in use.")
-                exit(1)
+                exit(EXIT_IPC)
+
+    if args.self_profile is not None:
+        write_gui_profile(args.self_profile, timings)
+        print(f"Self-profile will be written to {args.self_profile}")
 
     # Spawn the renderer process
     spawn_renderer(args)
 
     # Give the renderer a moment to start up and bind its sockets
     time.sleep(0.5)
+    if renderer_process.poll() is not None:
+        print(f"Error: renderer exited with code {renderer_process.returncode}")
+        sys.exit(renderer_process.returncode)
 
-    # Map theme name to palette
-    palette_map = {"cute": CUTE, "default": DEFAULT, "night": NIGHT}
-    palette = palette_map[args.theme]
+    palette = PALETTES[args.theme]
 
     # Run the GUI (this is now the main process)
     run_gui(args, palette)
@@ -0,0 +1,34 @@
+"""
+Localization of user-facing GUI/REPL strings.
+
+Messages live in Fluent files under locales/<lang>/snapviewer.ftl.
+Missing messages fall back to English.
+"""
+
+from pathlib import Path
+
+from fluent.runtime import FluentLocalization, FluentResourceLoader
+
+LOCALES_DIR = Path(__file__).parent / "locales"
+RESOURCE_ID = "snapviewer.ftl"
+FALLBACK_LANG = "en"
+SUPPORTED_LANGS = ["en", "zh"]
+
+_l10n = None
+
+
+def set_language(lang: str):
+    """Select the UI language. Must be called before the GUI is built."""
+    global _l10n
+    if lang not in SUPPORTED_LANGS:
+        raise ValueError(f"Unsupported language '{lang}', expected one of {SUPPORTED_LANGS}")
+    loader = FluentResourceLoader(str(LOCALES_DIR / "{locale}"))
+    # no Unicode isolation marks around $path, $count, ... they show up in the Tk title and REPL output
+    _l10n = FluentLocalization([lang, FALLBACK_LANG], [RESOURCE_ID], loader, use_isolating=False)
+
+
+def tr(msg_id: str, **kwargs) -> str:
+    """Look up a localized message, formatting it with `kwargs`."""
+    if _l10n is None:
+        set_language(FALLBACK_LANG)
+    return _l10n.format_value(msg_id, kwargs)
@@ -0,0 +1,75 @@
+## Main window
+
+window-title = SnapViewer - Memory Allocation Viewer & SQLite REPL ( Path: { $path } )
+hide-repl = Hide REPL
+show-repl = Show REPL
+quit-title = Quit SnapViewer
+quit-confirm = Are you sure you want to quit SnapViewer?
+
+## Message panel
+
+messages-title = Messages
+messages-initial =
+    This panel will show:
+    - On left click, info of the allocation you left clicked on
+    - On right click, your current mouse position (x -> timestamp, y -> memory)
+loading-progress = Loading snapshot: { $stage } ({ $percent }%)
+
+## REPL panel
+
+repl-title = SQLite REPL
+repl-hint =
+    SQLite REPL - This is a SQLite database storing the allocation data.
+    Type `--help` to see available commands.
+    Type `--find <pattern>` to search messages.
+    Ctrl+D to quit application.
+    Keyboard: Ctrl+1 messages, Ctrl+2 REPL input, Ctrl+R show/hide REPL, Ctrl+T switch theme, Tab to move focus.
+repl-help =
+    Execute any SqLite commands.
+    Special commands:
+        --help: display this help message
+        --schema: display database schema of the memory snapshot
+        --info: display snapshot metadata (PyTorch version, device, capacity, capture time)
+        --clear: clear REPL output
+        --find <pattern>: find the message panel (on the left) with a pattern.
+                          case INsensitive, does NOT support regex
+        --filter size > <size>: hide allocations not larger than <size>, e.g. `--filter size > 1MiB`
+        --filter time <start>..<stop>: only show allocations alive between two timestamps
+        --filter clear: show all allocations again
+        --annotate <timestamp> <label>: mark a timestamp, e.g. `--annotate 12000 optimizer.step()`
+        --annotate remove <timestamp> / --annotate clear: remove annotations, `--annotate` lists them
+        --bookmark save <name> / --bookmark go <name>: remember a camera position and return to it later
+        --bookmark remove <name>: delete a bookmark, `--bookmark` lists them
+        --diff: in diff mode (started with --diff <dir>), list allocations present in only one snapshot
+        --goto <idx>: center the view on allocation <idx>, e.g. after finding it with SQL
+        --device [id]: with --all-devices, show device <id> (Tab in the renderer cycles), `--device` lists them
+        --at <timestamp|peak>: mark that instant with a cursor and list the allocations live then, `--at` removes it
+        --group [idx]: highlight every allocation sharing the callstack of <idx> (default: the selected one), `--group clear` undoes it
+        --export-svg <path>: write the visible allocations and memory ticks to an SVG file, e.g. for papers
+        --export-bundle <path>: zip the snapshot as loaded (filtered or merged) to share, opened with `gui.py --dir <zip>`
+        --reload [dir]: load the shown snapshot again (or the one converted into <dir>), keeping the visible time range
+        --record [path]: record the window to a .gif or .mp4 file (needs ffmpeg), `--record stop` finishes
+        --theme [light|dark|cute]: switch the renderer's colors, without a name shows the current theme
+        --instrument: print Python to record or label the code the selected allocation comes from
+        --script <path>: run a Rhai script against the snapshot (see README)
+        --link: print a snapviewer:// link to the current view and selected allocation
+find-usage = Usage: --find <pattern>
+find-found = Found { $count } matching lines for '{ $pattern }':
+find-none = No matches found for '{ $pattern }'.
+find-no-panel = Error: Could not access message panel.
+link-not-cached = Links can only be made for snapshots opened with --pickle (they refer to the cache key).
+
+## Snapshot metadata
+
+meta-device = device { $device }
+meta-capacity = capacity { $size }
+meta-reserved = reserved { $size }
+meta-captured = captured { $time }
+info-none = No metadata for this snapshot. Re-convert it with the current `snapviewer-renderer convert` to record it.
+
+## Region stats (Shift + drag in the renderer)
+
+region-title = Region: timestamps { $t0 } – { $t1 }, memory { $m0 } – { $m1 }
+region-total = { $count } allocations, { $size } in total
+region-callstack = { $size } in { $count } allocations from:
+region-no-callstack = (no callstack)
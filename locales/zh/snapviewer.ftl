@@ -0,0 +1,75 @@
+## Main window
+
+window-title = SnapViewer - 显存分配查看器 & SQLite REPL ( 路径: { $path } )
+hide-repl = 隐藏 REPL
+show-repl = 显示 REPL
+quit-title = 退出 SnapViewer
+quit-confirm = 确定要退出 SnapViewer 吗？
+
+## Message panel
+
+messages-title = 消息
+messages-initial =
+    此面板将显示：
+    - 左键点击时，所点击的分配的详细信息
+    - 右键点击时，当前鼠标位置（x -> 时间戳，y -> 显存）
+loading-progress = 正在加载快照：{ $stage }（{ $percent }%）
+
+## REPL panel
+
+repl-title = SQLite REPL
+repl-hint =
+    SQLite REPL - 这是一个存储分配数据的 SQLite 数据库。
+    输入 `--help` 查看可用命令。
+    输入 `--find <pattern>` 搜索消息。
+    按 Ctrl+D 退出程序。
+    键盘操作：Ctrl+1 消息面板，Ctrl+2 REPL 输入框，Ctrl+R 显示/隐藏 REPL，Ctrl+T 切换主题，Tab 切换焦点。
+repl-help =
+    可执行任意 SQLite 命令。
+    特殊命令：
+        --help: 显示此帮助信息
+        --schema: 显示显存快照的数据库结构
+        --info: 显示快照元数据（PyTorch 版本、设备、容量、采集时间）
+        --clear: 清空 REPL 输出
+        --find <pattern>: 在左侧消息面板中查找包含该模式的行。
+                          不区分大小写，不支持正则表达式
+        --filter size > <size>: 隐藏不大于 <size> 的分配，例如 `--filter size > 1MiB`
+        --filter time <start>..<stop>: 只显示在两个时间戳之间存活的分配
+        --filter clear: 重新显示所有分配
+        --annotate <timestamp> <label>: 在某个时间戳添加标注，例如 `--annotate 12000 optimizer.step()`
+        --annotate remove <timestamp> / --annotate clear: 删除标注，`--annotate` 列出所有标注
+        --bookmark save <name> / --bookmark go <name>: 保存当前视角并在之后跳回
+        --bookmark remove <name>: 删除书签，`--bookmark` 列出所有书签
+        --diff: 对比模式下（以 --diff <dir> 启动），列出只存在于其中一个快照的分配
+        --goto <idx>: 将视图居中到第 <idx> 个分配，例如用 SQL 查到之后跳转
+        --device [id]: 使用 --all-devices 时切换到设备 <id>（渲染器中按 Tab 轮换），`--device` 列出所有设备
+        --at <timestamp|peak>: 在该时刻显示时间游标并列出当时存活的分配，`--at` 移除游标
+        --group [idx]: 高亮与 <idx>（默认为选中的分配）调用栈相同的所有分配，`--group clear` 取消高亮
+        --export-svg <path>: 将当前可见的分配和内存刻度导出为 SVG 文件，例如用于论文
+        --export-bundle <path>: 将当前加载的快照（含加载时过滤或合并）打包为 zip 以便分享，用 `gui.py --dir <zip>` 打开
+        --reload [dir]: 重新加载当前快照（或 <dir> 中转换好的快照），保持可见的时间范围
+        --record [path]: 将窗口录制为 .gif 或 .mp4 文件（需要 ffmpeg），`--record stop` 结束录制
+        --theme [light|dark|cute]: 切换渲染器配色，不带名称时显示当前主题
+        --instrument: 输出用于记录或标注所选分配来源代码的 Python 片段
+        --script <path>: 对当前快照运行 Rhai 脚本（见 README）
+        --link: 输出指向当前视图和所选分配的 snapviewer:// 链接
+find-usage = 用法: --find <pattern>
+find-found = 找到 { $count } 行匹配 '{ $pattern }'：
+find-none = 未找到匹配 '{ $pattern }' 的内容。
+find-no-panel = 错误：无法访问消息面板。
+link-not-cached = 只能为通过 --pickle 打开的快照生成链接（链接使用缓存键）。
+
+## Snapshot metadata
+
+meta-device = 设备 { $device }
+meta-capacity = 总容量 { $size }
+meta-reserved = 已预留 { $size }
+meta-captured = 采集于 { $time }
+info-none = 此快照没有元数据。请使用当前版本的 `snapviewer-renderer convert` 重新转换以记录元数据。
+
+## 区域统计（在渲染器中按住 Shift 拖动）
+
+region-title = 区域：时间戳 { $t0 } – { $t1 }，内存 { $m0 } – { $m1 }
+region-total = 共 { $count } 个分配，总计 { $size }
+region-callstack = { $count } 个分配共 { $size }，来自：
+region-no-callstack = （无调用栈）
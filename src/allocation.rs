@@ -1,15 +1,118 @@
 use crate::utils::format_bytes;
 use serde::Deserialize;
-use std::fmt::{Display, Formatter, Result};
+use std::fmt::{Debug, Display, Formatter, Result};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// What an allocation holds, as newer PyTorch snapshots record it (same names as
+/// `torch.profiler._memory_profiler.Category`, lowercase)
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Parameter,
+    Activation,
+    Gradient,
+    OptimizerState,
+    Input,
+    Temporary,
+    AutogradDetail,
+}
+
+impl Category {
+    pub const ALL: [Category; 7] = [
+        Category::Parameter,
+        Category::Activation,
+        Category::Gradient,
+        Category::OptimizerState,
+        Category::Input,
+        Category::Temporary,
+        Category::AutogradDetail,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Parameter => "parameter",
+            Category::Activation => "activation",
+            Category::Gradient => "gradient",
+            Category::OptimizerState => "optimizer_state",
+            Category::Input => "input",
+            Category::Temporary => "temporary",
+            Category::AutogradDetail => "autograd_detail",
+        }
+    }
+
+    /// `None` for names this version does not know, which are shown as uncategorized
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+}
+
+/// Timesteps or offsets of an allocation: owned, or a view into a memory-mapped allocations.bin
+/// so loading it does not copy the geometry into the heap.
+#[derive(Deserialize, Clone)]
+#[serde(from = "Vec<u64>")]
+pub enum U64s {
+    Owned(Vec<u64>),
+    Mapped(Arc<memmap2::Mmap>, Range<usize>),
+}
+
+impl U64s {
+    /// Views `len` u64s at byte `start` of `map`, `None` if they are out of bounds, not aligned or
+    /// the host is not little-endian like the file
+    pub fn mapped(map: &Arc<memmap2::Mmap>, start: usize, len: usize) -> Option<Self> {
+        let end = len.checked_mul(8)?.checked_add(start)?;
+        let bytes = map.get(start..end)?;
+        if cfg!(target_endian = "big") || bytes.as_ptr().align_offset(8) != 0 {
+            return None;
+        }
+        Some(U64s::Mapped(map.clone(), start..end))
+    }
+}
+
+impl Deref for U64s {
+    type Target = [u64];
+
+    fn deref(&self) -> &[u64] {
+        match self {
+            U64s::Owned(values) => values,
+            U64s::Mapped(map, range) => {
+                let bytes = &map[range.clone()];
+                // SAFETY: `mapped` checked the range is in bounds, aligned and little-endian
+                unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast(), bytes.len() / 8) }
+            }
+        }
+    }
+}
+
+impl From<Vec<u64>> for U64s {
+    fn from(values: Vec<u64>) -> Self {
+        U64s::Owned(values)
+    }
+}
+
+impl FromIterator<u64> for U64s {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        U64s::Owned(iter.into_iter().collect())
+    }
+}
+
+impl Debug for U64s {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        self.deref().fmt(f)
+    }
+}
 
 // Corresponds to the Python Allocation dataclass
 #[derive(Deserialize, Debug, Clone)]
 pub struct Allocation {
-    pub timesteps: Vec<u64>, // x coords, sorted
-    pub offsets: Vec<u64>,   // y coords, length same as `timesteps`
-    pub size: u64,           // height (sweep distance)
+    pub timesteps: U64s, // x coords, sorted
+    pub offsets: U64s,   // y coords, length same as `timesteps`
+    pub size: u64,       // height (sweep distance)
     pub peak_mem: u64,
     pub peak_timestamps: Vec<u64>, // reaches its peak at these timestamps
+    #[serde(default)]
+    pub category: Option<Category>, // for snapshots recording it
 }
 
 impl Display for Allocation {
@@ -22,6 +125,9 @@ impl Display for Allocation {
         writeln!(f, "|- Size: {}", format_bytes(self.size as i64))?;
         writeln!(f, "|- Peak Memory: {}", format_bytes(self.peak_mem as i64))?;
         writeln!(f, "|- Peak Timestamps: {:?}", self.peak_timestamps)?;
+        if let Some(category) = self.category {
+            writeln!(f, "|- Category: {}", category.name())?;
+        }
         writeln!(
             f,
             "|- Timesteps: start {}, stop {}",
@@ -37,23 +143,64 @@ impl Display for Allocation {
 }
 
 impl Allocation {
+    /// Computes peak memory and the timestamps it is reached at from the geometry
+    pub fn new(timesteps: U64s, offsets: U64s, size: u64, category: Option<Category>) -> Self {
+        let peak_base = offsets.iter().max().copied().unwrap_or(0);
+        let peak_timestamps = timesteps
+            .iter()
+            .zip(offsets.iter())
+            .filter_map(|(&timestamp, &offset)| {
+                if offset == peak_base {
+                    // if this timestep has peak memory
+                    Some(timestamp)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Allocation {
+            timesteps,
+            offsets,
+            size,
+            peak_mem: peak_base + size,
+            peak_timestamps,
+            category,
+        }
+    }
+
+    /// Whether the allocation is alive at any point in `start..=stop`
     pub fn is_alive_in_interval(&self, start: u64, stop: u64) -> bool {
-        self.is_alive_at(start) && self.is_alive_at(stop)
+        let (alloc_start, alloc_end) = self.start_end_time();
+        alloc_start <= stop && start <= alloc_end
     }
 
     pub fn is_alive_at(&self, timestamp: u64) -> bool {
-        self.timesteps[0] <= timestamp && timestamp <= *self.timesteps.last().unwrap()
+        let (start, end) = self.start_end_time();
+        start <= timestamp && timestamp <= end
     }
 
+    /// First and last timestep, 0 for allocations without any (from custom loaders)
     pub fn start_end_time(&self) -> (u64, u64) {
-        (self.timesteps[0], *self.timesteps.last().unwrap())
+        let first = self.timesteps.first().copied().unwrap_or(0);
+        (first, self.timesteps.last().copied().unwrap_or(first))
     }
 }
 
+/// Where an allocation is drawn when the plot is split into one lane per CUDA stream
+#[derive(Debug, Clone)]
+pub struct LaneData {
+    pub stream: i64,
+    /// Like `Allocation::offsets`, but stacking only allocations on the same stream
+    pub lane_offsets: Vec<u64>,
+}
+
 // Intermediate struct to help parse the structure of allocations.json
 #[derive(Deserialize)]
 pub struct RawAllocationData {
     pub timesteps: Vec<u64>,
     pub offsets: Vec<u64>,
     pub size: u64,
+    #[serde(default)]
+    pub category: Option<Category>,
 }
@@ -0,0 +1,126 @@
+use crate::constants::ANNOTATIONS_FILE_NAME;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A user label at a timestamp, e.g. "optimizer.step()"
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub timestamp: u64,
+    pub label: String,
+}
+
+/// Annotations of a snapshot, persisted to annotations.json in its directory.
+/// Changed at runtime via `--annotate` commands over IPC.
+#[derive(Debug)]
+pub struct Annotations {
+    path: PathBuf,
+    /// Sorted by timestamp
+    pub items: Vec<Annotation>,
+}
+
+impl Annotations {
+    /// Empty if the file does not exist or cannot be parsed
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(ANNOTATIONS_FILE_NAME);
+        let items = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                log::warn!("Ignoring malformed {:?}: {}", path, e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self { path, items }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        std::fs::write(&self.path, serde_json::to_string_pretty(&self.items)?)?;
+        Ok(())
+    }
+
+    /// Applies an annotate command (the part after `--annotate`), e.g.
+    /// - `` (nothing): list annotations
+    /// - `12345 forward starts here`
+    /// - `remove 12345`
+    /// - `clear`
+    ///
+    /// ## Returns
+    /// A message listing the annotations.
+    pub fn apply_command(&mut self, command: &str) -> anyhow::Result<String> {
+        let command = command.trim();
+        let (first, rest) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        let rest = rest.trim();
+        match (first, rest) {
+            ("", _) => return Ok(self.to_string()),
+            ("clear", "") => self.items.clear(),
+            ("remove", timestamp) => {
+                let timestamp: u64 = timestamp.parse()?;
+                let before = self.items.len();
+                self.items.retain(|a| a.timestamp != timestamp);
+                if self.items.len() == before {
+                    anyhow::bail!("No annotation at timestamp {}", timestamp);
+                }
+            }
+            (timestamp, label) if !label.is_empty() => {
+                let timestamp: u64 = timestamp.parse().map_err(|_| {
+                    anyhow::anyhow!("Invalid timestamp '{}', expected an integer", timestamp)
+                })?;
+                let at = self.items.partition_point(|a| a.timestamp <= timestamp);
+                self.items.insert(
+                    at,
+                    Annotation {
+                        timestamp,
+                        label: label.to_string(),
+                    },
+                );
+            }
+            _ => anyhow::bail!(
+                "Usage:\n    --annotate <timestamp> <label>\n    --annotate remove <timestamp>\n    --annotate clear\n    --annotate"
+            ),
+        }
+
+        self.save()?;
+        Ok(self.to_string())
+    }
+}
+
+impl std::fmt::Display for Annotations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.items.is_empty() {
+            return write!(f, "No annotations");
+        }
+        writeln!(f, "Annotations:")?;
+        for a in self.items.iter() {
+            writeln!(f, "    {}: {}", a.timestamp, a.label)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::annotation::Annotations;
+
+    #[test]
+    fn test_annotate_commands_persist() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-annotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut annotations = Annotations::load(&dir);
+        annotations.apply_command("200 optimizer.step()").unwrap();
+        annotations
+            .apply_command("100 forward starts here")
+            .unwrap();
+        assert!(annotations.apply_command("remove 300").is_err());
+        assert!(annotations.apply_command("abc label").is_err());
+
+        let loaded = Annotations::load(&dir);
+        let labels: Vec<_> = loaded.items.iter().map(|a| a.label.as_str()).collect();
+        assert_eq!(labels, vec!["forward starts here", "optimizer.step()"]);
+
+        annotations.apply_command("clear").unwrap();
+        assert!(Annotations::load(&dir).items.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
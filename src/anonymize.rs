@@ -0,0 +1,230 @@
+use crate::constants::{
+    ALLOCATIONS_BIN_FILE_NAME, ALLOCATIONS_FILE_NAME, ALLOCATIONS_SHARDS_FILE_NAME,
+    ALLOCATIONS_ZST_FILE_NAME, CALLSTACKS_TABLE, ELEMENT_DB_FILENAME, METADATA_FILE_NAME,
+};
+use crate::load::ShardIndex;
+use crate::utils::get_spinner;
+use log::info;
+use rusqlite::{Connection, Transaction};
+use std::path::Path;
+
+/// Copies a converted snapshot directory from `src` to `dst`, replacing file paths and function
+/// names in callstacks with hashes. Sizes, timelines and callstack structure are kept, so the
+/// result still reproduces rendering issues without leaking proprietary code.
+///
+/// Names are hashed with BLAKE3 keyed by `salt`, truncated to 64 bits. Without a salt a random
+/// key is drawn for this export, so hashes cannot be matched against guessed names or across
+/// exports; pass the same salt to get stable hashes, e.g. to compare two anonymized runs.
+///
+/// ## Returns
+/// Number of callstacks rewritten, including those of allocator events.
+pub fn anonymize_dir(src: &Path, dst: &Path, salt: Option<&str>) -> anyhow::Result<usize> {
+    if src == dst {
+        anyhow::bail!("Refusing to anonymize {:?} in place", src);
+    }
+    std::fs::create_dir_all(dst)?;
+
+    let shards = match std::fs::read(src.join(ALLOCATIONS_SHARDS_FILE_NAME)) {
+        Ok(index) => serde_json::from_slice::<ShardIndex>(&index)?.shards,
+        Err(_) => Vec::new(),
+    };
+    for file_name in [
+        ELEMENT_DB_FILENAME,
+        ALLOCATIONS_FILE_NAME,
+        ALLOCATIONS_ZST_FILE_NAME,
+        ALLOCATIONS_SHARDS_FILE_NAME,
+        ALLOCATIONS_BIN_FILE_NAME,
+        METADATA_FILE_NAME,
+    ]
+    .into_iter()
+    .chain(shards.iter().map(String::as_str))
+    {
+        let src_file = src.join(file_name);
+        if src_file.exists() {
+            info!("Copying {:?}", src_file);
+            std::fs::copy(&src_file, dst.join(file_name))?;
+        }
+    }
+
+    let key = anonymize_key(salt);
+    let bar = get_spinner("Anonymizing callstacks...")?;
+    let mut conn = Connection::open(dst.join(ELEMENT_DB_FILENAME))?;
+    let tx = conn.transaction()?;
+    let has_table = |name: &str| -> rusqlite::Result<bool> {
+        tx.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [name],
+            |row| row.get(0),
+        )
+    };
+    let interned = has_table(CALLSTACKS_TABLE)?;
+    // the event strip's oom and segment_free events keep their own callstack
+    let has_events = has_table("events")?;
+    // each unique callstack once, or each allocation's before callstacks were interned
+    let (select, update) = match interned {
+        true => (
+            format!("SELECT id, callstack FROM {}", CALLSTACKS_TABLE),
+            format!(
+                "UPDATE {} SET callstack = ?1 WHERE id = ?2",
+                CALLSTACKS_TABLE
+            ),
+        ),
+        false => (
+            "SELECT idx, callstack FROM allocs".to_string(),
+            "UPDATE allocs SET callstack = ?1 WHERE idx = ?2".to_string(),
+        ),
+    };
+    let mut count = rewrite_callstacks(&tx, &select, &update, &key)?;
+    if has_events {
+        count += rewrite_callstacks(
+            &tx,
+            "SELECT idx, callstack FROM events",
+            "UPDATE events SET callstack = ?1 WHERE idx = ?2",
+            &key,
+        )?;
+    }
+    tx.commit()?;
+    // do not leave the original text in free pages
+    conn.execute_batch("VACUUM")?;
+    bar.finish();
+
+    Ok(count)
+}
+
+/// Anonymizes the `(id, callstack)` rows returned by `select` with `update`
+fn rewrite_callstacks(
+    tx: &Transaction,
+    select: &str,
+    update: &str,
+    key: &[u8; 32],
+) -> anyhow::Result<usize> {
+    let rows: Vec<(i64, Option<String>)> = {
+        let mut stmt = tx.prepare(select)?;
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?
+    };
+    let mut update = tx.prepare(update)?;
+    for (idx, callstack) in rows.iter() {
+        if let Some(callstack) = callstack {
+            update.execute((anonymize_callstack(callstack, key), idx))?;
+        }
+    }
+    Ok(rows.len())
+}
+
+/// Key for [`anonymize_callstack`]: derived from `salt` when given, random otherwise.
+pub fn anonymize_key(salt: Option<&str>) -> [u8; 32] {
+    match salt {
+        Some(salt) => {
+            blake3::derive_key("snapviewer anonymize 2025 callstack salt", salt.as_bytes())
+        }
+        None => rand::random(),
+    }
+}
+
+/// Frames are formatted as `(index) filename:line:name` by the converter;
+/// filename and name are hashed, anything unrecognized is hashed as a whole.
+pub fn anonymize_callstack(callstack: &str, key: &[u8; 32]) -> String {
+    callstack
+        .lines()
+        .map(|frame| {
+            let Some((index, location)) = frame.split_once(") ") else {
+                return hash_str(frame, key);
+            };
+            let mut parts = location.rsplitn(3, ':');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(name), Some(line), Some(filename)) => format!(
+                    "{}) {}:{}:{}",
+                    index,
+                    hash_str(filename, key),
+                    line,
+                    hash_str(name, key)
+                ),
+                _ => format!("{}) {}", index, hash_str(location, key)),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn hash_str(text: &str, key: &[u8; 32]) -> String {
+    blake3::keyed_hash(key, text.as_bytes()).to_hex()[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::anonymize::{anonymize_callstack, anonymize_dir, anonymize_key};
+    use crate::constants::ELEMENT_DB_FILENAME;
+    use crate::convert::{TraceEvent, make_db, process_trace};
+    use crate::pickle::Value;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_anonymize_callstack() {
+        let callstack = "(0) /secret/model.py:42:forward\n(1) ??:0:??";
+        let key = anonymize_key(Some("bug-1234"));
+        let anonymized = anonymize_callstack(callstack, &key);
+        let frames: Vec<&str> = anonymized.lines().collect();
+
+        assert_eq!(frames.len(), 2);
+        assert!(!anonymized.contains("secret") && !anonymized.contains("forward"));
+        assert!(frames[0].starts_with("(0) ") && frames[0].contains(":42:"));
+        assert!(frames[1].starts_with("(1) ") && frames[1].contains(":0:"));
+        // same input and salt, same hash: structure stays comparable across frames and runs
+        assert_eq!(anonymized, anonymize_callstack(callstack, &key));
+        // a different or random salt hashes differently
+        assert_ne!(
+            anonymized,
+            anonymize_callstack(callstack, &anonymize_key(Some("other")))
+        );
+        assert_ne!(
+            anonymized,
+            anonymize_callstack(callstack, &anonymize_key(None))
+        );
+    }
+
+    #[test]
+    fn test_anonymize_dir_leaves_no_frames() {
+        let frames = Value::List(Rc::from([Value::Dict(Rc::from([
+            (
+                Value::Str("filename".into()),
+                Value::Str("/secret/model.py".into()),
+            ),
+            (Value::Str("line".into()), Value::Int(42)),
+            (Value::Str("name".into()), Value::Str("forward_pass".into())),
+        ]))]));
+        let event = |action: &str, addr| TraceEvent {
+            action: Rc::from(action),
+            addr,
+            size: 100,
+            stream: 0,
+            time_us: None,
+            category: None,
+            frames: frames.clone(),
+        };
+        let trace = [
+            event("alloc", Some(1)),
+            event("oom", None),
+            event("free", Some(1)),
+        ];
+        let converted = process_trace(&trace, None).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("snapviewer-anon-{}", std::process::id()));
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        std::fs::create_dir_all(&src).unwrap();
+        make_db(&trace, &converted, &src.join(ELEMENT_DB_FILENAME)).unwrap();
+        let count = anonymize_dir(&src, &dst, None).unwrap();
+        let original = std::fs::read(src.join(ELEMENT_DB_FILENAME)).unwrap();
+        let anonymized = std::fs::read(dst.join(ELEMENT_DB_FILENAME)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // the interned allocation callstack and the oom event's
+        assert_eq!(count, 2);
+        let contains = |db: &[u8], text: &str| db.windows(text.len()).any(|w| w == text.as_bytes());
+        for text in ["/secret/model.py", "forward_pass"] {
+            assert!(contains(&original, text));
+            // not in any table, index or free page
+            assert!(!contains(&anonymized, text));
+        }
+    }
+}
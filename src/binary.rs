@@ -0,0 +1,164 @@
+//! allocations.bin: allocation geometry as little-endian u64s, written by `convert --binary` for
+//! gui.py's cache. It is memory-mapped when loaded: timesteps and offsets are viewed in place
+//! rather than parsed from JSON or read from SQLite row by row, so they take page cache instead of
+//! heap and only the record headers are read at startup.
+//!
+//! Layout: the magic, the number of allocations, then for each allocation its size, category
+//! (index into `Category::ALL`, `NO_CATEGORY` for none), number of points, timesteps and offsets.
+//! Every value is 8 bytes, so all of them are aligned in the (page-aligned) map.
+
+use crate::allocation::{Allocation, Category, RawAllocationData, U64s};
+use anyhow::{Context, bail};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 8] = b"SNAPBIN1";
+const NO_CATEGORY: u64 = u64::MAX;
+
+pub fn write_allocations(path: &Path, allocs: &[RawAllocationData]) -> anyhow::Result<()> {
+    // renamed when complete, so an interrupted conversion leaves no truncated file to load
+    let tmp_path = path.with_extension("bin.tmp");
+    let mut out = BufWriter::new(File::create(&tmp_path)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&(allocs.len() as u64).to_le_bytes())?;
+    for alloc in allocs {
+        let category = alloc.category.map_or(NO_CATEGORY, |c| {
+            Category::ALL.iter().position(|&x| x == c).unwrap() as u64
+        });
+        let header = [alloc.size, category, alloc.timesteps.len() as u64];
+        for value in header.iter().chain(&alloc.timesteps).chain(&alloc.offsets) {
+            out.write_all(&value.to_le_bytes())?;
+        }
+    }
+    out.into_inner()?.sync_all()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Byte `start` plus `len` u64s, an error instead of overflowing for corrupt lengths
+fn end_of(start: usize, len: usize) -> anyhow::Result<usize> {
+    len.checked_mul(8)
+        .and_then(|bytes| bytes.checked_add(start))
+        .context("Corrupt length, the file is damaged")
+}
+
+/// Views `len` u64s at byte `start` of the map, copying them only if the host cannot view them
+fn u64s(map: &Arc<memmap2::Mmap>, start: usize, len: usize) -> anyhow::Result<U64s> {
+    if let Some(values) = U64s::mapped(map, start, len) {
+        return Ok(values);
+    }
+    let bytes = map
+        .get(start..end_of(start, len)?)
+        .context("Unexpected end of file")?;
+    Ok(bytes
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+fn u64_at(map: &memmap2::Mmap, start: usize) -> anyhow::Result<u64> {
+    let bytes = map
+        .get(start..start + 8)
+        .context("Unexpected end of file")?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+pub fn map_allocations(path: &Path) -> anyhow::Result<Arc<[Allocation]>> {
+    let file = File::open(path)?;
+    // SAFETY: allocations.bin is written to a temporary file and renamed, never modified in place
+    let map = Arc::new(unsafe { memmap2::Mmap::map(&file)? });
+    if map.get(..8) != Some(&MAGIC[..]) {
+        bail!("{:?} is not an allocations.bin file", path);
+    }
+    let count = u64_at(&map, 8)?;
+    // every allocation takes at least its 24 byte header, more cannot fit in a truncated file
+    if count > (map.len() as u64 - 16) / 24 {
+        bail!(
+            "{:?} claims {} allocations but holds only {} bytes, it is truncated or damaged",
+            path,
+            count,
+            map.len()
+        );
+    }
+    let mut pos = 16;
+    (0..count)
+        .map(|i| {
+            let size = u64_at(&map, pos)?;
+            let category = Category::ALL.get(u64_at(&map, pos + 8)? as usize).copied();
+            let points = usize::try_from(u64_at(&map, pos + 16)?)?;
+            if points == 0 {
+                bail!("Allocation {} has no timesteps", i);
+            }
+            let timesteps = u64s(&map, pos + 24, points)?;
+            let offsets_start = end_of(pos + 24, points)?;
+            let offsets = u64s(&map, offsets_start, points)?;
+            pos = end_of(offsets_start, points)?;
+            Ok(Allocation::new(timesteps, offsets, size, category))
+        })
+        .collect::<anyhow::Result<_>>()
+        .with_context(|| format!("Reading {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::{Category, RawAllocationData, U64s};
+    use crate::binary::{map_allocations, write_allocations};
+
+    #[test]
+    fn test_allocations_round_trip() {
+        let allocs = [
+            RawAllocationData {
+                timesteps: vec![0, 4, 7],
+                offsets: vec![0, 512, 0],
+                size: 1 << 40,
+                category: Some(Category::Gradient),
+            },
+            RawAllocationData {
+                timesteps: vec![2, 3],
+                offsets: vec![64, 64],
+                size: 3,
+                category: None,
+            },
+        ];
+        let path = std::env::temp_dir().join(format!("snapviewer-{}.bin", std::process::id()));
+        write_allocations(&path, &allocs).unwrap();
+        let read = map_allocations(&path).unwrap();
+        assert!(matches!(read[0].timesteps, U64s::Mapped(..)));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.len(), 2);
+        assert_eq!(*read[0].offsets, [0, 512, 0]);
+        assert_eq!(read[0].peak_mem, 512 + (1 << 40));
+        assert_eq!(read[0].size, 1 << 40);
+        assert_eq!(read[0].category, Some(Category::Gradient));
+        assert_eq!(*read[1].timesteps, [2, 3]);
+        assert_eq!(read[1].category, None);
+    }
+
+    #[test]
+    fn test_corrupt_lengths_are_errors() {
+        let path =
+            std::env::temp_dir().join(format!("snapviewer-corrupt-{}.bin", std::process::id()));
+        let file = |values: &[u64]| {
+            let mut bytes = b"SNAPBIN1".to_vec();
+            for value in values {
+                bytes.extend(value.to_le_bytes());
+            }
+            std::fs::write(&path, bytes).unwrap();
+            map_allocations(&path).is_err()
+        };
+        // an allocation count the file cannot hold
+        let huge_count = file(&[u64::MAX, 1, 0, 1]);
+        // a point count that overflows when converted to bytes
+        let huge_points = file(&[1, 1, 0, u64::MAX / 4]);
+        // a point count running past the end of the file
+        let truncated = file(&[1, 1, 0, 3, 0, 1]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(huge_count);
+        assert!(huge_points);
+        assert!(truncated);
+    }
+}
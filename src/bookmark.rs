@@ -0,0 +1,141 @@
+use crate::constants::BOOKMARKS_FILE_NAME;
+use crate::window_transform::{CameraState, WindowTransform};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A named camera position, e.g. "peak1"
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    pub camera: CameraState,
+}
+
+/// Camera bookmarks of a snapshot, persisted to bookmarks.json in its directory.
+/// Changed at runtime via `--bookmark` commands over IPC.
+#[derive(Debug)]
+pub struct Bookmarks {
+    path: PathBuf,
+    /// In the order they were saved
+    pub items: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    /// Empty if the file does not exist or cannot be parsed
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(BOOKMARKS_FILE_NAME);
+        let items = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                log::warn!("Ignoring malformed {:?}: {}", path, e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self { path, items }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        std::fs::write(&self.path, serde_json::to_string_pretty(&self.items)?)?;
+        Ok(())
+    }
+
+    /// Applies a bookmark command (the part after `--bookmark`), e.g.
+    /// - `` (nothing): list bookmarks
+    /// - `save peak1`: remember the current camera, replacing a bookmark of the same name
+    /// - `go peak1`: move the camera to a bookmark
+    /// - `remove peak1`
+    pub fn apply_command(
+        &mut self,
+        command: &str,
+        win_trans: &mut WindowTransform,
+    ) -> anyhow::Result<String> {
+        let command = command.trim();
+        let (first, name) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        let name = name.trim();
+        match (first, name) {
+            ("", _) => return Ok(self.to_string()),
+            ("go", name) if !name.is_empty() => {
+                let bookmark = self
+                    .items
+                    .iter()
+                    .find(|b| b.name == name)
+                    .ok_or_else(|| anyhow::anyhow!("No bookmark named '{}'", name))?;
+                win_trans.set_camera_state(&bookmark.camera);
+                return Ok(format!("Moved to bookmark '{}'", name));
+            }
+            ("save", name) if !name.is_empty() => {
+                let bookmark = Bookmark {
+                    name: name.to_string(),
+                    camera: win_trans.camera_state(),
+                };
+                match self.items.iter_mut().find(|b| b.name == name) {
+                    Some(existing) => *existing = bookmark,
+                    None => self.items.push(bookmark),
+                }
+            }
+            ("remove", name) if !name.is_empty() => {
+                let before = self.items.len();
+                self.items.retain(|b| b.name != name);
+                if self.items.len() == before {
+                    anyhow::bail!("No bookmark named '{}'", name);
+                }
+            }
+            _ => anyhow::bail!(
+                "Usage:\n    --bookmark save <name>\n    --bookmark go <name>\n    --bookmark remove <name>\n    --bookmark"
+            ),
+        }
+
+        self.save()?;
+        Ok(self.to_string())
+    }
+}
+
+impl std::fmt::Display for Bookmarks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.items.is_empty() {
+            return write!(f, "No bookmarks");
+        }
+        writeln!(f, "Bookmarks:")?;
+        for b in self.items.iter() {
+            writeln!(
+                f,
+                "    {}: center ({:.3}, {:.3}), zoom {:.2}",
+                b.name, b.camera.center.0, b.camera.center.1, b.camera.zoom
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bookmark::Bookmarks;
+    use crate::window_transform::WindowTransform;
+
+    #[test]
+    fn test_bookmarks_restore_across_resolutions() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-bookmark-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut win_trans = WindowTransform::new((1000, 500), 1.0);
+        win_trans.zoom = 4.0;
+        win_trans.center.x = 250.0;
+        let mut bookmarks = Bookmarks::load(&dir);
+        bookmarks
+            .apply_command("save peak1", &mut win_trans)
+            .unwrap();
+        assert!(bookmarks.apply_command("go peak2", &mut win_trans).is_err());
+
+        // restored in a renderer with twice the width
+        let mut other = WindowTransform::new((2000, 500), 1.0);
+        let mut loaded = Bookmarks::load(&dir);
+        loaded.apply_command("go peak1", &mut other).unwrap();
+        assert_eq!(other.zoom, 4.0);
+        assert_eq!(other.center.x, 500.0);
+
+        loaded.apply_command("remove peak1", &mut other).unwrap();
+        assert!(Bookmarks::load(&dir).items.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
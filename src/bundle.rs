@@ -0,0 +1,113 @@
+//! A converted snapshot zipped into a single file to share, holding the allocations as the
+//! renderer has them loaded: a snapshot filtered at load time (`load::filter_allocations`) or
+//! merged from ranks is bundled as it is drawn. `python gui.py --dir bundle.zip` opens it.
+
+use crate::{
+    constants::{
+        ANNOTATIONS_FILE_NAME, BOOKMARKS_FILE_NAME, ELEMENT_DB_FILENAME, METADATA_FILE_NAME,
+        TIMELINE_TABLE,
+    },
+    database::sqlite::AllocationDatabase,
+};
+use anyhow::bail;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+
+/// Files of a converted directory bundled next to elements.db, if it has them
+const BUNDLED_FILES: [&str; 3] = [
+    METADATA_FILE_NAME,
+    ANNOTATIONS_FILE_NAME,
+    BOOKMARKS_FILE_NAME,
+];
+
+/// Zips `db` (the one loaded from `dir`, or its filtered copy) with the metadata, annotations and
+/// bookmarks in `dir` to `out`. The database carries the allocation geometry in its timeline
+/// table, so nothing else is needed to open it. Returns the number of allocations bundled.
+pub fn write_bundle(db: &AllocationDatabase, dir: &Path, out: &Path) -> anyhow::Result<usize> {
+    if !db.has_elements()? {
+        bail!(
+            "{} has no {}, there is nothing to bundle",
+            dir.display(),
+            ELEMENT_DB_FILENAME
+        );
+    }
+    if !db.has_timeline()? {
+        bail!(
+            "{} predates the {} table in {}, re-convert it to bundle it",
+            dir.display(),
+            TIMELINE_TABLE,
+            ELEMENT_DB_FILENAME
+        );
+    }
+    let mut tmp_path = out.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let db_path = Path::new(&tmp_path).with_extension("db.tmp");
+    if db_path.exists() {
+        std::fs::remove_file(&db_path)?;
+    }
+    // a compact copy, whether the database is on disk or a temporary one
+    db.conn
+        .execute("VACUUM INTO ?1", [db_path.to_string_lossy()])?;
+
+    let written = (|| -> anyhow::Result<()> {
+        let mut zip = zip::ZipWriter::new(BufWriter::new(File::create(&tmp_path)?));
+        let db_size = std::fs::metadata(&db_path)?.len();
+        let options = SimpleFileOptions::default().large_file(db_size >= u32::MAX as u64);
+        zip.start_file(ELEMENT_DB_FILENAME, options)?;
+        std::io::copy(&mut File::open(&db_path)?, &mut zip)?;
+        for name in BUNDLED_FILES {
+            if let Ok(bytes) = std::fs::read(dir.join(name)) {
+                zip.start_file(name, SimpleFileOptions::default())?;
+                zip.write_all(&bytes)?;
+            }
+        }
+        zip.finish()?.flush()?;
+        Ok(())
+    })();
+    std::fs::remove_file(&db_path)?;
+    written?;
+    std::fs::rename(&tmp_path, out)?;
+    db.row_count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bundle::write_bundle;
+    use crate::convert::{ConvertOptions, convert_to_dir};
+    use crate::database::sqlite::AllocationDatabase;
+    use crate::load::load_allocations;
+
+    #[test]
+    fn test_bundle_opens_like_its_directory() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("samples.csv");
+        std::fs::write(&csv, "time_us,used_bytes\n0,100\n10,300\n20,0\n").unwrap();
+        let converted = dir.join("converted");
+        convert_to_dir(&csv, &converted, 0, ConvertOptions::default()).unwrap();
+
+        let db = AllocationDatabase::from_dir(&converted).unwrap();
+        let bundle = dir.join("bundle.zip");
+        let count = write_bundle(&db, &converted, &bundle).unwrap();
+        let extracted = dir.join("extracted");
+        zip::ZipArchive::new(std::fs::File::open(&bundle).unwrap())
+            .unwrap()
+            .extract(&extracted)
+            .unwrap();
+        let opened = AllocationDatabase::from_dir(&extracted).unwrap();
+        let sizes = |dir, db| {
+            let allocs = load_allocations(dir, db).unwrap();
+            allocs.iter().map(|a| a.size).collect::<Vec<_>>()
+        };
+        let (original, reopened) = (sizes(&converted, &db), sizes(&extracted, &opened));
+        let has_metadata = extracted.join("metadata.json").exists();
+        drop((db, opened));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(original, reopened);
+        assert!(has_metadata);
+    }
+}
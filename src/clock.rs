@@ -0,0 +1,105 @@
+use crate::constants::METADATA_FILE_NAME;
+use anyhow::bail;
+use std::path::Path;
+
+/// Wall-clock time of the trace's timesteps, which are otherwise just event counts.
+/// Built from the clock table, one (timestep, time) sample per allocator action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    /// Sorted
+    steps: Vec<u64>,
+    /// Microseconds since the first sample, same length as `steps`
+    times_us: Vec<f64>,
+}
+
+/// Microseconds per unit of the raw times, for the `time_unit` in metadata.json
+pub fn unit_scale(unit: &str) -> anyhow::Result<f64> {
+    match unit {
+        "ns" => Ok(1e-3),
+        "us" => Ok(1.0),
+        "ms" => Ok(1e3),
+        "s" => Ok(1e6),
+        _ => bail!("Unknown time unit `{}`, expected ns, us, ms or s", unit),
+    }
+}
+
+/// `time_unit` of the snapshot's metadata.json, microseconds if it is missing
+pub fn read_time_unit(dir: &Path) -> String {
+    std::fs::read_to_string(dir.join(METADATA_FILE_NAME))
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+        .and_then(|metadata| metadata["time_unit"].as_str().map(str::to_string))
+        .unwrap_or_else(|| "us".to_string())
+}
+
+/// "850 µs", "12.30 ms", "3.05 s"
+pub fn format_duration_us(us: f64) -> String {
+    let sign = if us < 0.0 { "-" } else { "" };
+    let us = us.abs();
+    if us < 1e3 {
+        format!("{}{:.0} µs", sign, us)
+    } else if us < 1e6 {
+        format!("{}{:.2} ms", sign, us / 1e3)
+    } else {
+        format!("{}{:.2} s", sign, us / 1e6)
+    }
+}
+
+impl Clock {
+    /// `samples` are (timestep, raw time) in `unit`. `None` without samples, e.g. for snapshots
+    /// whose events carry no times.
+    pub fn new(mut samples: Vec<(u64, i64)>, unit: &str) -> anyhow::Result<Option<Self>> {
+        let scale = unit_scale(unit)?;
+        samples.sort_unstable_by_key(|&(step, _)| step);
+        let Some(&(_, t0)) = samples.first() else {
+            return Ok(None);
+        };
+        let (steps, times_us) = samples
+            .into_iter()
+            .map(|(step, t)| (step, (t - t0) as f64 * scale))
+            .unzip();
+        Ok(Some(Self { steps, times_us }))
+    }
+
+    /// Time since the first sample at `timestep`, interpolated between samples
+    pub fn elapsed_us(&self, timestep: u64) -> f64 {
+        let i = self.steps.partition_point(|&s| s <= timestep);
+        if i == 0 {
+            return self.times_us[0];
+        }
+        if i == self.steps.len() {
+            return self.times_us[i - 1];
+        }
+        let (s0, s1) = (self.steps[i - 1] as f64, self.steps[i] as f64);
+        let (t0, t1) = (self.times_us[i - 1], self.times_us[i]);
+        t0 + (t1 - t0) * (timestep as f64 - s0) / (s1 - s0)
+    }
+
+    pub fn format(&self, timestep: u64) -> String {
+        format_duration_us(self.elapsed_us(timestep))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::{Clock, format_duration_us};
+
+    #[test]
+    fn test_clock() {
+        assert_eq!(format_duration_us(850.0), "850 µs");
+        assert_eq!(format_duration_us(12_300.0), "12.30 ms");
+        assert_eq!(format_duration_us(3_050_000.0), "3.05 s");
+
+        let clock = Clock::new(vec![(10, 2_000), (0, 1_000), (20, 4_000)], "ms")
+            .unwrap()
+            .unwrap();
+        assert_eq!(clock.elapsed_us(0), 0.0);
+        assert_eq!(clock.elapsed_us(5), 500_000.0);
+        assert_eq!(clock.elapsed_us(15), 2_000_000.0);
+        assert_eq!(clock.elapsed_us(100), 3_000_000.0);
+        assert_eq!(clock.format(15), "2.00 s");
+
+        assert!(Clock::new(vec![], "us").unwrap().is_none());
+        assert!(Clock::new(vec![(0, 0)], "fortnights").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    allocation::{Allocation, Category},
+    palette::Palette,
+    utils::format_bytes,
+};
+use anyhow::bail;
+use std::str::FromStr;
+use three_d::Srgba;
+
+/// Same alpha as the random allocation colors
+pub const ALPHA: u8 = 30;
+/// Low to high end of the color ramp
+const RAMP: [(u8, u8, u8); 4] = [(40, 70, 200), (40, 170, 170), (230, 200, 40), (220, 40, 40)];
+/// Swatches the legend's gradient bar is drawn with
+const LEGEND_STEPS: usize = 32;
+/// Colors of `Category::ALL`, then of allocations without a category
+const CATEGORY_COLORS: [(u8, u8, u8); 8] = [
+    (31, 119, 180),
+    (255, 127, 14),
+    (44, 160, 44),
+    (214, 39, 40),
+    (148, 103, 189),
+    (140, 86, 75),
+    (227, 119, 194),
+    (127, 127, 127),
+];
+
+/// What an allocation's color encodes
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorMode {
+    /// Random colors, see `--color-seed`
+    Random,
+    /// Size, on a log scale
+    Size,
+    /// Timesteps between allocation and free
+    Lifetime,
+    /// Colors from a palette file, see `--palette`
+    Palette(Palette),
+    /// PyTorch's category (parameter, activation, gradient, ...), for snapshots recording it
+    Category,
+}
+
+impl FromStr for ColorMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "random" => Ok(ColorMode::Random),
+            "size" => Ok(ColorMode::Size),
+            "lifetime" => Ok(ColorMode::Lifetime),
+            "category" => Ok(ColorMode::Category),
+            _ => bail!(
+                "Unknown color mode `{}`, expected random, size, lifetime or category",
+                s
+            ),
+        }
+    }
+}
+
+/// Color key for the viewer's corner, generated from the active color mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct Legend {
+    pub title: String,
+    /// Gradient from the low to the high end, drawn left to right
+    pub gradient: Vec<Srgba>,
+    /// Labels spread evenly below the gradient
+    pub labels: Vec<String>,
+    /// Discrete colors and their names, listed instead of the gradient if not empty
+    pub keys: Vec<(Srgba, String)>,
+}
+
+/// Color of `t` (0~1) along the ramp
+pub fn ramp(t: f64) -> Srgba {
+    let t = t.clamp(0.0, 1.0) * (RAMP.len() - 1) as f64;
+    let i = (t as usize).min(RAMP.len() - 2);
+    let frac = t - i as f64;
+    let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * frac).round() as u8;
+    let (lo, hi) = (RAMP[i], RAMP[i + 1]);
+    Srgba::new(lerp(lo.0, hi.0), lerp(lo.1, hi.1), lerp(lo.2, hi.2), ALPHA)
+}
+
+/// Color of allocations in `category`, `None` for uncategorized ones
+pub fn category_color(category: Option<Category>) -> Srgba {
+    let i = match category {
+        Some(category) => Category::ALL.iter().position(|&c| c == category).unwrap(),
+        None => Category::ALL.len(),
+    };
+    let (r, g, b) = CATEGORY_COLORS[i];
+    Srgba::new(r, g, b, ALPHA)
+}
+
+fn lifetime(alloc: &Allocation) -> u64 {
+    match (alloc.timesteps.first(), alloc.timesteps.last()) {
+        (Some(start), Some(stop)) => stop - start,
+        _ => 0,
+    }
+}
+
+/// Range of the encoded quantity over `allocations`, `None` for `Random` or no allocations
+fn value_range(mode: &ColorMode, allocations: &[Allocation]) -> Option<(f64, f64)> {
+    let value: fn(&Allocation) -> f64 = match mode {
+        ColorMode::Random | ColorMode::Palette(_) | ColorMode::Category => return None,
+        // log scale, sizes are at least a byte
+        ColorMode::Size => |a| (a.size.max(1) as f64).ln(),
+        ColorMode::Lifetime => |a| lifetime(a) as f64,
+    };
+    allocations.iter().map(value).fold(None, |range, v| {
+        let (lo, hi) = range.unwrap_or((v, v));
+        Some((lo.min(v), hi.max(v)))
+    })
+}
+
+impl ColorMode {
+    /// Color of each allocation, `None` for `Random`
+    pub fn colors(&self, allocations: &[Allocation]) -> Option<Vec<Srgba>> {
+        if let ColorMode::Category = self {
+            return Some(
+                allocations
+                    .iter()
+                    .map(|a| category_color(a.category))
+                    .collect(),
+            );
+        }
+        if let ColorMode::Palette(palette) = self {
+            let colors = allocations
+                .iter()
+                .enumerate()
+                .map(|(i, a)| palette.color(i, a))
+                .collect();
+            return Some(colors);
+        }
+        let (lo, hi) = value_range(self, allocations)?;
+        let t = |v: f64| if hi > lo { (v - lo) / (hi - lo) } else { 0.5 };
+        let colors = allocations
+            .iter()
+            .map(|a| match self {
+                ColorMode::Size => ramp(t((a.size.max(1) as f64).ln())),
+                _ => ramp(t(lifetime(a) as f64)),
+            })
+            .collect();
+        Some(colors)
+    }
+
+    /// Legend for the colors `colors` gave `allocations`, `None` for `Random` and palettes
+    pub fn legend(&self, allocations: &[Allocation]) -> Option<Legend> {
+        if let ColorMode::Category = self {
+            return Some(category_legend(allocations));
+        }
+        let (lo, hi) = value_range(self, allocations)?;
+        let mid = (lo + hi) / 2.0;
+        let (title, labels) = match self {
+            ColorMode::Size => (
+                "size",
+                [lo, mid, hi].map(|v| format_bytes(v.exp().round() as i64)),
+            ),
+            _ => (
+                "lifetime (timesteps)",
+                [lo, mid, hi].map(|v| format!("{}", v.round() as u64)),
+            ),
+        };
+        let gradient = (0..LEGEND_STEPS)
+            .map(|i| ramp(i as f64 / (LEGEND_STEPS - 1) as f64))
+            .collect();
+        Some(Legend {
+            title: title.to_string(),
+            gradient,
+            labels: labels.to_vec(),
+            keys: Vec::new(),
+        })
+    }
+}
+
+/// The categories occurring in `allocations`, in `Category::ALL` order
+fn category_legend(allocations: &[Allocation]) -> Legend {
+    let present: std::collections::HashSet<_> = allocations.iter().map(|a| a.category).collect();
+    let keys = Category::ALL
+        .into_iter()
+        .map(Some)
+        .chain([None])
+        .filter(|c| present.contains(c))
+        .map(|c| {
+            let name = c.map_or("uncategorized", |c| c.name());
+            (category_color(c), name.to_string())
+        })
+        .collect();
+    Legend {
+        title: "category".to_string(),
+        gradient: Vec::new(),
+        labels: Vec::new(),
+        keys,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::{Allocation, Category};
+    use crate::color_mode::{ColorMode, category_color, ramp};
+
+    fn alloc(size: u64, start: u64, stop: u64) -> Allocation {
+        Allocation {
+            timesteps: vec![start, stop].into(),
+            offsets: vec![0, 0].into(),
+            size,
+            peak_mem: 0,
+            peak_timestamps: vec![],
+            category: None,
+        }
+    }
+
+    #[test]
+    fn test_color_mode_legend() {
+        let allocs = [alloc(1024, 0, 10), alloc(1024 * 1024, 5, 105)];
+        assert!(ColorMode::Random.colors(&allocs).is_none());
+        assert!(ColorMode::Random.legend(&allocs).is_none());
+
+        let colors = ColorMode::Size.colors(&allocs).unwrap();
+        assert_eq!(colors, vec![ramp(0.0), ramp(1.0)]);
+        let legend = ColorMode::Size.legend(&allocs).unwrap();
+        assert_eq!(legend.labels, ["1.00 KiB", "32.00 KiB", "1.00 MiB"]);
+        assert_eq!(legend.gradient.first(), Some(&ramp(0.0)));
+
+        let legend = ColorMode::Lifetime.legend(&allocs).unwrap();
+        assert_eq!(legend.labels, ["10", "55", "100"]);
+    }
+
+    #[test]
+    fn test_category_colors() {
+        let mut gradient = alloc(1024, 0, 10);
+        gradient.category = Some(Category::Gradient);
+        let allocs = [alloc(1024, 0, 10), gradient];
+
+        let colors = ColorMode::Category.colors(&allocs).unwrap();
+        assert_eq!(colors[0], category_color(None));
+        assert_eq!(colors[1], category_color(Some(Category::Gradient)));
+        assert_ne!(colors[0], colors[1]);
+
+        // only what occurs, categories first
+        let legend = ColorMode::Category.legend(&allocs).unwrap();
+        let names: Vec<_> = legend.keys.iter().map(|(_, name)| name.as_str()).collect();
+        assert_eq!(names, ["gradient", "uncategorized"]);
+        assert_eq!(
+            Category::parse("OPTIMIZER_STATE"),
+            Some(Category::OptimizerState)
+        );
+        assert_eq!(Category::parse("weights"), None);
+    }
+}
@@ -0,0 +1,144 @@
+//! `compact`: directories converted before elements.db had a timeline table (or with
+//! `convert --json`) keep allocation geometry in allocations.json, about as large as the
+//! database itself. Compacting moves it into the timeline table, which the renderer loads before
+//! the JSON, and deletes the JSON.
+
+use crate::{
+    constants::{
+        ALLOCATIONS_FILE_NAME, ALLOCATIONS_SHARDS_FILE_NAME, ALLOCATIONS_ZST_FILE_NAME,
+        ELEMENT_DB_FILENAME, TIMELINE_TABLE,
+    },
+    convert::pack_u64s,
+    database::sqlite::AllocationDatabase,
+    load::{ShardIndex, read_allocations, read_allocations_from_db},
+    manifest::Manifest,
+};
+use anyhow::bail;
+use std::path::Path;
+
+/// Stores the geometry of allocations.json (or its shards, or allocations.json.zst) in `dir` in
+/// the timeline table of elements.db unless it is there already, checks that the table loads
+/// the same allocations, then deletes the JSON files. Returns the number of bytes freed.
+pub fn compact_dir(dir: &Path) -> anyhow::Result<u64> {
+    if !dir.join(ELEMENT_DB_FILENAME).exists() {
+        bail!(
+            "{} has no {}, the allocations have nowhere else to go",
+            dir.display(),
+            ELEMENT_DB_FILENAME
+        );
+    }
+    let mut json_files: Vec<String> = [
+        ALLOCATIONS_FILE_NAME,
+        ALLOCATIONS_ZST_FILE_NAME,
+        ALLOCATIONS_SHARDS_FILE_NAME,
+    ]
+    .map(String::from)
+    .into();
+    if let Ok(index) = std::fs::read(dir.join(ALLOCATIONS_SHARDS_FILE_NAME)) {
+        json_files.extend(serde_json::from_slice::<ShardIndex>(&index)?.shards);
+    }
+    json_files.retain(|name| dir.join(name).exists());
+    if json_files.is_empty() {
+        return Ok(0);
+    }
+
+    let from_json = read_allocations(dir)?;
+    let db = AllocationDatabase::from_dir(dir)?;
+    let rows = db.row_count()?;
+    if rows != from_json.len() {
+        bail!(
+            "{} lists {} allocations but {} holds {}, they are not from the same conversion",
+            ELEMENT_DB_FILENAME,
+            rows,
+            json_files[0],
+            from_json.len()
+        );
+    }
+    if !db.has_timeline()? {
+        let tx = db.conn.unchecked_transaction()?;
+        tx.execute(
+            &format!(
+                "CREATE TABLE {} (idx INTEGER PRIMARY KEY, timesteps BLOB, offsets BLOB)",
+                TIMELINE_TABLE
+            ),
+            [],
+        )?;
+        {
+            let mut insert =
+                tx.prepare(&format!("INSERT INTO {} VALUES (?, ?, ?)", TIMELINE_TABLE))?;
+            for (idx, alloc) in from_json.iter().enumerate() {
+                insert.execute((idx, pack_u64s(&alloc.timesteps), pack_u64s(&alloc.offsets)))?;
+            }
+        }
+        tx.commit()?;
+    }
+    // the JSON is only deleted once the table is known to hold the same geometry
+    let from_db = read_allocations_from_db(dir, &db)?;
+    let same = from_db
+        .iter()
+        .zip(from_json.iter())
+        .all(|(a, b)| a.size == b.size && *a.timesteps == *b.timesteps && *a.offsets == *b.offsets);
+    if from_db.len() != from_json.len() || !same {
+        bail!(
+            "The {} table of {} differs from {}, keeping both",
+            TIMELINE_TABLE,
+            ELEMENT_DB_FILENAME,
+            json_files[0]
+        );
+    }
+    drop(db);
+
+    let mut freed = 0;
+    for name in &json_files {
+        let path = dir.join(name);
+        freed += std::fs::metadata(&path)?.len();
+        std::fs::remove_file(&path)?;
+    }
+    Manifest::forget(dir, &json_files)?;
+    Ok(freed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compact::compact_dir;
+    use crate::convert::{ConvertOptions, convert_to_dir};
+    use crate::database::sqlite::AllocationDatabase;
+    use crate::load::load_allocations;
+
+    #[test]
+    fn test_compact_moves_json_into_db() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-compact-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("samples.csv");
+        std::fs::write(&csv, "time_us,used_bytes\n0,100\n10,300\n20,0\n").unwrap();
+        let converted = dir.join("converted");
+        let options = ConvertOptions {
+            json: true,
+            ..Default::default()
+        };
+        convert_to_dir(&csv, &converted, 0, options).unwrap();
+        // as converted before the timeline table
+        let db = AllocationDatabase::from_dir(&converted).unwrap();
+        db.conn.execute("DROP TABLE timeline", []).unwrap();
+        let sizes = |db: &AllocationDatabase| {
+            let allocs = load_allocations(&converted, db).unwrap();
+            allocs.iter().map(|a| a.size).collect::<Vec<_>>()
+        };
+        let before = sizes(&db);
+        drop(db);
+
+        let freed = compact_dir(&converted).unwrap();
+        let db = AllocationDatabase::from_dir(&converted).unwrap();
+        let (after, has_timeline) = (sizes(&db), db.has_timeline().unwrap());
+        let json_left = converted.join("allocations.json").exists();
+        drop(db);
+        let again = compact_dir(&converted).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(freed > 0);
+        assert_eq!(before, after);
+        assert!(has_timeline);
+        assert!(!json_left);
+        assert_eq!(again, 0);
+    }
+}
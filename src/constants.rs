@@ -1,5 +1,116 @@
 pub const ALLOCATIONS_FILE_NAME: &str = "allocations.json";
+/// allocations.json compressed with zstd, read in its place
+pub const ALLOCATIONS_ZST_FILE_NAME: &str = "allocations.json.zst";
+pub const ALLOCATIONS_BIN_FILE_NAME: &str = "allocations.bin";
+/// Lists the files allocations.json was split into (`convert --json-shard-size`), read in its place
+pub const ALLOCATIONS_SHARDS_FILE_NAME: &str = "allocations_shards.json";
 pub const ELEMENT_DB_FILENAME: &str = "elements.db";
+pub const METADATA_FILE_NAME: &str = "metadata.json";
+/// Outputs of a conversion written so far, for resuming it, see `manifest::Manifest`
+pub const MANIFEST_FILE_NAME: &str = "convert_manifest.json";
+/// Version of the converter's output, recorded in metadata.json, reported by `--version` and part
+/// of gui.py's cache keys. Bumped whenever converted output changes, so older conversions are
+/// recognized as stale; independent of the package version.
+///
+/// - 11: the version is recorded and keys the cache
+/// - 12: allocations.json may be split into shards listed in allocations_shards.json
+/// - 13: convert_manifest.json records the outputs written, for resuming
+/// - 14: PyTorch profiler memory timelines are converted, sampled events share their labelling
+/// - 15: allocations freed in the timestep they were allocated in are kept, drawn as markers
+/// - 16: elements.db merging ranks has a rank column in its allocs and events tables
+/// - 17: elements.db is read filtered by size, lifetime and device, which needs the device id
+/// - 18: callstacks are stored once each in the callstacks table, indexed by their text
+pub const CONVERTER_VERSION: &str = "18";
+pub const ANNOTATIONS_FILE_NAME: &str = "annotations.json";
+pub const BOOKMARKS_FILE_NAME: &str = "bookmarks.json";
+/// Table in elements.db holding each distinct callstack once, referenced by id from `ALLOC_ROWS_TABLE`
+pub const CALLSTACKS_TABLE: &str = "callstacks";
+/// Table in elements.db holding each allocation's size, lifetime and callstack id. SQL reads it
+/// through the allocs view, which spells the callstacks out.
+pub const ALLOC_ROWS_TABLE: &str = "alloc_rows";
+/// Table in elements.db holding per-allocation timesteps/offsets as little-endian u64 blobs
+pub const TIMELINE_TABLE: &str = "timeline";
+/// Table in elements.db holding each allocation's CUDA stream and offsets within its stream's lane
+pub const LANES_TABLE: &str = "lanes";
+/// Column of the allocs and events tables of an elements.db merging several ranks' snapshots
+/// (`--merge-ranks`), holding the position of each row's rank among the merged directories
+pub const RANK_COLUMN: &str = "rank";
+/// Table in elements.db holding allocator events (OOMs, segment frees, snapshots) and their timesteps
+pub const EVENTS_TABLE: &str = "events";
+/// Table in elements.db mapping timesteps to the wall-clock time of the action starting there
+pub const CLOCK_TABLE: &str = "clock";
+/// Table in elements.db holding the memory reserved in segments from each timestep on
+pub const RESERVED_TABLE: &str = "reserved";
+/// Table in elements.db holding each segment's size and the timesteps it was reserved over
+pub const SEGMENTS_TABLE: &str = "segments";
+/// Width in world units (logical pixels before zooming) of allocations freed in the timestep they
+/// were allocated in, which would otherwise be drawn zero wide
+pub const MARKER_WIDTH: f64 = 1.0;
+/// World units per screen column for each level-of-detail mesh, coarsest first.
+/// A level is used while one of its columns spans at most one logical pixel.
+pub const LOD_COLUMN_WIDTHS: [f64; 3] = [1.0, 0.25, 0.0625];
+/// Heatmap bins are this many world units (logical pixels at zoom 1) on each side
+pub const HEATMAP_BIN_PX: u32 = 4;
+/// Above this many quads the mesh build takes minutes and may run out of memory,
+/// so the geometry gets coarsened at load time.
+pub const DEFAULT_MAX_SEGMENTS: usize = 10_000_000;
+/// The exact mesh is split into this many time buckets, only those in view are rendered
+pub const MESH_TIME_CHUNKS: u32 = 64;
+/// At most this many vertical peak markers are drawn, a plateau at the peak only counts once
+pub const MAX_PEAK_MARKERS: usize = 64;
+/// View/filter states kept for undo
+pub const UNDO_HISTORY_LIMIT: usize = 100;
+/// Selected allocations kept for going back and forth with [ and ]
+pub const SELECTION_HISTORY_LIMIT: usize = 100;
+/// The view must be unchanged this long (ms) to become an undo step, so a burst of
+/// scroll-zooming or dragging is undone at once
+pub const UNDO_SETTLE_MS: u64 = 400;
+/// `--goto` zooms so the allocation's larger side spans this fraction of the window
+pub const GOTO_FILL: f32 = 0.3;
+/// Double-clicking an allocation zooms so its larger side spans this fraction of the window
+pub const DOUBLE_CLICK_FILL: f32 = 0.9;
+/// Longest gap between the clicks of a double-click
+pub const DOUBLE_CLICK_MS: u64 = 400;
+/// Farthest the cursor may move between the clicks of a double-click, in physical pixels
+pub const DOUBLE_CLICK_SLOP_PX: f32 = 4.0;
+/// Duration of the camera move to a double-clicked allocation
+pub const ZOOM_ANIMATION_MS: u64 = 300;
+/// Allocations kept undimmed when the spotlight (M) is turned on, adjusted with + / -
+pub const SPOTLIGHT_DEFAULT_COUNT: usize = 10;
+/// Largest allocations labeled when labels (T) are turned on without `--top-labels`
+pub const DEFAULT_TOP_LABELS: usize = 5;
+/// Largest live allocations listed by `--at`
+pub const AT_REPORT_LIMIT: usize = 20;
+/// Callstacks listed in the stats of a region dragged with Shift
+pub const REGION_TOP_CALLSTACKS: usize = 5;
+/// Members of a callstack group listed by `--group`
+pub const GROUP_REPORT_LIMIT: usize = 20;
+/// Logical pixels panned per pixel of wheel delta with Shift / Ctrl held
+pub const WHEEL_PAN_SPEED: f32 = 4.0;
+/// Time spent per frame moving mesh chunks to the GPU until the exact mesh is uploaded
+pub const UPLOAD_BUDGET_MS: u64 = 30;
+/// While nothing changes the window is not redrawn, input and IPC are polled at this interval
+pub const IDLE_POLL_MS: u64 = 8;
+/// Idle time after the last input before LOD meshes are built ahead of use
+pub const WARM_UP_IDLE_MS: u64 = 500;
+/// With `--watch`, how often the shown snapshot's files are checked for changes
+pub const WATCH_POLL_MS: u64 = 1000;
+/// First part of the two-part IPC message carrying the window's logical size as `WIDTH HEIGHT`
+/// in its second part, sent whenever it changes so the GUI can restore it next time
+pub const WINDOW_SIZE_EVENT: &str = "window_size";
+/// Limits of `--script` and `script` runs, so a runaway loop or allocation fails the script
+/// instead of hanging or exhausting the renderer
+pub const SCRIPT_MAX_OPERATIONS: u64 = 2_000_000_000;
+pub const SCRIPT_MAX_CALL_LEVELS: usize = 64;
+pub const SCRIPT_MAX_STRING_SIZE: usize = 64 << 20;
+/// Raised to the number of allocations so that `allocations()` always fits
+pub const SCRIPT_MAX_ARRAY_SIZE: usize = 1 << 20;
+/// Frame rate of screen recordings (R / `--record`)
+pub const RECORD_FPS: u32 = 15;
+/// Per-user state directory under home, shared with the GUI
+pub const CONFIG_DIR_NAME: &str = ".snapviewer_cache";
+/// Created in the config dir once the first-run controls overlay is dismissed
+pub const FIRST_RUN_MARKER: &str = "first_run_done";
 pub const UNITS: [&str; 8] = ["", "Ki", "Mi", "Gi", "Ti", "Pi", "Ei", "Zi"];
 pub const INTERVALS: [f64; 20] = [
     1.0_f64,
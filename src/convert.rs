@@ -0,0 +1,1080 @@
+//! Converting a snapshot pickle (or a samples CSV written by `record`, or a memory timeline
+//! exported by the PyTorch profiler) into a directory the
+//! renderer opens, holding elements.db and metadata.json. Behind the `convert` subcommand, which
+//! gui.py runs for `--pickle`, so opening a pickle needs neither Python packages nor torch.
+//! The pickle can also be read from a zip archive, with its entry stored, deflated or compressed
+//! with zstd.
+
+use crate::{
+    allocation::{Category, RawAllocationData},
+    binary,
+    constants::{
+        ALLOCATIONS_BIN_FILE_NAME, ALLOCATIONS_FILE_NAME, ALLOCATIONS_SHARDS_FILE_NAME,
+        CONVERTER_VERSION, ELEMENT_DB_FILENAME, MANIFEST_FILE_NAME, METADATA_FILE_NAME,
+    },
+    error::SnapError,
+    input::{InputKind, detect_input, open_decompressed},
+    load::ShardIndex,
+    manifest::Manifest,
+    pickle::{self, Value},
+    profiler_timeline::timeline_to_trace,
+    segments::Segment,
+    utils::get_spinner,
+};
+use anyhow::{Context, anyhow, bail};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Tables of elements.db
+pub const DATABASE_SCHEMA: &str = "CREATE TABLE callstacks (
+    id INTEGER PRIMARY KEY,
+    callstack TEXT
+);
+CREATE UNIQUE INDEX callstacks_by_text ON callstacks (callstack);
+CREATE TABLE alloc_rows (
+    idx INTEGER PRIMARY KEY,
+    size INTEGER,
+    start_time INTEGER,
+    end_time INTEGER,
+    callstack_id INTEGER,
+    category TEXT
+);
+CREATE VIEW allocs AS SELECT alloc_rows.*, callstacks.callstack
+    FROM alloc_rows LEFT JOIN callstacks ON callstacks.id = alloc_rows.callstack_id;
+CREATE TABLE timeline (
+    idx INTEGER PRIMARY KEY,
+    timesteps BLOB,
+    offsets BLOB
+);
+CREATE TABLE lanes (
+    idx INTEGER PRIMARY KEY,
+    stream INTEGER,
+    lane_offsets BLOB
+);
+CREATE TABLE events (
+    idx INTEGER PRIMARY KEY,
+    timestep INTEGER,
+    action TEXT,
+    size INTEGER,
+    stream INTEGER,
+    callstack TEXT
+);
+CREATE TABLE clock (
+    timestep INTEGER PRIMARY KEY,
+    time INTEGER
+);
+CREATE TABLE reserved (
+    timestep INTEGER PRIMARY KEY,
+    bytes INTEGER
+);
+CREATE TABLE segments (
+    idx INTEGER PRIMARY KEY,
+    start_time INTEGER,
+    end_time INTEGER,
+    size INTEGER
+);";
+
+/// Allocator events shown on the renderer's event strip; empty_cache releases segments
+const EVENT_ACTIONS: [&str; 3] = ["oom", "segment_free", "snapshot"];
+
+/// Fields of the pickle copied into metadata.json when present, at the top level or under
+/// `metadata` where some capture scripts put them
+const METADATA_FIELDS: [&str; 3] = ["torch_version", "device_name", "total_capacity"];
+
+/// Whether `action` reserves (1) or releases (-1) a segment of the caching allocator
+fn segment_direction(action: &str) -> Option<i64> {
+    match action {
+        "segment_alloc" | "segment_map" => Some(1),
+        "segment_free" | "segment_unmap" => Some(-1),
+        _ => None,
+    }
+}
+
+/// An int, or a float cut to one
+fn int(value: &Value) -> Option<i64> {
+    match value {
+        Value::Float(x) => Some(*x as i64),
+        value => value.as_i64(),
+    }
+}
+
+/// One event of a device trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent {
+    pub action: Rc<str>,
+    pub addr: Option<u64>,
+    pub size: u64,
+    pub stream: i64,
+    /// Since the epoch, recorded by recent PyTorch versions
+    pub time_us: Option<u64>,
+    /// PyTorch's category (parameter, activation, ...), recorded by some versions
+    pub category: Option<Rc<str>>,
+    /// List of dicts with `filename`, `line` and `name`
+    pub frames: Value,
+}
+
+impl TraceEvent {
+    pub fn from_value(value: &Value) -> anyhow::Result<Self> {
+        let field = |key| value.get(key).filter(|v| !v.is_none());
+        let action = match field("action") {
+            Some(Value::Str(action)) => Rc::clone(action),
+            _ => bail!("Trace event without an action: {:?}", value),
+        };
+        Ok(Self {
+            action,
+            addr: field("addr").and_then(int).map(|addr| addr as u64),
+            size: field("size").and_then(int).unwrap_or(0) as u64,
+            stream: field("stream").and_then(int).unwrap_or(0),
+            time_us: field("time_us").and_then(int).map(|t| t as u64),
+            category: match field("category") {
+                Some(Value::Str(category)) => Some(Rc::clone(category)),
+                _ => None,
+            },
+            frames: field("frames").cloned().unwrap_or(Value::None),
+        })
+    }
+
+    /// Frames formatted `(index) filename:line:name`, one per line
+    pub fn callstack(&self) -> String {
+        let frames = self.frames.as_list().unwrap_or_default();
+        let field = |frame: &Value, key| frame.get(key).map(Value::to_string).unwrap_or_default();
+        frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                format!(
+                    "({}) {}:{}:{}",
+                    i,
+                    field(frame, "filename"),
+                    field(frame, "line"),
+                    field(frame, "name")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn require_addr(&self, index: usize) -> anyhow::Result<u64> {
+        self.addr
+            .with_context(|| format!("Trace event {} ({}) has no addr", index, self.action))
+    }
+}
+
+/// One allocation over the timeline: its offset in the global stacking and in its stream's lane
+/// at each of `timesteps`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    /// Index into the trace of the event allocating it (or freeing it, if allocated before the
+    /// trace started)
+    pub event: usize,
+    pub size: u64,
+    pub stream: i64,
+    pub timesteps: Vec<u64>,
+    pub offsets: Vec<u64>,
+    pub lane_offsets: Vec<u64>,
+}
+
+impl Track {
+    /// Holds its offsets until `timestep`
+    fn extend(&mut self, timestep: u64) {
+        self.timesteps.push(timestep);
+        self.offsets.push(*self.offsets.last().unwrap());
+        self.lane_offsets.push(*self.lane_offsets.last().unwrap());
+    }
+}
+
+/// A device trace laid out for the renderer
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertedTrace {
+    pub allocations: Vec<Track>,
+    /// Timestep of each event in `EVENT_ACTIONS`, and its index into the trace
+    pub events: Vec<(u64, usize)>,
+    /// (timestep, time_us) of each action recording its time
+    pub clock: Vec<(u64, u64)>,
+    /// (timestep, bytes) where the memory reserved in segments changes
+    pub reserved: Vec<(u64, i64)>,
+    pub segments: Vec<Segment>,
+}
+
+/// The stack allocations are laid out in while the trace is replayed
+struct Stacker<'a> {
+    trace: &'a [TraceEvent],
+    tracks: Vec<Track>,
+    /// (element, track) of each live allocation, bottom of the stack first
+    current: Vec<(usize, usize)>,
+    total_mem: u64,
+    lane_total_mem: HashMap<i64, u64>,
+    timestep: u64,
+    /// Memory allocated at each timestep
+    max_at_time: Vec<u64>,
+}
+
+impl Stacker<'_> {
+    fn push(&mut self, elem: usize, event: usize) {
+        let TraceEvent { size, stream, .. } = self.trace[event];
+        let lane_mem = self.lane_total_mem.entry(stream).or_default();
+        self.current.push((elem, self.tracks.len()));
+        self.tracks.push(Track {
+            event,
+            size,
+            stream,
+            timesteps: vec![self.timestep],
+            offsets: vec![self.total_mem],
+            lane_offsets: vec![*lane_mem],
+        });
+        self.total_mem += size;
+        *lane_mem += size;
+    }
+
+    /// Frees the live allocation at `idx` in `current`, moving the ones above it down
+    fn remove(&mut self, idx: usize) {
+        let (_, track) = self.current.remove(idx);
+        let Track { size, stream, .. } = self.tracks[track];
+        self.tracks[track].extend(self.timestep);
+        if idx < self.current.len() {
+            for &(_, above) in &self.current[idx..] {
+                let entry = &mut self.tracks[above];
+                entry.extend(self.timestep);
+                entry.timesteps.push(self.timestep + 3);
+                entry.offsets.push(entry.offsets.last().unwrap() - size);
+                // only allocations on the same stream are stacked on top of it in its lane
+                let lane_shift = if entry.stream == stream { size } else { 0 };
+                entry
+                    .lane_offsets
+                    .push(entry.lane_offsets.last().unwrap() - lane_shift);
+            }
+            self.advance(3);
+        }
+        self.total_mem -= size;
+        *self.lane_total_mem.entry(stream).or_default() -= size;
+    }
+
+    fn advance(&mut self, n: u64) {
+        self.timestep += n;
+        self.max_at_time
+            .extend(std::iter::repeat_n(self.total_mem, n as usize));
+    }
+}
+
+/// Stacks the allocations of `trace` over time: each allocation goes on top of the stack, and the ones above an allocation move down
+/// over 3 timesteps when it is freed. Frees without an allocation in the trace are allocations
+/// made before it, stacked at the bottom.
+///
+/// Besides the global stacking, each allocation gets lane offsets: its offset when only
+/// allocations on the same CUDA stream are stacked. Segments reserved before the trace are not in
+/// it, so the reservation is raised until it holds every allocation (and matches
+/// `reserved_bytes` at the end, if known); what was reserved before the trace and never released
+/// in it is one segment spanning the whole trace.
+pub fn process_trace(
+    trace: &[TraceEvent],
+    reserved_bytes: Option<u64>,
+) -> anyhow::Result<ConvertedTrace> {
+    let mut elements: Vec<usize> = Vec::new(); // trace index of each allocation
+    let mut initially_allocated = Vec::new();
+    let mut actions: Vec<usize> = Vec::new(); // element allocated or freed by each action
+    let mut addr_to_alloc: HashMap<u64, usize> = HashMap::new();
+    let mut pending_events = Vec::new(); // (number of actions before the event, trace index)
+    let mut action_times: Vec<Option<u64>> = Vec::new();
+    let mut reserved_changes = Vec::new(); // (number of actions before the change, signed size)
+    let mut segment_events = Vec::new(); // (number of actions before, direction, addr, size)
+
+    for (i, event) in trace.iter().enumerate() {
+        let action = &*event.action;
+        if EVENT_ACTIONS.contains(&action) {
+            pending_events.push((actions.len(), i));
+        }
+        if let Some(direction) = segment_direction(action) {
+            reserved_changes.push((actions.len(), direction * event.size as i64));
+            segment_events.push((actions.len(), direction, event.addr, event.size));
+        }
+        if action == "alloc" {
+            elements.push(i);
+            addr_to_alloc.insert(event.require_addr(i)?, elements.len() - 1);
+            actions.push(elements.len() - 1);
+        } else if action == "free" || action == "free_completed" {
+            match addr_to_alloc.remove(&event.require_addr(i)?) {
+                Some(elem) => actions.push(elem),
+                None => {
+                    elements.push(i);
+                    initially_allocated.push(elements.len() - 1);
+                    actions.push(elements.len() - 1);
+                }
+            }
+        }
+        if action_times.len() < actions.len() {
+            action_times.push(event.time_us);
+        }
+    }
+
+    let mut stacker = Stacker {
+        trace,
+        tracks: Vec::new(),
+        current: Vec::new(),
+        total_mem: 0,
+        lane_total_mem: HashMap::new(),
+        timestep: 0,
+        max_at_time: Vec::new(),
+    };
+    for &elem in initially_allocated.iter().rev() {
+        stacker.push(elem, elements[elem]);
+    }
+
+    let mut action_timesteps = Vec::with_capacity(actions.len() + 1); // where each action starts
+    for &elem in &actions {
+        action_timesteps.push(stacker.timestep);
+        match stacker.current.iter().rposition(|&(e, _)| e == elem) {
+            Some(idx) => {
+                stacker.remove(idx);
+                stacker.advance(1);
+            }
+            None => {
+                stacker.push(elem, elements[elem]);
+                stacker.advance(1);
+            }
+        }
+    }
+    // close the timeline of allocations never freed
+    let end = stacker.timestep;
+    for &(_, track) in &stacker.current {
+        stacker.tracks[track].extend(end);
+    }
+    action_timesteps.push(end);
+
+    let events = pending_events
+        .into_iter()
+        .map(|(n_actions, event)| (action_timesteps[n_actions], event))
+        .collect();
+    let clock = action_timesteps
+        .iter()
+        .zip(&action_times)
+        .filter_map(|(&step, &time)| time.filter(|&t| t != 0).map(|t| (step, t)))
+        .collect();
+
+    let mut reserved: Vec<(u64, i64)> = Vec::new();
+    let mut segments = Vec::new();
+    if !reserved_changes.is_empty() || reserved_bytes.is_some_and(|b| b != 0) {
+        let mut relative = 0;
+        let curve: Vec<(u64, i64)> = reserved_changes
+            .iter()
+            .map(|&(n_actions, delta)| {
+                relative += delta;
+                (action_timesteps[n_actions], relative)
+            })
+            .collect();
+        // lowest starting reservation that holds every allocation
+        let (mut offset, mut i, mut current) = (0, 0, 0);
+        for (t, &allocated) in stacker.max_at_time.iter().enumerate() {
+            while i < curve.len() && curve[i].0 <= t as u64 {
+                current = curve[i].1;
+                i += 1;
+            }
+            offset = offset.max(allocated as i64 - current);
+        }
+        if let Some(reserved_bytes) = reserved_bytes {
+            offset = offset.max(reserved_bytes as i64 - relative);
+        }
+        // several changes between two timesteps: the last one holds
+        reserved.push((0, offset));
+        for &(step, r) in &curve {
+            match reserved.last_mut() {
+                Some(last) if last.0 == step => last.1 = offset + r,
+                _ => reserved.push((step, offset + r)),
+            }
+        }
+
+        let segment = |start, stop, size| Segment { start, stop, size };
+        let mut open_segments: Vec<(Option<u64>, u64, u64)> = Vec::new(); // (addr, start, size)
+        let mut released_early: i64 = 0; // bytes reserved before the trace and released in it
+        for &(n_actions, direction, addr, size) in &segment_events {
+            let step = action_timesteps[n_actions];
+            let open = open_segments.iter().position(|&(a, _, _)| a == addr);
+            match (direction > 0, open) {
+                (true, Some(open)) => open_segments[open] = (addr, step, size),
+                (true, None) => open_segments.push((addr, step, size)),
+                (false, Some(open)) => {
+                    let (_, start, _) = open_segments.remove(open);
+                    segments.push(segment(start, step, size));
+                }
+                (false, None) => {
+                    segments.push(segment(0, step, size));
+                    released_early += size as i64;
+                }
+            }
+        }
+        segments.extend(
+            open_segments
+                .into_iter()
+                .map(|(_, start, size)| segment(start, end, size)),
+        );
+        if offset > released_early {
+            segments.insert(0, segment(0, end, (offset - released_early) as u64));
+        }
+    }
+
+    Ok(ConvertedTrace {
+        allocations: stacker.tracks,
+        events,
+        clock,
+        reserved,
+        segments,
+    })
+}
+
+/// Every device's trace in a snapshot, empty for devices without one
+fn device_traces(dump: &Value) -> anyhow::Result<&[Value]> {
+    dump.get("device_traces")
+        .and_then(Value::as_list)
+        .context("The pickle is not a memory snapshot: it has no device_traces")
+        .context(SnapError::BadInput)
+}
+
+fn parse_trace(trace: &Value) -> anyhow::Result<Vec<TraceEvent>> {
+    trace
+        .as_list()
+        .unwrap_or_default()
+        .iter()
+        .map(TraceEvent::from_value)
+        .collect::<anyhow::Result<_>>()
+        .context(SnapError::BadInput)
+}
+
+/// The trace of `device` in a snapshot
+pub fn device_trace(dump: &Value, device: usize) -> anyhow::Result<Vec<TraceEvent>> {
+    let traces = device_traces(dump)?;
+    let Some(trace) = traces.get(device) else {
+        let expected = match traces.len() {
+            1 => "0".to_string(),
+            n => format!("0 ~ {}", n.saturating_sub(1)),
+        };
+        return Err(anyhow!(
+            "Device id out of range, expected {}, got {}",
+            expected,
+            device
+        )
+        .context(SnapError::BadInput));
+    };
+    if trace.as_list().is_none_or(<[Value]>::is_empty) {
+        let with_trace: Vec<usize> = (0..traces.len())
+            .filter(|&i| traces[i].as_list().is_some_and(|t| !t.is_empty()))
+            .collect();
+        return Err(anyhow!(
+            "Requested device ({}) has no trace in this snapshot. Devices with trace: {:?}",
+            device,
+            with_trace
+        )
+        .context(SnapError::BadInput));
+    }
+    parse_trace(trace)
+}
+
+/// GPU memory samples (`time_us,used_bytes` rows, as written by `record`) as a trace: growth
+/// between samples becomes an allocation, shrinking frees the most recent allocations
+/// (re-allocating the part of the last one that is still used).
+pub fn samples_to_trace(csv: &str) -> anyhow::Result<Vec<TraceEvent>> {
+    let mut lines = csv.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<&str> = lines.next().unwrap_or_default().split(',').collect();
+    let column = |name| {
+        header
+            .iter()
+            .position(|h| h.trim() == name)
+            .with_context(|| format!("Samples have no {} column", name))
+    };
+    let (time_col, used_col) = (column("time_us")?, column("used_bytes")?);
+
+    let mut trace = Vec::new();
+    let mut stack: Vec<(u64, u64)> = Vec::new(); // (addr, size) of live allocations, newest last
+    let mut used = 0;
+    let mut next_addr = 0;
+    let mut event = |action, addr, size, time_us, label: &str| {
+        trace.push(labelled_event(
+            action,
+            addr,
+            size,
+            time_us,
+            None,
+            "<sampled>",
+            label,
+        ));
+    };
+    for (i, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').collect();
+        let field = |col: usize| -> anyhow::Result<u64> {
+            let text = fields.get(col).map_or("", |f| f.trim());
+            text.parse()
+                .with_context(|| format!("Bad sample on line {}: {:?}", i + 2, line))
+        };
+        let (time_us, target) = (field(time_col)?, field(used_col)?);
+        let label = format!("sample {}", i);
+        while used > target {
+            let (addr, size) = stack.pop().unwrap();
+            event("free_completed", addr, size, time_us, &label);
+            used -= size;
+        }
+        if target > used {
+            event("alloc", next_addr, target - used, time_us, &label);
+            stack.push((next_addr, target - used));
+            next_addr += 1;
+            used = target;
+        }
+    }
+    Ok(trace)
+}
+
+/// An event on stream 0 of a trace made up from something other than a snapshot, with a single
+/// synthetic frame `filename:0:label` for its callstack
+pub(crate) fn labelled_event(
+    action: &str,
+    addr: u64,
+    size: u64,
+    time_us: u64,
+    category: Option<&str>,
+    filename: &str,
+    label: &str,
+) -> TraceEvent {
+    let str = |s: &str| Value::Str(Rc::from(s));
+    let frame = [
+        (str("filename"), str(filename)),
+        (str("line"), Value::Int(0)),
+        (str("name"), str(label)),
+    ];
+    TraceEvent {
+        action: Rc::from(action),
+        addr: Some(addr),
+        size,
+        stream: 0,
+        time_us: Some(time_us),
+        category: category.map(Rc::from),
+        frames: Value::List(Rc::from([Value::Dict(Rc::from(frame))])),
+    }
+}
+
+/// Scalars as JSON, for metadata.json
+fn json_scalar(value: &Value) -> Option<serde_json::Value> {
+    match value {
+        Value::Bool(b) => Some((*b).into()),
+        Value::Int(i) => Some((*i).into()),
+        Value::Float(x) => Some((*x).into()),
+        Value::Str(s) => Some((**s).into()),
+        _ => None,
+    }
+}
+
+/// The converter version recorded in `dir`'s metadata.json, `None` for conversions predating it
+pub fn converted_by(dir: &Path) -> Option<String> {
+    std::fs::read_to_string(dir.join(METADATA_FILE_NAME))
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+        .and_then(|metadata| metadata["converter_version"].as_str().map(str::to_string))
+}
+
+/// Snapshot metadata shown by the GUI, optional fields only if present in the pickle
+pub fn extract_metadata(dump: &Value, device: usize, trace: &[TraceEvent]) -> serde_json::Value {
+    let mut metadata = serde_json::Map::new();
+    metadata.insert("device".into(), device.into());
+    metadata.insert("num_events".into(), trace.len().into());
+    metadata.insert("converter_version".into(), CONVERTER_VERSION.into());
+
+    // not written by torch.cuda.memory._snapshot() itself, but recorded by some capture scripts
+    for key in METADATA_FIELDS {
+        let value = dump
+            .get(key)
+            .or_else(|| dump.get("metadata").and_then(|extra| extra.get(key)));
+        if let Some(value) = value.and_then(json_scalar) {
+            metadata.insert(key.into(), value);
+        }
+    }
+
+    let segments: Vec<&Value> = dump
+        .get("segments")
+        .and_then(Value::as_list)
+        .unwrap_or_default()
+        .iter()
+        .filter(|seg| seg.get("device").and_then(Value::as_i64) == Some(device as i64))
+        .collect();
+    if !segments.is_empty() {
+        let reserved: i64 = segments
+            .iter()
+            .filter_map(|seg| seg.get("total_size").and_then(Value::as_i64))
+            .sum();
+        metadata.insert("reserved_bytes".into(), reserved.into());
+    }
+
+    // trace events carry `time_us` (since the epoch) on recent PyTorch versions
+    if let Some(last) = trace
+        .iter()
+        .filter_map(|e| e.time_us)
+        .filter(|&t| t != 0)
+        .max()
+    {
+        // unit of the clock table
+        metadata.insert("time_unit".into(), "us".into());
+        let time = UNIX_EPOCH + Duration::from_micros(last);
+        let capture_time = humantime::format_rfc3339_seconds(time).to_string();
+        metadata.insert("capture_time".into(), capture_time.into());
+    }
+    serde_json::Value::Object(metadata)
+}
+
+pub(crate) fn pack_u64s(values: &[u64]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Writes elements.db, under a temporary name until complete so an interrupted conversion never
+/// leaves a valid-looking database
+pub fn make_db(
+    trace: &[TraceEvent],
+    converted: &ConvertedTrace,
+    db_path: &Path,
+) -> anyhow::Result<()> {
+    let tmp_path = db_path.with_extension("db.tmp");
+    if tmp_path.exists() {
+        std::fs::remove_file(&tmp_path)?;
+    }
+    let mut conn = Connection::open(&tmp_path)?;
+    conn.execute_batch(DATABASE_SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    {
+        let mut allocs = tx.prepare("INSERT INTO alloc_rows VALUES (?, ?, ?, ?, ?, ?)")?;
+        let mut callstacks = tx.prepare("INSERT INTO callstacks VALUES (?, ?)")?;
+        let mut timeline = tx.prepare("INSERT INTO timeline VALUES (?, ?, ?)")?;
+        let mut lanes = tx.prepare("INSERT INTO lanes VALUES (?, ?, ?)")?;
+        // most allocations share a handful of callstacks, each stored once
+        let mut callstack_ids: HashMap<String, usize> = HashMap::new();
+        for (idx, track) in converted.allocations.iter().enumerate() {
+            let event = &trace[track.event];
+            let callstack = event.callstack();
+            let callstack_id = match callstack_ids.get(&callstack) {
+                Some(&id) => id,
+                None => {
+                    let id = callstack_ids.len();
+                    callstacks.execute((id, &callstack))?;
+                    callstack_ids.insert(callstack, id);
+                    id
+                }
+            };
+            allocs.execute((
+                idx,
+                track.size,
+                track.timesteps[0],
+                track.timesteps.last(),
+                callstack_id,
+                event.category.as_deref(),
+            ))?;
+            timeline.execute((idx, pack_u64s(&track.timesteps), pack_u64s(&track.offsets)))?;
+            lanes.execute((idx, track.stream, pack_u64s(&track.lane_offsets)))?;
+        }
+
+        let mut events = tx.prepare("INSERT INTO events VALUES (?, ?, ?, ?, ?, ?)")?;
+        for (idx, &(timestep, event)) in converted.events.iter().enumerate() {
+            let event = &trace[event];
+            events.execute((
+                idx,
+                timestep,
+                &*event.action,
+                event.size,
+                event.stream,
+                event.callstack(),
+            ))?;
+        }
+        let mut clock = tx.prepare("INSERT INTO clock VALUES (?, ?)")?;
+        for &(timestep, time) in &converted.clock {
+            clock.execute((timestep, time))?;
+        }
+        let mut reserved = tx.prepare("INSERT INTO reserved VALUES (?, ?)")?;
+        for &(timestep, bytes) in &converted.reserved {
+            reserved.execute((timestep, bytes))?;
+        }
+        let mut segments = tx.prepare("INSERT INTO segments VALUES (?, ?, ?, ?)")?;
+        for (idx, seg) in converted.segments.iter().enumerate() {
+            segments.execute((idx, seg.start, seg.stop, seg.size))?;
+        }
+    }
+    tx.commit()?;
+    conn.close().map_err(|(_, e)| e)?;
+    std::fs::rename(&tmp_path, db_path)?;
+    Ok(())
+}
+
+/// allocations.json, for renderers predating the timeline table
+fn allocations_json(trace: &[TraceEvent], converted: &ConvertedTrace) -> Vec<serde_json::Value> {
+    converted
+        .allocations
+        .iter()
+        .map(|track| {
+            serde_json::json!({
+                "timesteps": track.timesteps,
+                "offsets": track.offsets,
+                "size": track.size,
+                "category": trace[track.event].category.as_deref(),
+                "stream": track.stream,
+                "lane_offsets": track.lane_offsets,
+            })
+        })
+        .collect()
+}
+
+/// Writes allocations.json as files of at most `shard_size` allocations and
+/// allocations_shards.json listing them, skipping the files `manifest` has as complete
+fn write_json_shards(
+    output: &Path,
+    json: &[serde_json::Value],
+    shard_size: usize,
+    manifest: &mut Manifest,
+) -> anyhow::Result<()> {
+    let mut shards = Vec::new();
+    for (i, shard) in json.chunks(shard_size.max(1)).enumerate() {
+        let name = format!("allocations_{}.json", i);
+        if !manifest.is_done(&name) {
+            manifest.write(output, &name, &serde_json::to_vec(shard)?)?;
+        }
+        shards.push(name);
+    }
+    let index = ShardIndex {
+        shards,
+        allocations: json.len(),
+    };
+    manifest.write(
+        output,
+        ALLOCATIONS_SHARDS_FILE_NAME,
+        &serde_json::to_vec(&index)?,
+    )
+}
+
+/// Geometry files written besides elements.db, which holds it too
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConvertOptions {
+    /// allocations.json, for renderers predating the timeline table
+    pub json: bool,
+    /// Split allocations.json into files of at most this many allocations
+    pub json_shard_size: Option<usize>,
+    /// allocations.bin, the fastest to load
+    pub binary: bool,
+}
+
+impl ConvertOptions {
+    /// Output files a conversion with these options writes, the last shard index standing for
+    /// the shards it lists
+    fn outputs(&self) -> Vec<&'static str> {
+        let mut outputs = vec![METADATA_FILE_NAME, ELEMENT_DB_FILENAME];
+        if self.binary {
+            outputs.push(ALLOCATIONS_BIN_FILE_NAME);
+        }
+        match (self.json, self.json_shard_size) {
+            (true, Some(_)) => outputs.push(ALLOCATIONS_SHARDS_FILE_NAME),
+            (true, None) => outputs.push(ALLOCATIONS_FILE_NAME),
+            (false, _) => {}
+        }
+        outputs
+    }
+
+    /// Whether `manifest` has every output of these options as complete
+    fn is_complete(&self, manifest: &Manifest) -> bool {
+        self.outputs().iter().all(|name| manifest.is_done(name))
+    }
+}
+
+/// A snapshot pickle, or a samples CSV or profiler memory timeline as the dump-less trace of
+/// device 0
+enum Input {
+    Pickle(Value),
+    Trace(Vec<TraceEvent>),
+}
+
+fn load_input(input: &Path) -> anyhow::Result<Input> {
+    let (kind, input) = detect_input(input)?;
+    let bar = get_spinner("Loading pickle file, this may take minutes...")?;
+    let loaded = match kind {
+        InputKind::Samples => {
+            let csv = std::fs::read_to_string(&input)?;
+            Input::Trace(samples_to_trace(&csv)?)
+        }
+        InputKind::ProfilerTimeline => {
+            let reader = open_decompressed(&input)?;
+            let json: serde_json::Value = serde_json::from_reader(BufReader::new(reader))
+                .with_context(|| format!("Failed to parse {}", input.display()))?;
+            Input::Trace(timeline_to_trace(&json)?)
+        }
+        InputKind::Zip => Input::Pickle(load_zipped_pickle(&input)?),
+        InputKind::Pickle => {
+            let reader = open_decompressed(&input)?;
+            Input::Pickle(pickle::load(BufReader::new(reader))?)
+        }
+        InputKind::Converted => bail!("{} is already converted", input.display()),
+    };
+    bar.finish();
+    Ok(loaded)
+}
+
+/// Loads the pickle in a zip archive: its .pickle (or .pkl) entry, or its only file.
+/// The entry is decompressed as it is read.
+fn load_zipped_pickle(path: &Path) -> anyhow::Result<Value> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+    let files: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(str::to_string)
+        .collect();
+    let name = match files
+        .iter()
+        .find(|name| name.ends_with(".pickle") || name.ends_with(".pkl"))
+    {
+        Some(name) => name,
+        None if files.len() == 1 => &files[0],
+        None => bail!(
+            "{} holds no .pickle file, only: {}",
+            path.display(),
+            files.join(", ")
+        ),
+    };
+    pickle::load(BufReader::new(archive.by_name(name)?))
+}
+
+/// Writes metadata.json and elements.db (and the files `options` asks for) for one device's
+/// trace to `output`, which must exist. Files `manifest` has as complete are not written again.
+fn convert_trace(
+    dump: &Value,
+    trace: &[TraceEvent],
+    output: &Path,
+    device: usize,
+    options: ConvertOptions,
+    manifest: &mut Manifest,
+) -> anyhow::Result<()> {
+    if !manifest.completed.is_empty() {
+        println!(
+            "Resuming the conversion in {}, already written: {}",
+            output.display(),
+            manifest.completed.join(", ")
+        );
+    }
+    let metadata = extract_metadata(dump, device, trace);
+    if !manifest.is_done(METADATA_FILE_NAME) {
+        manifest.write(output, METADATA_FILE_NAME, &serde_json::to_vec(&metadata)?)?;
+    }
+
+    let bar = get_spinner("Processing trace data, this may take minutes...")?;
+    let reserved_bytes = metadata["reserved_bytes"].as_u64();
+    let converted = process_trace(trace, reserved_bytes).context(SnapError::BadInput)?;
+    bar.finish();
+
+    if !manifest.is_done(ELEMENT_DB_FILENAME) {
+        let bar = get_spinner(&format!("Writing {}", ELEMENT_DB_FILENAME))?;
+        make_db(trace, &converted, &output.join(ELEMENT_DB_FILENAME))?;
+        manifest.complete(output, ELEMENT_DB_FILENAME)?;
+        bar.finish();
+    }
+
+    if options.binary && !manifest.is_done(ALLOCATIONS_BIN_FILE_NAME) {
+        let bar = get_spinner(&format!("Writing {}", ALLOCATIONS_BIN_FILE_NAME))?;
+        let allocs: Vec<RawAllocationData> = converted
+            .allocations
+            .iter()
+            .map(|track| RawAllocationData {
+                timesteps: track.timesteps.clone(),
+                offsets: track.offsets.clone(),
+                size: track.size,
+                category: trace[track.event]
+                    .category
+                    .as_deref()
+                    .and_then(Category::parse),
+            })
+            .collect();
+        binary::write_allocations(&output.join(ALLOCATIONS_BIN_FILE_NAME), &allocs)?;
+        manifest.complete(output, ALLOCATIONS_BIN_FILE_NAME)?;
+        bar.finish();
+    }
+    if !options.is_complete(manifest) {
+        // only the JSON output is left
+        let bar = get_spinner(&format!("Writing {}", ALLOCATIONS_FILE_NAME))?;
+        let json = allocations_json(trace, &converted);
+        match options.json_shard_size {
+            Some(shard_size) => write_json_shards(output, &json, shard_size, manifest)?,
+            None => manifest.write(output, ALLOCATIONS_FILE_NAME, &serde_json::to_vec(&json)?)?,
+        }
+        bar.finish();
+    }
+    Ok(())
+}
+
+/// Converts the trace of `device` in `input` (a snapshot pickle or a samples CSV) to `output`,
+/// created if needed. Resumes a conversion of the same input that did not finish, and does
+/// nothing if one did.
+pub fn convert_to_dir(
+    input: &Path,
+    output: &Path,
+    device: usize,
+    options: ConvertOptions,
+) -> anyhow::Result<()> {
+    let mut manifest = Manifest::load(output, input, options.json_shard_size);
+    if options.is_complete(&manifest) {
+        println!(
+            "{} is already converted to {}",
+            input.display(),
+            output.display()
+        );
+        return Ok(());
+    }
+    let (dump, trace) = match load_input(input).context(SnapError::BadInput)? {
+        Input::Pickle(dump) => {
+            let trace = device_trace(&dump, device)?;
+            (dump, trace)
+        }
+        Input::Trace(trace) => (Value::None, trace),
+    };
+    std::fs::create_dir_all(output).context(SnapError::Conversion)?;
+    convert_trace(&dump, &trace, output, device, options, &mut manifest)
+        .context(SnapError::Conversion)
+}
+
+/// Converts every device with a trace to `output_template` with `{device}` replaced by its id,
+/// loading the pickle once. Devices whose conversion completed before are skipped, e.g. the one
+/// the GUI just opened, and unfinished ones resumed. Returns the ids of the converted devices.
+pub fn convert_all_devices(
+    input: &Path,
+    output_template: &str,
+    options: ConvertOptions,
+) -> anyhow::Result<Vec<usize>> {
+    if !output_template.contains("{device}") {
+        return Err(
+            anyhow!("With `--device all`, the output path needs a {{device}} placeholder")
+                .context(SnapError::BadInput),
+        );
+    }
+    let (dump, traces) = match load_input(input).context(SnapError::BadInput)? {
+        Input::Pickle(dump) => {
+            let traces = device_traces(&dump)?
+                .iter()
+                .map(parse_trace)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            (dump, traces)
+        }
+        Input::Trace(trace) => (Value::None, vec![trace]),
+    };
+
+    let mut converted = Vec::new();
+    for (device, trace) in traces.iter().enumerate() {
+        let output = output_template.replace("{device}", &device.to_string());
+        let output = Path::new(&output);
+        let mut manifest = Manifest::load(output, input, options.json_shard_size);
+        // or converted before manifests were written
+        let legacy =
+            !output.join(MANIFEST_FILE_NAME).exists() && output.join(ELEMENT_DB_FILENAME).exists();
+        if trace.is_empty() || options.is_complete(&manifest) || legacy {
+            continue;
+        }
+        println!("Converting device {} to {}", device, output.display());
+        std::fs::create_dir_all(output).context(SnapError::Conversion)?;
+        convert_trace(&dump, trace, output, device, options, &mut manifest)
+            .context(SnapError::Conversion)?;
+        converted.push(device);
+    }
+    Ok(converted)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::{
+        ConvertOptions, TraceEvent, convert_to_dir, load_zipped_pickle, process_trace,
+    };
+    use crate::database::sqlite::AllocationDatabase;
+    use crate::geometry::TraceGeometry;
+    use crate::load::load_allocations;
+    use crate::pickle::Value;
+    use crate::segments::Segment;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_process_trace() {
+        let event = |action: &str, addr, size, stream| TraceEvent {
+            action: Rc::from(action),
+            addr: Some(addr),
+            size,
+            stream,
+            time_us: None,
+            category: None,
+            frames: Value::None,
+        };
+        let trace = [
+            // allocated before the trace
+            event("free_completed", 9, 10, 0),
+            event("alloc", 1, 100, 0),
+            event("alloc", 2, 50, 1),
+            event("free", 1, 100, 0),
+        ];
+        let converted = process_trace(&trace, Some(200)).unwrap();
+        let tracks = &converted.allocations;
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(tracks[0].event, 0);
+        assert_eq!(tracks[0].timesteps, [0, 0]);
+        // moves down over 3 timesteps when the one below is freed, but stays at the bottom of
+        // its own stream's lane
+        assert_eq!(tracks[2].timesteps, [2, 3, 6, 7]);
+        assert_eq!(tracks[2].offsets, [100, 100, 0, 0]);
+        assert_eq!(tracks[2].lane_offsets, [0, 0, 0, 0]);
+
+        assert_eq!(converted.reserved, [(0, 200)]);
+        let whole_trace = Segment {
+            start: 0,
+            stop: 7,
+            size: 200,
+        };
+        assert_eq!(converted.segments, [whole_trace]);
+    }
+
+    #[test]
+    fn test_load_zipped_pickle() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let path = std::env::temp_dir().join(format!("snapviewer-{}.zip", std::process::id()));
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        zip.start_file("readme.txt", SimpleFileOptions::default())
+            .unwrap();
+        let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Zstd);
+        zip.start_file("snap/step_100.pickle", options).unwrap();
+        // pickle.dumps(7, protocol=4)
+        zip.write_all(b"\x80\x04K\x07.").unwrap();
+        zip.finish().unwrap();
+
+        let value = load_zipped_pickle(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(value.unwrap().as_i64(), Some(7));
+    }
+
+    #[test]
+    fn test_empty_snapshot_loads() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-empty-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("empty.csv");
+        std::fs::write(&input, "time_us,used_bytes\n").unwrap();
+        let output = dir.join("converted");
+        let options = ConvertOptions {
+            json: true,
+            binary: true,
+            ..Default::default()
+        };
+        convert_to_dir(&input, &output, 0, options).unwrap();
+
+        // by the allocations.bin, timeline and allocations.json loaders in turn
+        let mut loaded = Vec::new();
+        for file in ["allocations.bin", "elements.db", "allocations.json"] {
+            let db = AllocationDatabase::from_dir(&output).unwrap();
+            loaded.push(load_allocations(&output, &db));
+            drop(db);
+            std::fs::remove_file(output.join(file)).unwrap();
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        for allocs in loaded {
+            let geom = TraceGeometry::from_allocations(allocs.unwrap(), (100, 100));
+            assert!(geom.allocations.is_empty());
+            assert_eq!(TraceGeometry::extent(&geom.raw_allocs), (0, 0));
+        }
+    }
+}
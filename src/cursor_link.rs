@@ -0,0 +1,54 @@
+//! Shares the cursor's timestamp between two renderers (e.g. two runs opened side by side), so
+//! each draws the other's cursor and its live-memory readout at the same time position.
+
+use anyhow::Context as _;
+
+pub struct CursorLink {
+    publisher: zmq::Socket,
+    subscriber: zmq::Socket,
+    /// Last timestamp sent, to only publish changes
+    sent: Option<Option<u64>>,
+    /// Cursor timestamp of the other renderer, `None` if its cursor is outside its window
+    pub linked: Option<u64>,
+}
+
+impl CursorLink {
+    /// Publishes on `pub_port` and follows the renderer publishing on `sub_port`.
+    /// The other renderer is started with the two ports swapped.
+    pub fn new(context: &zmq::Context, pub_port: u16, sub_port: u16) -> anyhow::Result<Self> {
+        let publisher = context.socket(zmq::SocketType::PUB)?;
+        publisher
+            .bind(&format!("tcp://*:{}", pub_port))
+            .with_context(|| format!("binding cursor link port {}", pub_port))?;
+        let subscriber = context.socket(zmq::SocketType::SUB)?;
+        subscriber.connect(&format!("tcp://127.0.0.1:{}", sub_port))?;
+        subscriber.set_subscribe(b"")?;
+        Ok(Self {
+            publisher,
+            subscriber,
+            sent: None,
+            linked: None,
+        })
+    }
+
+    /// Publish this renderer's cursor timestamp if it changed
+    pub fn send(&mut self, timestamp: Option<u64>) {
+        if self.sent == Some(timestamp) {
+            return;
+        }
+        let msg = timestamp.map(|t| t.to_string()).unwrap_or_default();
+        if self.publisher.send(msg.as_bytes(), zmq::DONTWAIT).is_ok() {
+            self.sent = Some(timestamp);
+        }
+    }
+
+    /// Take all pending updates from the other renderer, keeping the latest.
+    /// Returns whether the linked cursor moved.
+    pub fn receive(&mut self) -> bool {
+        let before = self.linked;
+        while let Ok(bytes) = self.subscriber.recv_bytes(zmq::DONTWAIT) {
+            self.linked = String::from_utf8_lossy(&bytes).parse().ok();
+        }
+        self.linked != before
+    }
+}
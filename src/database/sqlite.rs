@@ -1,16 +1,41 @@
 use crate::{
-    constants::ELEMENT_DB_FILENAME,
+    allocation::{Category, LaneData, RawAllocationData},
+    constants::{
+        ALLOC_ROWS_TABLE, CALLSTACKS_TABLE, CLOCK_TABLE, ELEMENT_DB_FILENAME, EVENTS_TABLE,
+        LANES_TABLE, RANK_COLUMN, RESERVED_TABLE, SEGMENTS_TABLE, TIMELINE_TABLE,
+    },
+    events::{AllocatorEvent, EventKind},
+    groups::CallstackGroups,
+    segments::Segment,
     utils::{get_spinner, memory_usage},
 };
-use rusqlite::Connection;
+use anyhow::{Context, bail};
+use rusqlite::{Connection, Row};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::Path;
 
+/// Allocations live at one timestamp, see `AllocationDatabase::live_at`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveSet {
+    pub count: usize,
+    pub bytes: u64,
+    /// (index, size, callstack) of the largest ones, largest first
+    pub largest: Vec<(usize, u64, String)>,
+}
+
+/// Condition on the allocs table for allocations live at timestamp `?1`: allocated by then and
+/// freed after
+pub const LIVE_AT_CONDITION: &str = "start_time <= ?1 AND end_time > ?1";
+
 #[derive(Debug)]
 pub struct AllocationDatabase {
     pub conn: Connection,
 }
 
 impl AllocationDatabase {
+    /// Opens elements.db in `dir`. Without one, an empty in-memory database stands in so
+    /// allocations from other sources still load, see `has_elements`.
     pub fn from_dir(dir: &Path) -> anyhow::Result<Self> {
         log::info!("Creating allocations database");
         println!(
@@ -19,12 +44,43 @@ impl AllocationDatabase {
         );
 
         let elements_path = dir.join(ELEMENT_DB_FILENAME);
+        if !elements_path.exists() {
+            log::warn!(
+                "{:?} not found, callstacks will be unavailable",
+                elements_path
+            );
+            return Ok(Self {
+                conn: Connection::open_in_memory()?,
+            });
+        }
 
         Ok(Self {
             conn: Connection::open(elements_path)?,
         })
     }
 
+    /// Whether the allocs table (sizes and callstacks) exists, false when elements.db is missing.
+    /// A view over `ALLOC_ROWS_TABLE` and `CALLSTACKS_TABLE` since callstacks are interned.
+    pub fn has_elements(&self) -> anyhow::Result<bool> {
+        self.has_table("allocs")
+    }
+
+    /// Whether callstacks are stored once each in `CALLSTACKS_TABLE` (converter v18+) rather
+    /// than with every allocation
+    pub fn has_interned_callstacks(&self) -> anyhow::Result<bool> {
+        self.has_table(CALLSTACKS_TABLE)
+    }
+
+    fn require_elements(&self) -> anyhow::Result<()> {
+        if !self.has_elements()? {
+            bail!(
+                "{} is missing, callstacks and SQL queries are unavailable",
+                ELEMENT_DB_FILENAME
+            );
+        }
+        Ok(())
+    }
+
     pub fn row_count(&self) -> anyhow::Result<usize> {
         let bar = get_spinner("Querying # of rows...")?;
         let mut stmt = self.conn.prepare("SELECT COUNT(*) FROM allocs")?;
@@ -33,8 +89,384 @@ impl AllocationDatabase {
         Ok(count as usize)
     }
 
+    /// Whether allocation geometry is stored in the database (converter v2+),
+    /// instead of a separate allocations.json
+    pub fn has_timeline(&self) -> anyhow::Result<bool> {
+        self.has_table(TIMELINE_TABLE)
+    }
+
+    /// Whether per-stream lane geometry is stored in the database (converter v3+)
+    pub fn has_lanes(&self) -> anyhow::Result<bool> {
+        self.has_table(LANES_TABLE)
+    }
+
+    fn has_table(&self, name: &str) -> anyhow::Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?1",
+            [name],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Whether allocations carry PyTorch's allocation categories (converter v7+)
+    pub fn has_categories(&self) -> anyhow::Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('allocs') WHERE name = 'category'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Whether the database merges several ranks' snapshots (see `merge::merge_ranks`), its
+    /// lanes being ranks rather than streams
+    pub fn has_ranks(&self) -> anyhow::Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('allocs') WHERE name = ?1",
+            [RANK_COLUMN],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Rank of each allocation of a database merging ranks, ordered by allocation index
+    pub fn read_ranks(&self) -> anyhow::Result<Vec<i64>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT {} FROM allocs ORDER BY idx", RANK_COLUMN))?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// A temporary copy holding only the allocations at `indices`, renumbered from 0 in that
+    /// order, and every other table whole; just `rank`'s rows of those with a rank column, if
+    /// given. Deleted when dropped.
+    pub fn filtered(&self, indices: &[usize], rank: Option<i64>) -> anyhow::Result<Self> {
+        let source = self
+            .conn
+            .path()
+            .filter(|path| !path.is_empty())
+            .context("Only an elements.db on disk can be filtered")?;
+        // an empty path opens a temporary database on disk rather than in memory
+        let mut conn = Connection::open("")?;
+        conn.execute("ATTACH DATABASE ?1 AS source", [source])?;
+        let tx = conn.transaction()?;
+        let schema: Vec<(String, String, String)> = tx
+            .prepare(
+                "SELECT type, name, sql FROM source.sqlite_master
+                WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+                ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 ELSE 2 END",
+            )?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<_, _>>()?;
+        tx.execute_batch("CREATE TEMP TABLE kept (idx INTEGER PRIMARY KEY, source_idx INTEGER)")?;
+        {
+            let mut kept = tx.prepare("INSERT INTO kept VALUES (?1, ?2)")?;
+            for (idx, &source_idx) in indices.iter().enumerate() {
+                kept.execute((idx, source_idx))?;
+            }
+        }
+        // tables, then indices and views on them
+        for (kind, name, sql) in &schema {
+            tx.execute_batch(sql)?;
+            if kind != "table" {
+                continue;
+            }
+            let columns: Vec<String> = tx
+                .prepare("SELECT name FROM pragma_table_info(?1, 'source')")?
+                .query_map([name], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+            if ["allocs", ALLOC_ROWS_TABLE, TIMELINE_TABLE, LANES_TABLE].contains(&name.as_str()) {
+                let columns: Vec<String> = columns
+                    .iter()
+                    .map(|column| match column.as_str() {
+                        "idx" => "kept.idx".to_string(),
+                        _ => format!("t.{}", column),
+                    })
+                    .collect();
+                tx.execute(
+                    &format!(
+                        "INSERT INTO main.{0} SELECT {1} FROM kept JOIN source.{0} t
+                        ON t.idx = kept.source_idx ORDER BY kept.idx",
+                        name,
+                        columns.join(", ")
+                    ),
+                    [],
+                )?;
+            } else {
+                let condition = match rank {
+                    Some(rank) if columns.iter().any(|c| c == RANK_COLUMN) => {
+                        format!("WHERE {} = {}", RANK_COLUMN, rank)
+                    }
+                    _ => String::new(),
+                };
+                tx.execute(
+                    &format!(
+                        "INSERT INTO main.{0} SELECT * FROM source.{0} {1}",
+                        name, condition
+                    ),
+                    [],
+                )?;
+            }
+        }
+        tx.execute_batch("DROP TABLE kept")?;
+        tx.commit()?;
+        conn.execute("DETACH DATABASE source", [])?;
+        Ok(Self { conn })
+    }
+
+    /// Read allocation geometry from the timeline table, ordered by allocation index
+    pub fn read_timeline(&self) -> anyhow::Result<Vec<RawAllocationData>> {
+        let category = match self.has_categories()? {
+            true => "allocs.category",
+            false => "NULL",
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT allocs.size, {0}.timesteps, {0}.offsets, {1} FROM allocs JOIN {0} USING (idx) ORDER BY idx",
+            TIMELINE_TABLE, category
+        ))?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(RawAllocationData {
+                size: row.get::<_, i64>(0)? as u64,
+                timesteps: decode_u64s(row.get_ref(1)?.as_blob()?),
+                offsets: decode_u64s(row.get_ref(2)?.as_blob()?),
+                category: row
+                    .get::<_, Option<String>>(3)?
+                    .as_deref()
+                    .and_then(Category::parse),
+            })
+        })?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Read per-stream lane geometry from the lanes table, ordered by allocation index
+    pub fn read_lanes(&self) -> anyhow::Result<Vec<LaneData>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT stream, lane_offsets FROM {} ORDER BY idx",
+            LANES_TABLE
+        ))?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(LaneData {
+                stream: row.get(0)?,
+                lane_offsets: decode_u64s(row.get_ref(1)?.as_blob()?),
+            })
+        })?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Allocator events ordered by time, empty for databases predating the events table.
+    /// Actions the renderer does not know are skipped.
+    pub fn read_events(&self) -> anyhow::Result<Vec<AllocatorEvent>> {
+        if !self.has_table(EVENTS_TABLE)? {
+            return Ok(Vec::new());
+        }
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT timestep, action, size, stream, callstack FROM {} ORDER BY timestep, idx",
+            EVENTS_TABLE
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)? as u64,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)? as u64,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            ))
+        })?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            let (timestep, action, size, stream, callstack) = row?;
+            if let Some(kind) = EventKind::from_action(&action) {
+                events.push(AllocatorEvent {
+                    timestep,
+                    kind,
+                    size,
+                    stream,
+                    callstack,
+                });
+            }
+        }
+        Ok(events)
+    }
+
+    /// (timestep, raw time) samples of the clock table, empty for databases predating it
+    pub fn read_clock(&self) -> anyhow::Result<Vec<(u64, i64)>> {
+        if !self.has_table(CLOCK_TABLE)? {
+            return Ok(Vec::new());
+        }
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT timestep, time FROM {}", CLOCK_TABLE))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)?))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// (timestep, bytes) where the memory reserved in segments changes, empty for databases
+    /// predating the reserved table
+    pub fn read_reserved(&self) -> anyhow::Result<Vec<(u64, u64)>> {
+        if !self.has_table(RESERVED_TABLE)? {
+            return Ok(Vec::new());
+        }
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT timestep, bytes FROM {}", RESERVED_TABLE))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Segments reserved in the trace, empty for databases predating the segments table
+    pub fn read_segments(&self) -> anyhow::Result<Vec<Segment>> {
+        if !self.has_table(SEGMENTS_TABLE)? {
+            return Ok(Vec::new());
+        }
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT start_time, end_time, size FROM {} ORDER BY idx",
+            SEGMENTS_TABLE
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Segment {
+                start: row.get::<_, i64>(0)? as u64,
+                stop: row.get::<_, i64>(1)? as u64,
+                size: row.get::<_, i64>(2)? as u64,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn read_callstack(&self, idx: usize) -> anyhow::Result<String> {
+        self.require_elements()?;
+        let callstack: Option<String> = self.conn.query_row(
+            "SELECT callstack FROM allocs WHERE idx = ?1",
+            [idx as i64],
+            |row| row.get(0),
+        )?;
+        Ok(callstack.unwrap_or_default())
+    }
+
+    /// Allocations live at `timestamp`, with the `limit` largest of them
+    pub fn live_at(&self, timestamp: u64, limit: usize) -> anyhow::Result<LiveSet> {
+        self.require_elements()?;
+        let (count, bytes) = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(size), 0) FROM allocs WHERE {}",
+                LIVE_AT_CONDITION
+            ),
+            [timestamp as i64],
+            |row| Ok((row.get::<_, i64>(0)? as usize, row.get::<_, i64>(1)? as u64)),
+        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT idx, size, callstack FROM allocs WHERE {} ORDER BY size DESC, idx LIMIT ?2",
+            LIVE_AT_CONDITION
+        ))?;
+        let rows = stmt.query_map([timestamp as i64, limit as i64], |row| {
+            Ok((
+                row.get::<_, i64>(0)? as usize,
+                row.get::<_, i64>(1)? as u64,
+                row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            ))
+        })?;
+        Ok(LiveSet {
+            count,
+            bytes,
+            largest: rows.collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Callstacks of the allocations `indices`, in the same order
+    pub fn read_callstacks(&self, indices: &[usize]) -> anyhow::Result<Vec<String>> {
+        self.require_elements()?;
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT callstack FROM allocs WHERE idx = ?1")?;
+        indices
+            .iter()
+            .map(|&idx| {
+                let callstack: Option<String> = stmt.query_row([idx as i64], |row| row.get(0))?;
+                Ok(callstack.unwrap_or_default())
+            })
+            .collect()
+    }
+
+    /// Allocations grouped by callstack
+    pub fn read_callstack_groups(&self) -> anyhow::Result<CallstackGroups> {
+        self.require_elements()?;
+        if self.has_interned_callstacks()? {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT callstack_id FROM {} ORDER BY idx",
+                ALLOC_ROWS_TABLE
+            ))?;
+            let rows = stmt.query_map([], |row| row.get::<_, Option<i64>>(0))?;
+            let ids: Vec<Option<i64>> = rows.collect::<Result<_, _>>()?;
+            return Ok(CallstackGroups::new(ids));
+        }
+        let mut stmt = self
+            .conn
+            .prepare("SELECT callstack FROM allocs ORDER BY idx")?;
+        let rows = stmt.query_map([], |row| callstack_hash(row, 0))?;
+        let hashes: Vec<u64> = rows.collect::<Result<_, _>>()?;
+        Ok(CallstackGroups::new(hashes))
+    }
+
+    /// Size and callstack hash of every allocation, ordered by allocation index, to match
+    /// allocations by callstack without reading every callstack into memory
+    pub fn read_sizes_and_callstack_hashes(&self) -> anyhow::Result<Vec<(u64, u64)>> {
+        self.require_elements()?;
+        if self.has_interned_callstacks()? {
+            // each distinct callstack hashed once
+            let hashes: HashMap<i64, u64> = self
+                .conn
+                .prepare(&format!("SELECT id, callstack FROM {}", CALLSTACKS_TABLE))?
+                .query_map([], |row| Ok((row.get(0)?, callstack_hash(row, 1)?)))?
+                .collect::<Result<_, _>>()?;
+            let none = hash_str("");
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT size, callstack_id FROM {} ORDER BY idx",
+                ALLOC_ROWS_TABLE
+            ))?;
+            let rows = stmt.query_map([], |row| {
+                let id: Option<i64> = row.get(1)?;
+                let hash = id.and_then(|id| hashes.get(&id)).copied();
+                Ok((row.get::<_, i64>(0)? as u64, hash.unwrap_or(none)))
+            })?;
+            return Ok(rows.collect::<Result<_, _>>()?);
+        }
+        let mut stmt = self
+            .conn
+            .prepare("SELECT size, callstack FROM allocs ORDER BY idx")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)? as u64, callstack_hash(row, 1)?))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Size and callstack of every allocation, ordered by allocation index
+    pub fn read_sizes_and_callstacks(&self) -> anyhow::Result<Vec<(u64, String)>> {
+        self.require_elements()?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT size, callstack FROM allocs ORDER BY idx")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)? as u64,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            ))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
     pub fn execute(&self, command: &str) -> anyhow::Result<String> {
         log::info!("Executing SQL query");
+        self.require_elements()?;
 
         let mut stmt = self.conn.prepare(command)?;
         let num_cols = stmt.column_count();
@@ -92,3 +524,104 @@ impl AllocationDatabase {
         Ok(output_string)
     }
 }
+
+/// Blob layout of the timeline table: little-endian u64 array
+fn decode_u64s(blob: &[u8]) -> Vec<u64> {
+    blob.chunks_exact(8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
+
+/// Hash of the callstack in column `col`, hashed in place: callstacks are most of a snapshot's
+/// size and only read whole on demand
+fn callstack_hash(row: &Row, col: usize) -> rusqlite::Result<u64> {
+    let callstack = row.get_ref(col)?.as_str_or_null()?;
+    Ok(hash_str(callstack.unwrap_or_default()))
+}
+
+fn hash_str(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::sqlite::AllocationDatabase;
+
+    #[test]
+    fn test_live_at() {
+        let db = AllocationDatabase {
+            conn: rusqlite::Connection::open_in_memory().unwrap(),
+        };
+        db.conn
+            .execute_batch(
+                "CREATE TABLE allocs (idx INTEGER PRIMARY KEY, size INTEGER, start_time INTEGER, \
+                 end_time INTEGER, callstack TEXT);
+                 INSERT INTO allocs VALUES (0, 100, 0, 10, 'a'), (1, 300, 5, 20, 'b'), \
+                 (2, 200, 5, 8, NULL);",
+            )
+            .unwrap();
+
+        let live = db.live_at(6, 2).unwrap();
+        assert_eq!((live.count, live.bytes), (3, 600));
+        assert_eq!(
+            live.largest,
+            [(1, 300, "b".to_string()), (2, 200, String::new())]
+        );
+        // freed at 10: gone by then
+        let live = db.live_at(10, 5).unwrap();
+        assert_eq!((live.count, live.bytes), (1, 300));
+        assert_eq!(db.live_at(50, 5).unwrap().count, 0);
+    }
+
+    #[test]
+    fn test_callstack_hashes() {
+        let db = AllocationDatabase {
+            conn: rusqlite::Connection::open_in_memory().unwrap(),
+        };
+        db.conn
+            .execute_batch(
+                "CREATE TABLE allocs (idx INTEGER PRIMARY KEY, size INTEGER, callstack TEXT);
+                 INSERT INTO allocs VALUES (0, 8, 'a'), (1, 8, NULL), (2, 16, 'a'), (3, 8, '');",
+            )
+            .unwrap();
+
+        let keys = db.read_sizes_and_callstack_hashes().unwrap();
+        assert_eq!(keys[0].1, keys[2].1);
+        assert_ne!(keys[0].1, keys[1].1);
+        // a missing callstack is an empty one
+        assert_eq!(keys[1], keys[3]);
+        let groups = db.read_callstack_groups().unwrap();
+        assert_eq!(groups.members(0), [0, 2]);
+        assert_eq!(groups.members(1), [1, 3]);
+    }
+
+    #[test]
+    fn test_interned_callstacks() {
+        let db = AllocationDatabase {
+            conn: rusqlite::Connection::open_in_memory().unwrap(),
+        };
+        db.conn
+            .execute_batch(
+                "CREATE TABLE callstacks (id INTEGER PRIMARY KEY, callstack TEXT);
+                 CREATE TABLE alloc_rows (idx INTEGER PRIMARY KEY, size INTEGER, \
+                 callstack_id INTEGER);
+                 CREATE VIEW allocs AS SELECT alloc_rows.*, callstacks.callstack FROM alloc_rows \
+                 LEFT JOIN callstacks ON callstacks.id = alloc_rows.callstack_id;
+                 INSERT INTO callstacks VALUES (0, 'a'), (1, 'b');
+                 INSERT INTO alloc_rows VALUES (0, 8, 0), (1, 8, 1), (2, 16, 0);",
+            )
+            .unwrap();
+
+        assert!(db.has_elements().unwrap());
+        let keys = db.read_sizes_and_callstack_hashes().unwrap();
+        assert_eq!(keys[0].1, keys[2].1);
+        assert_ne!(keys[0].1, keys[1].1);
+        assert_eq!(db.read_callstack_groups().unwrap().members(0), [0, 2]);
+        assert_eq!(
+            db.read_sizes_and_callstacks().unwrap()[2],
+            (16, "a".to_string())
+        );
+    }
+}
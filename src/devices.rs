@@ -0,0 +1,96 @@
+//! The devices of a multi-device snapshot, each converted into its own directory (e.g. by
+//! `convert --device all`). The renderer shows one of them at a time, switched with Tab or
+//! `--device <id>` over IPC.
+
+use crate::constants::METADATA_FILE_NAME;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+pub struct Devices {
+    /// Device ID and converted directory of each device, ordered by ID
+    pub dirs: Vec<(u64, PathBuf)>,
+    /// Index into `dirs` of the device shown
+    pub active: usize,
+}
+
+/// The device a converted directory holds, from its metadata.json
+pub fn device_id(dir: &Path) -> Option<u64> {
+    std::fs::read_to_string(dir.join(METADATA_FILE_NAME))
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+        .and_then(|metadata| metadata["device"].as_u64())
+}
+
+impl Devices {
+    /// `shown` and the `others`, numbered by the device in their metadata.json or, for
+    /// conversions not recording it, their position
+    pub fn new(shown: &Path, others: &[PathBuf]) -> Self {
+        let mut dirs: Vec<(u64, PathBuf)> = std::iter::once(shown.to_path_buf())
+            .chain(others.iter().cloned())
+            .enumerate()
+            .map(|(i, dir)| (device_id(&dir).unwrap_or(i as u64), dir))
+            .collect();
+        dirs.sort_by_key(|(id, _)| *id);
+        let active = dirs.iter().position(|(_, dir)| dir == shown).unwrap();
+        Self { dirs, active }
+    }
+
+    /// Index of the device after the shown one, wrapping around
+    pub fn next(&self) -> usize {
+        (self.active + 1) % self.dirs.len()
+    }
+
+    /// Index of device `id`, as `--device <id>` names it
+    pub fn find(&self, id: &str) -> anyhow::Result<usize> {
+        let id: u64 = id.trim().parse()?;
+        self.dirs
+            .iter()
+            .position(|(device, _)| *device == id)
+            .ok_or_else(|| anyhow::anyhow!("No device {}, {}", id, self))
+    }
+
+    pub fn active_id(&self) -> u64 {
+        self.dirs[self.active].0
+    }
+}
+
+impl Display for Devices {
+    /// "devices: 0 (shown), 2, 3"
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let ids: Vec<String> = self
+            .dirs
+            .iter()
+            .enumerate()
+            .map(|(i, (id, _))| {
+                if i == self.active {
+                    format!("{} (shown)", id)
+                } else {
+                    id.to_string()
+                }
+            })
+            .collect();
+        write!(f, "devices: {}", ids.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::devices::Devices;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_devices_by_position() {
+        let devices = Devices::new(
+            Path::new("no-such-dir/b"),
+            &[
+                PathBuf::from("no-such-dir/a"),
+                PathBuf::from("no-such-dir/c"),
+            ],
+        );
+        assert_eq!(devices.active, 0);
+        assert_eq!(devices.next(), 1);
+        assert_eq!(devices.find("2").unwrap(), 2);
+        assert!(devices.find("3").is_err());
+        assert_eq!(devices.to_string(), "devices: 0 (shown), 1, 2");
+    }
+}
@@ -0,0 +1,65 @@
+use crate::allocation::Allocation;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+
+/// A second snapshot to overlay on the primary one
+pub struct DiffInput {
+    /// Shown in reports, e.g. the directory
+    pub name: String,
+    pub allocations: Arc<[Allocation]>,
+    /// From `unmatched_allocations`
+    pub only_primary: Vec<usize>,
+    pub only_diff: Vec<usize>,
+}
+
+/// Matches allocations of two snapshots by size and callstack (timestamps differ between runs),
+/// each allocation matching at most one in the other snapshot. Callstacks can be given as hashes.
+///
+/// ## Returns
+/// Indices of allocations only in `primary`, and of those only in `other`.
+pub fn unmatched_allocations<K: Hash + Eq>(
+    primary: &[(u64, K)],
+    other: &[(u64, K)],
+) -> (Vec<usize>, Vec<usize>) {
+    let mut unmatched_other: HashMap<(u64, &K), Vec<usize>> = HashMap::new();
+    for (idx, (size, callstack)) in other.iter().enumerate().rev() {
+        unmatched_other
+            .entry((*size, callstack))
+            .or_default()
+            .push(idx);
+    }
+
+    let only_primary = primary
+        .iter()
+        .enumerate()
+        .filter(|(_, (size, callstack))| {
+            unmatched_other
+                .get_mut(&(*size, callstack))
+                .and_then(|candidates| candidates.pop())
+                .is_none()
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let mut only_other: Vec<usize> = unmatched_other.into_values().flatten().collect();
+    only_other.sort_unstable();
+
+    (only_primary, only_other)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::unmatched_allocations;
+
+    #[test]
+    fn test_unmatched_allocations() {
+        let key = |size: u64, callstack: &str| (size, callstack.to_string());
+        let primary = vec![key(8, "a"), key(8, "a"), key(16, "b"), key(32, "c")];
+        let other = vec![key(8, "a"), key(16, "b"), key(16, "b"), key(64, "d")];
+
+        let (only_primary, only_other) = unmatched_allocations(&primary, &other);
+        assert_eq!(only_primary, vec![1, 3]);
+        assert_eq!(only_other, vec![2, 3]);
+    }
+}
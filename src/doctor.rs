@@ -0,0 +1,188 @@
+//! Environment checks behind the `doctor` subcommand. Most reported problems are a missing GL
+//! driver, a busy port or a broken Python setup rather than bugs in SnapViewer.
+
+use crate::utils::config_dir;
+use anyhow::{Context as _, Result, bail};
+use std::fmt::Display;
+use std::process::Command;
+use three_d::context::HasContext;
+use three_d::{Window, WindowSettings};
+
+/// Modules `gui.py` imports; pickles are converted by the renderer itself
+const PYTHON_MODULES: &str = "blake3, zmq, fluent.runtime";
+
+pub struct Check {
+    pub name: &'static str,
+    /// What was found on success
+    pub outcome: Result<String>,
+    /// What to try if the check failed
+    pub hint: &'static str,
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.outcome {
+            Ok(found) => write!(f, "[PASS] {}: {}", self.name, found),
+            Err(e) => write!(
+                f,
+                "[FAIL] {}: {:#}\n       hint: {}",
+                self.name, e, self.hint
+            ),
+        }
+    }
+}
+
+/// Runs all checks; IPC is checked on the ports the renderer would bind
+pub fn run_checks(pub_port: u16, rep_port: u16) -> Vec<Check> {
+    vec![
+        Check {
+            name: "OpenGL",
+            outcome: check_gl(),
+            hint: "install or update the GPU driver (Mesa on Linux); over SSH or in a VM try \
+                   LIBGL_ALWAYS_SOFTWARE=1, and make sure DISPLAY or WAYLAND_DISPLAY is set",
+        },
+        Check {
+            name: "Renderer discovery",
+            outcome: check_renderer_discovery(),
+            hint: "run `cargo build --release` in the repository, or pass --bin to gui.py",
+        },
+        Check {
+            name: "Cache directory",
+            outcome: check_cache_dir(),
+            hint: "make sure the home directory exists and ~/.snapviewer_cache is writable",
+        },
+        Check {
+            name: "Python",
+            outcome: check_python(),
+            hint: "install Python 3 and run `pip install -r requirements.txt`",
+        },
+        Check {
+            name: "IPC",
+            outcome: check_ipc(pub_port, rep_port),
+            hint: "close other SnapViewer instances, or pick free ports with --pub-port / --rep-port",
+        },
+    ]
+}
+
+fn check_gl() -> Result<String> {
+    // winit panics instead of erroring when there is no display at all
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let window = std::panic::catch_unwind(|| {
+        Window::new(WindowSettings {
+            title: "SnapViewer doctor".to_string(),
+            min_size: (64, 64),
+            max_size: Some((64, 64)),
+            ..Default::default()
+        })
+    });
+    std::panic::set_hook(default_hook);
+    let window = match window {
+        Ok(window) => window.context("could not create a window with a GL context")?,
+        Err(panic) => {
+            let msg = panic
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| panic.downcast_ref::<&str>().copied())
+                .unwrap_or("window creation panicked");
+            bail!("{}", msg);
+        }
+    };
+    let gl = window.gl();
+    let (version, renderer) = unsafe {
+        (
+            gl.get_parameter_string(three_d::context::VERSION),
+            gl.get_parameter_string(three_d::context::RENDERER),
+        )
+    };
+    Ok(format!("{} ({})", version, renderer))
+}
+
+/// gui.py looks for the renderer in `target/release`, then `target/debug`, next to itself
+fn check_renderer_discovery() -> Result<String> {
+    let exe = std::env::current_exe()?;
+    let file_name = exe.file_name().context("renderer path has no file name")?;
+    let Some(root) = exe.ancestors().nth(3) else {
+        bail!("{} is not in a cargo target directory", exe.display());
+    };
+    if !root.join("gui.py").exists() {
+        bail!("gui.py not found in {}", root.display());
+    }
+    let release = root.join("target").join("release").join(file_name);
+    let debug = root.join("target").join("debug").join(file_name);
+    if release.exists() {
+        Ok(format!("gui.py will use {}", release.display()))
+    } else if debug.exists() {
+        Ok(format!(
+            "gui.py will use the (slow) debug build {}",
+            debug.display()
+        ))
+    } else {
+        bail!("gui.py will not find this renderer at {}", exe.display());
+    }
+}
+
+fn check_cache_dir() -> Result<String> {
+    let dir = config_dir().context("no home directory")?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+    let probe = dir.join(".doctor_probe");
+    std::fs::write(&probe, "").with_context(|| format!("writing to {}", dir.display()))?;
+    std::fs::remove_file(&probe)?;
+    Ok(format!("{} is writable", dir.display()))
+}
+
+fn check_python() -> Result<String> {
+    let script = format!(
+        "import sys, {}; print(sys.version.split()[0])",
+        PYTHON_MODULES
+    );
+    let mut errors = Vec::new();
+    for python in ["python3", "python"] {
+        match Command::new(python).args(["-c", &script]).output() {
+            Ok(output) if output.status.success() => {
+                let version = String::from_utf8_lossy(&output.stdout);
+                return Ok(format!("{} {}", python, version.trim()));
+            }
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                errors.push(format!(
+                    "{}: {}",
+                    python,
+                    stderr.lines().last().unwrap_or("failed")
+                ));
+            }
+            Err(e) => errors.push(format!("{}: {}", python, e)),
+        }
+    }
+    bail!("{}", errors.join("; "))
+}
+
+/// Binds both renderer sockets and round-trips a message, as the GUI would
+fn check_ipc(pub_port: u16, rep_port: u16) -> Result<String> {
+    let context = zmq::Context::new();
+    let pub_socket = context.socket(zmq::SocketType::PUB)?;
+    pub_socket
+        .bind(&format!("tcp://*:{}", pub_port))
+        .with_context(|| format!("binding PUB port {}", pub_port))?;
+    let rep_socket = context.socket(zmq::SocketType::REP)?;
+    rep_socket
+        .bind(&format!("tcp://*:{}", rep_port))
+        .with_context(|| format!("binding REP port {}", rep_port))?;
+
+    let req_socket = context.socket(zmq::SocketType::REQ)?;
+    req_socket.set_rcvtimeo(1000)?;
+    rep_socket.set_rcvtimeo(1000)?;
+    req_socket.connect(&format!("tcp://127.0.0.1:{}", rep_port))?;
+    req_socket.send("ping", 0)?;
+    let request = rep_socket
+        .recv_bytes(0)
+        .context("no request arrived over loopback")?;
+    rep_socket.send(request, 0)?;
+    let reply = req_socket
+        .recv_bytes(0)
+        .context("no reply arrived over loopback")?;
+    if reply != b"ping" {
+        bail!("reply was corrupted");
+    }
+    Ok(format!("ports {} and {} are free", pub_port, rep_port))
+}
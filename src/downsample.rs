@@ -0,0 +1,133 @@
+//! `--max-allocs`: a quick look at a trace too large for the machine, drawing only its largest
+//! (or longest-lived) allocations and the rest aggregated into grey "other" bands. The others
+//! stay in place with no height, so allocation indices still match the database.
+
+use crate::{allocation::Allocation, geometry::Lane, memory_timeline::MemoryTimeline};
+use anyhow::bail;
+use std::{cmp::Reverse, str::FromStr, sync::Arc};
+
+/// Which allocations `--max-allocs` keeps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeepBy {
+    Size,
+    /// Time from first to last timestep
+    Lifetime,
+}
+
+impl FromStr for KeepBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "size" => Ok(KeepBy::Size),
+            "lifetime" => Ok(KeepBy::Lifetime),
+            _ => bail!("Unknown ranking `{}`, expected size or lifetime", s),
+        }
+    }
+}
+
+/// The allocations `--max-allocs` left out, aggregated
+pub struct Others {
+    /// How many were left out
+    pub count: usize,
+    /// Live memory of all allocations, kept or not, for the readouts
+    pub memory: MemoryTimeline,
+    /// Base and live memory of all allocations of each lane, a single band at 0 without lanes
+    pub bands: Vec<(u64, MemoryTimeline)>,
+}
+
+/// Keeps the `max_allocs` allocations ranking first by `keep_by`, the others replaced by ones of
+/// no height over the same time span. `None` if there are no more than `max_allocs`.
+/// `streams` is the lane of each allocation with `lanes`, empty without.
+pub fn downsample(
+    allocations: &[Allocation],
+    max_allocs: usize,
+    keep_by: KeepBy,
+    streams: &[i64],
+    lanes: &[Lane],
+) -> Option<(Arc<[Allocation]>, Others)> {
+    if allocations.len() <= max_allocs {
+        return None;
+    }
+    let rank = |alloc: &Allocation| match keep_by {
+        KeepBy::Size => alloc.size,
+        KeepBy::Lifetime => {
+            let (start, stop) = alloc.start_end_time();
+            stop - start
+        }
+    };
+    let mut order: Vec<usize> = (0..allocations.len()).collect();
+    // ties go to the earlier allocation, so the same ones are kept every time
+    order.select_nth_unstable_by_key(max_allocs, |&i| (Reverse(rank(&allocations[i])), i));
+    let mut kept = vec![false; allocations.len()];
+    for &i in &order[..max_allocs] {
+        kept[i] = true;
+    }
+
+    let bands = match lanes.is_empty() {
+        true => vec![(0, MemoryTimeline::from_allocations(allocations))],
+        false => lanes
+            .iter()
+            .map(|lane| {
+                let timeline = MemoryTimeline::from_allocations_where(allocations, |i| {
+                    streams[i] == lane.stream
+                });
+                (lane.base, timeline)
+            })
+            .collect(),
+    };
+    let downsampled = allocations
+        .iter()
+        .zip(&kept)
+        .map(|(alloc, &kept)| match kept {
+            true => alloc.clone(),
+            false => {
+                let (start, stop) = alloc.start_end_time();
+                Allocation::new(
+                    vec![start, stop].into(),
+                    vec![0, 0].into(),
+                    0,
+                    alloc.category,
+                )
+            }
+        })
+        .collect();
+    let others = Others {
+        count: allocations.len() - max_allocs,
+        memory: MemoryTimeline::from_allocations(allocations),
+        bands,
+    };
+    Some((downsampled, others))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::Allocation;
+    use crate::downsample::{KeepBy, downsample};
+
+    #[test]
+    fn test_downsample() {
+        let alloc = |start: u64, stop: u64, offset: u64, size| {
+            Allocation::new(
+                vec![start, stop].into(),
+                vec![offset, offset].into(),
+                size,
+                None,
+            )
+        };
+        let allocs = [alloc(0, 10, 0, 8), alloc(0, 50, 8, 2), alloc(5, 6, 10, 4)];
+        assert!(downsample(&allocs, 3, KeepBy::Size, &[], &[]).is_none());
+
+        let sizes = |allocs: &[Allocation]| allocs.iter().map(|a| a.size).collect::<Vec<_>>();
+        let (by_size, others) = downsample(&allocs, 2, KeepBy::Size, &[], &[]).unwrap();
+        assert_eq!(sizes(&by_size), [8, 0, 4]);
+        assert_eq!(by_size[1].start_end_time(), (0, 50));
+        assert_eq!(others.count, 1);
+        // the left out allocation still counts in live memory
+        assert_eq!(others.memory.live_at(20), 2);
+        assert_eq!(others.bands[0].1.live_at(5), 14);
+
+        let (by_lifetime, _) = downsample(&allocs, 1, KeepBy::Lifetime, &[], &[]).unwrap();
+        assert_eq!(sizes(&by_lifetime), [0, 2, 0]);
+    }
+}
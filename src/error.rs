@@ -0,0 +1,67 @@
+//! Failure categories with stable process exit codes, for wrapper scripts and CI.
+//! gui.py exits with the same codes (`EXIT_*` there).
+
+use std::fmt;
+
+/// What kind of failure an error is, attached to it with `anyhow::Context`:
+/// `db.read_events().context(SnapError::BadInput)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapError {
+    /// Missing, malformed or inconsistent snapshot, arguments or files
+    BadInput,
+    /// Writing a converted snapshot failed
+    Conversion,
+    /// No window, OpenGL context or NVML
+    GpuInit,
+    /// ZeroMQ sockets to the GUI or a linked renderer
+    Ipc,
+}
+
+impl SnapError {
+    /// 1 is left for errors without a category, 2 matches clap's usage errors
+    pub fn exit_code(self) -> u8 {
+        match self {
+            SnapError::BadInput => 2,
+            SnapError::Conversion => 3,
+            SnapError::GpuInit => 4,
+            SnapError::Ipc => 5,
+        }
+    }
+}
+
+impl fmt::Display for SnapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self {
+            SnapError::BadInput => "invalid input",
+            SnapError::Conversion => "conversion failed",
+            SnapError::GpuInit => "GPU initialization failed",
+            SnapError::Ipc => "IPC failed",
+        };
+        f.write_str(what)
+    }
+}
+
+impl std::error::Error for SnapError {}
+
+/// Exit code for `err`, by the outermost `SnapError` attached to it, 1 without one
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    err.downcast_ref::<SnapError>()
+        .map_or(1, |kind| kind.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{SnapError, exit_code};
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_code() {
+        let err = anyhow::anyhow!("no such table: allocs");
+        assert_eq!(exit_code(&err), 1);
+
+        let err: anyhow::Result<()> = Err(err).context(SnapError::BadInput);
+        let err = err.context("Loading snapshot").unwrap_err();
+        assert_eq!(exit_code(&err), 2);
+        assert_eq!(SnapError::Ipc.exit_code(), 5);
+    }
+}
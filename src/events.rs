@@ -0,0 +1,89 @@
+//! Allocator events recorded in the snapshot (OOMs, segment releases, snapshot dumps),
+//! shown as glyphs on a strip along the bottom of the plot.
+
+use crate::utils::format_bytes;
+use std::fmt::{Display, Formatter, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    OutOfMemory,
+    /// A cached segment returned to the driver, typically by `empty_cache`
+    SegmentFree,
+    Snapshot,
+}
+
+impl EventKind {
+    pub fn from_action(action: &str) -> Option<Self> {
+        match action {
+            "oom" => Some(Self::OutOfMemory),
+            "segment_free" => Some(Self::SegmentFree),
+            "snapshot" => Some(Self::Snapshot),
+            _ => None,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::OutOfMemory => "allocation failure (out of memory)",
+            Self::SegmentFree => "segment released (e.g. empty_cache)",
+            Self::Snapshot => "snapshot dump",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AllocatorEvent {
+    pub timestep: u64,
+    pub kind: EventKind,
+    pub size: u64,
+    pub stream: i64,
+    pub callstack: String,
+}
+
+impl Display for AllocatorEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "Allocator Event: {}", self.kind.description())?;
+        writeln!(f, "|- Timestep: {}", self.timestep)?;
+        if self.size > 0 {
+            writeln!(f, "|- Size: {}", format_bytes(self.size as i64))?;
+        }
+        writeln!(f, "|- Stream: {}", self.stream)?;
+        if !self.callstack.is_empty() {
+            writeln!(f, "|- Callstack:\n{}", self.callstack)?;
+        }
+        Ok(())
+    }
+}
+
+/// Index of the event closest to `timestep`, if it is within `tolerance` timesteps
+pub fn nearest_event(events: &[AllocatorEvent], timestep: i64, tolerance: i64) -> Option<usize> {
+    events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| (i, (event.timestep as i64 - timestep).abs()))
+        .filter(|&(_, distance)| distance <= tolerance)
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{AllocatorEvent, EventKind, nearest_event};
+
+    #[test]
+    fn test_nearest_event() {
+        let events: Vec<_> = [10, 20, 40]
+            .into_iter()
+            .map(|timestep| AllocatorEvent {
+                timestep,
+                kind: EventKind::Snapshot,
+                size: 0,
+                stream: 0,
+                callstack: String::new(),
+            })
+            .collect();
+        assert_eq!(nearest_event(&events, 18, 5), Some(1));
+        assert_eq!(nearest_event(&events, 31, 5), None);
+        assert_eq!(nearest_event(&events, 41, 5), Some(2));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    allocation::Allocation,
+    utils::{format_bytes, parse_bytes, parse_time_range},
+};
+use std::fmt::{Display, Formatter};
+
+/// Restricts which allocations are rendered and pickable.
+/// Changed at runtime via `--filter` commands over IPC.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AllocationFilter {
+    /// Allocations smaller than this are hidden
+    pub min_size: Option<u64>,
+    /// Allocations not alive in this (inclusive) timestamp window are hidden
+    pub time_range: Option<(u64, u64)>,
+}
+
+impl AllocationFilter {
+    pub fn accepts(&self, alloc: &Allocation) -> bool {
+        self.min_size.is_none_or(|min| alloc.size >= min)
+            && self
+                .time_range
+                .is_none_or(|(start, stop)| alloc.is_alive_in_interval(start, stop))
+    }
+
+    /// Applies a filter command (the part after `--filter`), e.g.
+    /// - `size > 1MiB`, `size >= 512KiB`
+    /// - `size clear`
+    /// - `time 10000..50000`
+    /// - `time clear`
+    /// - `clear`
+    ///
+    /// ## Returns
+    /// A message describing the active filter.
+    pub fn apply_command(&mut self, command: &str) -> anyhow::Result<String> {
+        let tokens: Vec<&str> = command.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["clear"] => *self = Self::default(),
+            ["size", "clear"] => self.min_size = None,
+            ["size", op, rest @ ..] if !rest.is_empty() => {
+                let bytes = parse_bytes(&rest.concat())?;
+                self.min_size = match *op {
+                    ">" => Some(bytes.checked_add(1).ok_or_else(|| {
+                        anyhow::anyhow!("No allocation is larger than {} bytes", bytes)
+                    })?),
+                    ">=" => Some(bytes),
+                    op => anyhow::bail!("Unsupported size operator '{}', expected `>` or `>=`", op),
+                };
+            }
+            ["time", "clear"] => self.time_range = None,
+            ["time", range] => self.time_range = Some(parse_time_range(range)?),
+            _ => anyhow::bail!(
+                "Usage:\n    --filter size > <size>\n    --filter size clear\n    --filter time <start>..<stop>\n    --filter time clear\n    --filter clear"
+            ),
+        }
+
+        Ok(format!("Filter applied: {}", self))
+    }
+}
+
+impl Display for AllocationFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut conditions = Vec::new();
+        if let Some(min) = self.min_size {
+            conditions.push(format!("size >= {}", format_bytes(min as i64)));
+        }
+        if let Some((start, stop)) = self.time_range {
+            conditions.push(format!("alive in {}..{}", start, stop));
+        }
+
+        if conditions.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", conditions.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::AllocationFilter;
+
+    #[test]
+    fn test_size_filter_command() {
+        let mut filter = AllocationFilter::default();
+
+        filter.apply_command("size > 1MiB").unwrap();
+        assert_eq!(filter.min_size, Some(1024 * 1024 + 1));
+
+        filter.apply_command("size >= 1.5 KiB").unwrap();
+        assert_eq!(filter.min_size, Some(1536));
+
+        filter.apply_command("clear").unwrap();
+        assert_eq!(filter.min_size, None);
+
+        assert!(filter.apply_command("size < 1MiB").is_err());
+        assert!(filter.apply_command("size > lots").is_err());
+        assert!(filter.apply_command("size > 18446744073709551615").is_err());
+    }
+
+    #[test]
+    fn test_time_filter_command() {
+        let mut filter = AllocationFilter::default();
+
+        filter.apply_command("time 10000..50000").unwrap();
+        assert_eq!(filter.time_range, Some((10000, 50000)));
+
+        filter.apply_command("size > 1KiB").unwrap();
+        filter.apply_command("time clear").unwrap();
+        assert_eq!(filter.time_range, None);
+        assert!(filter.min_size.is_some());
+
+        assert!(filter.apply_command("time 50..10").is_err());
+        assert!(filter.apply_command("time 10-50").is_err());
+    }
+}
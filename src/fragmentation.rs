@@ -0,0 +1,130 @@
+use crate::memory_timeline::MemoryTimeline;
+
+/// A stretch of time where part of the memory reserved in segments holds no allocation:
+/// `live..reserved` bytes over `start..stop` (timesteps, `stop` exclusive)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnusedReserved {
+    pub start: u64,
+    pub stop: u64,
+    pub live: u64,
+    pub reserved: u64,
+}
+
+/// Reserved-but-unused memory of the caching allocator until `end`, the gap between the
+/// reservation and the allocations stacked below it. `reserved` are (timestep, bytes) where the
+/// reservation changes, as in the reserved table.
+pub fn unused_reserved(
+    reserved: &[(u64, u64)],
+    live: &MemoryTimeline,
+    end: u64,
+) -> Vec<UnusedReserved> {
+    let mut reserved = reserved.to_vec();
+    reserved.sort_unstable_by_key(|&(t, _)| t);
+    let Some(&(first, _)) = reserved.first() else {
+        return Vec::new();
+    };
+
+    let mut times: Vec<u64> = reserved
+        .iter()
+        .map(|&(t, _)| t)
+        .chain(live.change_times().iter().copied())
+        .filter(|&t| first <= t && t < end)
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+
+    let mut pieces: Vec<UnusedReserved> = Vec::new();
+    for (i, &start) in times.iter().enumerate() {
+        let stop = times.get(i + 1).copied().unwrap_or(end);
+        let r = reserved[reserved.partition_point(|&(t, _)| t <= start) - 1].1;
+        let l = live.live_at(start);
+        if r <= l {
+            continue;
+        }
+        match pieces.last_mut() {
+            Some(last) if last.stop == start && last.live == l && last.reserved == r => {
+                last.stop = stop
+            }
+            _ => pieces.push(UnusedReserved {
+                start,
+                stop,
+                live: l,
+                reserved: r,
+            }),
+        }
+    }
+    pieces
+}
+
+/// Unused reserved bytes at `timestep`
+pub fn unused_at(pieces: &[UnusedReserved], timestep: u64) -> u64 {
+    let i = pieces.partition_point(|p| p.stop <= timestep);
+    match pieces.get(i) {
+        Some(p) if p.start <= timestep => p.reserved - p.live,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::Allocation;
+    use crate::fragmentation::{UnusedReserved, unused_at, unused_reserved};
+    use crate::memory_timeline::MemoryTimeline;
+
+    fn alloc(size: u64, start: u64, stop: u64) -> Allocation {
+        Allocation {
+            timesteps: vec![start, stop].into(),
+            offsets: vec![0, 0].into(),
+            size,
+            peak_mem: 0,
+            peak_timestamps: vec![],
+            category: None,
+        }
+    }
+
+    #[test]
+    fn test_unused_reserved() {
+        // 30 bytes live over 0..=9, 50 more over 5..=19
+        let live = MemoryTimeline::from_allocations(&[alloc(30, 0, 9), alloc(50, 5, 19)]);
+        let pieces = unused_reserved(&[(0, 100), (15, 60)], &live, 30);
+        assert_eq!(
+            pieces,
+            [
+                UnusedReserved {
+                    start: 0,
+                    stop: 5,
+                    live: 30,
+                    reserved: 100
+                },
+                UnusedReserved {
+                    start: 5,
+                    stop: 10,
+                    live: 80,
+                    reserved: 100
+                },
+                UnusedReserved {
+                    start: 10,
+                    stop: 15,
+                    live: 50,
+                    reserved: 100
+                },
+                UnusedReserved {
+                    start: 15,
+                    stop: 20,
+                    live: 50,
+                    reserved: 60
+                },
+                UnusedReserved {
+                    start: 20,
+                    stop: 30,
+                    live: 0,
+                    reserved: 60
+                },
+            ]
+        );
+        assert_eq!(unused_at(&pieces, 7), 20);
+        assert_eq!(unused_at(&pieces, 25), 60);
+        assert_eq!(unused_at(&pieces, 30), 0);
+        assert!(unused_reserved(&[], &live, 30).is_empty());
+    }
+}
@@ -1,8 +1,16 @@
-use crate::allocation::Allocation;
-use indicatif::ProgressIterator;
-use log::info;
+use crate::{
+    allocation::{Allocation, LaneData},
+    constants::{MARKER_WIDTH, MAX_PEAK_MARKERS},
+    filter::AllocationFilter,
+    interval_tree::IntervalTree,
+    utils::format_bytes,
+};
+use indicatif::ParallelProgressIterator;
+use log::{info, warn};
 use nalgebra::Vector2;
-use std::sync::Arc;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
 
 pub struct AllocationGeometry {
     pub timesteps: Vec<f64>,
@@ -22,6 +30,223 @@ impl AllocationGeometry {
 
         self.timesteps.len()
     }
+
+    /// Whether the allocation spans no time, drawn and picked as a `MARKER_WIDTH` wide marker
+    pub fn is_marker(&self) -> bool {
+        self.timesteps.first() == self.timesteps.last()
+    }
+
+    /// Time range the allocation is drawn over, widened for markers
+    pub fn drawn_time_range(&self) -> (f64, f64) {
+        let start = self.timesteps[0];
+        match self.is_marker() {
+            true => (start, start + MARKER_WIDTH),
+            false => (start, *self.timesteps.last().unwrap()),
+        }
+    }
+
+    /// Moves a marker's last vertex `MARKER_WIDTH` to the right, for exports drawing its outline
+    pub fn widen_marker(&mut self) {
+        if self.is_marker() {
+            let (_, stop) = self.drawn_time_range();
+            *self.timesteps.last_mut().unwrap() = stop;
+        }
+    }
+
+    /// Gives allocations with a single timestep (or none, from custom loaders) a second vertex at
+    /// the same time, so every allocation has a segment and `num_steps() - 1` cannot underflow
+    fn pad_degenerate(&mut self) {
+        if self.timesteps.is_empty() {
+            self.timesteps.push(0.0);
+            self.offsets.push(0.0);
+        }
+        if self.timesteps.len() == 1 {
+            self.timesteps.push(self.timesteps[0]);
+            self.offsets.push(self.offsets[0]);
+        }
+    }
+
+    /// Level-of-detail copy: keeps only the first vertex inside each `column_width` wide column
+    /// (plus the last vertex), merging segments that would be narrower than a column on screen.
+    pub fn decimated(&self, column_width: f64) -> AllocationGeometry {
+        let n = self.num_steps();
+        let column = |t: f64| (t / column_width).floor();
+
+        let mut kept = vec![0];
+        let mut last_column = column(self.timesteps[0]);
+        for i in 1..n - 1 {
+            let col = column(self.timesteps[i]);
+            if col != last_column {
+                kept.push(i);
+                last_column = col;
+            }
+        }
+        if n > 1 {
+            kept.push(n - 1);
+        }
+
+        AllocationGeometry {
+            timesteps: kept.iter().map(|&i| self.timesteps[i]).collect(),
+            offsets: kept.iter().map(|&i| self.offsets[i]).collect(),
+            size: self.size,
+        }
+    }
+}
+
+/// A level-of-detail rectangle and the items of the allocations it stands in for
+pub type ColumnRect<T> = (AllocationGeometry, Vec<T>);
+
+/// Level-of-detail merge of allocations drawn narrower than `column_width`: those starting in the
+/// same column become one rectangle spanning the column, from the lowest bottom edge to the
+/// highest top edge among them, instead of slivers that flicker in and out between pixels.
+///
+/// ## Returns
+/// The wider allocations decimated to `column_width`, and the rectangles in column order, each
+/// with the items of the allocations it stands in for.
+pub fn binned_by_column<'a, T>(
+    allocations: impl Iterator<Item = (&'a AllocationGeometry, T)>,
+    column_width: f64,
+) -> (Vec<(AllocationGeometry, T)>, Vec<ColumnRect<T>>) {
+    let mut wide = Vec::new();
+    let mut columns: BTreeMap<i64, (f64, f64, Vec<T>)> = BTreeMap::new();
+    for (alloc, item) in allocations {
+        let (start, stop) = alloc.drawn_time_range();
+        if stop - start >= column_width || alloc.size == 0.0 {
+            wide.push((alloc.decimated(column_width), item));
+            continue;
+        }
+        let bottom = alloc.offsets.iter().copied().fold(f64::INFINITY, f64::min);
+        let top = alloc
+            .offsets
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max)
+            + alloc.size;
+        let column = columns
+            .entry((start / column_width).floor() as i64)
+            .or_insert((f64::INFINITY, f64::NEG_INFINITY, Vec::new()));
+        column.0 = column.0.min(bottom);
+        column.1 = column.1.max(top);
+        column.2.push(item);
+    }
+
+    let rects = columns
+        .into_iter()
+        .map(|(column, (bottom, top, items))| {
+            let left = column as f64 * column_width;
+            let rect = AllocationGeometry {
+                timesteps: vec![left, left + column_width],
+                offsets: vec![bottom, bottom],
+                size: top - bottom,
+            };
+            (rect, items)
+        })
+        .collect();
+    (wide, rects)
+}
+
+/// A horizontal band of the plot holding the allocations of one CUDA stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lane {
+    /// Stream id, or rank for a snapshot merging ranks (`merge::merge_ranks`)
+    pub stream: i64,
+    /// Memory coordinate of the bottom edge
+    pub base: u64,
+    /// The stream's own peak memory
+    pub height: u64,
+}
+
+/// Re-stacks allocations into one lane per stream, lowest stream id at the bottom.
+///
+/// ## Returns
+/// Copies of the allocations with offsets moved into their lane, and the lanes.
+pub fn stack_lanes(
+    allocations: &[Allocation],
+    lanes: &[LaneData],
+) -> (Arc<[Allocation]>, Vec<Lane>) {
+    let mut heights: Vec<(i64, u64)> = Vec::new();
+    for (alloc, lane) in allocations.iter().zip(lanes.iter()) {
+        let top = lane.lane_offsets.iter().max().unwrap_or(&0) + alloc.size;
+        match heights
+            .iter_mut()
+            .find(|(stream, _)| *stream == lane.stream)
+        {
+            Some((_, height)) => *height = (*height).max(top),
+            None => heights.push((lane.stream, top)),
+        }
+    }
+    heights.sort_unstable();
+
+    let mut base = 0;
+    let stacked: Vec<Lane> = heights
+        .into_iter()
+        .map(|(stream, height)| {
+            let lane = Lane {
+                stream,
+                base,
+                height,
+            };
+            base += height;
+            lane
+        })
+        .collect();
+
+    let laned = allocations
+        .iter()
+        .zip(lanes.iter())
+        .map(|(alloc, lane)| {
+            let base = stacked
+                .iter()
+                .find(|l| l.stream == lane.stream)
+                .map_or(0, |l| l.base);
+            Allocation {
+                offsets: lane.lane_offsets.iter().map(|off| base + off).collect(),
+                ..alloc.clone()
+            }
+        })
+        .collect();
+
+    (laned, stacked)
+}
+
+/// Where memory is drawn on the y axis: linear, or log scale so tiny allocations next to a
+/// giant one get some height. Maps the linear world y that `AllocationGeometry` holds
+/// (memory normalized to the window height) to the drawn one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YAxis {
+    pub log: bool,
+    /// World height of the peak memory
+    height: f64,
+    /// Peak memory in bytes
+    max_size: f64,
+}
+
+impl YAxis {
+    /// Linear until `log` is set
+    pub fn new(height: f64, max_size: f64) -> Self {
+        Self {
+            log: false,
+            height,
+            max_size,
+        }
+    }
+
+    pub fn map(&self, y: f64) -> f64 {
+        if !self.log {
+            return y;
+        }
+        let bytes = (y / self.height * self.max_size).max(0.0);
+        self.height * bytes.ln_1p() / self.max_size.ln_1p()
+    }
+
+    /// Inverse of `map`
+    pub fn unmap(&self, y: f64) -> f64 {
+        if !self.log {
+            return y;
+        }
+        let bytes = (y / self.height * self.max_size.ln_1p()).exp_m1();
+        bytes / self.max_size * self.height
+    }
 }
 
 pub struct TraceGeometry {
@@ -29,75 +254,252 @@ pub struct TraceGeometry {
     pub allocations: Vec<AllocationGeometry>,
     pub max_size: f64,
     pub max_time: f64,
+    /// Timestamps at which total memory (max over allocations of offset + size) reaches
+    /// `max_size`, the global peak
+    pub peak_timestamps: Vec<u64>,
+    /// Per-stream lanes, empty unless the plot is split by stream (see `stack_lanes`)
+    pub lanes: Vec<Lane>,
+    /// What a lane holds, for its label: "stream", or "rank" for merged ranks
+    pub lane_name: &'static str,
+    /// Human-readable notices about degenerate input, shown in the renderer window
+    pub warnings: Vec<String>,
+    pub y_axis: YAxis,
+    /// Time ranges of `allocations`, for picking. Built on a background thread so the first
+    /// frame is not held up by it, see `index_ready`.
+    index: Arc<OnceLock<IntervalTree>>,
     resolution: (u32, u32),
 }
 
 impl TraceGeometry {
     /// Executed at start
     pub fn from_allocations(allocations: Arc<[Allocation]>, resolution: (u32, u32)) -> Self {
-        info!("Transforming allocations memory snap to geometries...");
+        let extent = Self::extent(&allocations);
+        Self::from_allocations_scaled(allocations, resolution, extent)
+    }
+
+    /// Peak memory (maximum offset + size) and last timestamp of a trace
+    pub fn extent(allocations: &[Allocation]) -> (u64, u64) {
         let max_size = allocations
-            .iter()
-            .map(|a| *a.offsets.iter().max().unwrap() + a.size) // maximum offset + self size
+            .par_iter()
+            .map(|a| a.offsets.iter().max().unwrap_or(&0) + a.size) // maximum offset + self size
             .max()
-            .unwrap_or(0) as f64;
+            .unwrap_or(0);
 
         let max_time = allocations
-            .iter()
-            .map(|a| *a.timesteps.last().unwrap())
+            .par_iter()
+            .map(|a| a.start_end_time().1)
             .max()
-            .unwrap_or(0) as f64;
+            .unwrap_or(0);
+
+        (max_size, max_time)
+    }
+
+    /// Like `from_allocations`, but normalized to a given `(max_size, max_time)` extent instead
+    /// of the trace's own, so several traces can share axes (diff mode).
+    pub fn from_allocations_scaled(
+        allocations: Arc<[Allocation]>,
+        resolution: (u32, u32),
+        extent: (u64, u64),
+    ) -> Self {
+        info!("Transforming allocations memory snap to geometries...");
+        let max_size = extent.0 as f64;
+        let max_time = extent.1 as f64;
+
+        // A zero span would divide by zero below and produce NaN vertices (a blank window),
+        // so clamp the scales and tell the user why the plot looks flat.
+        let mut warnings = Vec::new();
+        if max_time == 0.0 {
+            warn!("Trace has zero time span (max_time == 0), clamping time scale");
+            warnings.push("Trace spans zero timesteps: time axis is clamped.".to_string());
+        }
+        if max_size == 0.0 {
+            warn!("Trace has zero memory span (max_size == 0), clamping memory scale");
+            warnings.push("Trace peaks at 0 bytes: memory axis is clamped.".to_string());
+        }
+        let peak_timestamps = find_peak_timestamps(&allocations, max_size as u64);
+        let max_time = max_time.max(1.0);
+        let max_size = max_size.max(1.0);
 
         let resolution_x = resolution.0 as f64;
         let resolution_y = resolution.1 as f64;
 
         let geometries = allocations
-            .iter()
-            .map(|alloc| AllocationGeometry {
-                // normalized
-                // normalize timesteps
-                timesteps: alloc
-                    .timesteps
-                    .iter()
-                    .map(|t| *t as f64 / max_time * resolution_x)
-                    .collect(),
-                // normalize offsets
-                offsets: alloc
-                    .offsets
-                    .iter()
-                    .map(|off| *off as f64 / max_size * resolution_y)
-                    .collect(),
-                // normalize size
-                size: alloc.size as f64 / max_size * resolution_y,
-            })
+            .par_iter()
             .progress()
+            .map(|alloc| {
+                let mut geometry = AllocationGeometry {
+                    // normalized
+                    // normalize timesteps
+                    timesteps: alloc
+                        .timesteps
+                        .iter()
+                        .map(|t| *t as f64 / max_time * resolution_x)
+                        .collect(),
+                    // normalize offsets
+                    offsets: alloc
+                        .offsets
+                        .iter()
+                        .map(|off| *off as f64 / max_size * resolution_y)
+                        .collect(),
+                    // normalize size
+                    size: alloc.size as f64 / max_size * resolution_y,
+                };
+                geometry.pad_degenerate();
+                geometry
+            })
+            .collect::<Vec<AllocationGeometry>>();
+
+        // coarsening keeps the first and last timesteps, so these stay valid
+        let time_ranges: Vec<(f64, f64)> = geometries
+            .iter()
+            .map(AllocationGeometry::drawn_time_range)
             .collect();
+        let index = Arc::new(OnceLock::new());
+        let pending = Arc::clone(&index);
+        std::thread::spawn(move || {
+            info!("Building spatial index...");
+            let _ = pending.set(IntervalTree::new(&time_ranges));
+            info!("Spatial index ready");
+        });
 
-        Self {
+        let geom = Self {
             raw_allocs: allocations,
             allocations: geometries,
             max_size,
             max_time,
+            peak_timestamps,
+            lanes: Vec::new(),
+            lane_name: "stream",
+            warnings,
+            y_axis: YAxis::new(resolution_y, max_size),
+            index,
             resolution,
+        };
+        geom.check_f32_precision();
+        geom
+    }
+
+    /// Mesh positions are f32. Warn if that cannot resolve a single timestep
+    /// or the smallest allocation at the far end of the axes.
+    fn check_f32_precision(&self) {
+        let time_error = f32::EPSILON as f64 * self.max_time;
+        if time_error > 1.0 {
+            warn!(
+                "f32 positions are accurate to ~{:.1} timesteps only, expect jitter at high zoom",
+                time_error
+            );
+        }
+
+        let size_error = f32::EPSILON as f64 * self.max_size;
+        let min_size = self
+            .raw_allocs
+            .iter()
+            .map(|a| a.size)
+            .filter(|&size| size > 0)
+            .min()
+            .unwrap_or(0) as f64;
+        if size_error > min_size && min_size > 0.0 {
+            warn!(
+                "f32 positions are accurate to ~{} only, smaller allocations may be distorted",
+                format_bytes(size_error as i64)
+            );
         }
     }
 
-    /// return index of allocation
-    /// FIXME: this is a fucking naive implementation
-    pub fn find_by_pos(&self, pos: Vector2<f32>) -> Option<usize> {
+    /// Number of quads the mesh will contain
+    pub fn num_segments(&self) -> usize {
+        self.allocations.iter().map(|a| a.num_steps() - 1).sum()
+    }
+
+    /// Guard against pathological traces: if there are more than `max_segments` quads,
+    /// merge sub-column segments with increasingly wide columns until the count fits.
+    ///
+    /// ## Returns
+    /// A message explaining what was merged, if anything was.
+    pub fn coarsen(&mut self, max_segments: usize) -> Option<String> {
+        let num_segments = self.num_segments();
+        if num_segments <= max_segments {
+            return None;
+        }
+
+        info!(
+            "Coarsening {} segments to at most {}",
+            num_segments, max_segments
+        );
+        let mut column_width = 1.0 / 64.0;
+        let coarse = loop {
+            let coarse: Vec<AllocationGeometry> = self
+                .allocations
+                .par_iter()
+                .map(|a| a.decimated(column_width))
+                .collect();
+            let coarse_segments: usize = coarse.iter().map(|a| a.num_steps() - 1).sum();
+            // cannot get coarser than one segment per allocation
+            if coarse_segments <= max_segments || column_width >= self.resolution.0 as f64 {
+                break coarse;
+            }
+            column_width *= 2.0;
+        };
+        self.allocations = coarse;
+
+        let num_columns = self.resolution.0 as f64 / column_width;
+        let msg = format!(
+            "Trace has {} segments (limit {}): merged segments narrower than 1/{:.0} of the window width into {}.",
+            num_segments,
+            max_segments,
+            num_columns,
+            self.num_segments(),
+        );
+        warn!("{}", msg);
+        self.warnings.push(msg.clone());
+        Some(msg)
+    }
+
+    /// Whether the spatial index finished building; picking works before, just slower
+    pub fn index_ready(&self) -> bool {
+        self.index.get().is_some()
+    }
+
+    /// return index of allocation, skipping allocations hidden by `filter`
+    pub fn find_by_pos(&self, pos: Vector2<f32>, filter: &AllocationFilter) -> Option<usize> {
         let x = pos.x as f64; // time
-        let y = pos.y as f64; // memory
-        for (ialloc, alloc) in self.allocations.iter().enumerate() {
-            // simple culling: check if x is in range of allocation, if not, position cannot be in this allocation
-            if x < alloc.timesteps[0] || x > *alloc.timesteps.last().unwrap() {
+        let y = self.y_axis.unmap(pos.y as f64); // memory
+
+        // only allocations alive at x can contain the position
+        let mut candidates = match self.index.get() {
+            Some(index) => index.query(x),
+            // still building, scan instead
+            None => self
+                .allocations
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| {
+                    let (start, stop) = a.drawn_time_range();
+                    start <= x && x <= stop
+                })
+                .map(|(i, _)| i)
+                .collect(),
+        };
+        candidates.sort_unstable();
+
+        for ialloc in candidates {
+            let alloc = &self.allocations[ialloc];
+            if alloc.num_steps() < 2 || !filter.accepts(&self.raw_allocs[ialloc]) {
+                continue;
+            }
+            if alloc.is_marker() {
+                if marker_overlaps(alloc, y, y) {
+                    return Some(ialloc);
+                }
                 continue;
             }
 
-            // find index of x in timesteps
+            // find index of x in timesteps; x at the very start lies in the first interval
             let idx = match alloc.timesteps.binary_search_by(|&e| e.total_cmp(&x)) {
                 Ok(i) => i,
                 Err(i) => i,
-            };
+            }
+            .max(1);
 
             // find the interval index of x in timesteps
             let left_idx = idx - 1;
@@ -127,10 +529,69 @@ impl TraceGeometry {
         None
     }
 
+    /// Allocations whose drawn shape intersects the rectangle spanning `x_range` and `y_range`
+    /// (world coords), skipping allocations hidden by `filter`. Sorted by index.
+    pub fn find_in_rect(
+        &self,
+        x_range: (f32, f32),
+        y_range: (f32, f32),
+        filter: &AllocationFilter,
+    ) -> Vec<usize> {
+        let (x0, x1) = (x_range.0 as f64, x_range.1 as f64);
+        // memory is monotonic in y, so the test can happen in memory units on either axis scale
+        let (y0, y1) = (
+            self.y_axis.unmap(y_range.0 as f64),
+            self.y_axis.unmap(y_range.1 as f64),
+        );
+
+        let mut candidates = match self.index.get() {
+            Some(index) => index.query_range(x0, x1),
+            None => self
+                .allocations
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| {
+                    let (start, stop) = a.drawn_time_range();
+                    start <= x1 && x0 <= stop
+                })
+                .map(|(i, _)| i)
+                .collect(),
+        };
+        candidates.sort_unstable();
+        candidates.retain(|&ialloc| {
+            let alloc = &self.allocations[ialloc];
+            if alloc.num_steps() < 2 || !filter.accepts(&self.raw_allocs[ialloc]) {
+                return false;
+            }
+            if alloc.is_marker() {
+                return marker_overlaps(alloc, y0, y1);
+            }
+            // per segment: the bottom edge is linear, so over the part of the segment inside
+            // x_range it takes every value between its ends
+            alloc
+                .timesteps
+                .windows(2)
+                .zip(alloc.offsets.windows(2))
+                .any(|(t, o)| {
+                    let (start, stop) = (t[0].max(x0), t[1].min(x1));
+                    if start > stop {
+                        return false;
+                    }
+                    let at = |x: f64| match t[1] > t[0] {
+                        true => o[0] + (o[1] - o[0]) * (x - t[0]) / (t[1] - t[0]),
+                        false => o[0],
+                    };
+                    let (a, b) = (at(start), at(stop));
+                    a.min(b) <= y1 && a.max(b) + alloc.size >= y0
+                })
+        });
+        candidates
+    }
+
     /// y_world: y position (world coords)
     /// Allow negative memory
     pub fn yworld2memory(&self, y_world: f32) -> i64 {
-        (y_world as f64 * self.max_size / self.resolution.1 as f64) as i64
+        (self.y_axis.unmap(y_world as f64) * self.max_size / self.resolution.1 as f64) as i64
     }
 
     /// x_world: x position (world timestamp)
@@ -138,4 +599,294 @@ impl TraceGeometry {
     pub fn xworld2timestamp(&self, x_world: f32) -> i64 {
         (x_world as f64 * self.max_time / self.resolution.0 as f64) as i64
     }
+
+    pub fn memory2yworld(&self, bytes: i64) -> f32 {
+        self.y_axis
+            .map(bytes as f64 / self.max_size * self.resolution.1 as f64) as f32
+    }
+
+    pub fn timestamp2xworld(&self, timestamp: u64) -> f32 {
+        (timestamp as f64 / self.max_time * self.resolution.0 as f64) as f32
+    }
+}
+
+/// Whether a marker (whose time range is checked by the caller) covers memory between `y0` and `y1`
+fn marker_overlaps(alloc: &AllocationGeometry, y0: f64, y1: f64) -> bool {
+    let lo = alloc.offsets.iter().copied().fold(f64::INFINITY, f64::min);
+    let hi = alloc
+        .offsets
+        .iter()
+        .copied()
+        .fold(f64::NEG_INFINITY, f64::max)
+        + alloc.size;
+    lo <= y1 && y0 <= hi
+}
+
+/// Start of each interval during which some allocation's top edge touches `peak`
+pub(crate) fn find_peak_timestamps(allocations: &[Allocation], peak: u64) -> Vec<u64> {
+    let mut timestamps: Vec<u64> = allocations
+        .par_iter()
+        .flat_map_iter(|a| {
+            let at_peak = |i: usize| a.offsets[i] + a.size == peak;
+            (0..a.offsets.len())
+                .filter(move |&i| at_peak(i) && (i == 0 || !at_peak(i - 1)))
+                .map(|i| a.timesteps[i])
+        })
+        .collect();
+    timestamps.sort_unstable();
+    timestamps.dedup();
+    if timestamps.len() > MAX_PEAK_MARKERS {
+        warn!(
+            "Memory peaks {} times, only marking the first {}",
+            timestamps.len(),
+            MAX_PEAK_MARKERS
+        );
+        timestamps.truncate(MAX_PEAK_MARKERS);
+    }
+    timestamps
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::{Allocation, LaneData};
+    use crate::filter::AllocationFilter;
+    use crate::geometry::{AllocationGeometry, Lane, TraceGeometry, binned_by_column, stack_lanes};
+    use nalgebra::Vector2;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_zero_span_is_clamped() {
+        let allocs: Arc<[Allocation]> = Arc::from(vec![Allocation {
+            timesteps: vec![0, 0].into(),
+            offsets: vec![0, 0].into(),
+            size: 0,
+            peak_mem: 0,
+            peak_timestamps: vec![0, 0],
+            category: None,
+        }]);
+        let geom = TraceGeometry::from_allocations(allocs, (100, 100));
+
+        assert_eq!(geom.warnings.len(), 2);
+        assert!(geom.allocations[0].timesteps.iter().all(|t| t.is_finite()));
+        assert!(geom.allocations[0].offsets.iter().all(|o| o.is_finite()));
+        assert!(geom.allocations[0].size.is_finite());
+    }
+
+    #[test]
+    fn test_decimated_merges_subcolumn_segments() {
+        let alloc = AllocationGeometry {
+            timesteps: vec![0.0, 0.1, 0.2, 0.9, 1.0, 1.5, 3.2],
+            offsets: vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+            size: 1.0,
+        };
+
+        let lod = alloc.decimated(1.0);
+        assert_eq!(lod.timesteps, vec![0.0, 1.0, 3.2]);
+        assert_eq!(lod.offsets, vec![0.0, 4.0, 6.0]);
+        assert_eq!(lod.size, 1.0);
+    }
+
+    #[test]
+    fn test_binned_by_column_merges_narrow_allocations() {
+        let alloc = |timesteps: Vec<f64>, offset: f64, size: f64| AllocationGeometry {
+            offsets: vec![offset; timesteps.len()],
+            timesteps,
+            size,
+        };
+        let allocs = [
+            alloc(vec![0.1, 0.3], 0.0, 2.0),
+            alloc(vec![0.5, 0.6], 5.0, 1.0),
+            alloc(vec![1.2, 1.4], 3.0, 1.0),
+            alloc(vec![0.0, 3.0], 0.0, 1.0),
+        ];
+
+        let (wide, rects) = binned_by_column(allocs.iter().zip(0..), 1.0);
+        assert_eq!(wide.len(), 1);
+        assert_eq!(wide[0].1, 3);
+        assert_eq!(rects.len(), 2);
+        let (first, items) = &rects[0];
+        assert_eq!(first.timesteps, vec![0.0, 1.0]);
+        assert_eq!(first.offsets, vec![0.0, 0.0]);
+        assert_eq!(first.size, 6.0);
+        assert_eq!(items, &vec![0, 1]);
+        assert_eq!(rects[1].0.timesteps, vec![1.0, 2.0]);
+        assert_eq!(rects[1].1, vec![2]);
+    }
+
+    #[test]
+    fn test_coarsen_caps_segments() {
+        let timesteps: Vec<u64> = (0..1000).collect();
+        let allocs: Arc<[Allocation]> = Arc::from(vec![Allocation {
+            offsets: vec![0; timesteps.len()].into(),
+            timesteps: timesteps.into(),
+            size: 1,
+            peak_mem: 1,
+            peak_timestamps: vec![0],
+            category: None,
+        }]);
+        let mut geom = TraceGeometry::from_allocations(allocs, (100, 100));
+        assert_eq!(geom.num_segments(), 999);
+
+        assert!(geom.coarsen(1000).is_none());
+        assert!(geom.coarsen(100).is_some());
+        assert!(geom.num_segments() <= 100);
+        assert_eq!(geom.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_stack_lanes() {
+        let alloc = |offsets: Vec<u64>, size| Allocation {
+            timesteps: (0..offsets.len() as u64).collect(),
+            offsets: offsets.into(),
+            size,
+            peak_mem: 0,
+            peak_timestamps: vec![],
+            category: None,
+        };
+        let lane = |stream, lane_offsets| LaneData {
+            stream,
+            lane_offsets,
+        };
+        let allocs = vec![
+            alloc(vec![0, 0], 10),
+            alloc(vec![10, 10], 5),
+            alloc(vec![15, 5], 3),
+        ];
+        let lanes = vec![
+            lane(7, vec![0, 0]),
+            lane(0, vec![0, 0]),
+            lane(7, vec![10, 0]),
+        ];
+
+        let (laned, stacked) = stack_lanes(&allocs, &lanes);
+        assert_eq!(
+            stacked,
+            vec![
+                Lane {
+                    stream: 0,
+                    base: 0,
+                    height: 5
+                },
+                Lane {
+                    stream: 7,
+                    base: 5,
+                    height: 13
+                },
+            ]
+        );
+        assert_eq!(*laned[0].offsets, [5, 5]);
+        assert_eq!(*laned[1].offsets, [0, 0]);
+        assert_eq!(*laned[2].offsets, [15, 5]);
+    }
+
+    #[test]
+    fn test_peak_timestamps() {
+        let allocs: Arc<[Allocation]> = Arc::from(vec![
+            Allocation {
+                timesteps: vec![0, 10].into(),
+                offsets: vec![0, 0].into(),
+                size: 4,
+                peak_mem: 6,
+                peak_timestamps: vec![2],
+                category: None,
+            },
+            Allocation {
+                timesteps: vec![2, 3, 4, 6, 8].into(),
+                offsets: vec![4, 4, 2, 4, 4].into(),
+                size: 2,
+                peak_mem: 6,
+                peak_timestamps: vec![2],
+                category: None,
+            },
+        ]);
+        let geom = TraceGeometry::from_allocations(allocs, (100, 100));
+
+        assert_eq!(geom.max_size, 6.0);
+        assert_eq!(geom.peak_timestamps, vec![2, 6]);
+    }
+
+    #[test]
+    fn test_log_y_axis() {
+        let allocs: Arc<[Allocation]> = Arc::from(vec![Allocation {
+            timesteps: vec![0, 10].into(),
+            offsets: vec![0, 0].into(),
+            size: 1 << 30,
+            peak_mem: 1 << 30,
+            peak_timestamps: vec![0],
+            category: None,
+        }]);
+        let mut geom = TraceGeometry::from_allocations(allocs, (100, 100));
+        assert_eq!(geom.memory2yworld(1 << 20), 100.0 / 1024.0);
+
+        geom.y_axis.log = true;
+        // ends stay put, 1 MiB of 1 GiB is two thirds up in log scale
+        assert_eq!(geom.memory2yworld(0), 0.0);
+        assert!((geom.memory2yworld(1 << 30) - 100.0).abs() < 1e-4);
+        assert!((geom.memory2yworld(1 << 20) - 200.0 / 3.0).abs() < 0.1);
+        assert_eq!(geom.yworld2memory(geom.memory2yworld(12345)), 12345);
+    }
+
+    #[test]
+    fn test_find_by_pos_before_and_after_index() {
+        let alloc = |start, stop, offset| Allocation {
+            timesteps: vec![start, stop].into(),
+            offsets: vec![offset, offset].into(),
+            size: 1,
+            peak_mem: 0,
+            peak_timestamps: vec![],
+            category: None,
+        };
+        let allocs: Arc<[Allocation]> = Arc::from(vec![alloc(0, 10, 0), alloc(5, 20, 1)]);
+        let geom = TraceGeometry::from_allocations(allocs, (20, 2));
+        let filter = AllocationFilter::default();
+        let picks = || {
+            [(2.0, 0.5), (7.0, 1.5), (15.0, 0.5)]
+                .map(|(x, y)| geom.find_by_pos(Vector2::new(x, y), &filter))
+        };
+
+        // whichever way candidates are found, the picks agree
+        let before = picks();
+        while !geom.index_ready() {
+            std::thread::yield_now();
+        }
+        assert_eq!(before, picks());
+        assert_eq!(before, [Some(0), Some(1), None]);
+
+        // the second allocation sits above the first, from 5 on
+        assert_eq!(geom.find_in_rect((6.0, 8.0), (0.0, 2.0), &filter), [0, 1]);
+        assert_eq!(geom.find_in_rect((6.0, 8.0), (1.2, 2.0), &filter), [1]);
+        assert!(
+            geom.find_in_rect((11.0, 30.0), (0.0, 0.9), &filter)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_degenerate_allocations() {
+        let alloc = |timesteps: Vec<u64>, offsets: Vec<u64>| {
+            Allocation::new(timesteps.into(), offsets.into(), 10, None)
+        };
+        // freed in the timestep it was allocated in, and one from a loader gone wrong
+        let allocs = vec![alloc(vec![0, 100], vec![0, 0]), alloc(vec![50], vec![10])];
+        let allocs = [allocs, vec![alloc(vec![], vec![])]].concat();
+        let geom = TraceGeometry::from_allocations(Arc::from(allocs), (100, 20));
+        let filter = AllocationFilter::default();
+
+        assert_eq!(geom.num_segments(), 3);
+        assert!(geom.allocations[1].is_marker());
+        assert_eq!(geom.allocations[1].drawn_time_range(), (50.0, 51.0));
+        // the marker is picked across its width, above the first allocation
+        assert_eq!(geom.find_by_pos(Vector2::new(50.5, 15.0), &filter), Some(1));
+        assert_eq!(geom.find_by_pos(Vector2::new(52.0, 15.0), &filter), None);
+        assert_eq!(geom.find_in_rect((50.2, 60.0), (11.0, 12.0), &filter), [1]);
+
+        let empty = TraceGeometry::from_allocations(Arc::from(vec![]), (100, 20));
+        assert_eq!(empty.num_segments(), 0);
+        assert_eq!(empty.find_by_pos(Vector2::new(1.0, 1.0), &filter), None);
+        assert!(
+            empty
+                .find_in_rect((0.0, 100.0), (0.0, 20.0), &filter)
+                .is_empty()
+        );
+    }
 }
@@ -0,0 +1,79 @@
+//! Allocations sharing a callstack, usually the same tensor allocated again every iteration.
+//! Grouping mode tints each group in one color and `--group` highlights a single group.
+
+use crate::render_data::seeded_color;
+use std::collections::HashMap;
+use std::hash::Hash;
+use three_d::Srgba;
+
+/// Group of every allocation, groups numbered in order of their first allocation
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallstackGroups {
+    ids: Vec<u32>,
+    counts: Vec<usize>,
+}
+
+impl CallstackGroups {
+    /// Groups of allocations given their callstacks (or hashes of them), in allocation order
+    pub fn new<K: Hash + Eq>(callstacks: impl IntoIterator<Item = K>) -> Self {
+        let mut by_callstack: HashMap<K, u32> = HashMap::new();
+        let mut counts = Vec::new();
+        let ids = callstacks
+            .into_iter()
+            .map(|callstack| {
+                let next = by_callstack.len() as u32;
+                let id = *by_callstack.entry(callstack).or_insert(next);
+                if id == next {
+                    counts.push(0);
+                }
+                counts[id as usize] += 1;
+                id
+            })
+            .collect();
+        Self { ids, counts }
+    }
+
+    /// Number of groups
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Allocations in the group of allocation `idx`, including it
+    pub fn group_size(&self, idx: usize) -> usize {
+        self.counts[self.ids[idx] as usize]
+    }
+
+    /// Allocations sharing `idx`'s callstack, in allocation order
+    pub fn members(&self, idx: usize) -> Vec<usize> {
+        let id = self.ids[idx];
+        (0..self.ids.len()).filter(|&i| self.ids[i] == id).collect()
+    }
+
+    /// One color per group for every allocation, the same in every run
+    pub fn colors(&self) -> Vec<Srgba> {
+        let palette: Vec<Srgba> = (0..self.len() as u64).map(seeded_color).collect();
+        self.ids.iter().map(|&id| palette[id as usize]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::groups::CallstackGroups;
+
+    #[test]
+    fn test_callstack_groups() {
+        let callstacks = ["fwd", "bwd", "fwd", "opt", "fwd"].map(str::to_string);
+        let groups = CallstackGroups::new(callstacks);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups.members(2), [0, 2, 4]);
+        assert_eq!(groups.group_size(3), 1);
+
+        let colors = groups.colors();
+        assert_eq!(colors[0], colors[4]);
+        assert_ne!(colors[0], colors[1]);
+    }
+}
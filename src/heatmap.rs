@@ -0,0 +1,174 @@
+use crate::{
+    color_mode::{Legend, ramp},
+    geometry::{AllocationGeometry, YAxis},
+    render_data,
+};
+use three_d::{CpuMesh, Srgba};
+
+/// Swatches of the legend's gradient bar
+const LEGEND_STEPS: usize = 32;
+
+/// Number of allocations in each time × memory bin, drawn instead of the allocations themselves
+/// in heatmap mode. Stays cheap to draw however many segments the trace has.
+pub struct Heatmap {
+    columns: usize,
+    rows: usize,
+    /// World size of a bin
+    bin_size: (f64, f64),
+    /// Row-major, row 0 at the bottom
+    counts: Vec<u32>,
+}
+
+impl Heatmap {
+    /// Bins `allocations` into `bins` (columns, rows) over the world rect from the origin to
+    /// `extent`, with memory placed by `y_axis`. An allocation counts once in every bin it covers.
+    pub fn from_allocations<'a>(
+        allocations: impl Iterator<Item = &'a AllocationGeometry>,
+        extent: (f64, f64),
+        bins: (usize, usize),
+        y_axis: YAxis,
+    ) -> Self {
+        let (columns, rows) = (bins.0.max(1), bins.1.max(1));
+        let bin_size = (extent.0 / columns as f64, extent.1 / rows as f64);
+        let column = |x: f64| ((x / bin_size.0).floor().max(0.0) as usize).min(columns - 1);
+        let row = |y: f64| ((y / bin_size.1).floor().max(0.0) as usize).min(rows - 1);
+        // exclusive, an allocation ending on a bin edge does not reach into the next bin
+        let row_end = |y: f64| ((y / bin_size.1).ceil().max(0.0) as usize).min(rows);
+
+        // 2D difference array: +1 / -1 at the corners of each covered block of bins
+        let stride = columns + 1;
+        let mut diff = vec![0i64; stride * (rows + 1)];
+        for alloc in allocations {
+            // one vertex per column, so the allocation's segments cover disjoint columns
+            let alloc = alloc.decimated(bin_size.0);
+            let n = alloc.num_steps();
+            for i in 0..n.saturating_sub(1) {
+                let c0 = column(alloc.timesteps[i]);
+                let c1 = if i + 2 == n {
+                    column(alloc.timesteps[i + 1]) + 1
+                } else {
+                    column(alloc.timesteps[i + 1])
+                };
+                if c1 <= c0 {
+                    continue;
+                }
+                let lo = alloc.offsets[i].min(alloc.offsets[i + 1]);
+                let hi = alloc.offsets[i].max(alloc.offsets[i + 1]) + alloc.size;
+                let r0 = row(y_axis.map(lo));
+                let r1 = row_end(y_axis.map(hi)).max(r0 + 1);
+                diff[r0 * stride + c0] += 1;
+                diff[r0 * stride + c1] -= 1;
+                diff[r1 * stride + c0] -= 1;
+                diff[r1 * stride + c1] += 1;
+            }
+        }
+
+        // prefix sums along rows, then columns
+        for r in 0..=rows {
+            for c in 1..=columns {
+                diff[r * stride + c] += diff[r * stride + c - 1];
+            }
+        }
+        for r in 1..=rows {
+            for c in 0..=columns {
+                diff[r * stride + c] += diff[(r - 1) * stride + c];
+            }
+        }
+        let counts = (0..rows)
+            .flat_map(|r| (0..columns).map(move |c| (r, c)))
+            .map(|(r, c)| diff[r * stride + c].max(0) as u32)
+            .collect();
+
+        Self {
+            columns,
+            rows,
+            bin_size,
+            counts,
+        }
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Allocations in the bin at `column`, `row` (0 at the bottom)
+    pub fn count(&self, column: usize, row: usize) -> u32 {
+        self.counts[row * self.columns + column]
+    }
+
+    /// Color of a bin holding `count` allocations, on a log scale up to `max`
+    fn color(count: u32, max: u32) -> Srgba {
+        let t = match max {
+            0 | 1 => 1.0,
+            _ => (count as f64).ln() / (max as f64).ln(),
+        };
+        Srgba { a: 255, ..ramp(t) }
+    }
+
+    /// One opaque quad per non-empty bin
+    pub fn mesh(&self) -> CpuMesh {
+        let max = self.max();
+        let (w, h) = self.bin_size;
+        let bins = (0..self.rows)
+            .flat_map(|r| (0..self.columns).map(move |c| (r, c)))
+            .filter(|&(r, c)| self.count(c, r) > 0)
+            .map(|(r, c)| {
+                let (x, y) = (c as f64 * w, r as f64 * h);
+                ((x, y, x + w, y + h), Self::color(self.count(c, r), max))
+            });
+        render_data::colored_rects_mesh(bins, 0.0)
+    }
+
+    pub fn legend(&self) -> Legend {
+        let max = self.max().max(1);
+        let mid = (max as f64).sqrt().round() as u32;
+        Legend {
+            title: "allocations per bin".to_string(),
+            gradient: (0..LEGEND_STEPS)
+                .map(|i| {
+                    let t = i as f64 / (LEGEND_STEPS - 1) as f64;
+                    Srgba { a: 255, ..ramp(t) }
+                })
+                .collect(),
+            labels: [1, mid, max].map(|n| n.to_string()).to_vec(),
+            keys: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::{AllocationGeometry, YAxis};
+    use crate::heatmap::Heatmap;
+
+    #[test]
+    fn test_heatmap_counts() {
+        let allocs = [
+            // bottom quarter over the whole time axis, in 4 segments
+            AllocationGeometry {
+                timesteps: vec![0.0, 1.0, 2.0, 3.0, 4.0],
+                offsets: vec![0.0; 5],
+                size: 1.0,
+            },
+            // short-lived, inside bin (2, 2)
+            AllocationGeometry {
+                timesteps: vec![2.2, 2.4],
+                offsets: vec![2.1, 2.1],
+                size: 0.5,
+            },
+        ];
+        let heatmap =
+            Heatmap::from_allocations(allocs.iter(), (4.0, 4.0), (4, 4), YAxis::new(4.0, 4.0));
+
+        // counted once per bin, not once per segment touching it
+        assert_eq!(
+            (0..4).map(|c| heatmap.count(c, 0)).collect::<Vec<_>>(),
+            [1, 1, 1, 1]
+        );
+        assert_eq!(heatmap.count(2, 2), 1);
+        assert_eq!(heatmap.count(1, 2), 0);
+        assert_eq!(heatmap.max(), 1);
+        // 5 bins, 6 vertices each
+        assert_eq!(heatmap.mesh().positions.len(), 30);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::filter::AllocationFilter;
+use nalgebra::Vector2;
+
+/// What undo/redo restores in the renderer
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewState {
+    pub center: Vector2<f32>,
+    pub zoom: f32,
+    pub filter: AllocationFilter,
+}
+
+/// Linear undo history: recording a state after undoing drops the redo entries
+pub struct History<T> {
+    states: Vec<T>,
+    current: usize,
+    limit: usize,
+}
+
+impl<T: Clone + PartialEq> History<T> {
+    /// Keeps at most `limit` states, dropping the oldest
+    pub fn new(initial: T, limit: usize) -> Self {
+        Self {
+            states: vec![initial],
+            current: 0,
+            limit: limit.max(1),
+        }
+    }
+
+    pub fn current(&self) -> &T {
+        &self.states[self.current]
+    }
+
+    /// Records `state` as the newest entry, unless it equals the current one
+    pub fn record(&mut self, state: T) {
+        if state == *self.current() {
+            return;
+        }
+        self.states.truncate(self.current + 1);
+        self.states.push(state);
+        if self.states.len() > self.limit {
+            self.states.remove(0);
+        }
+        self.current = self.states.len() - 1;
+    }
+
+    pub fn undo(&mut self) -> Option<&T> {
+        self.current = self.current.checked_sub(1)?;
+        Some(self.current())
+    }
+
+    pub fn redo(&mut self) -> Option<&T> {
+        if self.current + 1 >= self.states.len() {
+            return None;
+        }
+        self.current += 1;
+        Some(self.current())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::History;
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = History::new(0, 3);
+        history.record(1);
+        history.record(1);
+        history.record(2);
+        assert_eq!(history.undo(), Some(&1));
+        assert_eq!(history.undo(), Some(&0));
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(&1));
+
+        // a new state drops the redo entries
+        history.record(5);
+        assert_eq!(history.redo(), None);
+        history.record(6);
+        assert_eq!(history.undo(), Some(&5));
+        assert_eq!(history.undo(), Some(&1));
+        assert_eq!(history.undo(), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! What a path given on the command line holds, so one argument opens pickles, zip archives,
+//! sample CSVs, profiler memory timelines and converted directories alike. Pickles and
+//! timelines may be gzip or zstd compressed (`snap.pickle.gz`), and are decompressed as they
+//! are read.
+
+use crate::constants::{
+    ALLOCATIONS_BIN_FILE_NAME, ALLOCATIONS_FILE_NAME, ALLOCATIONS_SHARDS_FILE_NAME,
+    ALLOCATIONS_ZST_FILE_NAME, ELEMENT_DB_FILENAME,
+};
+use anyhow::{Context, bail};
+use flate2::read::MultiGzDecoder;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Files a converted directory is recognized by
+pub(crate) const CONVERTED_FILES: [&str; 5] = [
+    ELEMENT_DB_FILENAME,
+    ALLOCATIONS_BIN_FILE_NAME,
+    ALLOCATIONS_FILE_NAME,
+    ALLOCATIONS_ZST_FILE_NAME,
+    ALLOCATIONS_SHARDS_FILE_NAME,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    /// Snapshot pickle, optionally gzipped or zstd compressed, to convert
+    Pickle,
+    /// Zip archive holding a snapshot pickle, to convert
+    Zip,
+    /// CSV of samples written by `record`, to convert
+    Samples,
+    /// Memory timeline JSON exported by the PyTorch profiler, optionally compressed, to convert
+    ProfilerTimeline,
+    /// Directory written by `convert`
+    Converted,
+}
+
+impl Display for InputKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            InputKind::Pickle => "snapshot pickle",
+            InputKind::Zip => "zip archive",
+            InputKind::Samples => "CSV of samples",
+            InputKind::ProfilerTimeline => "profiler memory timeline",
+            InputKind::Converted => "converted snapshot",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+
+/// Opens `path` for reading, decompressing it if it starts like a gzip or zstd stream
+pub fn open_decompressed(path: &Path) -> anyhow::Result<Box<dyn Read>> {
+    let mut magic = Vec::with_capacity(4);
+    File::open(path)?.take(4).read_to_end(&mut magic)?;
+    let file = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+    );
+    Ok(if magic.starts_with(GZIP_MAGIC) {
+        // `gzip` of concatenated files writes several members
+        Box::new(MultiGzDecoder::new(file))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::Decoder::with_buffer(file)?)
+    } else {
+        Box::new(file)
+    })
+}
+
+/// What `path` holds, and the path to open it at: a file of a converted directory (e.g. its
+/// elements.db) stands for the directory. Files are told apart by their first bytes (those
+/// decompressed, for a compressed file), then by extension; anything else is taken for a
+/// pickle.
+pub fn detect_input(path: &Path) -> anyhow::Result<(InputKind, PathBuf)> {
+    if path.is_dir() {
+        if !CONVERTED_FILES.iter().any(|name| path.join(name).exists()) {
+            bail!(
+                "{} holds no converted snapshot (none of {})",
+                path.display(),
+                CONVERTED_FILES.join(", ")
+            );
+        }
+        return Ok((InputKind::Converted, path.to_path_buf()));
+    }
+    if !path.exists() {
+        bail!("{} does not exist", path.display());
+    }
+    if path
+        .file_name()
+        .is_some_and(|name| CONVERTED_FILES.iter().any(|file| name == *file))
+    {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        return Ok((InputKind::Converted, dir.to_path_buf()));
+    }
+
+    let mut magic = Vec::with_capacity(4);
+    File::open(path)?.take(4).read_to_end(&mut magic)?;
+    if magic.starts_with(GZIP_MAGIC) || magic.starts_with(ZSTD_MAGIC) {
+        magic.clear();
+        open_decompressed(path)?
+            .take(1)
+            .read_to_end(&mut magic)
+            .with_context(|| format!("{} is not a valid compressed file", path.display()))?;
+    }
+    let kind = if magic.starts_with(b"PK\x03\x04") {
+        InputKind::Zip
+    } else if magic.first() == Some(&0x80) {
+        // PROTO opcode, which pickles of protocol 2 and later start with
+        InputKind::Pickle
+    } else if path.extension().is_some_and(|ext| ext == "csv") {
+        InputKind::Samples
+    } else if magic.first() == Some(&b'[') {
+        // a JSON array; the profiler writes .json, .json.gz and .raw.json.gz
+        InputKind::ProfilerTimeline
+    } else {
+        InputKind::Pickle
+    };
+    Ok((kind, path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::{InputKind, detect_input, open_decompressed};
+    use std::io::Read;
+
+    #[test]
+    fn test_detect_input() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-input-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, bytes: &[u8]| {
+            let path = dir.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            path
+        };
+        let zip = file("snap.pickle", b"PK\x03\x04rest");
+        let pickle = file("snap.data", b"\x80\x04K\x07.");
+        let csv = file("samples.csv", b"timestamp_us,used_bytes\n");
+        let timeline = file("timeline.json", b"[[0], [[0, 0]]]");
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        std::io::Write::write_all(&mut gzipped, b"\x80\x04K\x07.").unwrap();
+        let pickle_gz = file("snap.pickle.gz", &gzipped.finish().unwrap());
+        let timeline_zst = file(
+            "timeline.json.zst",
+            &zstd::encode_all(&b"[[0], [[0, 0]]]"[..], 0).unwrap(),
+        );
+
+        assert!(detect_input(&dir).is_err());
+        let db = file("elements.db", b"");
+        let kinds: Vec<_> = [&zip, &pickle, &csv, &timeline, &db, &dir]
+            .map(|path| detect_input(path).unwrap())
+            .into();
+        let compressed = [&pickle_gz, &timeline_zst].map(|path| detect_input(path).unwrap().0);
+        let mut decompressed = Vec::new();
+        open_decompressed(&pickle_gz)
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(kinds[0].0, InputKind::Zip);
+        assert_eq!(kinds[1].0, InputKind::Pickle);
+        assert_eq!(kinds[2].0, InputKind::Samples);
+        assert_eq!(kinds[3].0, InputKind::ProfilerTimeline);
+        assert_eq!(kinds[4], (InputKind::Converted, dir.clone()));
+        assert_eq!(kinds[5], (InputKind::Converted, dir));
+        assert_eq!(compressed, [InputKind::Pickle, InputKind::ProfilerTimeline]);
+        assert_eq!(decompressed, b"\x80\x04K\x07.");
+    }
+}
@@ -0,0 +1,127 @@
+//! Turns an allocation's callstack into Python snippets for instrumenting the code that made
+//! it: a frame filter for snapshot traces and a `record_function` label for the profiler.
+
+use crate::utils::format_bytes;
+
+/// One `(i) file:line:name` line of a stored callstack
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub filename: String,
+    pub line: u32,
+    pub name: String,
+}
+
+/// Frames of a callstack as stored in elements.db, innermost first. Malformed lines are skipped.
+pub fn parse_frames(callstack: &str) -> Vec<Frame> {
+    callstack
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            // drop the "(i) " index
+            let line = match line.split_once(") ") {
+                Some((index, rest)) if index.starts_with('(') => rest,
+                _ => line,
+            };
+            // filenames may contain ':' (Windows drives), names do not
+            let mut parts = line.rsplitn(3, ':');
+            let name = parts.next()?;
+            let lineno = parts.next()?.parse().ok()?;
+            let filename = parts.next()?;
+            Some(Frame {
+                filename: filename.to_string(),
+                line: lineno,
+                name: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Whether a frame belongs to PyTorch, the standard library or another installed package
+fn is_library_frame(frame: &Frame) -> bool {
+    let path = frame.filename.replace('\\', "/");
+    path.starts_with('<')
+        || path == "??"
+        || path.contains("/site-packages/")
+        || path.contains("/dist-packages/")
+        || path.contains("/lib/python")
+        || path.contains("/torch/")
+}
+
+/// The innermost frame in user code, falling back to the innermost frame
+pub fn user_frame(frames: &[Frame]) -> Option<&Frame> {
+    frames
+        .iter()
+        .find(|frame| !is_library_frame(frame))
+        .or(frames.first())
+}
+
+/// Ready-to-paste Python for allocation `idx` of `size` bytes allocated from `callstack`
+pub fn python_snippet(idx: usize, size: u64, callstack: &str) -> anyhow::Result<String> {
+    let frames = parse_frames(callstack);
+    let Some(frame) = user_frame(&frames) else {
+        anyhow::bail!("Allocation {} has no Python callstack to instrument", idx);
+    };
+    let basename = frame
+        .filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(&frame.filename);
+    let label = format!("{}@{}:{}", frame.name, basename, frame.line);
+
+    Ok(format!(
+        r#"# Allocation {idx} ({size}) comes from {file}:{line} in {name}
+
+# 1. Record allocator history with Python stacks, then keep the events from this line:
+import torch
+torch.cuda.memory._record_memory_history(max_entries=100000, context="all", stacks="python")
+# ... run the workload, then:
+snapshot = torch.cuda.memory._snapshot()
+
+def from_target(event):
+    return any(
+        f["filename"].endswith({basename:?}) and f["line"] == {line}
+        for f in event.get("frames", [])
+    )
+
+events = [e for trace in snapshot["device_traces"] for e in trace if from_target(e)]
+
+# 2. Label the code in profiler traces (and snapshot callstacks), around {file}:{line}:
+with torch.profiler.record_function({label:?}):
+    ...  # the code at {file}:{line}
+"#,
+        idx = idx,
+        size = format_bytes(size as i64),
+        file = frame.filename,
+        line = frame.line,
+        name = frame.name,
+        basename = basename,
+        label = label,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instrument::{Frame, parse_frames, python_snippet, user_frame};
+
+    #[test]
+    fn test_user_frame_skips_torch() {
+        let callstack = "(0) /usr/lib/python3.11/site-packages/torch/nn/modules/linear.py:125:forward\n\
+                         (1) C:\\work\\train.py:42:step\n\
+                         garbage";
+        let frames = parse_frames(callstack);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(
+            user_frame(&frames),
+            Some(&Frame {
+                filename: "C:\\work\\train.py".to_string(),
+                line: 42,
+                name: "step".to_string(),
+            })
+        );
+
+        let snippet = python_snippet(7, 1024, callstack).unwrap();
+        assert!(snippet.contains(r#"f["filename"].endswith("train.py") and f["line"] == 42"#));
+        assert!(snippet.contains(r#"record_function("step@train.py:42")"#));
+        assert!(python_snippet(7, 1024, "").is_err());
+    }
+}
@@ -0,0 +1,167 @@
+/// Static centered interval tree over closed `[start, end]` intervals.
+/// Built once at load time, answers "which intervals contain x" in O(log n + k).
+pub struct IntervalTree {
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+struct Node {
+    center: f64,
+    /// Intervals containing `center`, sorted by start ascending
+    by_start: Vec<(f64, usize)>,
+    /// Same intervals, sorted by end descending
+    by_end: Vec<(f64, usize)>,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl IntervalTree {
+    /// `intervals[i]` is the `(start, end)` of item `i`
+    pub fn new(intervals: &[(f64, f64)]) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            root: None,
+        };
+        let items: Vec<usize> = (0..intervals.len()).collect();
+        tree.root = tree.build(intervals, items);
+        tree
+    }
+
+    fn build(&mut self, intervals: &[(f64, f64)], mut items: Vec<usize>) -> Option<usize> {
+        if items.is_empty() {
+            return None;
+        }
+
+        // median of interval midpoints splits the items roughly in half
+        items.sort_by(|&a, &b| {
+            let mid_a = intervals[a].0 + intervals[a].1;
+            let mid_b = intervals[b].0 + intervals[b].1;
+            mid_a.total_cmp(&mid_b)
+        });
+        let (start, end) = intervals[items[items.len() / 2]];
+        let center = (start + end) / 2.0;
+
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        let mut here = Vec::new();
+        for item in items {
+            let (start, end) = intervals[item];
+            if end < center {
+                left.push(item);
+            } else if start > center {
+                right.push(item);
+            } else {
+                here.push(item);
+            }
+        }
+
+        let mut by_start: Vec<(f64, usize)> = here.iter().map(|&i| (intervals[i].0, i)).collect();
+        by_start.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut by_end: Vec<(f64, usize)> = here.iter().map(|&i| (intervals[i].1, i)).collect();
+        by_end.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let left = self.build(intervals, left);
+        let right = self.build(intervals, right);
+        self.nodes.push(Node {
+            center,
+            by_start,
+            by_end,
+            left,
+            right,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Items whose interval contains `x`, in no particular order
+    pub fn query(&self, x: f64) -> Vec<usize> {
+        let mut result = Vec::new();
+        let mut node_idx = self.root;
+        while let Some(idx) = node_idx {
+            let node = &self.nodes[idx];
+            if x < node.center {
+                result.extend(
+                    node.by_start
+                        .iter()
+                        .take_while(|(s, _)| *s <= x)
+                        .map(|(_, i)| i),
+                );
+                node_idx = node.left;
+            } else {
+                result.extend(
+                    node.by_end
+                        .iter()
+                        .take_while(|(e, _)| *e >= x)
+                        .map(|(_, i)| i),
+                );
+                node_idx = node.right;
+            }
+        }
+        result
+    }
+
+    /// Items whose interval overlaps `[lo, hi]`, in no particular order
+    pub fn query_range(&self, lo: f64, hi: f64) -> Vec<usize> {
+        let mut result = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            if hi < node.center {
+                result.extend(
+                    node.by_start
+                        .iter()
+                        .take_while(|(s, _)| *s <= hi)
+                        .map(|(_, i)| i),
+                );
+                stack.extend(node.left);
+            } else if lo > node.center {
+                result.extend(
+                    node.by_end
+                        .iter()
+                        .take_while(|(e, _)| *e >= lo)
+                        .map(|(_, i)| i),
+                );
+                stack.extend(node.right);
+            } else {
+                // the range holds the center, so every interval here
+                result.extend(node.by_start.iter().map(|(_, i)| i));
+                stack.extend(node.left.into_iter().chain(node.right));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interval_tree::IntervalTree;
+    use rand::Rng;
+
+    #[test]
+    fn test_query_matches_linear_scan() {
+        let mut rng = rand::rng();
+        let intervals: Vec<(f64, f64)> = (0..500)
+            .map(|_| {
+                let start: f64 = rng.random_range(0.0..100.0);
+                (start, start + rng.random_range(0.0..20.0))
+            })
+            .collect();
+        let tree = IntervalTree::new(&intervals);
+
+        for x in (0..=130).map(|x| x as f64) {
+            let mut found = tree.query(x);
+            found.sort();
+            let expected: Vec<usize> = (0..intervals.len())
+                .filter(|&i| intervals[i].0 <= x && x <= intervals[i].1)
+                .collect();
+            assert_eq!(found, expected, "query at {}", x);
+
+            let hi = x + 7.5;
+            let mut found = tree.query_range(x, hi);
+            found.sort();
+            let expected: Vec<usize> = (0..intervals.len())
+                .filter(|&i| intervals[i].0 <= hi && x <= intervals[i].1)
+                .collect();
+            assert_eq!(found, expected, "query of {}..{}", x, hi);
+        }
+    }
+}
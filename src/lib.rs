@@ -1,11 +1,57 @@
 #![forbid(dead_code)]
 pub mod allocation;
+pub mod annotation;
+pub mod anonymize;
+pub mod binary;
+pub mod bookmark;
+pub mod bundle;
+pub mod clock;
+pub mod color_mode;
+pub mod compact;
 pub mod constants;
+pub mod convert;
+pub mod cursor_link;
 pub mod database;
+pub mod devices;
+pub mod diff;
+pub mod doctor;
+pub mod downsample;
+pub mod error;
+pub mod events;
+pub mod filter;
+pub mod fragmentation;
 pub mod geometry;
+pub mod groups;
+pub mod heatmap;
+pub mod history;
+pub mod input;
+pub mod instrument;
+pub mod interval_tree;
+pub mod lining;
 pub mod load;
+pub mod load_progress;
+#[cfg(feature = "loader-plugins")]
+pub mod loader_plugin;
+pub mod manifest;
+pub mod memory_timeline;
+pub mod merge;
+pub mod palette;
+pub mod parquet;
+pub mod pickle;
+pub mod profile;
+pub mod profiler_timeline;
+pub mod raster;
+pub mod record;
+pub mod region;
 pub mod render_data;
 pub mod render_loop;
+pub mod screencast;
+pub mod script;
+pub mod segments;
+pub mod svg;
+pub mod theme;
 pub mod ticks;
-pub mod window_transform;
 pub mod utils;
+pub mod watch;
+pub mod window;
+pub mod window_transform;
@@ -0,0 +1,88 @@
+use crate::geometry::{AllocationGeometry, YAxis};
+use three_d::{CpuMesh, Positions, Vec3};
+
+/// Closed outline of an allocation's band: the bottom edge left to right, then the top edge
+/// right to left. Markers are outlined as drawn, `MARKER_WIDTH` wide.
+pub fn outline(alloc: &AllocationGeometry, y_axis: YAxis) -> Vec<(f32, f32)> {
+    if alloc.is_marker() {
+        let (start, stop) = alloc.drawn_time_range();
+        let (first, last) = (alloc.offsets[0], *alloc.offsets.last().unwrap());
+        return [
+            (start, first),
+            (stop, last),
+            (stop, last + alloc.size),
+            (start, first + alloc.size),
+        ]
+        .map(|(t, off)| (t as f32, y_axis.map(off) as f32))
+        .into();
+    }
+    let bottom = alloc
+        .timesteps
+        .iter()
+        .zip(alloc.offsets.iter())
+        .map(|(&t, &off)| (t as f32, y_axis.map(off) as f32));
+    let top = alloc
+        .timesteps
+        .iter()
+        .zip(alloc.offsets.iter())
+        .rev()
+        .map(|(&t, &off)| (t as f32, y_axis.map(off + alloc.size) as f32));
+    bottom.chain(top).collect()
+}
+
+/// Closed polyline through `points` as quads of `width` world units, at depth `z`
+pub fn line_loop_mesh(points: &[(f32, f32)], width: f32, z: f32) -> CpuMesh {
+    let mut verts = Vec::with_capacity(points.len() * 6);
+    for (i, &(x0, y0)) in points.iter().enumerate() {
+        let (x1, y1) = points[(i + 1) % points.len()];
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let len = (dx * dx + dy * dy).sqrt();
+        if len == 0.0 {
+            continue;
+        }
+
+        // half width along the normal, extended along the segment so corners close
+        let (nx, ny) = (-dy / len * width / 2.0, dx / len * width / 2.0);
+        let (ex, ey) = (dx / len * width / 2.0, dy / len * width / 2.0);
+        let a = Vec3::new(x0 - ex + nx, y0 - ey + ny, z);
+        let b = Vec3::new(x0 - ex - nx, y0 - ey - ny, z);
+        let c = Vec3::new(x1 + ex + nx, y1 + ey + ny, z);
+        let d = Vec3::new(x1 + ex - nx, y1 + ey - ny, z);
+        verts.extend([a, b, c, c, b, d]);
+    }
+
+    CpuMesh {
+        positions: Positions::F32(verts),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::{AllocationGeometry, YAxis};
+    use crate::lining::{line_loop_mesh, outline};
+
+    #[test]
+    fn test_outline() {
+        let alloc = AllocationGeometry {
+            timesteps: vec![0.0, 2.0, 3.0],
+            offsets: vec![1.0, 1.0, 0.0],
+            size: 2.0,
+        };
+
+        let points = outline(&alloc, YAxis::new(3.0, 3.0));
+        assert_eq!(
+            points,
+            vec![
+                (0.0, 1.0),
+                (2.0, 1.0),
+                (3.0, 0.0),
+                (3.0, 2.0),
+                (2.0, 3.0),
+                (0.0, 3.0)
+            ]
+        );
+        // one quad per edge, including the closing one
+        assert_eq!(line_loop_mesh(&points, 0.5, 0.0).positions.len(), 6 * 6);
+    }
+}
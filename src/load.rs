@@ -1,73 +1,746 @@
-use crate::allocation::{Allocation, RawAllocationData};
-use crate::constants::ALLOCATIONS_FILE_NAME;
+use crate::allocation::{Allocation, Category, RawAllocationData};
+use crate::binary;
+use crate::constants::{
+    ALLOCATIONS_BIN_FILE_NAME, ALLOCATIONS_FILE_NAME, ALLOCATIONS_SHARDS_FILE_NAME,
+    ALLOCATIONS_ZST_FILE_NAME, CONVERTER_VERSION, TIMELINE_TABLE,
+};
+use crate::convert::converted_by;
+use crate::database::sqlite::AllocationDatabase;
+use crate::devices::device_id;
+use crate::parquet::{self, ColumnData};
 use crate::utils::{get_spinner, memory_usage};
-use indicatif::ProgressIterator;
+use anyhow::{Context, anyhow, bail};
+use indicatif::{ParallelProgressIterator, ProgressIterator};
 use log::info;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::Read;
 use std::path::Path;
 use std::sync::Arc;
 
-/// Reads from dir.join(allocations.json) and deserialize
+/// An input format allocations can be read from.
+/// Implement it and `LoaderRegistry::register` it to support custom allocators or in-house
+/// profilers, or build the renderer with `loader-plugins` and pass a shared library with
+/// `--loader` (see `loader_plugin`).
+pub trait SnapshotLoader {
+    fn name(&self) -> &str;
+
+    /// Whether this loader recognizes the snapshot in `dir`. `db` is elements.db in `dir`, or an
+    /// empty stand-in when there is none
+    fn can_load(&self, dir: &Path, db: &AllocationDatabase) -> anyhow::Result<bool>;
+
+    /// The database callstacks, events and SQL queries are served from, `db` by default.
+    /// Formats without an elements.db can build one here instead, with `convert::DATABASE_SCHEMA`
+    fn database(&self, _dir: &Path, db: AllocationDatabase) -> anyhow::Result<AllocationDatabase> {
+        Ok(db)
+    }
+
+    /// `db` is the one `database` returned
+    fn load(&self, dir: &Path, db: &AllocationDatabase) -> anyhow::Result<Arc<[Allocation]>>;
+}
+
+/// Timeline table of elements.db, written by current converters
+pub struct TimelineLoader;
+
+impl SnapshotLoader for TimelineLoader {
+    fn name(&self) -> &str {
+        "elements.db timeline"
+    }
+
+    fn can_load(&self, _dir: &Path, db: &AllocationDatabase) -> anyhow::Result<bool> {
+        db.has_timeline()
+    }
+
+    fn load(&self, dir: &Path, db: &AllocationDatabase) -> anyhow::Result<Arc<[Allocation]>> {
+        read_allocations_from_db(dir, db)
+    }
+}
+
+/// allocations.bin, written for gui.py's cache. Memory-mapped, geometry is not copied to the heap
+pub struct BinaryLoader;
+
+impl SnapshotLoader for BinaryLoader {
+    fn name(&self) -> &str {
+        ALLOCATIONS_BIN_FILE_NAME
+    }
+
+    fn can_load(&self, dir: &Path, _db: &AllocationDatabase) -> anyhow::Result<bool> {
+        Ok(dir.join(ALLOCATIONS_BIN_FILE_NAME).exists())
+    }
+
+    fn load(&self, dir: &Path, _db: &AllocationDatabase) -> anyhow::Result<Arc<[Allocation]>> {
+        let bar = get_spinner(&format!("Mapping {}", ALLOCATIONS_BIN_FILE_NAME))?;
+        let allocs = binary::map_allocations(&dir.join(ALLOCATIONS_BIN_FILE_NAME))?;
+        bar.finish();
+        println!("Memory after loading allocs: {} MiB", memory_usage());
+        Ok(allocs)
+    }
+}
+
+/// allocations.json (or its shards, or allocations.json.zst), written by older converters and
+/// `convert --json`
+pub struct JsonLoader;
+
+impl SnapshotLoader for JsonLoader {
+    fn name(&self) -> &str {
+        ALLOCATIONS_FILE_NAME
+    }
+
+    fn can_load(&self, dir: &Path, _db: &AllocationDatabase) -> anyhow::Result<bool> {
+        Ok(dir.join(ALLOCATIONS_FILE_NAME).exists()
+            || dir.join(ALLOCATIONS_SHARDS_FILE_NAME).exists()
+            || dir.join(ALLOCATIONS_ZST_FILE_NAME).exists())
+    }
+
+    fn load(&self, dir: &Path, _db: &AllocationDatabase) -> anyhow::Result<Arc<[Allocation]>> {
+        read_allocations(dir)
+    }
+}
+
+/// Loaders tried in order, the first one that recognizes the snapshot is used. The renderer
+/// builds one at startup and opens every snapshot (including `--diff`, `--devices`, merged ranks
+/// and exports) with it
+pub struct LoaderRegistry {
+    loaders: Vec<Box<dyn SnapshotLoader>>,
+}
+
+impl Default for LoaderRegistry {
+    /// The built-in formats
+    fn default() -> Self {
+        Self {
+            loaders: vec![
+                Box::new(BinaryLoader),
+                Box::new(TimelineLoader),
+                Box::new(JsonLoader),
+            ],
+        }
+    }
+}
+
+impl LoaderRegistry {
+    /// Adds a loader, tried before the ones already registered
+    pub fn register(&mut self, loader: Box<dyn SnapshotLoader>) {
+        self.loaders.insert(0, loader);
+    }
+
+    /// The loader recognizing the snapshot in `dir`, and the database it serves callstacks from
+    pub fn open_database(
+        &self,
+        dir: &Path,
+    ) -> anyhow::Result<(&dyn SnapshotLoader, AllocationDatabase)> {
+        let db = AllocationDatabase::from_dir(dir)?;
+        let loader = self.find(dir, &db)?;
+        Ok((loader, loader.database(dir, db)?))
+    }
+
+    /// Opens the snapshot in `dir`: its database and allocations
+    pub fn open(&self, dir: &Path) -> anyhow::Result<(AllocationDatabase, Arc<[Allocation]>)> {
+        let (loader, db) = self.open_database(dir)?;
+        let allocs = loader.load(dir, &db)?;
+        Ok((db, allocs))
+    }
+
+    /// Loads allocations alongside `db`, which was already opened
+    pub fn load(&self, dir: &Path, db: &AllocationDatabase) -> anyhow::Result<Arc<[Allocation]>> {
+        self.find(dir, db)?.load(dir, db)
+    }
+
+    fn find(&self, dir: &Path, db: &AllocationDatabase) -> anyhow::Result<&dyn SnapshotLoader> {
+        for loader in self.loaders.iter() {
+            if loader.can_load(dir, db)? {
+                info!("Loading allocations with the {} loader", loader.name());
+                return Ok(loader.as_ref());
+            }
+        }
+
+        let names: Vec<&str> = self.loaders.iter().map(|l| l.name()).collect();
+        anyhow::bail!(
+            "No loader recognizes the snapshot in {:?}, tried: {}",
+            dir,
+            names.join(", ")
+        )
+    }
+}
+
+/// Loads allocations with the built-in loaders: allocations.bin if present, then the timeline
+/// table of elements.db, otherwise allocations.json written by older converters.
+///
+/// Executed at start
+pub fn load_allocations(dir: &Path, db: &AllocationDatabase) -> anyhow::Result<Arc<[Allocation]>> {
+    LoaderRegistry::default().load(dir, db)
+}
+
+/// Reads allocation geometry from the timeline table of elements.db in `dir`
+///
+/// Executed at start
+pub fn read_allocations_from_db(
+    dir: &Path,
+    db: &AllocationDatabase,
+) -> anyhow::Result<Arc<[Allocation]>> {
+    info!("Loading allocations from {} table", TIMELINE_TABLE);
+
+    let bar = get_spinner(&format!("Reading {} table", TIMELINE_TABLE))?;
+    let raw_allocs = db.read_timeline()?;
+    bar.finish();
+    println!("Memory after loading allocs: {} MiB", memory_usage());
+    validate_allocations(
+        &raw_allocs,
+        &format!("the {} table", TIMELINE_TABLE),
+        0,
+        dir,
+    )?;
+
+    Ok(from_raw_allocations(raw_allocs))
+}
+
+/// Contents of allocations_shards.json: allocations.json split into `shards`, files in the same
+/// directory holding consecutive allocations, `allocations` of them in total
+#[derive(Serialize, Deserialize)]
+pub struct ShardIndex {
+    pub shards: Vec<String>,
+    pub allocations: usize,
+}
+
+/// Reads from dir.join(allocations.json) and deserialize, or from the shards listed in
+/// allocations_shards.json or allocations.json.zst if there is no allocations.json
+///
+/// The file is deserialized as it is read (and decompressed), never held whole in memory, so
+/// peak memory is about the size of the parsed allocations rather than twice that.
 ///
 /// ## Returns
 /// An atomic refcounted pointer to allocation slice.
 ///
 /// Executed at start
 pub fn read_allocations(dir: &Path) -> anyhow::Result<Arc<[Allocation]>> {
-    info!("Loading json strings from zip...");
-
-    // Open the zip file
     let allocations_path = dir.join(ALLOCATIONS_FILE_NAME);
-    let mut file = File::open(allocations_path)?;
+    if !allocations_path.exists() && dir.join(ALLOCATIONS_SHARDS_FILE_NAME).exists() {
+        return read_sharded_allocations(dir);
+    }
+    let compressed = !allocations_path.exists();
+    let file_name = if compressed {
+        ALLOCATIONS_ZST_FILE_NAME
+    } else {
+        ALLOCATIONS_FILE_NAME
+    };
+    let path = dir.join(file_name);
+    let file = File::open(&path)?;
 
-    info!("Reading {} to string", ALLOCATIONS_FILE_NAME);
+    info!("Deserializing {}", file_name);
 
-    let bar = get_spinner(&format!("Reading {} to string", ALLOCATIONS_FILE_NAME))?;
+    let bar = get_spinner(&format!("Deserializing {}...", file_name))?;
 
-    let mut content = String::new();
-    file.read_to_string(&mut content)?;
+    let raw_allocs = if compressed {
+        deserialize_compressed(file)
+    } else {
+        deserialize_allocations(&file)
+    }
+    .map_err(|e| explain_parse_error(e, &path, 0))?;
 
     bar.finish();
-    println!("Memory after loading allocs: {} MiB", memory_usage());
+    println!("Memory after deserializing allocs: {} MiB", memory_usage());
+    validate_allocations(&raw_allocs, file_name, 0, dir)?;
+
+    Ok(from_raw_allocations(raw_allocs))
+}
 
-    let bar = get_spinner("Deserializing allocations...")?;
+/// Reads the shards listed in allocations_shards.json in order, each deserialized like
+/// allocations.json, so no single JSON document holds the whole trace
+fn read_sharded_allocations(dir: &Path) -> anyhow::Result<Arc<[Allocation]>> {
+    let index: ShardIndex =
+        serde_json::from_slice(&std::fs::read(dir.join(ALLOCATIONS_SHARDS_FILE_NAME))?)
+            .with_context(|| format!("Failed to parse {:?}", ALLOCATIONS_SHARDS_FILE_NAME))?;
+    info!("Deserializing {} allocation shards", index.shards.len());
 
-    let raw_allocs: Vec<RawAllocationData> = serde_json::from_str(&content)
-        .map_err(|e| anyhow::anyhow!("Failed to parse allocations JSON from '{:?}': {}", dir, e))?;
+    let mut raw_allocs = Vec::with_capacity(index.allocations);
+    for name in index.shards.iter().progress() {
+        let path = dir.join(name);
+        let file = File::open(&path).with_context(|| format!("Missing shard {:?}", path))?;
+        let shard = deserialize_allocations(&file)
+            .map_err(|e| explain_parse_error(e, &path, raw_allocs.len()))?;
+        validate_allocations(&shard, name, raw_allocs.len(), dir)?;
+        raw_allocs.extend(shard);
+    }
+    if raw_allocs.len() != index.allocations {
+        bail!(
+            "Shards in {:?} hold {} allocations, {} lists {}",
+            dir,
+            raw_allocs.len(),
+            ALLOCATIONS_SHARDS_FILE_NAME,
+            index.allocations
+        );
+    }
     println!("Memory after deserializing allocs: {} MiB", memory_usage());
 
+    Ok(from_raw_allocations(raw_allocs))
+}
+
+#[cfg(not(feature = "fast-json"))]
+fn deserialize_allocations(file: &File) -> anyhow::Result<Vec<RawAllocationData>> {
+    Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+}
+
+fn deserialize_compressed(file: File) -> anyhow::Result<Vec<RawAllocationData>> {
+    // buffered, serde_json reads a byte at a time
+    let decoder = zstd::Decoder::new(file)?;
+    Ok(serde_json::from_reader(std::io::BufReader::new(decoder))?)
+}
+
+/// serde_json parses a slice much faster than a reader, which it reads a byte at a time
+#[cfg(feature = "fast-json")]
+fn deserialize_allocations(file: &File) -> anyhow::Result<Vec<RawAllocationData>> {
+    // SAFETY: allocations.json is only written by the converter, not while it is being read
+    let map = unsafe { memmap2::Mmap::map(file)? };
+    Ok(serde_json::from_slice(&map)?)
+}
+
+/// Reads allocations from a Parquet table with `size`, `timesteps` and `offsets` columns (the
+/// last two lists of integers) and optionally `category`, one row per allocation, as
+/// `export parquet` writes it. Other columns are ignored.
+pub fn read_parquet(path: &Path) -> anyhow::Result<Arc<[Allocation]>> {
+    let bar = get_spinner(&format!("Reading {:?}", path))?;
+    let columns = parquet::read_columns(path, &["size", "timesteps", "offsets", "category"])?;
     bar.finish();
+    let mut columns = columns.into_iter();
+    let mut next = |name| {
+        columns
+            .next()
+            .flatten()
+            .with_context(|| format!("{:?} has no {} column", path, name))
+    };
+    let (
+        ColumnData::Int64(sizes),
+        ColumnData::Int64List(timesteps),
+        ColumnData::Int64List(offsets),
+    ) = (next("size")?, next("timesteps")?, next("offsets")?)
+    else {
+        bail!(
+            "{:?}: size must be integers, timesteps and offsets lists of integers",
+            path
+        );
+    };
+    let categories = match next("category") {
+        Ok(ColumnData::String(categories)) => categories,
+        _ => vec![None; sizes.len()],
+    };
 
-    let allocations: Arc<[Allocation]> = raw_allocs
+    let raw_allocs = sizes
         .into_iter()
-        .map(|raw_alloc| {
-            let peak_base = *raw_alloc.offsets.iter().max().unwrap();
-            let peak_timestamps = raw_alloc
-                .timesteps
-                .iter()
-                .zip(raw_alloc.offsets.iter())
-                .filter_map(|(&timestamp, &offset)| {
-                    if offset == peak_base {
-                        // if this timestep has peak memory
-                        Some(timestamp)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-
-            let peak = peak_base + raw_alloc.size;
-            Allocation {
-                timesteps: raw_alloc.timesteps,
-                offsets: raw_alloc.offsets,
-                size: raw_alloc.size,
-                peak_mem: peak,
-                peak_timestamps,
+        .zip(timesteps)
+        .zip(offsets)
+        .zip(categories)
+        .map(
+            |(((size, timesteps), offsets), category)| RawAllocationData {
+                timesteps: timesteps.into_iter().map(|t| t as u64).collect(),
+                offsets: offsets.into_iter().map(|o| o as u64).collect(),
+                size: size as u64,
+                category: category.as_deref().and_then(Category::parse),
+            },
+        )
+        .collect::<Vec<_>>();
+    let source = path.display().to_string();
+    validate_allocations(
+        &raw_allocs,
+        &source,
+        0,
+        path.parent().unwrap_or(Path::new(".")),
+    )?;
+    Ok(from_raw_allocations(raw_allocs))
+}
+
+/// Checks allocations read from `source` in `dir` before their geometry is computed, which
+/// needs `timesteps` and `offsets` of the same nonzero length and sorted `timesteps`. The first
+/// offending allocation is reported by its index in the whole snapshot, the first one of `raw`
+/// being `first_index`, with what is wrong with it and why that might be.
+pub fn validate_allocations(
+    raw: &[RawAllocationData],
+    source: &str,
+    first_index: usize,
+    dir: &Path,
+) -> anyhow::Result<()> {
+    let problem = raw.par_iter().enumerate().find_map_first(|(i, alloc)| {
+        let problem = if alloc.timesteps.is_empty() {
+            "has empty `timesteps`".to_string()
+        } else if alloc.timesteps.len() != alloc.offsets.len() {
+            format!(
+                "has {} `timesteps` but {} `offsets`",
+                alloc.timesteps.len(),
+                alloc.offsets.len()
+            )
+        } else if let Some(pos) = alloc.timesteps.windows(2).position(|w| w[0] > w[1]) {
+            format!(
+                "has unsorted `timesteps`: {} comes after {} at position {}",
+                alloc.timesteps[pos + 1],
+                alloc.timesteps[pos],
+                pos + 1
+            )
+        } else {
+            return None;
+        };
+        Some((first_index + i, problem))
+    });
+    match problem {
+        Some((index, problem)) => bail!(
+            "Allocation {} in {} {}. {}",
+            index,
+            source,
+            problem,
+            likely_cause(dir)
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Why a converted snapshot in `dir` may not be what this version reads
+fn likely_cause(dir: &Path) -> String {
+    match converted_by(dir) {
+        Some(version) if version != CONVERTER_VERSION => format!(
+            "It was converted by snapviewer {}, this is {}: convert the snapshot again",
+            version, CONVERTER_VERSION
+        ),
+        _ => "It may have been written by another converter version, or edited: \
+            convert the snapshot again"
+            .to_string(),
+    }
+}
+
+/// `err` from parsing the allocations JSON at `path` explained: a truncated file, invalid JSON,
+/// or the first allocation (counting from `first_index`) with a missing or invalid field, found
+/// by parsing the file again entry by entry. Only run once parsing failed.
+fn explain_parse_error(err: anyhow::Error, path: &Path, first_index: usize) -> anyhow::Error {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let explained = || -> anyhow::Result<String> {
+        if err
+            .downcast_ref::<serde_json::Error>()
+            .is_some_and(|e| e.is_eof())
+        {
+            return Ok(format!(
+                "{:?} ends abruptly ({}): the file is truncated, e.g. by an interrupted \
+                conversion or copy; convert the snapshot again",
+                path, err
+            ));
+        }
+        let file = std::io::BufReader::new(File::open(path)?);
+        let entries: Vec<serde_json::Value> = if path.extension().is_some_and(|ext| ext == "zst") {
+            serde_json::from_reader(std::io::BufReader::new(zstd::Decoder::new(file)?))
+        } else {
+            serde_json::from_reader(file)
+        }
+        .map_err(|e| anyhow!("{:?} is not a JSON list of allocations: {}", path, e))?;
+        for (i, entry) in entries.into_iter().enumerate() {
+            let fields = match &entry {
+                serde_json::Value::Object(map) => {
+                    map.keys().cloned().collect::<Vec<_>>().join(", ")
+                }
+                other => format!("none, it is {}", other),
+            };
+            if let Err(e) = RawAllocationData::deserialize(entry) {
+                return Ok(format!(
+                    "Allocation {} in {:?}: {} (its fields: {}). Allocations need `timesteps`, \
+                    `offsets` and `size`. {}",
+                    first_index + i,
+                    path,
+                    e,
+                    fields,
+                    likely_cause(dir)
+                ));
             }
+        }
+        Ok(format!("Failed to parse {:?}: {}", path, err))
+    };
+    match explained() {
+        Ok(message) => anyhow!(message),
+        Err(e) => e,
+    }
+}
+
+/// Which allocations to load (`--min-size`, `--min-lifetime`, `--device`), so geometry and
+/// meshes are only built for those, see `filter_allocations`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadFilter {
+    /// Smallest size in bytes
+    pub min_size: Option<u64>,
+    /// Shortest time from first to last timestep
+    pub min_lifetime: Option<u64>,
+    /// Device the snapshot must be of, or rank to keep of a snapshot merging ranks
+    pub device: Option<i64>,
+}
+
+impl LoadFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn accepts(&self, alloc: &Allocation) -> bool {
+        let (start, stop) = alloc.start_end_time();
+        self.min_size.is_none_or(|min| alloc.size >= min)
+            && self.min_lifetime.is_none_or(|min| stop - start >= min)
+    }
+}
+
+/// Keeps the allocations `filter` accepts, renumbered from 0 and in place on the memory axis.
+/// `db` is replaced by a temporary copy holding just them, so their indices still match it.
+///
+/// Executed at start
+pub fn filter_allocations(
+    allocations: Arc<[Allocation]>,
+    dir: &Path,
+    db: &mut AllocationDatabase,
+    filter: &LoadFilter,
+) -> anyhow::Result<Arc<[Allocation]>> {
+    let has_elements = db.has_elements()?;
+    let ranks = match filter.device {
+        Some(_) if has_elements && db.has_ranks()? => Some(db.read_ranks()?),
+        Some(device) => {
+            if let Some(id) = device_id(dir).filter(|&id| id as i64 != device) {
+                bail!("{} holds device {}, not {}", dir.display(), id, device);
+            }
+            None
+        }
+        None => None,
+    };
+    let kept: Vec<usize> = (0..allocations.len())
+        .into_par_iter()
+        .filter(|&i| {
+            filter.accepts(&allocations[i])
+                && ranks
+                    .as_ref()
+                    .is_none_or(|ranks| Some(ranks[i]) == filter.device)
         })
-        .progress()
         .collect();
+    println!(
+        "Keeping {} of {} allocations ({})",
+        kept.len(),
+        allocations.len(),
+        filter_description(filter)
+    );
+    if has_elements {
+        let bar = get_spinner("Copying the kept allocations' rows")?;
+        *db = db.filtered(&kept, filter.device.filter(|_| ranks.is_some()))?;
+        bar.finish();
+    }
+    Ok(kept.iter().map(|&i| allocations[i].clone()).collect())
+}
+
+fn filter_description(filter: &LoadFilter) -> String {
+    let mut parts = Vec::new();
+    if let Some(min) = filter.min_size {
+        parts.push(format!("size >= {}", min));
+    }
+    if let Some(min) = filter.min_lifetime {
+        parts.push(format!("lifetime >= {}", min));
+    }
+    if let Some(device) = filter.device {
+        parts.push(format!("device {}", device));
+    }
+    parts.join(", ")
+}
+
+/// Computes peak memory and peak timestamps of each allocation, on all cores.
+/// Custom loaders can produce `RawAllocationData` and finish with this.
+pub fn from_raw_allocations(raw_allocs: Vec<RawAllocationData>) -> Arc<[Allocation]> {
+    raw_allocs
+        .into_par_iter()
+        .progress()
+        .map(|raw_alloc| {
+            Allocation::new(
+                raw_alloc.timesteps.into(),
+                raw_alloc.offsets.into(),
+                raw_alloc.size,
+                raw_alloc.category,
+            )
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::{Allocation, RawAllocationData};
+    use crate::convert::{ConvertOptions, DATABASE_SCHEMA, convert_to_dir};
+    use crate::database::sqlite::AllocationDatabase;
+    use crate::load::{
+        LoadFilter, LoaderRegistry, SnapshotLoader, filter_allocations, from_raw_allocations,
+        load_allocations, read_allocations,
+    };
+    use std::path::Path;
+    use std::sync::Arc;
+
+    struct FixedLoader;
+
+    impl SnapshotLoader for FixedLoader {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn can_load(&self, _dir: &Path, _db: &AllocationDatabase) -> anyhow::Result<bool> {
+            Ok(true)
+        }
+
+        fn load(&self, _dir: &Path, _db: &AllocationDatabase) -> anyhow::Result<Arc<[Allocation]>> {
+            Ok(from_raw_allocations(vec![RawAllocationData {
+                timesteps: vec![0, 5],
+                offsets: vec![8, 0],
+                size: 4,
+                category: None,
+            }]))
+        }
+    }
+
+    #[test]
+    fn test_registered_loader_is_used() {
+        let db = AllocationDatabase {
+            conn: rusqlite::Connection::open_in_memory().unwrap(),
+        };
+        let dir = Path::new("does-not-exist");
+
+        let registry = LoaderRegistry::default();
+        assert!(registry.load(dir, &db).is_err());
+
+        let mut registry = LoaderRegistry::default();
+        registry.register(Box::new(FixedLoader));
+        let allocs = registry.load(dir, &db).unwrap();
+        assert_eq!(allocs.len(), 1);
+        assert_eq!(allocs[0].peak_mem, 12);
+        assert_eq!(allocs[0].peak_timestamps, vec![0]);
+    }
+
+    /// A format without elements.db, serving its callstacks from a database it builds
+    struct InMemoryLoader;
+
+    impl SnapshotLoader for InMemoryLoader {
+        fn name(&self) -> &str {
+            "in-memory"
+        }
+
+        fn can_load(&self, _dir: &Path, _db: &AllocationDatabase) -> anyhow::Result<bool> {
+            Ok(true)
+        }
+
+        fn database(
+            &self,
+            _dir: &Path,
+            _db: AllocationDatabase,
+        ) -> anyhow::Result<AllocationDatabase> {
+            let conn = rusqlite::Connection::open_in_memory()?;
+            conn.execute_batch(DATABASE_SCHEMA)?;
+            conn.execute(
+                "INSERT INTO callstacks VALUES (0, '(0) alloc.c:7:my_malloc')",
+                [],
+            )?;
+            conn.execute("INSERT INTO alloc_rows VALUES (0, 4, 0, 5, 0, NULL)", [])?;
+            Ok(AllocationDatabase { conn })
+        }
+
+        fn load(&self, dir: &Path, db: &AllocationDatabase) -> anyhow::Result<Arc<[Allocation]>> {
+            FixedLoader.load(dir, db)
+        }
+    }
+
+    #[test]
+    fn test_loader_provides_database() {
+        let mut registry = LoaderRegistry::default();
+        registry.register(Box::new(InMemoryLoader));
+        let (db, allocs) = registry.open(Path::new("does-not-exist")).unwrap();
+        assert_eq!(allocs.len(), 1);
+        assert_eq!(db.read_callstack(0).unwrap(), "(0) alloc.c:7:my_malloc");
+    }
+
+    #[test]
+    fn test_sharded_allocations() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-shards-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let alloc = |size| format!(r#"{{"timesteps":[0,5],"offsets":[0,0],"size":{}}}"#, size);
+        std::fs::write(dir.join("allocations_0.json"), format!("[{}]", alloc(1))).unwrap();
+        std::fs::write(
+            dir.join("allocations_1.json"),
+            format!("[{},{}]", alloc(2), alloc(3)),
+        )
+        .unwrap();
+        let index = r#"{"shards":["allocations_0.json","allocations_1.json"],"allocations":3}"#;
+        std::fs::write(dir.join("allocations_shards.json"), index).unwrap();
+        let allocs = read_allocations(&dir);
+
+        std::fs::write(dir.join("allocations_shards.json"), index.replace('3', "4")).unwrap();
+        let miscounted = read_allocations(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let sizes: Vec<u64> = allocs.unwrap().iter().map(|a| a.size).collect();
+        assert_eq!(sizes, [1, 2, 3]);
+        assert!(miscounted.is_err());
+    }
+
+    #[test]
+    fn test_invalid_allocations_are_explained() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-invalid-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let error = |json: &str| {
+            std::fs::write(dir.join("allocations.json"), json).unwrap();
+            read_allocations(&dir).unwrap_err().to_string()
+        };
+        let valid = r#"{"timesteps":[0,5],"offsets":[0,0],"size":1}"#;
+        let missing = error(&format!(
+            r#"[{},{{"timesteps":[0],"offset":[0],"size":1}}]"#,
+            valid
+        ));
+        let empty = error(&format!(
+            r#"[{},{{"timesteps":[],"offsets":[],"size":1}}]"#,
+            valid
+        ));
+        let truncated = error(&format!("[{},{}", valid, &valid[..20]));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(missing.starts_with("Allocation 1 in"), "{}", missing);
+        assert!(missing.contains("missing field `offsets`"), "{}", missing);
+        assert!(missing.contains("offset, size, timesteps"), "{}", missing);
+        assert!(empty.starts_with("Allocation 1 in allocations.json has empty `timesteps`"));
+        assert!(truncated.contains("truncated"), "{}", truncated);
+    }
+
+    #[test]
+    fn test_missing_elements_db() {
+        let db = AllocationDatabase::from_dir(Path::new("does-not-exist")).unwrap();
+        assert!(!db.has_elements().unwrap());
+        assert!(db.read_callstack(0).is_err());
+        assert!(db.read_events().unwrap().is_empty());
+        assert!(db.read_clock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_filter() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-filter-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("samples.csv");
+        std::fs::write(&csv, "time_us,used_bytes\n0,100\n10,300\n20,400\n30,0\n").unwrap();
+        let output = dir.join("converted");
+        convert_to_dir(&csv, &output, 0, ConvertOptions::default()).unwrap();
+
+        let mut db = AllocationDatabase::from_dir(&output).unwrap();
+        let allocs = load_allocations(&output, &db).unwrap();
+        let filter = LoadFilter {
+            min_size: Some(150),
+            ..Default::default()
+        };
+        let kept = filter_allocations(allocs, &output, &mut db, &filter).unwrap();
+        let sizes: Vec<(i64, i64)> = db
+            .conn
+            .prepare("SELECT idx, size FROM allocs ORDER BY idx")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let timeline = db.read_timeline().unwrap().len();
+        let wrong_device = LoadFilter {
+            device: Some(1),
+            ..Default::default()
+        };
+        let wrong_device = filter_allocations(kept.clone(), &output, &mut db, &wrong_device);
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
 
-    Ok(allocations)
+        assert_eq!(kept.iter().map(|a| a.size).collect::<Vec<_>>(), [200]);
+        assert_eq!(sizes, [(0, 200)]);
+        assert_eq!(timeline, 1);
+        assert!(wrong_device.is_err());
+    }
 }
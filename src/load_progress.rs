@@ -0,0 +1,37 @@
+//! Loading progress published to the GUI while a snapshot loads, so it can show a progress bar
+//! instead of looking hung.
+
+use serde_json::json;
+
+/// First part of the two-part IPC message carrying `{"stage": ..., "percent": ...}` as JSON in
+/// its second part. `percent` is of the whole load and reaches 100 once the meshes are on the GPU.
+pub const LOAD_PROGRESS_EVENT: &str = "load_progress";
+
+/// Reports loading stages over the PUB socket. Does nothing if created with
+/// `LoadProgress::disabled`, e.g. without a GUI listening.
+pub struct LoadProgress<'a> {
+    socket: Option<&'a zmq::Socket>,
+}
+
+impl<'a> LoadProgress<'a> {
+    pub fn new(socket: &'a zmq::Socket) -> Self {
+        Self {
+            socket: Some(socket),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self { socket: None }
+    }
+
+    /// `stage` started, with `percent` of the whole load done
+    pub fn stage(&self, stage: &str, percent: u8) {
+        let Some(socket) = self.socket else {
+            return;
+        };
+        let msg = json!({ "stage": stage, "percent": percent.min(100) }).to_string();
+        if let Err(e) = socket.send_multipart([LOAD_PROGRESS_EVENT.as_bytes(), msg.as_bytes()], 0) {
+            log::warn!("Could not send load progress: {}", e);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Input formats from shared libraries passed with `--loader`, so in-house allocators and
+//! profilers can be opened without rebuilding the renderer. Behind the `loader-plugins` feature.
+//!
+//! A plugin exports three C functions:
+//! - `const char *snapviewer_loader_name(void)`
+//! - `bool snapviewer_loader_can_load(const char *dir)`: whether it recognizes the snapshot in `dir`
+//! - `int snapviewer_loader_write_db(const char *dir, const char *db_path)`: writes the snapshot
+//!   as an elements.db (`convert::DATABASE_SCHEMA`, timeline table and callstacks included) to
+//!   `db_path`, returning 0 on success
+//!
+//! The database is written next to the snapshot once, later loads read it like a converted one.
+
+use crate::allocation::Allocation;
+use crate::constants::ELEMENT_DB_FILENAME;
+use crate::database::sqlite::AllocationDatabase;
+use crate::load::{LoaderRegistry, SnapshotLoader, read_allocations_from_db};
+use anyhow::{Context, bail};
+use libloading::{Library, Symbol};
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::path::Path;
+use std::sync::Arc;
+
+pub struct PluginLoader {
+    lib: Library,
+    name: String,
+}
+
+fn c_path(path: &Path) -> anyhow::Result<CString> {
+    CString::new(path.to_string_lossy().as_bytes())
+        .with_context(|| format!("{:?} contains a NUL byte", path))
+}
+
+impl PluginLoader {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        // SAFETY: loading a plugin runs its initializers, it is trusted like the renderer itself
+        let lib = unsafe { Library::new(path) }
+            .with_context(|| format!("Could not load loader plugin {:?}", path))?;
+        // SAFETY: signature documented above, the name is a static NUL-terminated string
+        let name = unsafe {
+            let name: Symbol<unsafe extern "C" fn() -> *const c_char> =
+                lib.get(b"snapviewer_loader_name")?;
+            CStr::from_ptr(name()).to_string_lossy().into_owned()
+        };
+        Ok(Self { lib, name })
+    }
+}
+
+impl SnapshotLoader for PluginLoader {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn can_load(&self, dir: &Path, _db: &AllocationDatabase) -> anyhow::Result<bool> {
+        let dir = c_path(dir)?;
+        // SAFETY: signature documented above
+        unsafe {
+            let can_load: Symbol<unsafe extern "C" fn(*const c_char) -> bool> =
+                self.lib.get(b"snapviewer_loader_can_load")?;
+            Ok(can_load(dir.as_ptr()))
+        }
+    }
+
+    fn database(&self, dir: &Path, db: AllocationDatabase) -> anyhow::Result<AllocationDatabase> {
+        // written by an earlier load
+        if db.has_timeline()? {
+            return Ok(db);
+        }
+        drop(db);
+        let db_path = dir.join(ELEMENT_DB_FILENAME);
+        let tmp_path = db_path.with_extension("db.tmp");
+        let (c_dir, c_tmp) = (c_path(dir)?, c_path(&tmp_path)?);
+        // SAFETY: signature documented above
+        let code = unsafe {
+            let write_db: Symbol<unsafe extern "C" fn(*const c_char, *const c_char) -> c_int> =
+                self.lib.get(b"snapviewer_loader_write_db")?;
+            write_db(c_dir.as_ptr(), c_tmp.as_ptr())
+        };
+        if code != 0 {
+            bail!(
+                "The {} loader failed on {:?} with code {}",
+                self.name,
+                dir,
+                code
+            );
+        }
+        std::fs::rename(&tmp_path, &db_path)?;
+        AllocationDatabase::from_dir(dir)
+    }
+
+    fn load(&self, dir: &Path, db: &AllocationDatabase) -> anyhow::Result<Arc<[Allocation]>> {
+        read_allocations_from_db(dir, db)
+    }
+}
+
+/// `loaders` with the plugins at `paths` registered, the first one tried first
+pub fn with_plugins(
+    mut loaders: LoaderRegistry,
+    paths: &[impl AsRef<Path>],
+) -> anyhow::Result<LoaderRegistry> {
+    for path in paths.iter().rev() {
+        loaders.register(Box::new(PluginLoader::open(path.as_ref())?));
+    }
+    Ok(loaders)
+}
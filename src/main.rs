@@ -1,34 +1,102 @@
-use anyhow::Result as AnyhowResult;
-use clap::Parser;
+use anyhow::{Context as _, Result as AnyhowResult, anyhow};
+use clap::{Parser, Subcommand};
 use log::info;
 use nalgebra::Vector2;
 use snapviewer::{
-    database::sqlite::AllocationDatabase,
-    load::read_allocations,
-    render_loop::{FpsTimer, RenderLoop},
-    ticks::TickGenerator,
+    annotation::Annotations,
+    anonymize::anonymize_dir,
+    bookmark::Bookmarks,
+    bundle::write_bundle,
+    clock::{Clock, read_time_unit},
+    color_mode::ColorMode,
+    compact::compact_dir,
+    constants::{
+        AT_REPORT_LIMIT, CONVERTER_VERSION, DEFAULT_MAX_SEGMENTS, DEFAULT_TOP_LABELS,
+        DOUBLE_CLICK_FILL, DOUBLE_CLICK_MS, DOUBLE_CLICK_SLOP_PX, ELEMENT_DB_FILENAME,
+        FIRST_RUN_MARKER, GOTO_FILL, GROUP_REPORT_LIMIT, IDLE_POLL_MS, RECORD_FPS,
+        REGION_TOP_CALLSTACKS, SPOTLIGHT_DEFAULT_COUNT, UNDO_HISTORY_LIMIT, UNDO_SETTLE_MS,
+        UPLOAD_BUDGET_MS, WARM_UP_IDLE_MS, WHEEL_PAN_SPEED, WINDOW_SIZE_EVENT, ZOOM_ANIMATION_MS,
+    },
+    convert::{ConvertOptions, convert_all_devices, convert_to_dir, converted_by},
+    cursor_link::CursorLink,
+    database::sqlite::{AllocationDatabase, LIVE_AT_CONDITION},
+    devices::Devices,
+    diff::{DiffInput, unmatched_allocations},
+    doctor::run_checks,
+    downsample::KeepBy,
+    error::{SnapError, exit_code},
+    events::{AllocatorEvent, EventKind, nearest_event},
+    fragmentation::{UnusedReserved, unused_at, unused_reserved},
+    geometry::TraceGeometry,
+    groups::CallstackGroups,
+    history::{History, ViewState},
+    input::{InputKind, detect_input},
+    instrument::python_snippet,
+    load::{LoadFilter, LoaderRegistry, filter_allocations},
+    load_progress::LoadProgress,
+    memory_timeline::MemoryTimeline,
+    merge::{default_output, merge_ranks},
+    palette::{Palette, PaletteOrder},
+    parquet::write_allocations,
+    profile::Profiler,
+    record::record,
+    region::{REGION_STATS_EVENT, RegionStats},
+    render_loop::{ChunkedMesh, FpsTimer, RenderLoop, top_label},
+    screencast::Screencast,
+    script::{ScriptAction, run_script},
+    segments::{Segment, SegmentBand, reserved_at, stack_segments},
+    theme::{Theme, parse_color},
+    ticks::{
+        AT_CURSOR_COLOR, EVENT_GLYPH_SIZE_PX, EVENT_STRIP_HEIGHT_PX, LINKED_CURSOR_COLOR,
+        TickCache, TickGenerator, memory_ticks,
+    },
+    utils::{
+        config_dir, format_bytes, format_bytes_precision, get_spinner, memory_usage, parse_bytes,
+        parse_time_range,
+    },
+    watch::{RELOADED_EVENT, Watcher},
+    window::{ViewerWindow, window_title},
     window_transform::{TranslateDir, WindowTransform},
-    utils::{format_bytes_precision, get_spinner, memory_usage},
 };
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use three_d::{
-    ClearState, ColorMaterial, CpuMesh, Event, FrameOutput, Gm, Mesh, MouseButton, Srgba, Window,
-    WindowSettings,
+    ClearState, Context, Event, FrameOutput, Geometry, MouseButton, Srgba, WindowSettings,
 };
 
 /// SnapViewer Renderer - Standalone OpenGL renderer with ZeroMQ IPC
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version = CONVERTER_VERSION, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    /// Directory containing allocations.json and elements.db
-    #[arg(short, long)]
-    dir: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Snapshot to open: a converted directory or a file in one (e.g. its elements.db), like
+    /// `--dir`. Pickles, zip archives and sample CSVs are recognized and need `convert` first
+    #[arg(value_name = "PATH", conflicts_with = "dir")]
+    path: Option<PathBuf>,
+
+    /// Directory containing elements.db (and allocations.json for older conversions)
+    #[arg(short, long, required_unless_present_any = ["path", "merge_ranks"])]
+    dir: Option<String>,
+
+    /// Converted snapshots of a job's ranks, rank 0 first, merged into one snapshot drawn with a
+    /// lane per rank and opened. Written to `--dir` if given, otherwise to a temporary directory;
+    /// SQL sees every rank's allocations, told apart by their `rank` column
+    #[arg(long, value_name = "DIR", num_args = 1.., conflicts_with_all = ["path", "diff", "devices"])]
+    merge_ranks: Vec<PathBuf>,
 
     /// Resolution width and height
     #[arg(long, value_name = "WIDTH HEIGHT", num_args = 2, default_values_t = [2400, 1000])]
     res: Vec<u32>,
 
+    /// Snapshot name for the window title (e.g. the pickle's file name), defaults to the name
+    /// of `--dir`
+    #[arg(long)]
+    title: Option<String>,
+
     /// ZeroMQ PUB socket port (Renderer -> UI)
     #[arg(long, default_value_t = 5555)]
     pub_port: u16,
@@ -41,20 +109,391 @@ struct Args {
     #[arg(long, default_value_t = String::from("info"))]
     log: String,
 
-    /// Resolution ratio for high-DPI displays (e.g., 2.0 for Retina)
-    #[arg(long, default_value_t = 1.0)]
-    resolution_ratio: f64,
+    /// Resolution ratio for high-DPI displays (e.g., 2.0 for Retina).
+    /// Detected from the window's device pixel ratio if omitted
+    #[arg(long)]
+    resolution_ratio: Option<f64>,
+
+    /// Coarsen the geometry if the trace has more quads than this
+    #[arg(long, default_value_t = DEFAULT_MAX_SEGMENTS)]
+    max_segments: usize,
+
+    /// Draw only the N largest allocations, the others aggregated into grey bands, for a quick
+    /// look at a trace too large to draw whole
+    #[arg(long, value_name = "N")]
+    max_allocs: Option<usize>,
+
+    /// What `--max-allocs` keeps the largest by: `size`, or `lifetime`
+    #[arg(long, default_value = "size", requires = "max_allocs")]
+    max_allocs_by: KeepBy,
+
+    /// Initial visible time range, as `<start>..<stop>` timestamps
+    #[arg(long, value_parser = parse_time_range)]
+    view: Option<(u64, u64)>,
+
+    /// Allocation to select at startup
+    #[arg(long)]
+    select: Option<usize>,
+
+    /// Split the plot into one lane per CUDA stream
+    #[arg(long, conflicts_with = "diff")]
+    lanes: bool,
+
+    /// Load only allocations of at least this size (e.g. `1MiB`): geometry and meshes are built
+    /// for them alone, and SQL queries see only them
+    #[arg(long, value_name = "SIZE", value_parser = parse_bytes)]
+    min_size: Option<u64>,
+
+    /// Load only allocations living at least this many timesteps, like `--min-size`
+    #[arg(long, value_name = "TIMESTEPS")]
+    min_lifetime: Option<u64>,
+
+    /// Load only the allocations of rank ID of a snapshot merging ranks, like `--min-size`.
+    /// Otherwise checks that the snapshot was converted from device ID.
+    #[arg(long, value_name = "ID", conflicts_with = "devices")]
+    device: Option<i64>,
+
+    /// Overlay another converted snapshot (e.g. after a fix) on this one for comparison
+    #[arg(long, value_name = "DIR")]
+    diff: Option<String>,
+
+    /// Converted directories of the snapshot's other devices (e.g. from `convert --device all`),
+    /// switched to with Tab or `--device <id>`. Each is loaded on its first switch and kept
+    #[arg(long, value_name = "DIR", num_args = 1.., conflicts_with = "diff")]
+    devices: Vec<PathBuf>,
+
+    /// Reload the shown snapshot when its directory changes, e.g. converted again from a newer
+    /// dump, keeping the visible time range. `--reload` in the REPL does so on demand
+    #[arg(long)]
+    watch: bool,
+
+    /// Cap the frame rate while redrawing, e.g. during the selection fade (the window is only
+    /// redrawn when something changes)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_fps: Option<u32>,
+
+    /// Share the cursor's timestamp with another renderer: publish on PUB_PORT, follow the one
+    /// publishing on SUB_PORT (started with the ports swapped)
+    #[arg(long, value_names = ["PUB_PORT", "SUB_PORT"], num_args = 2)]
+    link_cursor: Option<Vec<u16>>,
+
+    /// Seed for the random allocation colors, so colors are the same across runs and snapshots
+    #[arg(long)]
+    color_seed: Option<u64>,
+
+    /// What allocation colors encode (random, size, lifetime or category); all but `random`
+    /// come with a legend in the bottom right
+    #[arg(long, default_value = "random", conflicts_with = "diff")]
+    color_by: ColorMode,
+
+    /// Color allocations from this file of hex colors (e.g. `#1f77b4`, one or more per line)
+    /// instead of random ones
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["diff", "color_by"])]
+    palette: Option<PathBuf>,
+
+    /// How allocations pick a palette color: `round-robin`, or `hash` of the size so same-sized
+    /// allocations match
+    #[arg(long, default_value = "round-robin", requires = "palette")]
+    palette_order: PaletteOrder,
+
+    /// Start in heatmap mode (toggled with B): allocation counts per time × memory bin instead of
+    /// the allocations, readable and fast for traces with tens of millions of segments
+    #[arg(long)]
+    heatmap: bool,
+
+    /// Start with the reserved segments drawn as faint bands behind the allocations (toggled
+    /// with E), showing which segment each allocation was carved from
+    #[arg(long)]
+    segments: bool,
+
+    /// Start with the N largest allocations labeled with their size and top stack frame where
+    /// they are large enough on screen (toggled with T, which labels 5 without this flag)
+    #[arg(long, value_name = "N")]
+    top_labels: Option<usize>,
+
+    /// Colors around the allocations: light, dark or cute (also `default` and `night`, after the
+    /// GUI palettes they match). Switched at runtime with N or `--theme`.
+    #[arg(long, default_value = "light")]
+    theme: Theme,
+
+    /// Background color (e.g. `#002b36`) replacing the theme's
+    #[arg(long, value_name = "HEX", value_parser = parse_color)]
+    background: Option<Srgba>,
+
+    /// Color of tick labels and other text replacing the theme's
+    #[arg(long, value_name = "HEX", value_parser = parse_color)]
+    tick_color: Option<Srgba>,
+
+    /// Color of the selected allocation's outline replacing the theme's
+    #[arg(long, value_name = "HEX", value_parser = parse_color)]
+    selection_color: Option<Srgba>,
+
+    /// Write startup stage timings and frame time statistics to this file
+    #[arg(long, value_name = "FILE")]
+    self_profile: Option<PathBuf>,
+
+    /// Shared library adding an input format, tried before the built-in loaders (see "Custom
+    /// input formats" in the README). Repeatable, the first one is tried first
+    #[cfg(feature = "loader-plugins")]
+    #[arg(long, value_name = "LIB", global = true)]
+    loader: Vec<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Copy a converted snapshot directory with file paths and function names in callstacks
+    /// replaced by hashes, so it can be shared in bug reports
+    Anonymize {
+        /// Directory containing elements.db
+        #[arg(short, long)]
+        dir: String,
+
+        /// Output directory
+        #[arg(short, long)]
+        out: String,
+
+        /// Salt for the name hashes; the same salt gives the same hashes across exports.
+        /// Random by default
+        #[arg(long)]
+        salt: Option<String>,
+    },
+    /// Move allocation geometry from allocations.json into elements.db's timeline table and
+    /// delete the JSON, for directories converted before timelines or with `--json`; the
+    /// renderer loads elements.db alone
+    Compact {
+        /// Directory containing elements.db and allocations.json
+        #[arg(short, long)]
+        dir: String,
+    },
+    /// Convert a snapshot pickle (or a CSV written by `record`, or a profiler memory timeline) into
+    /// a directory holding elements.db and metadata.json; gui.py runs this for `--pickle`. Needs
+    /// neither Python nor a display, so CI jobs and servers can preprocess snapshots
+    Convert {
+        /// Snapshot pickle (gzip or zstd compressed, or in a .zip), a .csv of samples from
+        /// `record`, or a memory timeline .json (or .json.gz) exported by the PyTorch profiler
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output directory, with a {device} placeholder for `--device all`
+        #[arg(short, long)]
+        output: String,
+
+        /// Device index, or `all` for every device with a trace
+        #[arg(short, long, default_value = "0")]
+        device: String,
+
+        /// Also write allocations.json, for renderers predating elements.db timelines
+        #[arg(long)]
+        json: bool,
+
+        /// With --json, split allocations.json into allocations_0.json, allocations_1.json, ...
+        /// of at most N allocations each, for traces too large for one JSON file
+        #[arg(long, value_name = "N", requires = "json")]
+        json_shard_size: Option<usize>,
+
+        /// Also write allocations.bin, which loads faster than elements.db
+        #[arg(long)]
+        binary: bool,
+    },
+    /// Check OpenGL, IPC, the cache directory and Python, printing a fix for each failure
+    Doctor,
+    /// Write a converted snapshot in another format
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
+    /// Sample GPU memory through NVML into a CSV, for PyTorch versions without memory snapshots.
+    /// `snapviewer-renderer convert -i <csv> -o <dir>` turns it into a viewable directory
+    Record {
+        /// Process to sample, e.g. the training script; the whole device if omitted.
+        /// Recording stops when the process exits
+        #[arg(long)]
+        pid: Option<u32>,
+
+        /// CUDA device index, as NVML numbers them
+        #[arg(long, default_value_t = 0)]
+        device: u32,
+
+        /// Time between samples, in milliseconds
+        #[arg(long, default_value_t = 100)]
+        interval_ms: u64,
+
+        /// Stop after this many seconds
+        #[arg(long)]
+        duration: Option<f64>,
+
+        /// CSV file to write
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// Render the whole trace offscreen into a PNG, without opening a window or needing a GPU
+    Png {
+        /// Directory containing elements.db
+        #[arg(short, long)]
+        dir: String,
+
+        /// PNG file to write
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// Image width and height
+        #[arg(long, value_names = ["WIDTH", "HEIGHT"], num_args = 2, default_values_t = [16000, 4000])]
+        size: Vec<u32>,
+
+        /// Seed for the random allocation colors, see the renderer's `--color-seed`
+        #[arg(long)]
+        color_seed: Option<u64>,
+    },
+    /// Run a Rhai script against a converted snapshot without opening a window
+    Script {
+        /// Directory containing elements.db
+        #[arg(short, long)]
+        dir: String,
+
+        /// Script file
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ExportFormat {
+    /// One row per allocation (size, lifetime, category, callstack, timesteps and offsets), for
+    /// pandas, polars or DuckDB
+    Parquet {
+        /// Directory containing elements.db
+        #[arg(short, long)]
+        dir: String,
+
+        /// Parquet file to write
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// A zip holding elements.db and metadata.json, to share; `python gui.py --dir <zip>` opens
+    /// it. `--export-bundle` in the REPL bundles the snapshot as loaded, e.g. filtered
+    Bundle {
+        /// Directory containing elements.db
+        #[arg(short, long)]
+        dir: String,
+
+        /// Zip file to write
+        #[arg(short, long)]
+        out: PathBuf,
+    },
 }
 
 struct RendererState {
-    db_ptr: u64,
+    title: String,
     resolution: (u32, u32),
-    resolution_ratio: f64,
+    resolution_ratio: Option<f64>,
+    view: Option<(u64, u64)>,
+    select: Option<usize>,
+    bookmarks: Bookmarks,
+    cursor_link: Option<CursorLink>,
+    max_fps: Option<u32>,
+    heatmap: bool,
+    segments: bool,
+    top_labels: Option<usize>,
+    devices: Devices,
+    load_options: LoadOptions,
+    /// Overlaid on the first device, and on it again when it is reloaded
+    diff: Option<String>,
+    watch: bool,
     pub_socket: zmq::Socket,
     rep_socket: zmq::Socket,
+    profiler: Profiler,
+}
+
+/// How each device's snapshot is loaded and drawn, from the command line
+struct LoadOptions {
+    resolution: (u32, u32),
+    lanes: bool,
+    max_segments: usize,
+    max_allocs: Option<(usize, KeepBy)>,
+    filter: LoadFilter,
+    color_seed: Option<u64>,
+    color_mode: ColorMode,
+    theme: Theme,
+    loaders: LoaderRegistry,
+}
+
+/// One device's converted snapshot, ready to draw. The shown device's lives in the render loop,
+/// the others' are kept aside once loaded.
+struct DeviceData {
+    db_ptr: u64,
+    rl: RenderLoop,
+    meshes: ChunkedMesh,
+    annotations: Annotations,
+    events: Vec<AllocatorEvent>,
+    /// (timestep, bytes) where the memory reserved in segments changes
+    reserved: Vec<(u64, u64)>,
+    /// The segments themselves, empty for databases predating the segments table
+    segment_list: Vec<Segment>,
+}
+
+/// Computed from the shown device's trace for the overlays and readouts, again on a switch
+struct TraceOverlays {
+    /// Global memory peak, in world coords
+    peak_y: f32,
+    peak_xs: Vec<f32>,
+    lanes: Vec<(f32, f32, String)>,
+    /// Live memory over time, for the readout in the top right corner
+    memory_timeline: MemoryTimeline,
+    /// Live allocation count over time, drawn as a curve when toggled with C
+    count_timeline: MemoryTimeline,
+    /// Reserved-but-unused memory on top of the allocations, toggled with F.
+    /// Only meaningful for the global stacking, not per-stream lanes.
+    unused: Vec<UnusedReserved>,
+    /// Reserved segments as bands behind the allocations, toggled with E.
+    /// Like fragmentation, not shown with per-stream lanes.
+    segment_bands: Vec<SegmentBand>,
+    /// Allocator event glyphs, clickable for details
+    event_xs: Vec<(f32, EventKind)>,
+}
+
+impl TraceOverlays {
+    fn new(
+        rl: &RenderLoop,
+        events: &[AllocatorEvent],
+        reserved: &[(u64, u64)],
+        segment_list: &[Segment],
+    ) -> Self {
+        let geom = &rl.trace_geom;
+        // the readouts count the allocations `--max-allocs` left out too
+        let memory_timeline = match &rl.others {
+            Some(others) => others.memory.clone(),
+            None => MemoryTimeline::from_allocations(&geom.raw_allocs),
+        };
+        Self {
+            peak_y: geom.memory2yworld(geom.max_size as i64),
+            peak_xs: geom
+                .peak_timestamps
+                .iter()
+                .map(|&t| geom.timestamp2xworld(t))
+                .collect(),
+            lanes: lane_bounds(geom),
+            count_timeline: MemoryTimeline::allocation_counts(&geom.raw_allocs),
+            unused: unused_reserved(reserved, &memory_timeline, geom.max_time as u64 + 1),
+            memory_timeline,
+            segment_bands: stack_segments(segment_list),
+            event_xs: events
+                .iter()
+                .map(|e| (geom.timestamp2xworld(e.timestep), e.kind))
+                .collect(),
+        }
+    }
 }
 
-fn main() -> AnyhowResult<()> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(exit_code(&err))
+        }
+    }
+}
+
+fn run() -> AnyhowResult<()> {
     let args = Args::parse();
 
     // Initialize logger
@@ -62,122 +501,566 @@ fn main() -> AnyhowResult<()> {
         "trace" => log::LevelFilter::Trace,
         "info" => log::LevelFilter::Info,
         lvl => {
-            anyhow::bail!("Expected `info` or `trace`, got {}", lvl);
+            return Err(
+                anyhow!("Expected `info` or `trace`, got {}", lvl).context(SnapError::BadInput)
+            );
         }
     };
     pretty_env_logger::formatted_builder()
         .filter_level(log_level)
         .init();
 
-    // Validate resolution
-    let resolution = match args.res.len() {
-        2 => (args.res[0], args.res[1]),
-        _ => anyhow::bail!("Resolution must have exactly 2 values (width height)"),
-    };
+    let loaders = LoaderRegistry::default();
+    #[cfg(feature = "loader-plugins")]
+    let loaders = snapviewer::loader_plugin::with_plugins(loaders, &args.loader)
+        .context(SnapError::BadInput)?;
 
-    // Load allocations
-    let dir = PathBuf::from(&args.dir);
-    let allocs = read_allocations(&dir)?;
+    if let Some(Command::Anonymize { dir, out, salt }) = &args.command {
+        let count = anonymize_dir(Path::new(dir), Path::new(out), salt.as_deref())
+            .context(SnapError::Conversion)?;
+        println!("Anonymized {} callstacks, written to {}", count, out);
+        return Ok(());
+    }
 
-    // Load database
-    let db = Box::leak(Box::new(AllocationDatabase::from_dir(&dir)?));
-    let num_elems = db.row_count()?;
+    if let Some(Command::Compact { dir }) = &args.command {
+        match compact_dir(Path::new(dir)).context(SnapError::Conversion)? {
+            0 => println!("{} holds no allocations.json, nothing to compact", dir),
+            freed => println!(
+                "Moved allocations into {}, freeing {}",
+                ELEMENT_DB_FILENAME,
+                format_bytes(freed as i64)
+            ),
+        }
+        return Ok(());
+    }
 
-    // Data integrity check
-    if allocs.len() != num_elems {
-        anyhow::bail!(
-            "# of allocation and elements mismatch: {} allocations, {} elements",
-            allocs.len(),
-            num_elems
+    if let Some(Command::Convert {
+        input,
+        output,
+        device,
+        json,
+        json_shard_size,
+        binary,
+    }) = &args.command
+    {
+        let options = ConvertOptions {
+            json: *json,
+            json_shard_size: *json_shard_size,
+            binary: *binary,
+        };
+        if !input.exists() {
+            return Err(anyhow!("Input file {} does not exist", input.display())
+                .context(SnapError::BadInput));
+        }
+        if device == "all" {
+            let converted = convert_all_devices(input, output, options)?;
+            println!("Done, converted devices {:?}.", converted);
+            return Ok(());
+        }
+        let device = device
+            .parse()
+            .map_err(|_| anyhow!("Expected a device ID or `all`, got {}", device))
+            .context(SnapError::BadInput)?;
+        convert_to_dir(input, Path::new(output), device, options)?;
+        println!("Done, output written to {}", output);
+        return Ok(());
+    }
+
+    if let Some(Command::Export {
+        format: ExportFormat::Parquet { dir, out },
+    }) = &args.command
+    {
+        let dir = Path::new(dir);
+        let (db, allocs) = loaders.open(dir).context(SnapError::BadInput)?;
+        let callstacks = db
+            .read_sizes_and_callstacks()
+            .context(SnapError::BadInput)?
+            .into_iter()
+            .map(|(_, callstack)| callstack)
+            .collect();
+        write_allocations(out, &allocs, callstacks).context(SnapError::Conversion)?;
+        println!("Wrote {} allocations to {}", allocs.len(), out.display());
+        return Ok(());
+    }
+
+    if let Some(Command::Export {
+        format: ExportFormat::Bundle { dir, out },
+    }) = &args.command
+    {
+        let dir = Path::new(dir);
+        let (_, db) = loaders.open_database(dir).context(SnapError::BadInput)?;
+        let count = write_bundle(&db, dir, out).context(SnapError::Conversion)?;
+        println!("Bundled {} allocations into {}", count, out.display());
+        return Ok(());
+    }
+
+    if let Some(Command::Doctor) = &args.command {
+        let checks = run_checks(args.pub_port, args.rep_port);
+        for check in &checks {
+            println!("{}", check);
+        }
+        if checks.iter().any(|check| check.outcome.is_err()) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Record {
+        pid,
+        device,
+        interval_ms,
+        duration,
+        out,
+    }) = &args.command
+    {
+        let samples = record(
+            *pid,
+            *device,
+            Duration::from_millis(*interval_ms),
+            duration.map(Duration::from_secs_f64),
+            out,
+        )?;
+        println!("Recorded {} samples to {}", samples, out.display());
+        return Ok(());
+    }
+
+    if let Some(Command::Png {
+        dir,
+        out,
+        size,
+        color_seed,
+    }) = &args.command
+    {
+        let &[width, height] = size.as_slice() else {
+            unreachable!("clap takes exactly 2 values");
+        };
+        if width == 0 || height == 0 {
+            return Err(anyhow!("Image size must be positive").context(SnapError::BadInput));
+        }
+        let dir = Path::new(dir);
+        let (loader, db) = loaders.open_database(dir).context(SnapError::BadInput)?;
+        let db = Box::leak(Box::new(db));
+        let allocs = loader.load(dir, db).context(SnapError::BadInput)?;
+        // world coords are image pixels, the CPU meshes are not needed
+        let (render_loop, _) = RenderLoop::initialize(
+            allocs,
+            None,
+            None,
+            (width, height),
+            DEFAULT_MAX_SEGMENTS,
+            *color_seed,
+            ColorMode::Random,
+            None,
+            &LoadProgress::disabled(),
+        )?;
+        let bar = get_spinner("Rasterizing...")?;
+        let (image, count) =
+            render_loop.rasterize((0.0, width as f32), (0.0, height as f32), (width, height));
+        bar.finish();
+        image.write_png(out)?;
+        println!(
+            "Rendered {} allocations at {}x{} to {}",
+            count,
+            width,
+            height,
+            out.display()
         );
+        return Ok(());
     }
 
-    println!("Found {} entries", allocs.len());
-    println!("Memory after init: {} MiB", memory_usage());
+    if let Some(Command::Script { dir, file }) = &args.command {
+        let source = std::fs::read_to_string(file).context(SnapError::BadInput)?;
+        let dir = Path::new(dir);
+        let (loader, db) = loaders.open_database(dir).context(SnapError::BadInput)?;
+        let db = Box::leak(Box::new(db));
+        let allocs = loader.load(dir, db).context(SnapError::BadInput)?;
+        let output = run_script(&source, allocs, db).context(SnapError::BadInput)?;
+        print!("{}", output.printed);
+        for action in output.actions {
+            println!("(renderer command ignored without a window: {:?})", action);
+        }
+        return Ok(());
+    }
+
+    // Validate resolution
+    let resolution = match args.res.len() {
+        2 => (args.res[0], args.res[1]),
+        _ => {
+            return Err(
+                anyhow!("Resolution must have exactly 2 values (width height)")
+                    .context(SnapError::BadInput),
+            );
+        }
+    };
+
+    let mut profiler = match &args.self_profile {
+        Some(path) => Profiler::new(path.clone()),
+        None => Profiler::disabled(),
+    };
 
+    let dir = match &args.path {
+        Some(path) => match detect_input(path).context(SnapError::BadInput)? {
+            (InputKind::Converted, dir) => dir,
+            (kind, _) => {
+                return Err(anyhow!(
+                    "{} is a {}, convert it first: `snapviewer-renderer convert -i {} -o <dir>` (gui.py --pickle does this and caches the result)",
+                    path.display(),
+                    kind,
+                    path.display()
+                )
+                .context(SnapError::BadInput));
+            }
+        },
+        None if !args.merge_ranks.is_empty() => {
+            let output = match &args.dir {
+                Some(dir) => PathBuf::from(dir),
+                None => default_output(&args.merge_ranks),
+            };
+            merge_ranks(&args.merge_ranks, &output, &loaders).context(SnapError::BadInput)?;
+            output
+        }
+        None => PathBuf::from(args.dir.as_deref().unwrap_or_default()),
+    };
+    let load_options = LoadOptions {
+        resolution,
+        lanes: args.lanes,
+        max_segments: args.max_segments,
+        max_allocs: args.max_allocs.map(|max| (max, args.max_allocs_by)),
+        filter: LoadFilter {
+            min_size: args.min_size,
+            min_lifetime: args.min_lifetime,
+            device: args.device,
+        },
+        color_seed: args.color_seed,
+        color_mode: match &args.palette {
+            Some(path) => ColorMode::Palette(
+                Palette::load(path, args.palette_order).context(SnapError::BadInput)?,
+            ),
+            None => args.color_by,
+        },
+        theme: Theme {
+            background: args.background.unwrap_or(args.theme.background),
+            ticks: args.tick_color.unwrap_or(args.theme.ticks),
+            selection: args.selection_color.unwrap_or(args.theme.selection),
+            ..args.theme
+        },
+        loaders,
+    };
     // Create ZeroMQ context
     let context = zmq::Context::new();
 
     // Create PUB socket for sending click events to UI
-    let pub_socket = context.socket(zmq::SocketType::PUB)?;
+    let pub_socket = context
+        .socket(zmq::SocketType::PUB)
+        .context(SnapError::Ipc)?;
     let pub_endpoint = format!("tcp://*:{}", args.pub_port);
-    pub_socket.bind(&pub_endpoint)?;
+    pub_socket
+        .bind(&pub_endpoint)
+        .with_context(|| format!("Could not bind {}", pub_endpoint))
+        .context(SnapError::Ipc)?;
     println!("PUB socket bound to {}", pub_endpoint);
 
     // Create REP socket for receiving SQL commands from UI
-    let rep_socket = context.socket(zmq::SocketType::REP)?;
+    let rep_socket = context
+        .socket(zmq::SocketType::REP)
+        .context(SnapError::Ipc)?;
     let rep_endpoint = format!("tcp://*:{}", args.rep_port);
-    rep_socket.bind(&rep_endpoint)?;
+    rep_socket
+        .bind(&rep_endpoint)
+        .with_context(|| format!("Could not bind {}", rep_endpoint))
+        .context(SnapError::Ipc)?;
     println!("REP socket bound to {}", rep_endpoint);
 
+    let cursor_link = match args.link_cursor.as_deref() {
+        Some(&[pub_port, sub_port]) => {
+            Some(CursorLink::new(&context, pub_port, sub_port).context(SnapError::Ipc)?)
+        }
+        _ => None,
+    };
+
+    // sockets come first, so the GUI sees the loading progress
+    let device = load_device(
+        &dir,
+        &load_options,
+        args.diff.as_deref(),
+        &mut profiler,
+        &LoadProgress::new(&pub_socket),
+    )?;
+
+    // Run render loop
+    // the merged directory is named by a hash
+    let merged_title =
+        (!args.merge_ranks.is_empty()).then(|| format!("{} ranks merged", args.merge_ranks.len()));
+    let state = RendererState {
+        title: window_title(
+            &dir.to_string_lossy(),
+            args.title.as_deref().or(merged_title.as_deref()),
+        ),
+        resolution,
+        resolution_ratio: args.resolution_ratio,
+        view: args.view,
+        select: args.select,
+        bookmarks: Bookmarks::load(&dir),
+        cursor_link,
+        max_fps: args.max_fps,
+        heatmap: args.heatmap,
+        segments: args.segments,
+        top_labels: args.top_labels,
+        devices: Devices::new(&dir, &args.devices),
+        load_options,
+        diff: args.diff.clone(),
+        watch: args.watch,
+        pub_socket,
+        rep_socket,
+        profiler,
+    };
+
+    run_render_loop(state, device)?;
+
+    Ok(())
+}
+
+/// Loads the converted snapshot in `dir` and builds its geometry, with the one in `diff_dir`
+/// overlaid if given
+fn load_device(
+    dir: &Path,
+    options: &LoadOptions,
+    diff_dir: Option<&str>,
+    profiler: &mut Profiler,
+    progress: &LoadProgress,
+) -> AnyhowResult<DeviceData> {
+    match converted_by(dir) {
+        Some(version) if version == CONVERTER_VERSION => {}
+        version => log::warn!(
+            "{} was converted by {}, this renderer is {}: re-convert it if anything is missing or looks off",
+            dir.display(),
+            version.map_or("an older converter".to_string(), |v| format!(
+                "converter {}",
+                v
+            )),
+            CONVERTER_VERSION
+        ),
+    }
+
+    // Load database
+    progress.stage("opening database", 0);
+    let (loader, db) = options
+        .loaders
+        .open_database(dir)
+        .context(SnapError::BadInput)?;
+    let db = Box::leak(Box::new(db));
+    let has_elements = db.has_elements().context(SnapError::BadInput)?;
+    let num_elems = if has_elements {
+        db.row_count().context(SnapError::BadInput)?
+    } else {
+        0
+    };
+    profiler.stage("open database");
+
+    // Load allocations
+    progress.stage("loading allocations", 5);
+    let allocs = loader.load(dir, db).context(SnapError::BadInput)?;
+    profiler.stage("load allocations");
+
+    // Data integrity check, nothing to check against without elements.db
+    if has_elements && allocs.len() != num_elems {
+        return Err(anyhow!(
+            "# of allocation and elements mismatch: {} allocations, {} elements",
+            allocs.len(),
+            num_elems
+        )
+        .context(SnapError::BadInput));
+    }
+
+    println!("Found {} entries", allocs.len());
+    let allocs = match options.filter.is_empty() {
+        true => allocs,
+        false => {
+            let allocs = filter_allocations(allocs, dir, db, &options.filter)
+                .context(SnapError::BadInput)?;
+            profiler.stage("filter allocations");
+            allocs
+        }
+    };
+
+    // a merged snapshot is always drawn with a lane per rank
+    let by_rank = db.has_ranks().context(SnapError::BadInput)?;
+    let lanes = if options.lanes || by_rank {
+        if !db.has_lanes()? {
+            return Err(anyhow!(
+                "{:?} has no per-stream data, re-convert it with the current `snapviewer-renderer convert` to use --lanes",
+                dir
+            )
+            .context(SnapError::BadInput));
+        }
+        Some(db.read_lanes().context(SnapError::BadInput)?)
+    } else {
+        None
+    };
+
+    let diff = match diff_dir {
+        Some(diff_dir) => Some(
+            load_diff(Path::new(diff_dir), db, &options.loaders).context(SnapError::BadInput)?,
+        ),
+        None => None,
+    };
+    println!("Memory after init: {} MiB", memory_usage());
+
     // Initialize render loop
     println!(
         "Memory before initializing render loop: {} MiB",
         memory_usage()
     );
-    let bar = get_spinner(&format!("Initializing render loop..."))?;
-    let (render_loop, cpu_mesh) = RenderLoop::initialize(Arc::clone(&allocs), resolution)?;
+    let bar = get_spinner("Initializing render loop...")?;
+    let (mut render_loop, cpu_meshes) = RenderLoop::initialize(
+        allocs,
+        lanes,
+        diff,
+        options.resolution,
+        options.max_segments,
+        options.color_seed,
+        options.color_mode.clone(),
+        options.max_allocs,
+        progress,
+    )?;
     println!(
         "Memory after initializing render loop: {} MiB",
         memory_usage()
     );
     bar.finish();
+    profiler.stage("build geometry");
+    progress.stage("reading events", 85);
+    if by_rank {
+        render_loop.trace_geom.lane_name = "rank";
+    }
+    if !has_elements {
+        render_loop
+            .trace_geom
+            .warnings
+            .push(MISSING_ELEMENTS_NOTICE.to_string());
+    }
+    render_loop.clock = Clock::new(db.read_clock()?, &read_time_unit(dir))?;
+    render_loop.theme = options.theme;
 
-    // Run render loop
-    let state = RendererState {
+    Ok(DeviceData {
         db_ptr: db as *mut AllocationDatabase as u64,
-        resolution,
-        resolution_ratio: args.resolution_ratio,
-        pub_socket,
-        rep_socket,
-    };
-
-    run_render_loop(state, render_loop, cpu_mesh)?;
-
-    Ok(())
+        rl: render_loop,
+        // moved to the GPU over the first frames it is shown, see `ChunkedMesh`
+        meshes: ChunkedMesh::new(cpu_meshes),
+        annotations: Annotations::load(dir),
+        events: db.read_events()?,
+        reserved: db.read_reserved()?,
+        segment_list: db.read_segments()?,
+    })
 }
 
-fn run_render_loop(
-    state: RendererState,
-    mut rl: RenderLoop,
-    cpu_mesh: CpuMesh,
-) -> AnyhowResult<()> {
-    let bar = get_spinner(&format!("Initializing window and UI..."))?;
+fn run_render_loop(mut state: RendererState, device: DeviceData) -> AnyhowResult<()> {
+    let bar = get_spinner("Initializing window and UI...")?;
     println!(
         "Memory before render loop init work: {} MiB",
         memory_usage()
     );
 
-    let window = Window::new(WindowSettings {
-        title: "SnapViewer Renderer".to_string(),
-        min_size: state.resolution,
-        max_size: Some(state.resolution),
+    LoadProgress::new(&state.pub_socket).stage("opening window", 90);
+    let window = ViewerWindow::new(WindowSettings {
+        title: state.title.clone(),
+        min_size: (320, 240),
+        initial_size: Some(state.resolution),
         ..Default::default()
-    })?;
+    })
+    .context(SnapError::GpuInit)?;
     let context = window.gl();
+    state.profiler.stage("create window");
 
-    info!("Moving mesh to GPU...");
-    let mesh: Gm<Mesh, ColorMaterial> = Gm::new(
-        Mesh::new(&context, &cpu_mesh),
-        ColorMaterial {
-            color: Srgba::WHITE,
-            ..Default::default()
-        },
-    );
-
-    drop(cpu_mesh);
+    let DeviceData {
+        mut db_ptr,
+        mut rl,
+        mut meshes,
+        mut annotations,
+        mut events,
+        mut reserved,
+        mut segment_list,
+    } = device;
+    let mut initial_upload = true;
+    // Other devices' snapshots, by index into `devices.dirs`, once loaded
+    let mut parked: Vec<Option<DeviceData>> = state.devices.dirs.iter().map(|_| None).collect();
+    let mut switch_to: Option<usize> = None;
 
     info!("Setting up window and UI...");
 
     // Window transformation
-    let mut win_trans = WindowTransform::new(state.resolution, state.resolution_ratio);
+    let mut win_trans =
+        WindowTransform::new(state.resolution, state.resolution_ratio.unwrap_or(1.0));
     win_trans.set_zoom_limits(0.75, (rl.trace_geom.max_time as f32 / 100.0).max(2.0));
-    let resolution_ratio = state.resolution_ratio; // Store for use in render loop
+    let resolution_ratio = state.resolution_ratio; // Store for use in render loop, None = auto
 
     // Ticks
     // pick a reasonable font size
     let fontsize_px = 20.0 * (rl.resolution.1 as f32 / 800.0);
-    let tickgen = TickGenerator::jbmono(state.resolution, fontsize_px);
+    let mut tickgen = TickGenerator::jbmono(state.resolution, fontsize_px, rl.theme);
+    let mut tick_cache = TickCache::default();
+
+    let TraceOverlays {
+        mut peak_y,
+        mut peak_xs,
+        mut lanes,
+        mut memory_timeline,
+        mut count_timeline,
+        mut unused,
+        mut segment_bands,
+        mut event_xs,
+    } = TraceOverlays::new(&rl, &events, &reserved, &segment_list);
+
+    // Controls overlay, shown until dismissed on first launch, H brings it back
+    let mut show_overlay = first_run_marker().is_some_and(|marker| !marker.exists());
+
+    // Undo/redo of view and filter, Ctrl+Z / Ctrl+Shift+Z
+    let mut history = History::new(view_state(&rl, &win_trans), UNDO_HISTORY_LIMIT);
+    let mut last_input = Instant::now();
+
+    let diff_timeline = rl.diff.as_ref().map(|diff| {
+        (
+            diff.name.clone(),
+            MemoryTimeline::from_allocations(&diff.trace_geom.raw_allocs),
+        )
+    });
+    let mut cursor_pos: Option<(f32, f32)> = None; // physical pixels
+
+    // Live allocation count curve, toggled with C
+    let mut show_counts = false;
+
+    // Size and top frame on the largest allocations, toggled with T
+    let label_count = state.top_labels.unwrap_or(DEFAULT_TOP_LABELS);
+    let mut show_labels = state.top_labels.is_some();
+
+    // Time cursor placed with `--at`
+    let mut at_cursor: Option<u64> = None;
+
+    // Everything but the allocation under the cursor faded, toggled with V
+    let mut hover_dim = false;
+    let mut hovered: Option<usize> = None;
+
+    // Reserved-but-unused memory, toggled with F
+    let mut show_fragmentation = false;
+    let mut fragmentation_mesh = None;
+
+    // Reserved segments as bands, toggled with E
+    let mut show_segments = state.segments;
+    let mut segments_mesh = None;
+
+    // Allocations left out by `--max-allocs`, as bands
+    let mut others_mesh = None;
+
+    // Dim all but the largest visible allocations, toggled with M
+    let mut spotlight_count = SPOTLIGHT_DEFAULT_COUNT;
+
+    // Allocations grouped by callstack, read on first use: tinted per group with K, one group
+    // highlighted (through the spotlight) with `--group`, by the allocation it was asked for
+    let mut groups: Option<CallstackGroups> = None;
+    let mut highlighted_group: Option<usize> = None;
+
+    // Grid lines at tick positions, toggled with G
+    let mut show_grid = false;
 
     // FPS timer
     let mut timer = FpsTimer::new();
@@ -186,34 +1069,272 @@ fn run_render_loop(
     let mut dragging = false;
     let mut drag_start_mouse_pos: (f32, f32) = (0.0, 0.0); // physical pixels
     let mut drag_start_center: Vector2<f32> = Vector2::new(0.0, 0.0);
+    let mut last_click: Option<(Instant, (f32, f32))> = None; // physical pixels
+
+    // Corners of the rectangle being dragged with Shift for region stats, world coords
+    let mut region: Option<(Vector2<f32>, Vector2<f32>)> = None;
 
     bar.finish();
 
     println!("Memory at start of render loop: {} MiB", memory_usage());
 
     let RendererState {
-        db_ptr,
+        title: _,
         resolution: _,
         resolution_ratio: _,
+        view,
+        select,
+        mut bookmarks,
+        mut cursor_link,
+        max_fps,
+        heatmap: mut show_heatmap,
+        segments: _,
+        top_labels: _,
+        mut devices,
+        load_options,
+        diff,
+        watch,
         pub_socket,
         rep_socket,
+        mut profiler,
     } = state;
 
+    // `--watch`: the shown device's directory, reloaded when it changes; `--reload` asks for
+    // that directory (or another one) to be reloaded at the end of the frame
+    let mut watcher = watch.then(|| Watcher::new(&devices.dirs[devices.active].1));
+    let mut reload_from: Option<PathBuf> = None;
+
+    // Opened from a link: restore its view and selection
+    if let Some((start, stop)) = view {
+        win_trans.show_x_range(
+            rl.trace_geom.timestamp2xworld(start),
+            rl.trace_geom.timestamp2xworld(stop),
+        );
+    }
+    if let Some(idx) = select {
+        match select_allocation(&mut rl, &context, db_ptr, idx) {
+            Ok(msg) => {
+                let _ = pub_socket.send(msg.as_bytes(), 0);
+            }
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+
+    let mut last_viewport = None;
+    let mut index_announced = false;
+    let mut screencast: Option<Screencast> = None;
+
     window.render_loop(move |frame_input| {
+        let frame_start = Instant::now();
         let resolution_ratio = resolution_ratio; // Force move into closure
 
+        // Follow window resizes and moves between monitors with different DPI
+        win_trans.set_resolution_ratio(
+            resolution_ratio.unwrap_or(frame_input.device_pixel_ratio as f64),
+        );
+        win_trans.set_viewport(frame_input.viewport);
+        tickgen.resize(win_trans.screen_size());
+
+        // Redraw only on input, IPC, resizes, the linked cursor moving or the selection fading
+        let resized = last_viewport != Some(frame_input.viewport);
+        let mut dirty = frame_input.first_frame || !frame_input.events.is_empty() || resized;
+        last_viewport = Some(frame_input.viewport);
+        if resized {
+            let ratio = frame_input.device_pixel_ratio;
+            let size = format!(
+                "{} {}",
+                (frame_input.viewport.width as f32 / ratio).round() as u32,
+                (frame_input.viewport.height as f32 / ratio).round() as u32
+            );
+            let sent =
+                pub_socket.send_multipart([WINDOW_SIZE_EVENT.as_bytes(), size.as_bytes()], 0);
+            if let Err(e) = sent {
+                log::warn!("Could not send window size: {}", e);
+            }
+        }
+
         // Handle incoming ZeroMQ messages (non-blocking)
         if let Ok(bytes) = rep_socket.recv_bytes(zmq::DONTWAIT) {
+            last_input = Instant::now();
+            dirty = true;
             let command = String::from_utf8_lossy(&bytes);
-            let response = match handle_sql_command(db_ptr, &command) {
-                Ok(result) => result,
-                Err(e) => format!("(!) SQL execution Error\n{}", e),
+            let response = if let Some(filter_cmd) = command.trim().strip_prefix("--filter") {
+                match apply_filter(&mut rl, &mut meshes, filter_cmd) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Filter Error\n{}", e),
+                }
+            } else if let Some(path) = command.trim().strip_prefix("--script") {
+                let output = std::fs::read_to_string(path.trim())
+                    .map_err(anyhow::Error::from)
+                    .and_then(|source| {
+                        run_script(&source, Arc::clone(&rl.trace_geom.raw_allocs), unsafe {
+                            &*(db_ptr as *const AllocationDatabase)
+                        })
+                    });
+                match output {
+                    Ok(output) => {
+                        let mut result = output.printed;
+                        for action in output.actions {
+                            let applied = match &action {
+                                ScriptAction::Select(idx) => {
+                                    select_allocation(&mut rl, &context, db_ptr, *idx).map(|msg| {
+                                        let _ = pub_socket.send(msg.as_bytes(), 0);
+                                    })
+                                }
+                                ScriptAction::View(t0, t1) => handle_view_command(
+                                    &rl,
+                                    &mut win_trans,
+                                    &format!("{}..{}", t0, t1),
+                                )
+                                .map(drop),
+                                ScriptAction::Filter(cmd) => {
+                                    apply_filter(&mut rl, &mut meshes, cmd).map(drop)
+                                }
+                            };
+                            if let Err(e) = applied {
+                                result += &format!("(!) {:?} failed: {}\n", action, e);
+                            }
+                        }
+                        result
+                    }
+                    Err(e) => format!("(!) Script Error\n{}", e),
+                }
+            } else if let Some(annotate_cmd) = command.trim().strip_prefix("--annotate") {
+                match annotations.apply_command(annotate_cmd) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Annotate Error\n{}", e),
+                }
+            } else if let Some(bookmark_cmd) = command.trim().strip_prefix("--bookmark") {
+                match bookmarks.apply_command(bookmark_cmd, &mut win_trans) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Bookmark Error\n{}", e),
+                }
+            } else if command.trim() == "--instrument" {
+                match handle_instrument_command(&rl, db_ptr) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Instrument Error\n{}", e),
+                }
+            } else if let Some(path) = command.trim().strip_prefix("--export-svg") {
+                match handle_export_svg_command(&rl, &win_trans, path) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Export Error\n{}", e),
+                }
+            } else if let Some(path) = command.trim().strip_prefix("--export-bundle") {
+                match handle_export_bundle_command(db_ptr, &devices, path) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Export Error\n{}", e),
+                }
+            } else if let Some(dir) = command.trim().strip_prefix("--reload") {
+                match handle_reload_command(&devices, &mut reload_from, dir) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Reload Error\n{}", e),
+                }
+            } else if let Some(record_cmd) = command.trim().strip_prefix("--record") {
+                let size = (frame_input.viewport.width, frame_input.viewport.height);
+                match handle_record_command(&mut screencast, record_cmd, size) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Record Error\n{}", e),
+                }
+            } else if let Some(name) = command.trim().strip_prefix("--theme") {
+                match name.trim() {
+                    "" => format!(
+                        "Theme: {}, expected light, dark or cute",
+                        rl.theme.name().unwrap_or("custom")
+                    ),
+                    name => match name.parse() {
+                        Ok(theme) => {
+                            apply_theme(&mut rl, &mut tickgen, &mut tick_cache, theme);
+                            format!("Theme: {}", name)
+                        }
+                        Err(e) => format!("(!) Theme Error\n{}", e),
+                    },
+                }
+            } else if let Some(group_cmd) = command.trim().strip_prefix("--group") {
+                let result = handle_group_command(
+                    &mut rl,
+                    db_ptr,
+                    &mut groups,
+                    &mut highlighted_group,
+                    group_cmd,
+                );
+                match result {
+                    Ok(result) => {
+                        rebuild_meshes(&mut rl, &mut meshes);
+                        result
+                    }
+                    Err(e) => format!("(!) Group Error\n{}", e),
+                }
+            } else if let Some(at_cmd) = command.trim().strip_prefix("--at") {
+                match handle_at_command(&rl, &mut win_trans, db_ptr, &mut at_cursor, at_cmd) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) At Error\n{}", e),
+                }
+            } else if command.trim() == "--diff" {
+                rl.diff_report()
+            } else if let Some(device_cmd) = command.trim().strip_prefix("--device") {
+                match handle_device_command(&devices, &mut switch_to, device_cmd) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Device Error\n{}", e),
+                }
+            } else if let Some(goto_cmd) = command.trim().strip_prefix("--goto") {
+                match handle_goto_command(&rl, &mut win_trans, goto_cmd) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) Goto Error\n{}", e),
+                }
+            } else if let Some(view_cmd) = command.trim().strip_prefix("--view") {
+                match handle_view_command(&rl, &mut win_trans, view_cmd) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) View Error\n{}", e),
+                }
+            } else if let Some(idx) = command.trim().strip_prefix("--select") {
+                let selected = idx
+                    .trim()
+                    .parse()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|idx| select_allocation(&mut rl, &context, db_ptr, idx));
+                match selected {
+                    Ok(msg) => {
+                        let _ = pub_socket.send(msg.as_bytes(), 0);
+                        msg
+                    }
+                    Err(e) => format!("(!) Select Error\n{}", e),
+                }
+            } else {
+                match handle_sql_command(db_ptr, &command) {
+                    Ok(result) => result,
+                    Err(e) => format!("(!) SQL execution Error\n{}", e),
+                }
             };
             let _ = rep_socket.send(response.as_bytes(), 0);
         }
 
         // Handle events
+        if !frame_input.events.is_empty() {
+            last_input = Instant::now();
+        }
+        // the user takes over from a running camera move
+        if frame_input.events.iter().any(|e| {
+            matches!(
+                e,
+                Event::MousePress { .. } | Event::MouseWheel { .. } | Event::KeyPress { .. }
+            )
+        }) {
+            win_trans.cancel_animation();
+        }
         for event in frame_input.events.iter() {
+            if let Event::MouseMotion { position, .. } = event {
+                cursor_pos = Some((position.x, position.y));
+                if hover_dim {
+                    let world_pos = win_trans.screen2world_physical((*position).into());
+                    hovered = rl.trace_geom.find_by_pos(world_pos, &rl.filter);
+                }
+            }
+            if show_overlay && matches!(event, Event::KeyPress { .. } | Event::MousePress { .. }) {
+                // the dismissing input is not acted on
+                show_overlay = false;
+                mark_first_run_done();
+                continue;
+            }
             match *event {
                 Event::MousePress {
                     button,
@@ -223,31 +1344,62 @@ fn run_render_loop(
                 } => {
                     match button {
                         MouseButton::Left => {
-                            if modifiers.ctrl {
+                            let cursor_world_pos = win_trans.screen2world_physical(position.into());
+                            let double_click = last_click.take().is_some_and(|(time, pos)| {
+                                time.elapsed() <= Duration::from_millis(DOUBLE_CLICK_MS)
+                                    && (position.x - pos.0).abs() <= DOUBLE_CLICK_SLOP_PX
+                                    && (position.y - pos.1).abs() <= DOUBLE_CLICK_SLOP_PX
+                            });
+                            if !double_click {
+                                last_click = Some((Instant::now(), (position.x, position.y)));
+                            }
+                            let on_event_strip = !events.is_empty()
+                                && cursor_world_pos.y - win_trans.ybot_world()
+                                    <= EVENT_STRIP_HEIGHT_PX * win_trans.scale();
+                            if modifiers.ctrl && on_event_strip {
+                                // Show the allocator event under the cursor
+                                let tolerance = rl.trace_geom.xworld2timestamp(
+                                    cursor_world_pos.x + EVENT_GLYPH_SIZE_PX * win_trans.scale(),
+                                ) - rl
+                                    .trace_geom
+                                    .xworld2timestamp(cursor_world_pos.x);
+                                let timestep = rl.trace_geom.xworld2timestamp(cursor_world_pos.x);
+                                if let Some(i) = nearest_event(&events, timestep, tolerance) {
+                                    let _ = pub_socket.send(events[i].to_string().as_bytes(), 0);
+                                }
+                            } else if modifiers.ctrl {
                                 // Show allocation detail
                                 info!("Left click window pos: ({}, {})", position.x, position.y);
-                                let cursor_world_pos =
-                                    win_trans.screen2world_physical(position.into());
                                 info!(
                                     "Left click world pos: ({}, {})",
                                     cursor_world_pos.x, cursor_world_pos.y
                                 );
 
-                                let alloc_idx = rl.trace_geom.find_by_pos(cursor_world_pos);
+                                let alloc_idx =
+                                    rl.trace_geom.find_by_pos(cursor_world_pos, &rl.filter);
                                 info!("Find by pos results: alloc id: {:?}", alloc_idx);
 
-                                if let Some(idx) = alloc_idx {
-                                    let msg = format!(
-                                        "Allocation #{}\n{}",
-                                        idx,
-                                        rl.allocation_info(db_ptr, idx)
-                                    );
-
+                                if let Some(idx) = alloc_idx
+                                    && let Ok(msg) =
+                                        select_allocation(&mut rl, &context, db_ptr, idx)
+                                {
                                     // Send to UI via ZeroMQ
                                     let _ = pub_socket.send(msg.as_bytes(), 0);
-
-                                    rl.show_alloc(&context, idx);
                                 }
+                            } else if modifiers.shift {
+                                region = Some((cursor_world_pos, cursor_world_pos));
+                            } else if double_click
+                                && let Some(idx) =
+                                    rl.trace_geom.find_by_pos(cursor_world_pos, &rl.filter)
+                            {
+                                // Frame the allocation's time span and memory band
+                                let (p0, p1) = allocation_rect(&rl, idx);
+                                win_trans.animate_to_rect(
+                                    p0,
+                                    p1,
+                                    DOUBLE_CLICK_FILL,
+                                    Duration::from_millis(ZOOM_ANIMATION_MS),
+                                );
                             } else {
                                 // Start dragging - record start positions
                                 dragging = true;
@@ -264,7 +1416,9 @@ fn run_render_loop(
                             );
 
                             let indent = "\n    ";
-                            let msg = format!(
+                            let timestamp =
+                                rl.trace_geom.xworld2timestamp(cursor_world_pos.x).max(0) as u64;
+                            let mut msg = format!(
                                 "Cursor is at :{}memory: {}{}timestamp: {}",
                                 indent,
                                 format_bytes_precision(
@@ -272,8 +1426,15 @@ fn run_render_loop(
                                     3
                                 ),
                                 indent,
-                                rl.trace_geom.xworld2timestamp(cursor_world_pos.x),
+                                timestamp,
                             );
+                            if rl.clock.is_some() {
+                                msg.push_str(&format!(
+                                    "{}time: {}",
+                                    indent,
+                                    rl.format_time(timestamp)
+                                ));
+                            }
 
                             // Send to UI via ZeroMQ
                             let _ = pub_socket.send(msg.as_bytes(), 0);
@@ -282,73 +1443,811 @@ fn run_render_loop(
                     }
                 }
                 Event::MouseWheel {
-                    delta, position, ..
+                    delta,
+                    position,
+                    modifiers,
+                    ..
                 } => {
-                    if delta.1 > 0.0 {
+                    // some platforms turn Shift+wheel into a horizontal delta
+                    let amount = if delta.1 != 0.0 { delta.1 } else { delta.0 };
+                    if modifiers.shift {
+                        win_trans.translate_x_by(-amount * WHEEL_PAN_SPEED);
+                    } else if modifiers.ctrl {
+                        win_trans.translate_y_by(amount * WHEEL_PAN_SPEED);
+                    } else if delta.1 > 0.0 {
                         win_trans.zoom_in(position.into());
                     } else if delta.1 < 0.0 {
                         win_trans.zoom_out(position.into());
                     }
                 }
-                Event::KeyPress { kind, .. } => match kind {
+                Event::KeyPress {
+                    kind, modifiers, ..
+                } => match kind {
+                    three_d::Key::H => show_overlay = true,
+                    three_d::Key::Tab if devices.dirs.len() > 1 => switch_to = Some(devices.next()),
+                    three_d::Key::Escape if !meshes.is_done() => {
+                        meshes.cancel();
+                        let _ = pub_socket.send(
+                            "Mesh upload cancelled, showing decimated geometry".as_bytes(),
+                            0,
+                        );
+                    }
+                    three_d::Key::Z if modifiers.ctrl => {
+                        // an unsettled change is undone first
+                        history.record(view_state(&rl, &win_trans));
+                        let state = if modifiers.shift {
+                            history.redo()
+                        } else {
+                            history.undo()
+                        };
+                        if let Some(state) = state.cloned() {
+                            restore_view_state(state, &mut rl, &mut win_trans, &mut meshes);
+                        }
+                    }
                     three_d::Key::W => win_trans.translate(TranslateDir::Up),
                     three_d::Key::A => win_trans.translate(TranslateDir::Left),
                     three_d::Key::S => win_trans.translate(TranslateDir::Down),
                     three_d::Key::D => win_trans.translate(TranslateDir::Right),
+                    three_d::Key::G => show_grid = !show_grid,
+                    three_d::Key::C => show_counts = !show_counts,
+                    three_d::Key::B => show_heatmap = !show_heatmap,
+                    three_d::Key::T => show_labels = !show_labels,
+                    three_d::Key::V => {
+                        hover_dim = !hover_dim;
+                        hovered = None;
+                    }
+                    three_d::Key::N => {
+                        let theme = rl.theme.next();
+                        apply_theme(&mut rl, &mut tickgen, &mut tick_cache, theme);
+                    }
+                    three_d::Key::R => {
+                        let size = (frame_input.viewport.width, frame_input.viewport.height);
+                        let msg = match handle_record_command(&mut screencast, "", size) {
+                            Ok(msg) => msg,
+                            Err(e) => format!("(!) Record Error\n{}", e),
+                        };
+                        let _ = pub_socket.send(msg.as_bytes(), 0);
+                    }
+                    three_d::Key::F => {
+                        show_fragmentation = !show_fragmentation;
+                        if show_fragmentation
+                            && fragmentation_mesh.is_none()
+                            && rl.trace_geom.lanes.is_empty()
+                        {
+                            fragmentation_mesh = rl.fragmentation_mesh(&context, &unused);
+                        }
+                    }
+                    three_d::Key::E => show_segments = !show_segments,
+                    three_d::Key::L => {
+                        rl.set_log_y(!rl.trace_geom.y_axis.log);
+                        rebuild_meshes(&mut rl, &mut meshes);
+                        lanes = lane_bounds(&rl.trace_geom);
+                        if fragmentation_mesh.is_some() {
+                            fragmentation_mesh = rl.fragmentation_mesh(&context, &unused);
+                        }
+                        if segments_mesh.is_some() {
+                            segments_mesh = rl.segments_mesh(&context, &segment_bands);
+                        }
+                        others_mesh = None;
+                        if let Some(idx) = rl.selected {
+                            rl.show_alloc(&context, idx);
+                        }
+                    }
+                    three_d::Key::K => {
+                        let msg = match toggle_grouping(&mut rl, &mut groups, db_ptr) {
+                            Ok(msg) => {
+                                rebuild_meshes(&mut rl, &mut meshes);
+                                msg
+                            }
+                            Err(e) => format!("(!) Group Error\n{}", e),
+                        };
+                        let _ = pub_socket.send(msg.as_bytes(), 0);
+                    }
+                    three_d::Key::M => {
+                        highlighted_group = None;
+                        if rl.spotlight.is_some() {
+                            rl.spotlight = None;
+                            rebuild_meshes(&mut rl, &mut meshes);
+                        } else {
+                            update_spotlight(&mut rl, &win_trans, &mut meshes, spotlight_count);
+                        }
+                    }
                     key => {
                         info!("{:?},", key);
                     }
                 },
-                Event::MouseMotion { position, .. } => {
-                    if dragging {
-                        let ratio = resolution_ratio as f32;
-                        let scale = win_trans.scale();
-                        // Calculate mouse displacement in logical pixels, then scale to world coords
-                        let dx = (position.x - drag_start_mouse_pos.0) / ratio * scale;
-                        let dy = (position.y - drag_start_mouse_pos.1) / ratio * scale;
-                        // New center = start center - displacement (dragging left moves view right)
-                        win_trans.center.x = drag_start_center.x - dx;
-                        win_trans.center.y = drag_start_center.y - dy;
-                        win_trans.enforce_boundaries();
+                Event::Text(ref text) if text == "[" || text == "]" => {
+                    let idx = match text.as_str() {
+                        "[" => rl.selection_back(),
+                        _ => rl.selection_forward(),
+                    };
+                    // re-selected without becoming a new history entry
+                    if let Some(idx) = idx {
+                        rl.show_alloc(&context, idx);
+                        let msg = selection_message(&rl, db_ptr, idx);
+                        let _ = pub_socket.send(msg.as_bytes(), 0);
+                    }
+                }
+                Event::Text(ref text) if rl.spotlight.is_some() && highlighted_group.is_none() => {
+                    let count = match text.as_str() {
+                        "+" | "=" => spotlight_count + 1,
+                        "-" => spotlight_count.saturating_sub(1).max(1),
+                        _ => spotlight_count,
+                    };
+                    if count != spotlight_count {
+                        spotlight_count = count;
+                        update_spotlight(&mut rl, &win_trans, &mut meshes, spotlight_count);
                     }
                 }
-                Event::MouseRelease { button, .. } => {
-                    if button == MouseButton::Left {
-                        dragging = false;
+                Event::MouseMotion { position, .. } if dragging => {
+                    let ratio = win_trans.resolution_ratio() as f32;
+                    let scale = win_trans.scale();
+                    // Calculate mouse displacement in logical pixels, then scale to world coords
+                    let dx = (position.x - drag_start_mouse_pos.0) / ratio * scale;
+                    let dy = (position.y - drag_start_mouse_pos.1) / ratio * scale;
+                    // New center = start center - displacement (dragging left moves view right)
+                    win_trans.center.x = drag_start_center.x - dx;
+                    win_trans.center.y = drag_start_center.y - dy;
+                    win_trans.enforce_boundaries();
+                }
+                Event::MouseMotion { position, .. } if region.is_some() => {
+                    if let Some((_, corner)) = &mut region {
+                        *corner = win_trans.screen2world_physical(position.into());
+                    }
+                }
+                Event::MouseRelease {
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    dragging = false;
+                    if let Some((p0, p1)) = region.take() {
+                        // a Shift-click without a drag selects nothing
+                        let min_px = 2.0 * win_trans.scale();
+                        if (p1.x - p0.x).abs() >= min_px || (p1.y - p0.y).abs() >= min_px {
+                            let sent = match region_stats(&rl, db_ptr, p0, p1) {
+                                Ok(stats) => pub_socket.send_multipart(
+                                    [REGION_STATS_EVENT.as_bytes(), stats.to_json().as_bytes()],
+                                    0,
+                                ),
+                                Err(e) => pub_socket
+                                    .send(format!("(!) Region Error\n{}", e).as_bytes(), 0),
+                            };
+                            if let Err(e) = sent {
+                                log::warn!("Could not send region stats: {}", e);
+                            }
+                        }
                     }
                 }
                 Event::MouseLeave => {
                     dragging = false;
+                    region = None;
+                    cursor_pos = None;
                 }
                 _ => {}
             }
         }
 
+        // Tab or `--device`: show another device, keeping the view and the one shown so far.
+        // `--reload`, or `--watch` seeing the shown one change: load it again, keeping the
+        // visible time range
+        if watcher.as_mut().is_some_and(Watcher::poll) && reload_from.is_none() {
+            reload_from = Some(devices.dirs[devices.active].1.clone());
+        }
+        let incoming = match switch_to.take() {
+            Some(target) => Some((target, devices.dirs[target].1.clone(), false)),
+            None => reload_from.take().map(|dir| (devices.active, dir, true)),
+        };
+        if let Some((target, dir, reload)) = incoming {
+            let id = devices.dirs[target].0;
+            let loaded = match parked[target].take() {
+                Some(device) => Ok(device),
+                None => {
+                    let msg = match reload {
+                        true => format!("Reloading {}", dir.display()),
+                        false => format!("Loading device {} from {}", id, dir.display()),
+                    };
+                    let _ = pub_socket.send(msg.as_bytes(), 0);
+                    let diff = diff.as_deref().filter(|_| reload && rl.diff.is_some());
+                    load_device(
+                        &dir,
+                        &load_options,
+                        diff,
+                        &mut Profiler::disabled(),
+                        &LoadProgress::new(&pub_socket),
+                    )
+                }
+            };
+            let reloaded = reload && loaded.is_ok();
+            let msg = match loaded {
+                Ok(mut device) => {
+                    let theme = rl.theme;
+                    let geom = &rl.trace_geom;
+                    let visible = (
+                        geom.xworld2timestamp(win_trans.xleft_world()).max(0) as u64,
+                        geom.xworld2timestamp(win_trans.xright_world()).max(0) as u64,
+                    );
+                    std::mem::swap(&mut db_ptr, &mut device.db_ptr);
+                    std::mem::swap(&mut rl, &mut device.rl);
+                    std::mem::swap(&mut meshes, &mut device.meshes);
+                    std::mem::swap(&mut annotations, &mut device.annotations);
+                    std::mem::swap(&mut events, &mut device.events);
+                    std::mem::swap(&mut reserved, &mut device.reserved);
+                    std::mem::swap(&mut segment_list, &mut device.segment_list);
+                    match reload {
+                        true => {
+                            // nothing else refers to the replaced snapshot's database
+                            drop(unsafe {
+                                Box::from_raw(device.db_ptr as *mut AllocationDatabase)
+                            });
+                            devices.dirs[target].1 = dir.clone();
+                        }
+                        false => {
+                            parked[devices.active] = Some(device);
+                            devices.active = target;
+                        }
+                    }
+                    if watcher.is_some() {
+                        watcher = Some(Watcher::new(&dir));
+                    }
+
+                    TraceOverlays {
+                        peak_y,
+                        peak_xs,
+                        lanes,
+                        memory_timeline,
+                        count_timeline,
+                        unused,
+                        segment_bands,
+                        event_xs,
+                    } = TraceOverlays::new(&rl, &events, &reserved, &segment_list);
+                    apply_theme(&mut rl, &mut tickgen, &mut tick_cache, theme);
+                    win_trans
+                        .set_zoom_limits(0.75, (rl.trace_geom.max_time as f32 / 100.0).max(2.0));
+                    win_trans.enforce_boundaries();
+                    if reload {
+                        win_trans.show_x_range(
+                            rl.trace_geom.timestamp2xworld(visible.0),
+                            rl.trace_geom.timestamp2xworld(visible.1),
+                        );
+                    }
+                    history = History::new(view_state(&rl, &win_trans), UNDO_HISTORY_LIMIT);
+                    fragmentation_mesh = None;
+                    segments_mesh = None;
+                    others_mesh = None;
+                    groups = None;
+                    highlighted_group = None;
+                    hovered = None;
+                    at_cursor = None;
+                    match reload {
+                        true => format!(
+                            "Reloaded {}: {} allocations",
+                            dir.display(),
+                            rl.trace_geom.raw_allocs.len()
+                        ),
+                        false => format!("Showing device {} ({}), {}", id, dir.display(), devices),
+                    }
+                }
+                Err(e) if reload => format!("(!) Reload Error\n{:?}", e),
+                Err(e) => format!("(!) Device Error\n{:?}", e),
+            };
+            let _ = pub_socket.send(msg.as_bytes(), 0);
+            if reloaded {
+                let path = dir.to_string_lossy();
+                let sent =
+                    pub_socket.send_multipart([RELOADED_EVENT.as_bytes(), path.as_bytes()], 0);
+                if let Err(e) = sent {
+                    log::warn!("Could not send reload event: {}", e);
+                }
+            }
+            dirty = true;
+        }
+
+        if let Some(link) = &mut cursor_link {
+            dirty |= link.receive();
+        }
+        if !meshes.is_done() {
+            meshes.upload_step(&context, Duration::from_millis(UPLOAD_BUDGET_MS));
+            dirty = true;
+            let (uploaded, total) = meshes.progress();
+            if meshes.is_done() {
+                LoadProgress::new(&pub_socket).stage("ready", 100);
+            } else {
+                let percent = 90 + 10 * uploaded / total.max(1);
+                LoadProgress::new(&pub_socket).stage("uploading meshes", percent as u8);
+            }
+            // only worth reporting for uploads that take a while
+            if meshes.elapsed() >= Duration::from_secs(1) {
+                let msg = if meshes.is_done() {
+                    format!("Mesh upload finished ({} chunks)", total)
+                } else {
+                    format!(
+                        "Moving mesh to GPU: {}/{} chunks (press Esc in the renderer to cancel)",
+                        uploaded, total
+                    )
+                };
+                let _ = pub_socket.send(msg.as_bytes(), 0);
+            }
+            if meshes.is_done() && initial_upload {
+                initial_upload = false;
+                profiler.stage("GPU upload");
+            }
+        }
+        dirty |= rl.selected_mesh.is_some() && rl.decaying_color.is_fading();
+        rl.decaying_color.tick(frame_input.elapsed_time / 1000.0);
+        if last_input.elapsed() >= Duration::from_millis(UNDO_SETTLE_MS) {
+            history.record(view_state(&rl, &win_trans));
+        }
+        // recordings get frames at a fixed rate, changed or not
+        dirty |= screencast.as_ref().is_some_and(Screencast::due);
+        dirty |= win_trans.step_animation();
+        if !index_announced && rl.trace_geom.index_ready() {
+            index_announced = true;
+            let _ = pub_socket.send("Spatial index ready".as_bytes(), 0);
+        }
+        if !dirty {
+            // bring the LOD meshes online while nothing else happens
+            if meshes.is_done()
+                && last_input.elapsed() >= Duration::from_millis(WARM_UP_IDLE_MS)
+                && rl.warm_up_lod(&context)
+            {
+                if rl.lod_ready() {
+                    let _ = pub_socket.send("LOD meshes ready".as_bytes(), 0);
+                }
+                return FrameOutput {
+                    swap_buffers: false,
+                    ..Default::default()
+                };
+            }
+            std::thread::sleep(Duration::from_millis(IDLE_POLL_MS));
+            return FrameOutput {
+                swap_buffers: false,
+                ..Default::default()
+            };
+        }
+
         let cam = win_trans.camera(frame_input.viewport);
 
         let high_bytes = rl.trace_geom.yworld2memory(win_trans.ytop_world());
         let low_bytes = rl.trace_geom.yworld2memory(win_trans.ybot_world());
-        let ticks = tickgen.generate_memory_ticks(
-            low_bytes,
-            high_bytes,
+        let memory_ticks = memory_ticks(
+            &rl.trace_geom,
+            (win_trans.ybot_world(), win_trans.ytop_world()),
+        );
+        tick_cache.update(
+            &tickgen,
+            &memory_ticks,
+            win_trans.scale(),
+            win_trans.center,
+            &context,
+        );
+        let grid = show_grid
+            .then(|| {
+                tickgen.generate_memory_grid(
+                    &memory_ticks,
+                    win_trans.scale(),
+                    (win_trans.xleft_world(), win_trans.xright_world()),
+                    (win_trans.ybot_world(), win_trans.ytop_world()),
+                    &context,
+                )
+            })
+            .flatten();
+        let peak_markers = tickgen.generate_peak_markers(
+            rl.trace_geom.max_size as i64,
+            peak_y,
+            &peak_xs,
+            win_trans.scale(),
+            win_trans.center,
+            (win_trans.xleft_world(), win_trans.xright_world()),
+            (win_trans.ybot_world(), win_trans.ytop_world()),
+            &context,
+        );
+
+        let annotation_xs: Vec<(f32, &str)> = annotations
+            .items
+            .iter()
+            .map(|a| {
+                (
+                    rl.trace_geom.timestamp2xworld(a.timestamp),
+                    a.label.as_str(),
+                )
+            })
+            .collect();
+        let annotation_markers = tickgen.generate_annotation_markers(
+            &annotation_xs,
+            win_trans.scale(),
+            win_trans.center,
+            (win_trans.xleft_world(), win_trans.xright_world()),
+            (win_trans.ybot_world(), win_trans.ytop_world()),
+            &context,
+        );
+
+        let allocation_labels = if show_labels && !show_heatmap {
+            let x_range = (win_trans.xleft_world(), win_trans.xright_world());
+            let spots: Vec<_> = rl
+                .top_labels(db_ptr, label_count)
+                .iter()
+                .filter_map(|(idx, label)| Some((rl.label_spot(*idx, x_range)?, label.as_str())))
+                .collect();
+            tickgen.generate_allocation_labels(&spots, win_trans.scale(), &context)
+        } else {
+            Vec::new()
+        };
+
+        let event_strip = if events.is_empty() {
+            Vec::new()
+        } else {
+            tickgen.generate_event_strip(
+                &event_xs,
+                win_trans.scale(),
+                (win_trans.xleft_world(), win_trans.xright_world()),
+                win_trans.ybot_world(),
+                &context,
+            )
+        };
+
+        let lane_separators = tickgen.generate_lane_separators(
+            &lanes,
+            win_trans.scale(),
+            win_trans.center,
+            (win_trans.xleft_world(), win_trans.xright_world()),
+            (win_trans.ybot_world(), win_trans.ytop_world()),
+            &context,
+        );
+
+        // Pinned readout of absolute memory, useful when zoomed in deep
+        let visible_max = memory_timeline.max_in(
+            rl.trace_geom
+                .xworld2timestamp(win_trans.xleft_world())
+                .max(0) as u64,
+            rl.trace_geom
+                .xworld2timestamp(win_trans.xright_world())
+                .max(0) as u64,
+        );
+        let mut readout = vec![format!(
+            "visible max: {}",
+            format_bytes_precision(visible_max as i64, 3)
+        )];
+        if let Some(others) = &rl.others {
+            if others_mesh.is_none() {
+                others_mesh = rl.others_mesh(&context);
+            }
+            readout.push(format!(
+                "{} more allocations in grey (--max-allocs)",
+                others.count
+            ));
+        }
+        if !meshes.is_done() {
+            let (uploaded, total) = meshes.progress();
+            readout.push(format!(
+                "uploading mesh {}/{} (Esc cancels)",
+                uploaded, total
+            ));
+        }
+        if rl.trace_geom.y_axis.log {
+            readout.push("log memory axis (L)".to_string());
+        }
+        if show_heatmap {
+            readout.push("density heatmap (B)".to_string());
+        }
+        if hover_dim {
+            readout.push("hover-dim: hover an allocation to follow it (V)".to_string());
+        }
+        if show_fragmentation {
+            let status = if !rl.trace_geom.lanes.is_empty() {
+                "not shown with --lanes"
+            } else if reserved.is_empty() {
+                "no segment data, re-convert the snapshot"
+            } else {
+                "reserved but unused in grey"
+            };
+            readout.push(format!("fragmentation: {} (F)", status));
+        }
+        if show_segments
+            && segments_mesh.is_none()
+            && rl.trace_geom.lanes.is_empty()
+            && !segment_bands.is_empty()
+        {
+            segments_mesh = rl.segments_mesh(&context, &segment_bands);
+        }
+        if show_segments {
+            let status = if !rl.trace_geom.lanes.is_empty() {
+                "not shown with --lanes"
+            } else if segment_list.is_empty() {
+                "no segment data, re-convert the snapshot"
+            } else {
+                "reserved segments as bands"
+            };
+            readout.push(format!("segments: {} (E)", status));
+        }
+
+        // Allocation count curve over the visible part of the trace, one step per 2 pixels
+        let mut count_curve = None;
+        if show_counts {
+            let x_range = (
+                win_trans.xleft_world().max(0.0),
+                win_trans.xright_world().min(
+                    rl.trace_geom
+                        .timestamp2xworld(rl.trace_geom.max_time as u64),
+                ),
+            );
+            if x_range.0 < x_range.1 {
+                let columns = (win_trans.screen_size().0 as f32 / 2.0 * (x_range.1 - x_range.0)
+                    / (win_trans.xright_world() - win_trans.xleft_world()))
+                .ceil()
+                .max(1.0) as usize;
+                let counts = count_timeline.column_maxima(
+                    rl.trace_geom.xworld2timestamp(x_range.0).max(0) as u64,
+                    rl.trace_geom.xworld2timestamp(x_range.1).max(0) as u64,
+                    columns,
+                );
+                readout.push(format!(
+                    "live allocations: up to {} in view (C)",
+                    counts.iter().max().copied().unwrap_or(0)
+                ));
+                count_curve = tickgen.generate_count_curve(
+                    &counts,
+                    win_trans.scale(),
+                    x_range,
+                    (win_trans.ybot_world(), win_trans.ytop_world()),
+                    &context,
+                );
+            }
+        }
+        if rl.is_grouped() {
+            readout.push("grouped by callstack (K)".to_string());
+        }
+        if let (Some(idx), Some(groups)) = (highlighted_group, &groups) {
+            readout.push(format!(
+                "group of #{}: {} allocations (--group clear)",
+                idx,
+                groups.group_size(idx)
+            ));
+        } else if rl.spotlight.is_some() {
+            readout.push(format!("spotlight: largest {} (+/-)", spotlight_count));
+        }
+        let cursor_timestamp = cursor_pos.map(|pos| {
+            rl.trace_geom
+                .xworld2timestamp(win_trans.screen2world_physical(pos).x)
+                .max(0) as u64
+        });
+        let mut cursor_readout = Vec::new();
+        if let Some(timestamp) = cursor_timestamp {
+            cursor_readout.push(format!(
+                "live @ {}: {}",
+                rl.format_time(timestamp),
+                format_bytes_precision(memory_timeline.live_at(timestamp) as i64, 3)
+            ));
+            if fragmentation_mesh.is_some() && show_fragmentation {
+                cursor_readout.push(format!(
+                    "unused @ {}: {}",
+                    rl.format_time(timestamp),
+                    format_bytes_precision(unused_at(&unused, timestamp) as i64, 3)
+                ));
+            }
+            if segments_mesh.is_some() && show_segments {
+                cursor_readout.push(format!(
+                    "reserved @ {}: {}",
+                    rl.format_time(timestamp),
+                    format_bytes_precision(reserved_at(&segment_bands, timestamp) as i64, 3)
+                ));
+            }
+            if let Some((name, timeline)) = &diff_timeline {
+                cursor_readout.push(format!(
+                    "{} @ {}: {}",
+                    name,
+                    rl.format_time(timestamp),
+                    format_bytes_precision(timeline.live_at(timestamp) as i64, 3)
+                ));
+            }
+        }
+        let mut at_line = None;
+        if let Some(timestamp) = at_cursor {
+            cursor_readout.push(format!(
+                "at {}: {}",
+                rl.format_time(timestamp),
+                format_bytes_precision(memory_timeline.live_at(timestamp) as i64, 3)
+            ));
+            at_line = tickgen.generate_cursor_line(
+                rl.trace_geom.timestamp2xworld(timestamp),
+                AT_CURSOR_COLOR,
+                win_trans.scale(),
+                (win_trans.xleft_world(), win_trans.xright_world()),
+                (win_trans.ybot_world(), win_trans.ytop_world()),
+                &context,
+            );
+        }
+        let region_box = match region {
+            Some((p0, p1)) => tickgen.generate_region_box(p0, p1, win_trans.scale(), &context),
+            None => Vec::new(),
+        };
+        let mut linked_cursor = None;
+        if let Some(link) = &mut cursor_link {
+            link.send(cursor_timestamp);
+            if let Some(timestamp) = link.linked {
+                cursor_readout.push(format!(
+                    "linked @ {}: {}",
+                    rl.format_time(timestamp),
+                    format_bytes_precision(memory_timeline.live_at(timestamp) as i64, 3)
+                ));
+                linked_cursor = tickgen.generate_cursor_line(
+                    rl.trace_geom.timestamp2xworld(timestamp),
+                    LINKED_CURSOR_COLOR,
+                    win_trans.scale(),
+                    (win_trans.xleft_world(), win_trans.xright_world()),
+                    (win_trans.ybot_world(), win_trans.ytop_world()),
+                    &context,
+                );
+            }
+        }
+        readout.splice(0..0, cursor_readout);
+        let readout: Vec<_> = readout
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                tickgen.generate_text_mesh_at(
+                    line,
+                    0.72,
+                    0.97 - 0.04 * i as f32,
+                    win_trans.scale(),
+                    win_trans.center,
+                    &context,
+                )
+            })
+            .collect();
+
+        // Scale of what is on screen, above the event strip
+        let hud = tickgen.generate_text_mesh_at(
+            &format!(
+                "zoom {:.2}x | time {}..{} | memory {}..{}",
+                win_trans.zoom,
+                rl.format_time(
+                    rl.trace_geom
+                        .xworld2timestamp(win_trans.xleft_world())
+                        .max(0) as u64
+                ),
+                rl.format_time(
+                    rl.trace_geom
+                        .xworld2timestamp(win_trans.xright_world())
+                        .max(0) as u64
+                ),
+                format_bytes_precision(low_bytes.max(0), 3),
+                format_bytes_precision(high_bytes.max(0), 3),
+            ),
+            0.25,
+            0.06,
             win_trans.scale(),
             win_trans.center,
             &context,
         );
 
-        let mut allocation_meshes = vec![&mesh];
+        // Explain degenerate traces in the window instead of leaving it blank
+        let notices: Vec<_> = rl
+            .trace_geom
+            .warnings
+            .iter()
+            .enumerate()
+            .map(|(i, msg)| {
+                tickgen.generate_text_mesh(
+                    msg,
+                    0.9 - 0.05 * i as f32,
+                    win_trans.scale(),
+                    win_trans.center,
+                    &context,
+                )
+            })
+            .collect();
+
+        let legend = if show_heatmap {
+            Some(rl.heatmap(&context).1)
+        } else {
+            rl.legend.as_ref()
+        };
+        let legend = match legend {
+            Some(legend) => {
+                tickgen.generate_legend(legend, win_trans.scale(), win_trans.center, &context)
+            }
+            None => Vec::new(),
+        };
+
+        let overlay = if show_overlay {
+            tickgen.generate_overlay(HELP_OVERLAY, win_trans.scale(), win_trans.center, &context)
+        } else {
+            Vec::new()
+        };
+
         if let Some(selected_mesh) = &mut rl.selected_mesh {
             selected_mesh.material = rl.decaying_color.material();
-            allocation_meshes.push(selected_mesh);
         }
+        rl.update_selected_outline(&context, win_trans.zoom);
+        // Zoomed out: draw merged geometry instead of sub-pixel quads
+        // Heatmap mode: bins instead of allocations
+        let lod_mesh = match show_heatmap {
+            true => Some(rl.heatmap(&context).0),
+            false => rl.lod_mesh(&context, win_trans.zoom),
+        };
+        let mut allocation_meshes: Vec<_> = match lod_mesh {
+            Some(lod_mesh) => lod_mesh.into_iter().collect(),
+            None if meshes.cancelled => rl.finest_lod_mesh(&context).into_iter().collect(),
+            None => {
+                // only chunks overlapping the visible time range
+                let (xleft, xright) = (win_trans.xleft_world(), win_trans.xright_world());
+                meshes
+                    .uploaded
+                    .iter()
+                    .filter(|m| {
+                        let aabb = m.aabb();
+                        aabb.max().x >= xleft && aabb.min().x <= xright
+                    })
+                    .collect()
+            }
+        };
+        if show_fragmentation {
+            allocation_meshes.extend(fragmentation_mesh.iter());
+        }
+        if show_segments {
+            allocation_meshes.extend(segments_mesh.iter());
+        }
+        allocation_meshes.extend(others_mesh.iter());
+        let hover_meshes = match hover_dim && !show_heatmap {
+            true => rl.hover_meshes(
+                &context,
+                hovered,
+                (win_trans.xleft_world(), win_trans.xright_world()),
+                (win_trans.ybot_world(), win_trans.ytop_world()),
+            ),
+            false => Vec::new(),
+        };
+        allocation_meshes.extend(&hover_meshes);
+        allocation_meshes.extend(rl.selected_mesh.iter());
+        allocation_meshes.extend(rl.selected_outline.iter().map(|(_, outline)| outline));
 
         frame_input
             .screen()
-            .clear(ClearState::color_and_depth(1.0, 1.0, 1.0, 1.0, 1.0))
-            .render(cam, ticks.iter().chain(allocation_meshes), &[]);
+            .clear({
+                let (r, g, b, a) = rl.theme.clear_color();
+                ClearState::color_and_depth(r, g, b, a, 1.0)
+            })
+            .render(
+                cam,
+                tick_cache
+                    .meshes()
+                    .chain(&notices)
+                    .chain(&grid)
+                    .chain(&peak_markers)
+                    .chain(&annotation_markers)
+                    .chain(&allocation_labels)
+                    .chain(&lane_separators)
+                    .chain(&event_strip)
+                    .chain(&readout)
+                    .chain(std::iter::once(&hud))
+                    .chain(&linked_cursor)
+                    .chain(&at_line)
+                    .chain(&region_box)
+                    .chain(&count_curve)
+                    .chain(&legend)
+                    .chain(&overlay)
+                    .chain(allocation_meshes),
+                &[],
+            );
+
+        if let Some(cast) = &mut screencast {
+            let pixels = frame_input.screen().read_color::<[u8; 4]>();
+            let size = (frame_input.viewport.width, frame_input.viewport.height);
+            if let Err(e) = cast.capture(&pixels, size) {
+                let msg = format!("(!) Recording stopped\n{:#}", e);
+                let _ = pub_socket.send(msg.as_bytes(), 0);
+                screencast = None;
+            }
+        }
 
         timer.tick();
-        rl.decaying_color.tick(frame_input.elapsed_time / 1000.0);
+        profiler.frame(frame_start.elapsed());
+        if let Some(max_fps) = max_fps {
+            let min_frame_time = Duration::from_secs_f64(1.0 / max_fps as f64);
+            if let Some(rest) = min_frame_time.checked_sub(frame_start.elapsed()) {
+                std::thread::sleep(rest);
+            }
+        }
 
         FrameOutput::default()
     });
@@ -356,6 +2255,569 @@ fn run_render_loop(
     Ok(())
 }
 
+/// Loads the snapshot in `dir` to compare against the primary one (whose database is `db`)
+fn load_diff(
+    dir: &Path,
+    db: &AllocationDatabase,
+    loaders: &LoaderRegistry,
+) -> AnyhowResult<DiffInput> {
+    let (diff_db, allocations) = loaders.open(dir)?;
+    let diff_keys = diff_db.read_sizes_and_callstack_hashes()?;
+    if allocations.len() != diff_keys.len() {
+        anyhow::bail!(
+            "# of allocation and elements mismatch in {:?}: {} allocations, {} elements",
+            dir,
+            allocations.len(),
+            diff_keys.len()
+        );
+    }
+
+    let bar = get_spinner("Matching allocations of both snapshots...")?;
+    let (only_primary, only_diff) =
+        unmatched_allocations(&db.read_sizes_and_callstack_hashes()?, &diff_keys);
+    bar.finish();
+    println!(
+        "Diff: {} allocations only in this snapshot, {} only in {:?}",
+        only_primary.len(),
+        only_diff.len(),
+        dir
+    );
+
+    Ok(DiffInput {
+        name: dir.display().to_string(),
+        allocations,
+        only_primary,
+        only_diff,
+    })
+}
+
+/// Banner shown when the snapshot directory has no elements.db
+const MISSING_ELEMENTS_NOTICE: &str =
+    "elements.db is missing: showing memory only, callstacks and SQL are unavailable.";
+
+const HELP_OVERLAY: &[&str] = &[
+    "Welcome to SnapViewer",
+    "",
+    "Pan: left mouse drag, W A S D, Shift / Ctrl + mouse wheel",
+    "Zoom: mouse wheel",
+    "Ctrl + left click: select an allocation    Shift + drag: stats of the allocations in a box",
+    "Ctrl+Z / Ctrl+Shift+Z: undo / redo view    G: grid lines    M: spotlight largest",
+    "L: log scale memory axis    C: live allocation count curve    F: fragmentation",
+    "K: one color per callstack, to spot allocations repeated every iteration    F11: fullscreen",
+    "E: reserved segments as bands behind the allocations",
+    "B: density heatmap instead of allocations    R: record the window to a GIF",
+    "T: label the largest allocations with size and top stack frame",
+    "V: hover-dim, fading all but the allocation under the cursor    N: next color theme",
+    "[ / ]: back / forward through selected allocations    Double-click: zoom to an allocation",
+    "Tab: next device, for renderers started with --devices",
+    "",
+    "In the GUI window:",
+    "  left panel shows the callstack of the selected allocation",
+    "  right panel is a REPL: SQL queries, --filter, --view, --select ...",
+    "",
+    "Press any key or click to dismiss, H shows this again",
+];
+
+fn first_run_marker() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(FIRST_RUN_MARKER))
+}
+
+fn mark_first_run_done() {
+    let Some(marker) = first_run_marker() else {
+        return;
+    };
+    let result = marker
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&marker, ""));
+    if let Err(e) = result {
+        log::warn!("Could not record first run at {}: {}", marker.display(), e);
+    }
+}
+
+/// Applies a `--filter` command and rebuilds the meshes to match
+fn apply_filter(
+    rl: &mut RenderLoop,
+    meshes: &mut ChunkedMesh,
+    command: &str,
+) -> AnyhowResult<String> {
+    let result = rl.filter.apply_command(command)?;
+    rebuild_filtered(rl, meshes);
+    Ok(result)
+}
+
+fn rebuild_filtered(rl: &mut RenderLoop, meshes: &mut ChunkedMesh) {
+    info!("Rebuilding mesh with filter: {}", rl.filter);
+    rebuild_meshes(rl, meshes);
+    // the selected allocation may be hidden now
+    rl.clear_selection();
+}
+
+fn rebuild_meshes(rl: &mut RenderLoop, meshes: &mut ChunkedMesh) {
+    *meshes = ChunkedMesh::new(rl.build_filtered_meshes());
+    rl.invalidate_lod();
+}
+
+/// Spotlight the `count` largest allocations in the visible time range
+fn update_spotlight(
+    rl: &mut RenderLoop,
+    win_trans: &WindowTransform,
+    meshes: &mut ChunkedMesh,
+    count: usize,
+) {
+    let start = rl
+        .trace_geom
+        .xworld2timestamp(win_trans.xleft_world())
+        .max(0) as u64;
+    let stop = rl
+        .trace_geom
+        .xworld2timestamp(win_trans.xright_world())
+        .max(0) as u64;
+    rl.set_spotlight(count, start, stop);
+    rebuild_meshes(rl, meshes);
+}
+
+fn view_state(rl: &RenderLoop, win_trans: &WindowTransform) -> ViewState {
+    ViewState {
+        center: win_trans.center,
+        zoom: win_trans.zoom,
+        filter: rl.filter.clone(),
+    }
+}
+
+fn restore_view_state(
+    state: ViewState,
+    rl: &mut RenderLoop,
+    win_trans: &mut WindowTransform,
+    meshes: &mut ChunkedMesh,
+) {
+    win_trans.center = state.center;
+    win_trans.zoom = state.zoom;
+    if rl.filter != state.filter {
+        rl.filter = state.filter;
+        rebuild_filtered(rl, meshes);
+    }
+}
+
+/// Highlights allocation `idx` and returns its details for the UI
+fn select_allocation(
+    rl: &mut RenderLoop,
+    context: &Context,
+    db_ptr: u64,
+    idx: usize,
+) -> AnyhowResult<String> {
+    let num_allocs = rl.trace_geom.allocations.len();
+    if idx >= num_allocs {
+        anyhow::bail!(
+            "Allocation #{} does not exist, there are {}",
+            idx,
+            num_allocs
+        );
+    }
+    rl.show_alloc(context, idx);
+    rl.record_selection(idx);
+    Ok(selection_message(rl, db_ptr, idx))
+}
+
+/// What the GUI shows for a selected allocation
+fn selection_message(rl: &RenderLoop, db_ptr: u64, idx: usize) -> String {
+    format!("Allocation #{}\n{}", idx, rl.allocation_info(db_ptr, idx))
+}
+
+/// `--view` reports the visible time range and selection as `t0=..&t1=..[&idx=..]`
+/// (the query part of a snapviewer:// link), `--view <start>..<stop>` shows that time range.
+fn handle_view_command(
+    rl: &RenderLoop,
+    win_trans: &mut WindowTransform,
+    args: &str,
+) -> AnyhowResult<String> {
+    let args = args.trim();
+    if !args.is_empty() {
+        let (start, stop) = parse_time_range(args)?;
+        win_trans.show_x_range(
+            rl.trace_geom.timestamp2xworld(start),
+            rl.trace_geom.timestamp2xworld(stop),
+        );
+    }
+
+    let t0 = rl
+        .trace_geom
+        .xworld2timestamp(win_trans.xleft_world())
+        .max(0);
+    let t1 = rl
+        .trace_geom
+        .xworld2timestamp(win_trans.xright_world())
+        .max(0);
+    let mut query = format!("t0={}&t1={}", t0, t1);
+    if let Some(idx) = rl.selected {
+        query += &format!("&idx={}", idx);
+    }
+    Ok(query)
+}
+
+/// `--device`: list the snapshot's devices, `--device <id>` shows device `id` (switched to at the
+/// end of the frame)
+fn handle_device_command(
+    devices: &Devices,
+    switch_to: &mut Option<usize>,
+    args: &str,
+) -> AnyhowResult<String> {
+    if args.trim().is_empty() {
+        return Ok(format!("{}, `--device <id>` or Tab switches", devices));
+    }
+    let target = devices.find(args)?;
+    if target == devices.active {
+        return Ok(format!("Already showing device {}", devices.active_id()));
+    }
+    *switch_to = Some(target);
+    Ok(format!("Switching to device {}", devices.dirs[target].0))
+}
+
+/// `--reload`: load the shown snapshot again, `--reload <dir>` the one converted into `dir` in
+/// its place (e.g. from a newer dump of the same job), at the end of the frame
+fn handle_reload_command(
+    devices: &Devices,
+    reload_from: &mut Option<PathBuf>,
+    args: &str,
+) -> AnyhowResult<String> {
+    let dir = match args.trim() {
+        "" => devices.dirs[devices.active].1.clone(),
+        path => match detect_input(Path::new(path))? {
+            (InputKind::Converted, dir) => dir,
+            (kind, _) => anyhow::bail!(
+                "{} is a {}, convert it with `snapviewer-renderer convert` first",
+                path,
+                kind
+            ),
+        },
+    };
+    let msg = format!("Reloading {}", dir.display());
+    *reload_from = Some(dir);
+    Ok(msg)
+}
+
+/// `--instrument`: Python snippets for instrumenting where the selected allocation comes from
+fn handle_instrument_command(rl: &RenderLoop, db_ptr: u64) -> AnyhowResult<String> {
+    let Some(idx) = rl.selected else {
+        anyhow::bail!("No allocation selected, Ctrl + Left click one first");
+    };
+    let db = unsafe { &*(db_ptr as *const AllocationDatabase) };
+    let callstack = db.read_callstack(idx)?;
+    python_snippet(idx, rl.trace_geom.raw_allocs[idx].size, &callstack)
+}
+
+/// Lanes as (bottom, top) in world coords, with labels
+fn lane_bounds(geom: &TraceGeometry) -> Vec<(f32, f32, String)> {
+    geom.lanes
+        .iter()
+        .map(|lane| {
+            (
+                geom.memory2yworld(lane.base as i64),
+                geom.memory2yworld((lane.base + lane.height) as i64),
+                format!("{} {}", geom.lane_name, lane.stream),
+            )
+        })
+        .collect()
+}
+
+/// Stats of the allocations intersecting the rectangle between the corners `p0` and `p1`
+/// (world coords)
+fn region_stats(
+    rl: &RenderLoop,
+    db_ptr: u64,
+    p0: Vector2<f32>,
+    p1: Vector2<f32>,
+) -> AnyhowResult<RegionStats> {
+    let x_range = (p0.x.min(p1.x), p0.x.max(p1.x));
+    let y_range = (p0.y.min(p1.y), p0.y.max(p1.y));
+    let indices = rl.trace_geom.find_in_rect(x_range, y_range, &rl.filter);
+    let db = unsafe { &*(db_ptr as *const AllocationDatabase) };
+    let callstacks = db.read_callstacks(&indices)?;
+    let geom = &rl.trace_geom;
+    let timestamp = |x: f32| geom.xworld2timestamp(x).max(0) as u64;
+    let memory = |y: f32| geom.yworld2memory(y).max(0) as u64;
+    Ok(RegionStats::new(
+        &geom.raw_allocs,
+        &indices,
+        callstacks,
+        (timestamp(x_range.0), timestamp(x_range.1)),
+        (memory(y_range.0), memory(y_range.1)),
+        REGION_TOP_CALLSTACKS,
+    ))
+}
+
+/// `--at <timestamp>`: place the time cursor there and list the allocations live at that instant,
+/// `--at peak` at the memory peak, `--at` alone removes the cursor
+fn handle_at_command(
+    rl: &RenderLoop,
+    win_trans: &mut WindowTransform,
+    db_ptr: u64,
+    at_cursor: &mut Option<u64>,
+    args: &str,
+) -> AnyhowResult<String> {
+    let timestamp: u64 = match args.trim() {
+        "" => {
+            *at_cursor = None;
+            return Ok("Time cursor removed".to_string());
+        }
+        "peak" => rl
+            .trace_geom
+            .peak_timestamps
+            .first()
+            .copied()
+            .context("The trace has no peak")?,
+        timestamp => timestamp.parse()?,
+    };
+    let max_time = rl.trace_geom.max_time as u64;
+    if timestamp > max_time {
+        anyhow::bail!(
+            "Timestamp {} is past the end of the trace at {}",
+            timestamp,
+            max_time
+        );
+    }
+    let db = unsafe { &*(db_ptr as *const AllocationDatabase) };
+    let live = db.live_at(timestamp, AT_REPORT_LIMIT)?;
+    *at_cursor = Some(timestamp);
+
+    // pan to the cursor if it is off screen, keeping the zoom
+    let x = rl.trace_geom.timestamp2xworld(timestamp);
+    if x < win_trans.xleft_world() || x > win_trans.xright_world() {
+        win_trans.center.x = x;
+    }
+
+    let mut report = format!(
+        "At {}: {} allocations live, {}\n",
+        rl.format_time(timestamp),
+        live.count,
+        format_bytes_precision(live.bytes as i64, 3)
+    );
+    for (idx, size, callstack) in &live.largest {
+        report += &format!("  {:>8}  {}\n", idx, top_label(*size, callstack));
+    }
+    if live.count > live.largest.len() {
+        report += &format!("  ... and {} smaller\n", live.count - live.largest.len());
+    }
+    report += &format!(
+        "All of them: SELECT idx, size, callstack FROM allocs WHERE {} ORDER BY size DESC",
+        LIVE_AT_CONDITION.replace("?1", &timestamp.to_string())
+    );
+    Ok(report)
+}
+
+/// Callstack groups, read from the database on first use
+fn callstack_groups(
+    groups: &mut Option<CallstackGroups>,
+    db_ptr: u64,
+) -> AnyhowResult<&CallstackGroups> {
+    if groups.is_none() {
+        let db = unsafe { &*(db_ptr as *const AllocationDatabase) };
+        *groups = Some(db.read_callstack_groups()?);
+    }
+    Ok(groups.as_ref().unwrap())
+}
+
+/// K: tint allocations sharing a callstack in one color, or back to their own colors.
+/// Meshes need a rebuild afterwards.
+fn toggle_grouping(
+    rl: &mut RenderLoop,
+    groups: &mut Option<CallstackGroups>,
+    db_ptr: u64,
+) -> AnyhowResult<String> {
+    if rl.is_grouped() {
+        rl.set_grouped(None);
+        return Ok("Grouping by callstack off".to_string());
+    }
+    let groups = callstack_groups(groups, db_ptr)?;
+    rl.set_grouped(Some(groups));
+    Ok(format!(
+        "Grouping by callstack: {} allocations in {} groups, `--group <idx>` highlights one",
+        rl.alloc_colors.len(),
+        groups.len()
+    ))
+}
+
+/// `--group [idx]`: dim everything but the allocations sharing allocation `idx`'s callstack
+/// (the selected allocation's without `idx`), `--group clear` undoes it. Meshes need a rebuild
+/// afterwards.
+fn handle_group_command(
+    rl: &mut RenderLoop,
+    db_ptr: u64,
+    groups: &mut Option<CallstackGroups>,
+    highlighted_group: &mut Option<usize>,
+    args: &str,
+) -> AnyhowResult<String> {
+    let idx: usize = match args.trim() {
+        "clear" => {
+            *highlighted_group = None;
+            rl.spotlight = None;
+            return Ok("Group highlight removed".to_string());
+        }
+        "" => rl
+            .selected
+            .context("No allocation selected, use --group <idx>")?,
+        idx => idx.parse()?,
+    };
+    let num_allocs = rl.trace_geom.raw_allocs.len();
+    if idx >= num_allocs {
+        anyhow::bail!(
+            "Allocation #{} does not exist, there are {}",
+            idx,
+            num_allocs
+        );
+    }
+    let members = callstack_groups(groups, db_ptr)?.members(idx);
+    let raw_allocs = &rl.trace_geom.raw_allocs;
+    let bytes: u64 = members.iter().map(|&i| raw_allocs[i].size).sum();
+    let db = unsafe { &*(db_ptr as *const AllocationDatabase) };
+    let callstack = db.read_callstack(idx)?;
+
+    let mut report = format!(
+        "Group of #{}: {} allocations sharing its callstack, {} total\n  {}\n",
+        idx,
+        members.len(),
+        format_bytes_precision(bytes as i64, 3),
+        top_label(raw_allocs[idx].size, &callstack)
+    );
+    for &i in members.iter().take(GROUP_REPORT_LIMIT) {
+        let (start, stop) = raw_allocs[i].start_end_time();
+        report += &format!(
+            "  {:>8}  {}, alive {}..{}\n",
+            i,
+            format_bytes_precision(raw_allocs[i].size as i64, 3),
+            rl.format_time(start),
+            rl.format_time(stop)
+        );
+    }
+    if members.len() > GROUP_REPORT_LIMIT {
+        report += &format!("  ... and {} more\n", members.len() - GROUP_REPORT_LIMIT);
+    }
+
+    rl.spotlight = Some(members.into_iter().collect());
+    *highlighted_group = Some(idx);
+    Ok(report)
+}
+
+/// Recolors everything drawn around the allocations; tick labels are rebuilt in the new color
+fn apply_theme(
+    rl: &mut RenderLoop,
+    tickgen: &mut TickGenerator,
+    tick_cache: &mut TickCache,
+    theme: Theme,
+) {
+    rl.set_theme(theme);
+    tickgen.theme = theme;
+    *tick_cache = TickCache::default();
+}
+
+/// `--record [path]`: start recording the window to a .gif or .mp4 file, `--record` again (or
+/// R) stops. Without a path, records to a timestamped GIF in the working directory.
+fn handle_record_command(
+    screencast: &mut Option<Screencast>,
+    args: &str,
+    size: (u32, u32),
+) -> AnyhowResult<String> {
+    let args = args.trim();
+    if let Some(cast) = screencast.take() {
+        if !args.is_empty() && args != "stop" {
+            let msg = format!("Already recording to {}", cast.path.display());
+            *screencast = Some(cast);
+            anyhow::bail!("{}, stop with `--record stop` first", msg);
+        }
+        return cast.finish();
+    }
+    let path = match args {
+        "stop" => anyhow::bail!("Not recording"),
+        "" => PathBuf::from(format!(
+            "snapviewer-{}.gif",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs()
+        )),
+        path => PathBuf::from(path),
+    };
+    let cast = Screencast::start(&path, size, RECORD_FPS)?;
+    *screencast = Some(cast);
+    Ok(format!(
+        "Recording to {} at {} fps, press R or `--record stop` to finish",
+        path.display(),
+        RECORD_FPS
+    ))
+}
+
+/// `--export-svg <path>`: write the visible allocations and memory ticks to an SVG file
+fn handle_export_svg_command(
+    rl: &RenderLoop,
+    win_trans: &WindowTransform,
+    path: &str,
+) -> AnyhowResult<String> {
+    let path = path.trim();
+    if path.is_empty() {
+        anyhow::bail!("Usage: --export-svg <path>, e.g. `--export-svg figure.svg`");
+    }
+    let (svg, count) = rl.export_svg(
+        (win_trans.xleft_world(), win_trans.xright_world()),
+        (win_trans.ybot_world(), win_trans.ytop_world()),
+        win_trans.screen_size(),
+    );
+    std::fs::write(path, svg).with_context(|| format!("Could not write {}", path))?;
+    Ok(format!("Exported {} allocations to {}", count, path))
+}
+
+/// `--export-bundle <path>`: zip the shown device's snapshot as loaded, to share
+fn handle_export_bundle_command(
+    db_ptr: u64,
+    devices: &Devices,
+    path: &str,
+) -> AnyhowResult<String> {
+    let path = path.trim();
+    if path.is_empty() {
+        anyhow::bail!("Usage: --export-bundle <path>, e.g. `--export-bundle snapshot.zip`");
+    }
+    let db = unsafe { &*(db_ptr as *const AllocationDatabase) };
+    let count = write_bundle(db, &devices.dirs[devices.active].1, Path::new(path))?;
+    Ok(format!(
+        "Bundled {} allocations into {}, open it with `python gui.py --dir {}`",
+        count, path, path
+    ))
+}
+
+/// `--goto <idx>`: center the view on allocation `idx`
+fn handle_goto_command(
+    rl: &RenderLoop,
+    win_trans: &mut WindowTransform,
+    args: &str,
+) -> AnyhowResult<String> {
+    let idx: usize = args.trim().parse()?;
+    if idx >= rl.trace_geom.allocations.len() {
+        anyhow::bail!(
+            "Allocation index {} out of range, the trace has {} allocations",
+            idx,
+            rl.trace_geom.allocations.len()
+        );
+    }
+    let (p0, p1) = allocation_rect(rl, idx);
+    win_trans.show_rect(p0, p1, GOTO_FILL);
+    Ok(format!("Centered on allocation {}", idx))
+}
+
+/// Bounding rect of allocation `idx` in drawn world coords, as ((x0, y0), (x1, y1))
+fn allocation_rect(rl: &RenderLoop, idx: usize) -> ((f32, f32), (f32, f32)) {
+    let geom = &rl.trace_geom.allocations[idx];
+    let x0 = geom.timesteps.first().copied().unwrap_or_default();
+    let x1 = geom.timesteps.last().copied().unwrap_or_default();
+    let y0 = geom.offsets.iter().copied().fold(f64::INFINITY, f64::min);
+    let y1 = geom
+        .offsets
+        .iter()
+        .copied()
+        .fold(f64::NEG_INFINITY, f64::max)
+        + geom.size;
+    let y_axis = rl.trace_geom.y_axis;
+    let (y0, y1) = (y_axis.map(y0), y_axis.map(y1));
+    ((x0 as f32, y0 as f32), (x1 as f32, y1 as f32))
+}
+
 fn handle_sql_command(db_ptr: u64, command: &str) -> AnyhowResult<String> {
     let db = unsafe { &mut *(db_ptr as *mut AllocationDatabase) };
     let command = command.trim();
@@ -0,0 +1,125 @@
+//! Which outputs of a conversion are complete, kept next to them as convert_manifest.json. A
+//! conversion that fails halfway, or is rerun asking for more outputs, resumes by writing only
+//! what is missing, shard by shard for a sharded allocations.json; a device whose outputs are all
+//! complete is skipped without processing its trace.
+
+use crate::constants::{CONVERTER_VERSION, MANIFEST_FILE_NAME};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// Converter and input the outputs were written by and from; a manifest for any other is
+    /// discarded, and everything written again
+    pub converter_version: String,
+    pub input_size: u64,
+    pub input_modified_ns: u64,
+    /// Allocations per allocations_{i}.json the shards were written with
+    pub json_shard_size: Option<usize>,
+    /// Output files written completely
+    pub completed: Vec<String>,
+}
+
+impl Manifest {
+    /// The manifest in `output` if it was written by this converter for `input` as it is now,
+    /// otherwise an empty one. Shards of another `json_shard_size` are not complete.
+    pub fn load(output: &Path, input: &Path, json_shard_size: Option<usize>) -> Self {
+        let input_meta = std::fs::metadata(input).ok();
+        let fresh = Self {
+            converter_version: CONVERTER_VERSION.to_string(),
+            input_size: input_meta.as_ref().map_or(0, |m| m.len()),
+            input_modified_ns: input_meta
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos() as u64),
+            json_shard_size,
+            completed: Vec::new(),
+        };
+        let existing = std::fs::read(output.join(MANIFEST_FILE_NAME))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Self>(&bytes).ok());
+        match existing {
+            Some(mut manifest)
+                if manifest.converter_version == fresh.converter_version
+                    && manifest.input_size == fresh.input_size
+                    && manifest.input_modified_ns == fresh.input_modified_ns =>
+            {
+                if manifest.json_shard_size != json_shard_size {
+                    // allocations_{i}.json and allocations_shards.json
+                    manifest
+                        .completed
+                        .retain(|name| !name.starts_with("allocations_"));
+                    manifest.json_shard_size = json_shard_size;
+                }
+                manifest
+            }
+            _ => fresh,
+        }
+    }
+
+    pub fn is_done(&self, name: &str) -> bool {
+        self.completed.iter().any(|done| done == name)
+    }
+
+    /// Records `name` in `output` as complete
+    pub fn complete(&mut self, output: &Path, name: &str) -> anyhow::Result<()> {
+        if !self.is_done(name) {
+            self.completed.push(name.to_string());
+        }
+        write_atomic(&output.join(MANIFEST_FILE_NAME), &serde_json::to_vec(self)?)
+    }
+
+    /// Writes `name` in `output` and records it as complete
+    pub fn write(&mut self, output: &Path, name: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        write_atomic(&output.join(name), bytes)?;
+        self.complete(output, name)
+    }
+
+    /// Drops `names` from the manifest in `output`, if there is one, for outputs deleted since
+    pub fn forget(output: &Path, names: &[String]) -> anyhow::Result<()> {
+        let path = output.join(MANIFEST_FILE_NAME);
+        let Ok(bytes) = std::fs::read(&path) else {
+            return Ok(());
+        };
+        let mut manifest: Self = serde_json::from_slice(&bytes)?;
+        manifest.completed.retain(|name| !names.contains(name));
+        write_atomic(&path, &serde_json::to_vec(&manifest)?)
+    }
+}
+
+/// Writes through a temporary file renamed into place, so an interrupted write leaves no
+/// truncated file behind
+fn write_atomic(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::Manifest;
+
+    #[test]
+    fn test_manifest_resumes_for_same_input() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("snap.pickle");
+        std::fs::write(&input, b"pickle").unwrap();
+
+        let mut manifest = Manifest::load(&dir, &input, Some(10));
+        manifest.write(&dir, "metadata.json", b"{}").unwrap();
+        manifest.complete(&dir, "allocations_0.json").unwrap();
+        let resumed = Manifest::load(&dir, &input, Some(10));
+        let reshared = Manifest::load(&dir, &input, Some(20));
+        std::fs::write(&input, b"another pickle").unwrap();
+        let changed = Manifest::load(&dir, &input, Some(10));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(resumed, manifest);
+        assert_eq!(reshared.completed, ["metadata.json"]);
+        assert!(changed.completed.is_empty());
+    }
+}
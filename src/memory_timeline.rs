@@ -0,0 +1,171 @@
+use crate::allocation::Allocation;
+
+/// Entries per block of precomputed maxima in `MemoryTimeline`
+const BLOCK_SIZE: usize = 1024;
+
+/// Total live memory over time as a step function, for the pinned memory readout.
+/// Also counts live allocations instead of bytes, see `allocation_counts`.
+#[derive(Clone)]
+pub struct MemoryTimeline {
+    /// Timestamps where live memory changes, sorted
+    times: Vec<u64>,
+    /// Live memory from `times[i]` until `times[i + 1]`
+    live: Vec<u64>,
+    /// Maximum of `live` over each `BLOCK_SIZE` chunk, so range maxima don't scan everything
+    block_max: Vec<u64>,
+}
+
+impl MemoryTimeline {
+    /// An allocation counts as live from its first to its last timestamp, inclusive
+    pub fn from_allocations(allocations: &[Allocation]) -> Self {
+        Self::from_weights(allocations, |_, alloc| alloc.size)
+    }
+
+    /// Like `from_allocations`, over the allocations whose index `keep` accepts
+    pub fn from_allocations_where(
+        allocations: &[Allocation],
+        keep: impl Fn(usize) -> bool,
+    ) -> Self {
+        Self::from_weights(allocations, |i, alloc| match keep(i) {
+            true => alloc.size,
+            false => 0,
+        })
+    }
+
+    /// Number of live allocations over time, for the allocation count curve
+    pub fn allocation_counts(allocations: &[Allocation]) -> Self {
+        Self::from_weights(allocations, |_, _| 1)
+    }
+
+    /// Sum of `weight` over live allocations
+    fn from_weights(
+        allocations: &[Allocation],
+        weight: impl Fn(usize, &Allocation) -> u64,
+    ) -> Self {
+        let mut deltas: Vec<(u64, i128)> = Vec::with_capacity(allocations.len() * 2);
+        for (i, alloc) in allocations.iter().enumerate() {
+            let weight = weight(i, alloc) as i128;
+            if weight == 0 {
+                continue;
+            }
+            let (start, stop) = alloc.start_end_time();
+            deltas.push((start, weight));
+            deltas.push((stop + 1, -weight));
+        }
+        deltas.sort_unstable_by_key(|&(t, _)| t);
+
+        let mut times = Vec::new();
+        let mut live = Vec::new();
+        let mut current: i128 = 0;
+        for (i, &(t, delta)) in deltas.iter().enumerate() {
+            current += delta;
+            // one step per distinct timestamp
+            if deltas.get(i + 1).is_none_or(|&(next, _)| next != t) {
+                times.push(t);
+                live.push(current.max(0) as u64);
+            }
+        }
+
+        let block_max = live
+            .chunks(BLOCK_SIZE)
+            .map(|block| block.iter().copied().max().unwrap_or(0))
+            .collect();
+
+        Self {
+            times,
+            live,
+            block_max,
+        }
+    }
+
+    /// Index of the step containing `timestamp`, `None` before the first allocation
+    fn step_at(&self, timestamp: u64) -> Option<usize> {
+        self.times
+            .partition_point(|&t| t <= timestamp)
+            .checked_sub(1)
+    }
+
+    /// Timestamps where the total changes, sorted
+    pub fn change_times(&self) -> &[u64] {
+        &self.times
+    }
+
+    pub fn live_at(&self, timestamp: u64) -> u64 {
+        self.step_at(timestamp).map_or(0, |i| self.live[i])
+    }
+
+    /// Maximum live memory at any time in `start..=stop`
+    pub fn max_in(&self, start: u64, stop: u64) -> u64 {
+        if start > stop {
+            return 0;
+        }
+        let first = self.step_at(start).unwrap_or(0);
+        let Some(last) = self.step_at(stop) else {
+            return 0;
+        };
+
+        let mut max = 0;
+        let mut i = first;
+        while i <= last {
+            if i.is_multiple_of(BLOCK_SIZE) && i + BLOCK_SIZE - 1 <= last {
+                max = max.max(self.block_max[i / BLOCK_SIZE]);
+                i += BLOCK_SIZE;
+            } else {
+                max = max.max(self.live[i]);
+                i += 1;
+            }
+        }
+        max
+    }
+
+    /// Maxima over `columns` equal slices of `start..=stop`, e.g. one per pixel column when
+    /// drawing the timeline as a curve
+    pub fn column_maxima(&self, start: u64, stop: u64, columns: usize) -> Vec<u64> {
+        let span = stop.saturating_sub(start) as f64 + 1.0;
+        (0..columns)
+            .map(|i| {
+                let lo = start + (span * i as f64 / columns as f64) as u64;
+                let hi = start + (span * (i + 1) as f64 / columns as f64) as u64;
+                self.max_in(lo, hi.saturating_sub(1).max(lo))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::Allocation;
+    use crate::memory_timeline::MemoryTimeline;
+
+    #[test]
+    fn test_live_memory() {
+        let alloc = |start, stop, size| Allocation {
+            timesteps: vec![start, stop].into(),
+            offsets: vec![0, 0].into(),
+            size,
+            peak_mem: size,
+            peak_timestamps: vec![start],
+            category: None,
+        };
+        let mut allocs = vec![alloc(0, 10, 4), alloc(5, 20, 8), alloc(15, 15, 1)];
+        // enough steps to use the block maxima
+        allocs.extend((0..3000).map(|i| alloc(100 + i, 100 + i, i % 7)));
+        let timeline = MemoryTimeline::from_allocations(&allocs);
+
+        assert_eq!(timeline.live_at(0), 4);
+        assert_eq!(timeline.live_at(7), 12);
+        assert_eq!(timeline.live_at(11), 8);
+        assert_eq!(timeline.live_at(15), 9);
+        assert_eq!(timeline.live_at(21), 0);
+        assert_eq!(timeline.max_in(0, 4), 4);
+        assert_eq!(timeline.max_in(11, 30), 9);
+        assert_eq!(timeline.max_in(21, 99), 0);
+        assert_eq!(timeline.max_in(0, 5000), 12);
+        assert_eq!(timeline.max_in(200, 3099), 6);
+
+        let counts = MemoryTimeline::allocation_counts(&allocs[..3]);
+        assert_eq!(counts.live_at(7), 2);
+        assert_eq!(counts.live_at(15), 2);
+        assert_eq!(counts.column_maxima(0, 19, 4), vec![1, 2, 2, 2]);
+    }
+}
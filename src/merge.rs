@@ -0,0 +1,244 @@
+//! Snapshots of several ranks of a job (e.g. `rank-0` .. `rank-7` of DDP or FSDP training), each
+//! converted on its own, merged into one converted directory that is drawn with a lane per rank.
+//! Its elements.db holds every rank's allocations and events, one rank after the other, with a
+//! `rank` column telling them apart so SQL can compare ranks.
+
+use crate::{
+    constants::{
+        ALLOC_ROWS_TABLE, ALLOCATIONS_BIN_FILE_NAME, ALLOCATIONS_FILE_NAME,
+        ALLOCATIONS_SHARDS_FILE_NAME, ALLOCATIONS_ZST_FILE_NAME, CALLSTACKS_TABLE,
+        CONVERTER_VERSION, ELEMENT_DB_FILENAME, EVENTS_TABLE, METADATA_FILE_NAME, RANK_COLUMN,
+    },
+    convert::{DATABASE_SCHEMA, pack_u64s},
+    load::LoaderRegistry,
+};
+use anyhow::{Context, bail};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// metadata.json of a merged directory
+#[derive(Serialize, Deserialize, PartialEq)]
+struct MergedMetadata {
+    converter_version: String,
+    /// Converted directory of each rank, canonicalized
+    ranks: Vec<PathBuf>,
+}
+
+/// Where `--merge-ranks` writes without `--dir`: a directory in the system's temporary
+/// directory named after the merged ones, so merging them again reuses it
+pub fn default_output(dirs: &[PathBuf]) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    for dir in dirs {
+        dir.canonicalize()
+            .unwrap_or_else(|_| dir.clone())
+            .hash(&mut hasher);
+    }
+    std::env::temp_dir().join(format!("snapviewer-merged-{:016x}", hasher.finish()))
+}
+
+/// Merges the converted snapshots in `dirs`, the i-th being rank i, into `output`. Skipped if
+/// `output` holds them merged already and no rank's elements.db changed since. Ranks are opened
+/// with `loaders`, which must leave an elements.db file to merge from.
+pub fn merge_ranks(
+    dirs: &[PathBuf],
+    output: &Path,
+    loaders: &LoaderRegistry,
+) -> anyhow::Result<()> {
+    let metadata = MergedMetadata {
+        converter_version: CONVERTER_VERSION.to_string(),
+        ranks: dirs
+            .iter()
+            .map(|dir| {
+                dir.canonicalize()
+                    .with_context(|| format!("{} does not exist", dir.display()))
+            })
+            .collect::<anyhow::Result<_>>()?,
+    };
+    let db_path = output.join(ELEMENT_DB_FILENAME);
+    if is_up_to_date(&metadata, output) {
+        println!("{} holds these ranks merged already", output.display());
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(output)?;
+    // loaded in place of elements.db, if an earlier merge or conversion left any
+    for name in [
+        ALLOCATIONS_BIN_FILE_NAME,
+        ALLOCATIONS_FILE_NAME,
+        ALLOCATIONS_ZST_FILE_NAME,
+        ALLOCATIONS_SHARDS_FILE_NAME,
+    ] {
+        if output.join(name).exists() {
+            std::fs::remove_file(output.join(name))?;
+        }
+    }
+    let tmp_path = db_path.with_extension("db.tmp");
+    if tmp_path.exists() {
+        std::fs::remove_file(&tmp_path)?;
+    }
+    let mut conn = Connection::open(&tmp_path)?;
+    conn.execute_batch(DATABASE_SCHEMA)?;
+    conn.execute_batch(&format!(
+        "ALTER TABLE {1} ADD COLUMN {0} INTEGER; ALTER TABLE {2} ADD COLUMN {0} INTEGER;",
+        RANK_COLUMN, ALLOC_ROWS_TABLE, EVENTS_TABLE
+    ))?;
+
+    let (mut first_alloc, mut first_event) = (0, 0);
+    for (rank, dir) in metadata.ranks.iter().enumerate() {
+        let (allocs, category, db_file) = {
+            let (db, allocs) = loaders
+                .open(dir)
+                .with_context(|| format!("Loading rank {} from {}", rank, dir.display()))?;
+            let db_file = match db.conn.path() {
+                Some(path) if !path.is_empty() && db.has_elements()? => PathBuf::from(path),
+                _ => bail!(
+                    "{} has no {}: merge directories written by `snapviewer-renderer convert`",
+                    dir.display(),
+                    ELEMENT_DB_FILENAME
+                ),
+            };
+            let category = match db.has_categories()? {
+                true => "category",
+                false => "NULL",
+            };
+            (allocs, category, db_file)
+        };
+        println!("Rank {}: {} allocations", rank, allocs.len());
+
+        // a database cannot be attached within a transaction
+        conn.execute("ATTACH DATABASE ?1 AS rank_db", [db_file.to_string_lossy()])?;
+        let tx = conn.transaction()?;
+        // callstacks the ranks share are stored once; the rank's allocs may be a table holding
+        // callstacks, if converted before they were interned
+        tx.execute(
+            &format!(
+                "INSERT INTO {0} (callstack) SELECT DISTINCT callstack FROM rank_db.allocs
+                WHERE callstack IS NOT NULL AND callstack NOT IN (SELECT callstack FROM {0})",
+                CALLSTACKS_TABLE
+            ),
+            [],
+        )?;
+        let copied = tx.execute(
+            &format!(
+                "INSERT INTO {0} SELECT a.idx + ?1, a.size, a.start_time, a.end_time, c.id, {1}, ?2
+                FROM rank_db.allocs a LEFT JOIN {2} c ON c.callstack = a.callstack
+                ORDER BY a.idx",
+                ALLOC_ROWS_TABLE, category, CALLSTACKS_TABLE
+            ),
+            (first_alloc, rank),
+        )?;
+        if copied != allocs.len() {
+            bail!(
+                "{} has {} allocations but {} rows in its allocs table, convert it again",
+                dir.display(),
+                allocs.len(),
+                copied
+            );
+        }
+        {
+            // geometry as loaded, so ranks converted before the timeline table merge too
+            let mut timeline = tx.prepare("INSERT INTO timeline VALUES (?, ?, ?)")?;
+            let mut lanes = tx.prepare("INSERT INTO lanes VALUES (?, ?, ?)")?;
+            for (i, alloc) in allocs.iter().enumerate() {
+                let offsets = pack_u64s(&alloc.offsets);
+                timeline.execute((first_alloc + i, pack_u64s(&alloc.timesteps), &offsets))?;
+                lanes.execute((first_alloc + i, rank, &offsets))?;
+            }
+        }
+        let has_events: bool = tx.query_row(
+            "SELECT COUNT(*) > 0 FROM rank_db.sqlite_master WHERE type = 'table' AND name = ?1",
+            [EVENTS_TABLE],
+            |row| row.get(0),
+        )?;
+        if has_events {
+            first_event += tx.execute(
+                &format!(
+                    "INSERT INTO {0} SELECT idx + ?1, timestep, action, size, stream, callstack, ?2
+                    FROM rank_db.{0} ORDER BY idx",
+                    EVENTS_TABLE
+                ),
+                (first_event, rank),
+            )?;
+        }
+        tx.commit()?;
+        conn.execute("DETACH DATABASE rank_db", [])?;
+        first_alloc += allocs.len();
+    }
+    conn.close().map_err(|(_, e)| e)?;
+    std::fs::rename(&tmp_path, &db_path)?;
+    std::fs::write(
+        output.join(METADATA_FILE_NAME),
+        serde_json::to_vec_pretty(&metadata)?,
+    )?;
+    println!(
+        "Merged {} ranks ({} allocations) into {}",
+        metadata.ranks.len(),
+        first_alloc,
+        output.display()
+    );
+    Ok(())
+}
+
+fn is_up_to_date(metadata: &MergedMetadata, output: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let merged = std::fs::read(output.join(METADATA_FILE_NAME))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<MergedMetadata>(&bytes).ok());
+    let Some(db_modified) = modified(&output.join(ELEMENT_DB_FILENAME)) else {
+        return false;
+    };
+    merged.as_ref() == Some(metadata)
+        && metadata.ranks.iter().all(|dir| {
+            modified(&dir.join(ELEMENT_DB_FILENAME)).is_some_and(|rank| rank <= db_modified)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::{ConvertOptions, convert_to_dir};
+    use crate::database::sqlite::AllocationDatabase;
+    use crate::load::LoaderRegistry;
+    use crate::merge::merge_ranks;
+
+    #[test]
+    fn test_merge_ranks() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-ranks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ranks: Vec<_> = ["0,100\n10,300\n20,0\n", "0,50\n10,0\n"]
+            .iter()
+            .enumerate()
+            .map(|(rank, samples)| {
+                let csv = dir.join(format!("rank-{}.csv", rank));
+                std::fs::write(&csv, format!("time_us,used_bytes\n{}", samples)).unwrap();
+                let output = dir.join(format!("rank-{}", rank));
+                convert_to_dir(&csv, &output, 0, ConvertOptions::default()).unwrap();
+                output
+            })
+            .collect();
+        let merged = dir.join("merged");
+        let loaders = LoaderRegistry::default();
+        merge_ranks(&ranks, &merged, &loaders).unwrap();
+        // up to date, left as is
+        merge_ranks(&ranks, &merged, &loaders).unwrap();
+
+        let db = AllocationDatabase::from_dir(&merged).unwrap();
+        let per_rank: Vec<(i64, i64, i64)> = db
+            .conn
+            .prepare("SELECT rank, COUNT(*), SUM(size) FROM allocs GROUP BY rank")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let lanes: Vec<i64> = db.read_lanes().unwrap().iter().map(|l| l.stream).collect();
+        let has_ranks = db.has_ranks().unwrap();
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(per_rank, [(0, 2, 300), (1, 1, 50)]);
+        assert_eq!(lanes, [0, 0, 1]);
+        assert!(has_ranks);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{allocation::Allocation, color_mode::ALPHA};
+use anyhow::{Context, bail};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use std::str::FromStr;
+use three_d::Srgba;
+
+/// How allocations pick a color from a palette
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteOrder {
+    /// The i-th allocation gets the i-th color, wrapping around
+    RoundRobin,
+    /// Picked by a hash of the allocation's size, so same-sized allocations share a color
+    Hash,
+}
+
+impl FromStr for PaletteOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "round-robin" => Ok(PaletteOrder::RoundRobin),
+            "hash" => Ok(PaletteOrder::Hash),
+            _ => bail!(
+                "Unknown palette order `{}`, expected round-robin or hash",
+                s
+            ),
+        }
+    }
+}
+
+/// Allocation colors read from a file instead of generated randomly
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub colors: Vec<Srgba>,
+    pub order: PaletteOrder,
+}
+
+impl Palette {
+    pub fn load(path: &Path, order: PaletteOrder) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read palette {}", path.display()))?;
+        Self::parse(&text, order).with_context(|| format!("Invalid palette {}", path.display()))
+    }
+
+    /// Hex colors (`#1f77b4` or `1f77b4`) separated by whitespace or commas.
+    /// Lines starting with `//` are comments.
+    pub fn parse(text: &str, order: PaletteOrder) -> anyhow::Result<Self> {
+        let colors = text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| parse_hex(token, ALPHA))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if colors.is_empty() {
+            bail!("No colors in palette");
+        }
+        Ok(Self { colors, order })
+    }
+
+    /// Color of the `index`-th allocation
+    pub fn color(&self, index: usize, alloc: &Allocation) -> Srgba {
+        let i = match self.order {
+            PaletteOrder::RoundRobin => index,
+            PaletteOrder::Hash => {
+                let mut hasher = DefaultHasher::new();
+                alloc.size.hash(&mut hasher);
+                hasher.finish() as usize
+            }
+        };
+        self.colors[i % self.colors.len()]
+    }
+}
+
+/// A hex color like `#1f77b4` or `1f77b4`, with `alpha`
+pub fn parse_hex(token: &str, alpha: u8) -> anyhow::Result<Srgba> {
+    let hex = token.strip_prefix('#').unwrap_or(token);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("`{}` is not a hex color like #1f77b4", token);
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+    Ok(Srgba::new(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::palette::{Palette, PaletteOrder};
+    use three_d::Srgba;
+
+    #[test]
+    fn test_parse_palette() {
+        let text = "// company colors\n#1f77b4, ff7f0e\n\n2CA02C\n";
+        let palette = Palette::parse(text, PaletteOrder::RoundRobin).unwrap();
+        assert_eq!(
+            palette.colors,
+            [
+                Srgba::new(0x1f, 0x77, 0xb4, 30),
+                Srgba::new(0xff, 0x7f, 0x0e, 30),
+                Srgba::new(0x2c, 0xa0, 0x2c, 30),
+            ]
+        );
+
+        assert!(Palette::parse("#12345", PaletteOrder::Hash).is_err());
+        assert!(Palette::parse("// nothing\n", PaletteOrder::Hash).is_err());
+    }
+}
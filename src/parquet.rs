@@ -0,0 +1,427 @@
+//! Allocation tables as Parquet, for pandas, polars or DuckDB and back: int64, string and
+//! list-of-int64 columns, read and written with the `parquet` crate.
+//!
+//! Files are written snappy-compressed in row groups of `ROW_GROUP_ROWS` rows. Reading takes
+//! whatever the crate does, which covers pyarrow's defaults (dictionary encoding, snappy, v1 or v2
+//! data pages) as well as its nullable columns and three-level lists.
+
+use crate::allocation::Allocation;
+use anyhow::{Context, bail};
+use parquet::basic::{Compression, ConvertedType, Repetition, Type as PhysicalType};
+use parquet::column::reader::{ColumnReader, ColumnReaderImpl};
+use parquet::data_type::{ByteArray, ByteArrayType, DataType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::{ColumnDescriptor, Type};
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Rows per row group written, so readers can stream the file instead of decoding it whole
+const ROW_GROUP_ROWS: usize = 64 * 1024;
+/// Records decoded per `read_records` call
+const READ_BATCH: usize = 64 * 1024;
+
+/// Values of a column, one entry per row
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnData {
+    Int64(Vec<i64>),
+    /// Nullable UTF-8 strings
+    String(Vec<Option<String>>),
+    Int64List(Vec<Vec<i64>>),
+}
+
+impl ColumnData {
+    fn len(&self) -> usize {
+        match self {
+            ColumnData::Int64(values) => values.len(),
+            ColumnData::String(values) => values.len(),
+            ColumnData::Int64List(values) => values.len(),
+        }
+    }
+
+    /// Rows `range` of the column, for one row group
+    fn slice(&self, range: std::ops::Range<usize>) -> ColumnData {
+        match self {
+            ColumnData::Int64(values) => ColumnData::Int64(values[range].to_vec()),
+            ColumnData::String(values) => ColumnData::String(values[range].to_vec()),
+            ColumnData::Int64List(values) => ColumnData::Int64List(values[range].to_vec()),
+        }
+    }
+
+    fn extend(&mut self, other: ColumnData) -> anyhow::Result<()> {
+        match (self, other) {
+            (ColumnData::Int64(a), ColumnData::Int64(b)) => a.extend(b),
+            (ColumnData::String(a), ColumnData::String(b)) => a.extend(b),
+            (ColumnData::Int64List(a), ColumnData::Int64List(b)) => a.extend(b),
+            _ => bail!("Column changes type between row groups"),
+        }
+        Ok(())
+    }
+
+    /// Schema field of the column: required int64, optional UTF-8 string, or a required list of
+    /// required int64 in the standard three-level layout
+    fn field(&self, name: &str) -> anyhow::Result<Arc<Type>> {
+        let field = match self {
+            ColumnData::Int64(_) => Type::primitive_type_builder(name, PhysicalType::INT64)
+                .with_repetition(Repetition::REQUIRED)
+                .build()?,
+            ColumnData::String(_) => Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+                .with_repetition(Repetition::OPTIONAL)
+                .with_converted_type(ConvertedType::UTF8)
+                .build()?,
+            ColumnData::Int64List(_) => {
+                let element = Type::primitive_type_builder("element", PhysicalType::INT64)
+                    .with_repetition(Repetition::REQUIRED)
+                    .build()?;
+                let list = Type::group_type_builder("list")
+                    .with_repetition(Repetition::REPEATED)
+                    .with_fields(vec![Arc::new(element)])
+                    .build()?;
+                Type::group_type_builder(name)
+                    .with_repetition(Repetition::REQUIRED)
+                    .with_converted_type(ConvertedType::LIST)
+                    .with_fields(vec![Arc::new(list)])
+                    .build()?
+            }
+        };
+        Ok(Arc::new(field))
+    }
+}
+
+/// Values read from a column chunk, before levels turn them into rows
+enum Values {
+    Ints(Vec<i64>),
+    Bytes(Vec<ByteArray>),
+}
+
+/// All records of a column chunk, with their definition and repetition levels
+fn read_records<T: DataType>(
+    mut reader: ColumnReaderImpl<T>,
+    defs: &mut Vec<i16>,
+    reps: &mut Vec<i16>,
+) -> anyhow::Result<Vec<T::T>> {
+    let mut values = Vec::new();
+    while reader
+        .read_records(READ_BATCH, Some(&mut *defs), Some(&mut *reps), &mut values)?
+        .0
+        > 0
+    {}
+    Ok(values)
+}
+
+/// Definition levels, repetition levels and values of a column chunk
+fn read_chunk(reader: ColumnReader) -> anyhow::Result<(Vec<i16>, Vec<i16>, Values)> {
+    let (mut defs, mut reps) = (Vec::new(), Vec::new());
+    let values = match reader {
+        ColumnReader::Int64ColumnReader(reader) => {
+            Values::Ints(read_records(reader, &mut defs, &mut reps)?)
+        }
+        ColumnReader::Int32ColumnReader(reader) => {
+            let values = read_records(reader, &mut defs, &mut reps)?;
+            Values::Ints(values.into_iter().map(i64::from).collect())
+        }
+        ColumnReader::ByteArrayColumnReader(reader) => {
+            Values::Bytes(read_records(reader, &mut defs, &mut reps)?)
+        }
+        _ => bail!("Only integer and string columns are supported"),
+    };
+    Ok((defs, reps, values))
+}
+
+/// Rows of a column from its levels and values. Null list elements are left out.
+fn assemble(
+    column: &ColumnDescriptor,
+    defs: &[i16],
+    reps: &[i16],
+    values: Values,
+) -> anyhow::Result<ColumnData> {
+    let (max_def, max_rep) = (column.max_def_level(), column.max_rep_level());
+    // without definition levels every value is present
+    let present = |i: usize| max_def == 0 || defs[i] == max_def;
+    match (values, max_rep) {
+        (Values::Ints(values), 0) => {
+            if max_def > 0 && defs.iter().any(|&d| d != max_def) {
+                bail!("Column has nulls");
+            }
+            Ok(ColumnData::Int64(values))
+        }
+        (Values::Bytes(values), 0) => {
+            let mut values = values.into_iter();
+            let rows = (0..values.len().max(defs.len()))
+                .map(|i| {
+                    present(i).then(|| {
+                        String::from_utf8_lossy(values.next().unwrap().data()).into_owned()
+                    })
+                })
+                .collect();
+            Ok(ColumnData::String(rows))
+        }
+        (Values::Ints(values), 1) => {
+            let mut values = values.into_iter();
+            let mut rows: Vec<Vec<i64>> = Vec::new();
+            for (i, &rep) in reps.iter().enumerate() {
+                if rep == 0 {
+                    rows.push(Vec::new());
+                }
+                if present(i) {
+                    rows.last_mut().unwrap().push(values.next().unwrap());
+                }
+            }
+            Ok(ColumnData::Int64List(rows))
+        }
+        _ => bail!("Column is neither integers, strings nor a list of integers"),
+    }
+}
+
+/// Columns `names` of a Parquet file, `None` for the ones it does not have
+pub fn read_columns(path: &Path, names: &[&str]) -> anyhow::Result<Vec<Option<ColumnData>>> {
+    let reader = SerializedFileReader::new(File::open(path)?)
+        .with_context(|| format!("{:?} is not a Parquet file", path))?;
+    let schema = reader.metadata().file_metadata().schema_descr_ptr();
+
+    let mut columns: Vec<Option<ColumnData>> = vec![None; names.len()];
+    for row_group in 0..reader.num_row_groups() {
+        let row_group = reader.get_row_group(row_group)?;
+        for (i, column) in schema.columns().iter().enumerate() {
+            let name = &column.path().parts()[0];
+            let Some(slot) = names.iter().position(|n| n == name) else {
+                continue;
+            };
+            let rows = read_chunk(row_group.get_column_reader(i)?)
+                .and_then(|(defs, reps, values)| assemble(column, &defs, &reps, values))
+                .with_context(|| format!("Reading column {}", name))?;
+            match &mut columns[slot] {
+                Some(column) => column
+                    .extend(rows)
+                    .with_context(|| format!("Reading column {}", name))?,
+                None => columns[slot] = Some(rows),
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// Writes `columns`, all as long as each other, in row groups of `ROW_GROUP_ROWS` rows
+pub fn write_columns(path: &Path, columns: &[(&str, ColumnData)]) -> anyhow::Result<()> {
+    let num_rows = columns.first().map_or(0, |(_, data)| data.len());
+    if columns.iter().any(|(_, data)| data.len() != num_rows) {
+        bail!("Parquet columns differ in length");
+    }
+    let fields = columns
+        .iter()
+        .map(|(name, data)| data.field(name))
+        .collect::<anyhow::Result<_>>()?;
+    let schema = Type::group_type_builder("schema")
+        .with_fields(fields)
+        .build()?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_created_by("snapviewer".to_string())
+        .build();
+    let mut writer =
+        SerializedFileWriter::new(File::create(path)?, Arc::new(schema), Arc::new(props))?;
+
+    for start in (0..num_rows).step_by(ROW_GROUP_ROWS) {
+        let rows = start..(start + ROW_GROUP_ROWS).min(num_rows);
+        let mut row_group = writer.next_row_group()?;
+        for (_, data) in columns {
+            let mut column = row_group
+                .next_column()?
+                .context("Parquet schema has too few columns")?;
+            match data.slice(rows.clone()) {
+                ColumnData::Int64(values) => {
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                ColumnData::String(values) => {
+                    let defs: Vec<i16> = values.iter().map(|v| v.is_some() as i16).collect();
+                    let present: Vec<ByteArray> =
+                        values.iter().flatten().map(|v| v.as_str().into()).collect();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&present, Some(&defs), None)?;
+                }
+                ColumnData::Int64List(rows) => {
+                    // an empty list is one level entry without a value
+                    let (mut reps, mut defs) = (Vec::new(), Vec::new());
+                    for row in &rows {
+                        let entries = row.len().max(1);
+                        reps.extend((0..entries).map(|i| (i > 0) as i16));
+                        defs.extend(std::iter::repeat_n(!row.is_empty() as i16, entries));
+                    }
+                    let values: Vec<i64> = rows.into_iter().flatten().collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, Some(&defs), Some(&reps))?;
+                }
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+/// One row per allocation: its index, size, lifetime, peak, category, callstack and geometry
+/// (timesteps and offsets as lists), the table `load::read_parquet` reads back
+pub fn write_allocations(
+    path: &Path,
+    allocs: &[Allocation],
+    callstacks: Vec<String>,
+) -> anyhow::Result<()> {
+    let ints = |f: &dyn Fn(&Allocation) -> u64| {
+        ColumnData::Int64(allocs.iter().map(|a| f(a) as i64).collect())
+    };
+    let lists = |f: &dyn Fn(&Allocation) -> &[u64]| {
+        ColumnData::Int64List(
+            allocs
+                .iter()
+                .map(|a| f(a).iter().map(|&v| v as i64).collect())
+                .collect(),
+        )
+    };
+    let categories = allocs
+        .iter()
+        .map(|a| a.category.map(|c| c.name().to_string()))
+        .collect();
+    let columns = [
+        ("idx", ColumnData::Int64((0..allocs.len() as i64).collect())),
+        ("size", ints(&|a| a.size)),
+        ("start", ints(&|a| a.start_end_time().0)),
+        ("stop", ints(&|a| a.start_end_time().1)),
+        ("peak_mem", ints(&|a| a.peak_mem)),
+        ("category", ColumnData::String(categories)),
+        (
+            "callstack",
+            ColumnData::String(callstacks.into_iter().map(Some).collect()),
+        ),
+        ("timesteps", lists(&|a| &a.timesteps)),
+        ("offsets", lists(&|a| &a.offsets)),
+    ];
+    write_columns(path, &columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parquet::{ColumnData, ROW_GROUP_ROWS, read_columns, write_columns};
+    use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use parquet::file::properties::{WriterProperties, WriterVersion};
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let columns = [
+            ("size", ColumnData::Int64(vec![512, 1 << 40, 3])),
+            (
+                "category",
+                ColumnData::String(vec![Some("activation".into()), None, Some("".into())]),
+            ),
+            (
+                "timesteps",
+                ColumnData::Int64List(vec![vec![0, 4, 7], vec![], vec![2]]),
+            ),
+        ];
+        let path = std::env::temp_dir().join(format!("snapviewer-{}.parquet", std::process::id()));
+        write_columns(&path, &columns).unwrap();
+        let read = read_columns(&path, &["timesteps", "missing", "category", "size"]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read[0].as_ref(), Some(&columns[2].1));
+        assert_eq!(read[1], None);
+        assert_eq!(read[2].as_ref(), Some(&columns[1].1));
+        assert_eq!(read[3].as_ref(), Some(&columns[0].1));
+    }
+
+    #[test]
+    fn test_parquet_row_groups() {
+        let rows = ROW_GROUP_ROWS * 2 + 5;
+        let columns = [
+            ("idx", ColumnData::Int64((0..rows as i64).collect())),
+            (
+                "timesteps",
+                ColumnData::Int64List((0..rows as i64).map(|i| vec![i, i + 1]).collect()),
+            ),
+        ];
+        let path =
+            std::env::temp_dir().join(format!("snapviewer-rg-{}.parquet", std::process::id()));
+        write_columns(&path, &columns).unwrap();
+        let read = read_columns(&path, &["idx", "timesteps"]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read[0].as_ref(), Some(&columns[0].1));
+        assert_eq!(read[1].as_ref(), Some(&columns[1].1));
+    }
+
+    #[test]
+    fn test_pyarrow_layout() {
+        // the schema and defaults of `pyarrow.parquet.write_table` for a DataFrame of these
+        // columns: everything nullable, lists with nullable elements, dictionary encoding, v2 pages
+        let schema = parse_message_type(
+            "message schema {
+                optional int64 size;
+                optional binary category (STRING);
+                optional group offsets (LIST) {
+                    repeated group list {
+                        optional int64 element;
+                    }
+                }
+            }",
+        )
+        .unwrap();
+        let props = WriterProperties::builder()
+            .set_writer_version(WriterVersion::PARQUET_2_0)
+            .set_dictionary_enabled(true)
+            .build();
+        let path =
+            std::env::temp_dir().join(format!("snapviewer-pa-{}.parquet", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer =
+            SerializedFileWriter::new(file, Arc::new(schema), Arc::new(props)).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column
+            .typed::<Int64Type>()
+            .write_batch(&[8, 8, 16], Some(&[1, 1, 1]), None)
+            .unwrap();
+        column.close().unwrap();
+        let mut column = row_group.next_column().unwrap().unwrap();
+        let categories: Vec<ByteArray> = vec!["parameter".into(), "parameter".into()];
+        column
+            .typed::<ByteArrayType>()
+            .write_batch(&categories, Some(&[1, 0, 1]), None)
+            .unwrap();
+        column.close().unwrap();
+        // rows [0, 64], [] and [32]: def 3 is a value, 1 an empty list
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column
+            .typed::<Int64Type>()
+            .write_batch(&[0, 64, 32], Some(&[3, 3, 1, 3]), Some(&[0, 1, 0, 0]))
+            .unwrap();
+        column.close().unwrap();
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        let read = read_columns(&path, &["size", "category", "offsets"]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read[0], Some(ColumnData::Int64(vec![8, 8, 16])));
+        assert_eq!(
+            read[1],
+            Some(ColumnData::String(vec![
+                Some("parameter".into()),
+                None,
+                Some("parameter".into())
+            ]))
+        );
+        assert_eq!(
+            read[2],
+            Some(ColumnData::Int64List(vec![vec![0, 64], vec![], vec![32]]))
+        );
+    }
+}
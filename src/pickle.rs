@@ -0,0 +1,505 @@
+//! Reader for the pickles `torch.cuda.memory._snapshot()` dumps are saved as: plain dicts, lists,
+//! strings and numbers written with pickle protocol 2 to 5. Pickles holding other Python objects
+//! are rejected, they would need Python to load.
+
+use anyhow::{Context, bail};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::rc::Rc;
+
+/// Snapshots nest a handful of levels deep; anything deeper is malformed or self-referencing
+/// and would overflow the stack
+const MAX_DEPTH: usize = 100;
+
+/// A Python object from a pickle. Containers are shared, the way the pickle shares them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(Rc<str>),
+    Bytes(Rc<[u8]>),
+    /// Lists, and sets and frozensets in no particular order
+    List(Rc<[Value]>),
+    Tuple(Rc<[Value]>),
+    /// Items in insertion order
+    Dict(Rc<[(Value, Value)]>),
+}
+
+impl Value {
+    /// Item `key` of a dict with string keys, `None` for anything else or a missing key
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Dict(items) => items
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(i) => Some(*i),
+            Value::Bool(b) => Some(*b as i64),
+            _ => None,
+        }
+    }
+
+    /// Items of a list or tuple
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) | Value::Tuple(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self, Value::None)
+    }
+}
+
+/// Formatted like Python's `str()`, for the scalars callstack frames hold
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::None => f.write_str("None"),
+            Value::Bool(true) => f.write_str("True"),
+            Value::Bool(false) => f.write_str("False"),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Str(s) => f.write_str(s),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Objects on the unpickler's stack and in its memo; lists and dicts are still being filled
+#[derive(Debug, Clone)]
+enum Obj {
+    Value(Value),
+    List(Rc<RefCell<Vec<Obj>>>),
+    Dict(Rc<RefCell<Vec<(Obj, Obj)>>>),
+    Tuple(Rc<[Obj]>),
+    Mark,
+}
+
+/// The finished objects. Containers only the object being frozen refers to are taken apart on
+/// the way, the ones reached through several references are built once.
+#[derive(Default)]
+struct Freezer {
+    shared: HashMap<*const (), Value>,
+    /// Containers being frozen, `MAX_DEPTH` at most
+    depth: usize,
+}
+
+impl Freezer {
+    fn freeze(&mut self, obj: Obj) -> anyhow::Result<Value> {
+        if let Obj::Value(value) = obj {
+            return Ok(value);
+        }
+        let (key, shared) = match &obj {
+            Obj::Value(_) | Obj::Mark => (std::ptr::null(), false),
+            Obj::List(list) => (Rc::as_ptr(list) as *const (), Rc::strong_count(list) > 1),
+            Obj::Dict(dict) => (Rc::as_ptr(dict) as *const (), Rc::strong_count(dict) > 1),
+            Obj::Tuple(items) => (Rc::as_ptr(items) as *const (), Rc::strong_count(items) > 1),
+        };
+        if let Some(value) = self.shared.get(&key) {
+            return Ok(value.clone());
+        }
+        if self.depth == MAX_DEPTH {
+            bail!(
+                "Malformed pickle: containers nested deeper than {} levels or containing themselves",
+                MAX_DEPTH
+            );
+        }
+        self.depth += 1;
+        let value = match obj {
+            Obj::Value(value) => value,
+            Obj::Mark => bail!("Malformed pickle: MARK left on the stack"),
+            Obj::List(list) => {
+                let items =
+                    Rc::try_unwrap(list).map_or_else(|rc| rc.borrow().clone(), RefCell::into_inner);
+                Value::List(self.freeze_all(items)?)
+            }
+            Obj::Tuple(items) => Value::Tuple(self.freeze_all(items.to_vec())?),
+            Obj::Dict(dict) => {
+                let items =
+                    Rc::try_unwrap(dict).map_or_else(|rc| rc.borrow().clone(), RefCell::into_inner);
+                Value::Dict(
+                    items
+                        .into_iter()
+                        .map(|(k, v)| Ok((self.freeze(k)?, self.freeze(v)?)))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
+        };
+        self.depth -= 1;
+        if shared {
+            self.shared.insert(key, value.clone());
+        }
+        Ok(value)
+    }
+
+    fn freeze_all(&mut self, objs: Vec<Obj>) -> anyhow::Result<Rc<[Value]>> {
+        objs.into_iter().map(|obj| self.freeze(obj)).collect()
+    }
+}
+
+struct Unpickler<R> {
+    reader: R,
+    /// Bytes read so far, for error messages
+    pos: u64,
+    stack: Vec<Obj>,
+    memo: HashMap<u32, Obj>,
+}
+
+impl<R: Read> Unpickler<R> {
+    /// `n` comes from the pickle, so the buffer only grows as the data actually arrives
+    fn bytes(&mut self, n: usize) -> anyhow::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        (&mut self.reader)
+            .take(n as u64)
+            .read_to_end(&mut buf)
+            .context("Truncated pickle")?;
+        if buf.len() != n {
+            bail!("Truncated pickle");
+        }
+        self.pos += n as u64;
+        Ok(buf)
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let mut buf = [0; N];
+        self.reader
+            .read_exact(&mut buf)
+            .context("Truncated pickle")?;
+        self.pos += N as u64;
+        Ok(buf)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn string(&mut self, len: usize) -> anyhow::Result<Value> {
+        let bytes = self.bytes(len)?;
+        let s = String::from_utf8(bytes).context("Malformed pickle: invalid UTF-8 string")?;
+        Ok(Value::Str(s.into()))
+    }
+
+    /// Little-endian two's complement, as LONG1 and LONG4 store ints
+    fn long(&mut self, len: usize) -> anyhow::Result<Value> {
+        if len > 8 {
+            bail!("Integer of {} bytes does not fit in 64 bits", len);
+        }
+        let bytes = self.bytes(len)?;
+        let negative = bytes.last().is_some_and(|&b| b & 0x80 != 0);
+        let mut buf = [if negative { 0xff } else { 0 }; 8];
+        buf[..len].copy_from_slice(&bytes);
+        Ok(Value::Int(i64::from_le_bytes(buf)))
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(Obj::Value(value));
+    }
+
+    fn pop(&mut self) -> anyhow::Result<Obj> {
+        self.stack
+            .pop()
+            .context("Malformed pickle: stack underflow")
+    }
+
+    fn top(&self) -> anyhow::Result<&Obj> {
+        self.stack.last().context("Malformed pickle: empty stack")
+    }
+
+    /// Objects pushed since the last MARK, which is removed
+    fn pop_mark(&mut self) -> anyhow::Result<Vec<Obj>> {
+        let mark = self
+            .stack
+            .iter()
+            .rposition(|obj| matches!(obj, Obj::Mark))
+            .context("Malformed pickle: no MARK")?;
+        let items = self.stack.split_off(mark + 1);
+        self.stack.pop();
+        Ok(items)
+    }
+
+    fn append(&mut self, items: Vec<Obj>) -> anyhow::Result<()> {
+        match self.top()? {
+            Obj::List(list) => list.borrow_mut().extend(items),
+            other => bail!("Malformed pickle: appending to {:?}", other),
+        }
+        Ok(())
+    }
+
+    fn set_items(&mut self, items: Vec<Obj>) -> anyhow::Result<()> {
+        if !items.len().is_multiple_of(2) {
+            bail!("Malformed pickle: odd number of dict items");
+        }
+        match self.top()? {
+            Obj::Dict(dict) => {
+                let mut dict = dict.borrow_mut();
+                let mut items = items.into_iter();
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    dict.push((k, v));
+                }
+            }
+            other => bail!("Malformed pickle: setting items of {:?}", other),
+        }
+        Ok(())
+    }
+
+    fn memo_get(&mut self, index: u32) -> anyhow::Result<()> {
+        let obj = self
+            .memo
+            .get(&index)
+            .with_context(|| format!("Malformed pickle: memo {} is not set", index))?
+            .clone();
+        self.stack.push(obj);
+        Ok(())
+    }
+
+    fn memo_put(&mut self, index: u32) -> anyhow::Result<()> {
+        let obj = self.top()?.clone();
+        self.memo.insert(index, obj);
+        Ok(())
+    }
+
+    fn tuple(&mut self, items: Vec<Obj>) {
+        self.stack.push(Obj::Tuple(items.into()));
+    }
+
+    fn load(mut self) -> anyhow::Result<Value> {
+        loop {
+            let opcode = self.u8()?;
+            match opcode {
+                // PROTO
+                0x80 => {
+                    let proto = self.u8()?;
+                    if !(2..=5).contains(&proto) {
+                        bail!("Unsupported pickle protocol {}", proto);
+                    }
+                }
+                // FRAME, only a hint for buffering
+                0x95 => {
+                    self.u64()?;
+                }
+                b'.' => {
+                    let obj = self.pop()?;
+                    // only references between objects keep containers shared now
+                    self.memo.clear();
+                    self.stack.clear();
+                    return Freezer::default().freeze(obj);
+                }
+                b'(' => self.stack.push(Obj::Mark),
+                b'0' => {
+                    self.pop()?;
+                }
+                b'1' => {
+                    self.pop_mark()?;
+                }
+                b'2' => {
+                    let obj = self.top()?.clone();
+                    self.stack.push(obj);
+                }
+                b'N' => self.push(Value::None),
+                0x88 => self.push(Value::Bool(true)),
+                0x89 => self.push(Value::Bool(false)),
+                b'J' => {
+                    let i = i32::from_le_bytes(self.array()?);
+                    self.push(Value::Int(i as i64));
+                }
+                b'K' => {
+                    let i = self.u8()?;
+                    self.push(Value::Int(i as i64));
+                }
+                b'M' => {
+                    let i = u16::from_le_bytes(self.array()?);
+                    self.push(Value::Int(i as i64));
+                }
+                0x8a => {
+                    let len = self.u8()? as usize;
+                    let value = self.long(len)?;
+                    self.stack.push(Obj::Value(value));
+                }
+                0x8b => {
+                    let len = self.u32()? as usize;
+                    let value = self.long(len)?;
+                    self.stack.push(Obj::Value(value));
+                }
+                b'G' => {
+                    let x = f64::from_be_bytes(self.array()?);
+                    self.push(Value::Float(x));
+                }
+                // SHORT_BINUNICODE, BINUNICODE, BINUNICODE8, and Python 2 strings
+                0x8c | b'U' => {
+                    let len = self.u8()? as usize;
+                    let value = self.string(len)?;
+                    self.stack.push(Obj::Value(value));
+                }
+                b'X' | b'T' => {
+                    let len = self.u32()? as usize;
+                    let value = self.string(len)?;
+                    self.stack.push(Obj::Value(value));
+                }
+                0x8d => {
+                    let len = self.u64()? as usize;
+                    let value = self.string(len)?;
+                    self.stack.push(Obj::Value(value));
+                }
+                // SHORT_BINBYTES, BINBYTES, BINBYTES8
+                b'C' => {
+                    let len = self.u8()? as usize;
+                    let bytes = self.bytes(len)?;
+                    self.push(Value::Bytes(bytes.into()));
+                }
+                b'B' => {
+                    let len = self.u32()? as usize;
+                    let bytes = self.bytes(len)?;
+                    self.push(Value::Bytes(bytes.into()));
+                }
+                0x8e => {
+                    let len = self.u64()? as usize;
+                    let bytes = self.bytes(len)?;
+                    self.push(Value::Bytes(bytes.into()));
+                }
+                // EMPTY_LIST, EMPTY_SET
+                b']' | 0x8f => self.stack.push(Obj::List(Default::default())),
+                b'}' => self.stack.push(Obj::Dict(Default::default())),
+                b')' => self.tuple(Vec::new()),
+                b'a' => {
+                    let item = self.pop()?;
+                    self.append(vec![item])?;
+                }
+                // APPENDS, ADDITEMS
+                b'e' | 0x90 => {
+                    let items = self.pop_mark()?;
+                    self.append(items)?;
+                }
+                b's' => {
+                    let value = self.pop()?;
+                    let key = self.pop()?;
+                    self.set_items(vec![key, value])?;
+                }
+                b'u' => {
+                    let items = self.pop_mark()?;
+                    self.set_items(items)?;
+                }
+                b't' => {
+                    let items = self.pop_mark()?;
+                    self.tuple(items);
+                }
+                // FROZENSET
+                0x91 => {
+                    let items = self.pop_mark()?;
+                    self.stack.push(Obj::List(Rc::new(RefCell::new(items))));
+                }
+                0x85..=0x87 => {
+                    let n = (opcode - 0x84) as usize;
+                    if self.stack.len() < n {
+                        bail!("Malformed pickle: stack underflow");
+                    }
+                    let items = self.stack.split_off(self.stack.len() - n);
+                    self.tuple(items);
+                }
+                // MEMOIZE, BINPUT, LONG_BINPUT
+                0x94 => self.memo_put(self.memo.len() as u32)?,
+                b'q' => {
+                    let index = self.u8()? as u32;
+                    self.memo_put(index)?;
+                }
+                b'r' => {
+                    let index = self.u32()?;
+                    self.memo_put(index)?;
+                }
+                // BINGET, LONG_BINGET
+                b'h' => {
+                    let index = self.u8()? as u32;
+                    self.memo_get(index)?;
+                }
+                b'j' => {
+                    let index = self.u32()?;
+                    self.memo_get(index)?;
+                }
+                b'c' | 0x93 | b'R' | 0x81 | 0x92 | b'b' => bail!(
+                    "The pickle holds Python objects (opcode {:#04x} at byte {}), only dicts, \
+                     lists, strings and numbers are supported",
+                    opcode,
+                    self.pos - 1
+                ),
+                _ => bail!(
+                    "Unsupported pickle opcode {:#04x} at byte {}",
+                    opcode,
+                    self.pos - 1
+                ),
+            }
+        }
+    }
+}
+
+/// The object pickled in `reader`
+pub fn load(reader: impl Read) -> anyhow::Result<Value> {
+    Unpickler {
+        reader,
+        pos: 0,
+        stack: Vec::new(),
+        memo: HashMap::new(),
+    }
+    .load()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pickle::{Value, load};
+
+    #[test]
+    fn test_load_pickle() {
+        // pickle.dumps({"device_traces": [[{"action": "alloc", "size": 512, "addr": 2**40,
+        // "frames": f}, {"action": "free", "size": 512, "addr": 2**40, "frames": f}]],
+        // "ok": (True, None, -1.5)}, protocol=4) with f = [{"line": -3}] shared
+        let bytes = b"\x80\x04\x95\x97\x00\x00\x00\x00\x00\x00\x00}\x94(\x8c\x0ddevice_traces\x94]\x94]\x94(}\x94(\x8c\x06action\x94\x8c\x05alloc\x94\x8c\x04size\x94M\x00\x02\x8c\x04addr\x94\x8a\x06\x00\x00\x00\x00\x00\x01\x8c\x06frames\x94]\x94}\x94\x8c\x04line\x94J\xfd\xff\xff\xffsau}\x94(h\x05\x8c\x04free\x94h\x07M\x00\x02h\x08\x8a\x06\x00\x00\x00\x00\x00\x01h\x09h\x0auea\x8c\x02ok\x94\x88NG\xbf\xf8\x00\x00\x00\x00\x00\x00\x87\x94u.";
+        let value = load(&bytes[..]).unwrap();
+
+        let trace = &value.get("device_traces").unwrap().as_list().unwrap()[0];
+        let events = trace.as_list().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].get("action").unwrap().as_str(), Some("alloc"));
+        assert_eq!(events[0].get("size").unwrap().as_i64(), Some(512));
+        assert_eq!(events[0].get("addr").unwrap().as_i64(), Some(1 << 40));
+        let frames = events[0].get("frames").unwrap().as_list().unwrap();
+        assert_eq!(frames[0].get("line").unwrap().as_i64(), Some(-3));
+        // the second event refers to the first one's frames through the memo
+        assert_eq!(events[1].get("frames"), events[0].get("frames"));
+        assert_eq!(events[1].get("action").unwrap().as_str(), Some("free"));
+
+        let ok = value.get("ok").unwrap().as_list().unwrap();
+        assert_eq!(ok, [Value::Bool(true), Value::None, Value::Float(-1.5)]);
+        assert_eq!(ok[1].to_string(), "None");
+
+        assert!(load(&b"\x80\x04\x8c\x01a"[..]).is_err());
+        assert!(load(&b"\x80\x04cbuiltins\nset\n"[..]).is_err());
+        // a huge length with no data behind it, and l = []; l.append(l)
+        assert!(load(&b"\x80\x04\x8e\xff\xff\xff\xff\xff\xff\x00\x00"[..]).is_err());
+        assert!(load(&b"\x80\x04]\x94h\x00a."[..]).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Frame times kept for percentiles
+const RECENT_FRAMES: usize = 10_000;
+/// Summary file is rewritten this often, the renderer is usually killed rather than exited
+const WRITE_INTERVAL: Duration = Duration::from_secs(5);
+const RENDERER_HEADER: &str = "== Renderer startup stages ==";
+
+/// `--self-profile`: times startup stages and frames, and writes a summary file users can attach
+/// to bug reports. Does nothing if created with `Profiler::disabled`.
+///
+/// Anything already in the file when the renderer starts (e.g. the GUI's hash/convert stages)
+/// is kept at the top of the summary.
+pub struct Profiler {
+    path: Option<PathBuf>,
+    preamble: String,
+    stages: Vec<(String, Duration)>,
+    stage_start: Instant,
+    recent_frames: VecDeque<Duration>,
+    num_frames: u64,
+    total_frame_time: Duration,
+    max_frame_time: Duration,
+    last_write: Instant,
+}
+
+impl Profiler {
+    pub fn new(path: PathBuf) -> Self {
+        // Drop the renderer sections of an earlier run
+        let mut preamble = std::fs::read_to_string(&path).unwrap_or_default();
+        if let Some(end) = preamble.find(RENDERER_HEADER) {
+            preamble.truncate(end);
+        }
+        Self {
+            path: Some(path),
+            preamble,
+            ..Self::disabled()
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self {
+            path: None,
+            preamble: String::new(),
+            stages: Vec::new(),
+            stage_start: Instant::now(),
+            recent_frames: VecDeque::new(),
+            num_frames: 0,
+            total_frame_time: Duration::ZERO,
+            max_frame_time: Duration::ZERO,
+            last_write: Instant::now(),
+        }
+    }
+
+    /// Ends the current startup stage, naming it `name`, and starts the next one
+    pub fn stage(&mut self, name: &str) {
+        if self.path.is_none() {
+            return;
+        }
+        self.stages
+            .push((name.to_string(), self.stage_start.elapsed()));
+        self.stage_start = Instant::now();
+        self.write();
+    }
+
+    pub fn frame(&mut self, frame_time: Duration) {
+        if self.path.is_none() {
+            return;
+        }
+        self.num_frames += 1;
+        self.total_frame_time += frame_time;
+        self.max_frame_time = self.max_frame_time.max(frame_time);
+        if self.recent_frames.len() == RECENT_FRAMES {
+            self.recent_frames.pop_front();
+        }
+        self.recent_frames.push_back(frame_time);
+
+        if self.last_write.elapsed() >= WRITE_INTERVAL {
+            self.write();
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let mut out = self.preamble.clone();
+        let _ = writeln!(out, "{}", RENDERER_HEADER);
+        for (name, time) in self.stages.iter() {
+            let _ = writeln!(out, "{:<24} {:>10.3} s", name, time.as_secs_f64());
+        }
+
+        let _ = writeln!(out, "== Renderer frames ==");
+        let _ = writeln!(out, "frames                   {:>10}", self.num_frames);
+        if self.num_frames > 0 {
+            let mut recent: Vec<Duration> = self.recent_frames.iter().copied().collect();
+            recent.sort_unstable();
+            let percentile = |p: f64| recent[((recent.len() - 1) as f64 * p) as usize];
+            let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+
+            let mean = self.total_frame_time / self.num_frames as u32;
+            let _ = writeln!(out, "mean                     {:>10.3} ms", ms(mean));
+            let _ = writeln!(
+                out,
+                "p50 (recent)             {:>10.3} ms",
+                ms(percentile(0.5))
+            );
+            let _ = writeln!(
+                out,
+                "p95 (recent)             {:>10.3} ms",
+                ms(percentile(0.95))
+            );
+            let _ = writeln!(
+                out,
+                "max                      {:>10.3} ms",
+                ms(self.max_frame_time)
+            );
+        }
+        out
+    }
+
+    fn write(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(e) = std::fs::write(path, self.summary()) {
+            log::warn!("Failed to write self-profile to {:?}: {}", path, e);
+        }
+        self.last_write = Instant::now();
+    }
+}
@@ -0,0 +1,216 @@
+//! Memory timelines exported by the PyTorch profiler (`prof.export_memory_timeline(path)` with a
+//! `.json`, `.json.gz` or `.raw.json.gz` path), which record memory by category rather than
+//! individual allocations with callstacks. Turned into a trace like a snapshot's, so they are
+//! converted and viewed the same way.
+
+use crate::convert::{TraceEvent, labelled_event};
+use anyhow::{Context, bail};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Categories in the order the profiler indexes them (`_CATEGORY_TO_INDEX` in
+/// `torch.profiler._memory_profiler`), the last one for memory it could not categorize
+const CATEGORIES: [Option<&str>; 8] = [
+    Some("parameter"),
+    Some("optimizer_state"),
+    Some("input"),
+    Some("temporary"),
+    Some("activation"),
+    Some("gradient"),
+    Some("autograd_detail"),
+    None,
+];
+
+/// A profiler memory timeline as a trace on device 0. Accepts both exports:
+/// - `[times, sizes]`, bytes of each category at each time (µs): growth of a category becomes an
+///   allocation, shrinking frees its most recent allocations, like samples from `record`
+/// - raw `[time, action, bytes, category]` events (time in ns, -1 for memory allocated before
+///   profiling): positive bytes allocate, negative ones free the most recent allocation of the
+///   same category and size
+pub fn timeline_to_trace(json: &Value) -> anyhow::Result<Vec<TraceEvent>> {
+    let Some(items) = json.as_array() else {
+        bail!("Not a profiler memory timeline: expected a JSON array");
+    };
+    let is_raw = items
+        .first()
+        .and_then(Value::as_array)
+        .is_some_and(|first| first.len() == 4 && first.iter().all(Value::is_number));
+    if is_raw {
+        raw_to_trace(items)
+    } else {
+        match items.as_slice() {
+            [times, sizes] => coalesced_to_trace(times, sizes),
+            _ => bail!("Not a profiler memory timeline: expected [times, sizes] or raw events"),
+        }
+    }
+}
+
+fn category_of(index: usize) -> anyhow::Result<Option<&'static str>> {
+    CATEGORIES
+        .get(index)
+        .copied()
+        .with_context(|| format!("Unknown memory category {}", index))
+}
+
+fn event(action: &str, addr: u64, size: u64, time_us: u64, category: Option<&str>) -> TraceEvent {
+    let label = category.unwrap_or("uncategorized");
+    labelled_event(action, addr, size, time_us, category, "<profiler>", label)
+}
+
+fn coalesced_to_trace(times: &Value, sizes: &Value) -> anyhow::Result<Vec<TraceEvent>> {
+    let parse = || -> Option<(Vec<u64>, Vec<Vec<i64>>)> {
+        let times = times
+            .as_array()?
+            .iter()
+            .map(Value::as_u64)
+            .collect::<Option<_>>()?;
+        let sizes = sizes
+            .as_array()?
+            .iter()
+            .map(|row| row.as_array()?.iter().map(Value::as_i64).collect())
+            .collect::<Option<_>>()?;
+        Some((times, sizes))
+    };
+    let (times, sizes) = parse().context("Profiler memory timeline with non-integer values")?;
+    if times.len() != sizes.len() {
+        bail!(
+            "Profiler memory timeline has {} times but {} rows of sizes",
+            times.len(),
+            sizes.len()
+        );
+    }
+
+    let mut trace = Vec::new();
+    // (addr, size) of the live allocations of each category, newest last
+    let mut stacks: Vec<Vec<(u64, u64)>> = vec![Vec::new(); CATEGORIES.len()];
+    let mut used = [0; CATEGORIES.len()];
+    let mut next_addr = 0;
+    for (&time_us, row) in times.iter().zip(&sizes) {
+        // the first column is unused by the profiler
+        for (index, &target) in row.iter().skip(1).enumerate() {
+            let category = category_of(index)?;
+            let target = target.max(0) as u64;
+            while used[index] > target {
+                let (addr, size) = stacks[index].pop().unwrap();
+                trace.push(event("free_completed", addr, size, time_us, category));
+                used[index] -= size;
+            }
+            if target > used[index] {
+                let size = target - used[index];
+                trace.push(event("alloc", next_addr, size, time_us, category));
+                stacks[index].push((next_addr, size));
+                next_addr += 1;
+                used[index] = target;
+            }
+        }
+    }
+    Ok(trace)
+}
+
+fn raw_to_trace(items: &[Value]) -> anyhow::Result<Vec<TraceEvent>> {
+    let events = items
+        .iter()
+        .map(|item| {
+            let fields: Option<Vec<i64>> = item.as_array()?.iter().map(Value::as_i64).collect();
+            match fields?.as_slice() {
+                &[time_ns, _action, bytes, category] => Some((time_ns, bytes, category)),
+                _ => None,
+            }
+        })
+        .collect::<Option<Vec<_>>>()
+        .context("Raw profiler memory events must be [time, action, bytes, category]")?;
+    // memory allocated before profiling started
+    let start_ns = events
+        .iter()
+        .map(|&(t, _, _)| t)
+        .filter(|&t| t >= 0)
+        .min()
+        .unwrap_or(0);
+
+    let mut trace = Vec::new();
+    // addrs of the live allocations of each (category, size), newest last
+    let mut live: HashMap<(usize, u64), Vec<u64>> = HashMap::new();
+    let mut unmatched = 0;
+    for (addr, &(time_ns, bytes, index)) in events.iter().enumerate() {
+        let index = index.clamp(0, CATEGORIES.len() as i64 - 1) as usize;
+        let category = category_of(index)?;
+        let time_us = time_ns.max(start_ns) as u64 / 1000;
+        let size = bytes.unsigned_abs();
+        if bytes > 0 {
+            trace.push(event("alloc", addr as u64, size, time_us, category));
+            live.entry((index, size)).or_default().push(addr as u64);
+        } else if bytes < 0 {
+            match live.get_mut(&(index, size)).and_then(Vec::pop) {
+                Some(addr) => trace.push(event("free_completed", addr, size, time_us, category)),
+                None => unmatched += 1,
+            }
+        }
+    }
+    if unmatched > 0 {
+        log::warn!(
+            "{} frees in the profiler memory timeline match no allocation, ignored",
+            unmatched
+        );
+    }
+    Ok(trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profiler_timeline::timeline_to_trace;
+    use serde_json::json;
+
+    fn summary(json: serde_json::Value) -> Vec<(String, u64, Option<String>)> {
+        timeline_to_trace(&json)
+            .unwrap()
+            .iter()
+            .map(|e| {
+                let category = e.category.as_deref().map(str::to_string);
+                (e.action.to_string(), e.size, category)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_profiler_timelines() {
+        let ev = |action: &str, size, category: &str| {
+            (action.to_string(), size, Some(category.to_string()))
+        };
+        // parameters grow to 100 bytes, then activations come and go
+        let coalesced = json!([
+            [10, 20, 30],
+            [
+                [0, 100, 0, 0, 0, 0, 0, 0, 0],
+                [0, 100, 0, 0, 0, 50, 0, 0, 0],
+                [0, 100, 0, 0, 0, 20, 0, 0, 0]
+            ]
+        ]);
+        assert_eq!(
+            summary(coalesced),
+            [
+                ev("alloc", 100, "parameter"),
+                ev("alloc", 50, "activation"),
+                ev("free_completed", 50, "activation"),
+                ev("alloc", 20, "activation"),
+            ]
+        );
+
+        let raw = json!([
+            [-1, 1, 100, 0],
+            [5000, 2, 64, 4],
+            [6000, 2, 64, 4],
+            [7000, 4, -64, 4],
+            [8000, 4, -32, 4]
+        ]);
+        assert_eq!(
+            summary(raw),
+            [
+                ev("alloc", 100, "parameter"),
+                ev("alloc", 64, "activation"),
+                ev("alloc", 64, "activation"),
+                ev("free_completed", 64, "activation"),
+            ]
+        );
+        assert!(timeline_to_trace(&json!({"times": []})).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{
+    geometry::{AllocationGeometry, TraceGeometry},
+    ticks::memory_ticks,
+};
+use anyhow::Context;
+use std::io::BufWriter;
+use std::path::Path;
+use three_d::Srgba;
+
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+const GRID_COLOR: [u8; 3] = [215, 215, 215];
+
+/// An RGB image drawn on the CPU, so it can be made without a window or GPU
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Rows top to bottom, 3 bytes per pixel
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: BACKGROUND.repeat(width as usize * height as usize),
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let i = (y as usize * self.width as usize + x as usize) * 3;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    /// Blend `color` over rows `rows` of column `x`
+    fn fill_column(&mut self, x: u32, rows: std::ops::Range<u32>, color: Srgba) {
+        let alpha = color.a as u32;
+        for y in rows {
+            let i = (y as usize * self.width as usize + x as usize) * 3;
+            for (dst, src) in self.pixels[i..i + 3]
+                .iter_mut()
+                .zip([color.r, color.g, color.b])
+            {
+                *dst = ((*dst as u32 * (255 - alpha) + src as u32 * alpha + 127) / 255) as u8;
+            }
+        }
+    }
+
+    pub fn write_png(&self, path: &Path) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Could not create {}", path.display()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Rows whose centers lie between the screen coords `top` and `bottom`
+fn covered_rows(top: f64, bottom: f64, height: u32) -> std::ops::Range<u32> {
+    let first = (top - 0.5).ceil().clamp(0.0, height as f64) as u32;
+    let last = (bottom - 0.5).ceil().clamp(0.0, height as f64) as u32;
+    first..last.max(first)
+}
+
+/// The plot rasterized the way the window draws it, with memory grid lines but without text.
+///
+/// Same arguments as `svg::render`: `x_range` and `y_range` are the drawn world coords, `size`
+/// the image size in pixels. Each allocation is sampled at the center of every pixel column.
+///
+/// ## Returns
+/// The image and the number of allocations in it.
+pub fn render<'a>(
+    geom: &TraceGeometry,
+    allocations: impl Iterator<Item = (&'a AllocationGeometry, Srgba)>,
+    x_range: (f32, f32),
+    y_range: (f32, f32),
+    size: (u32, u32),
+) -> (Image, usize) {
+    let mut image = Image::new(size.0, size.1);
+    let (width, height) = (size.0 as f64, size.1 as f64);
+    let (x0, x1) = (x_range.0 as f64, x_range.1 as f64);
+    let (y0, y1) = (y_range.0 as f64, y_range.1 as f64);
+    let sx = |x: f64| (x - x0) / (x1 - x0) * width;
+    let sy = |y: f64| (y1 - geom.y_axis.map(y)) / (y1 - y0) * height;
+
+    for (_, ratio) in memory_ticks(geom, y_range) {
+        let y = ((1.0 - ratio as f64) * height).floor();
+        if (0.0..height).contains(&y) {
+            let row = y as usize * size.0 as usize * 3;
+            for pixel in image.pixels[row..row + size.0 as usize * 3].chunks_mut(3) {
+                pixel.copy_from_slice(&GRID_COLOR);
+            }
+        }
+    }
+
+    let column_width = (x1 - x0) / width;
+    let mut count = 0;
+    for (alloc, color) in allocations {
+        let (start, stop) = alloc.drawn_time_range();
+        if stop < x0 || start > x1 {
+            continue;
+        }
+        let mut alloc = alloc.decimated(column_width);
+        let marker = alloc.is_marker();
+        alloc.widen_marker();
+        for (t, o) in alloc.timesteps.windows(2).zip(alloc.offsets.windows(2)) {
+            let (left, right) = (sx(t[0]), sx(t[1]));
+            let first = (left - 0.5).ceil().clamp(0.0, width) as u32;
+            let mut last = (right - 0.5).ceil().clamp(0.0, width) as u32;
+            if marker {
+                // at least a pixel, however far zoomed out
+                last = last.max(first + 1).min(size.0);
+            }
+            for x in first..last {
+                // offsets are linear between vertices, as in the mesh
+                let frac = (x as f64 + 0.5 - left) / (right - left);
+                let offset = o[0] + (o[1] - o[0]) * frac;
+                let rows = covered_rows(sy(offset + alloc.size), sy(offset), size.1);
+                image.fill_column(x, rows, color);
+            }
+        }
+        count += 1;
+    }
+    (image, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::Allocation;
+    use crate::geometry::TraceGeometry;
+    use crate::raster::render;
+    use std::sync::Arc;
+    use three_d::Srgba;
+
+    #[test]
+    fn test_render_raster() {
+        let alloc = |start, stop, offset| Allocation {
+            timesteps: vec![start, stop].into(),
+            offsets: vec![offset, offset].into(),
+            size: 1024,
+            peak_mem: 0,
+            peak_timestamps: vec![],
+            category: None,
+        };
+        let allocs: Arc<[Allocation]> = Arc::from(vec![alloc(0, 10, 0), alloc(50, 100, 1024)]);
+        let geom = TraceGeometry::from_allocations(allocs, (100, 100));
+        let color = Srgba::new(255, 0, 0, 255);
+        let colored = geom.allocations.iter().map(|a| (a, color));
+
+        // only the first allocation is in the first fifth of the time axis
+        let (image, count) = render(&geom, colored, (0.0, 20.0), (0.0, 100.0), (20, 10));
+        assert_eq!(count, 1);
+        assert_eq!(image.pixels.len(), 20 * 10 * 3);
+        // 0..10 of 0..20 is the left half; 1 KiB of the 2 KiB peak is the bottom half
+        assert_eq!(image.pixel(0, 9), [255, 0, 0]);
+        assert_eq!(image.pixel(9, 5), [255, 0, 0]);
+        assert_ne!(image.pixel(10, 9), [255, 0, 0]);
+        assert_ne!(image.pixel(0, 4), [255, 0, 0]);
+    }
+}
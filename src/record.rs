@@ -0,0 +1,201 @@
+//! GPU memory sampling behind the `record` subcommand, for PyTorch versions without
+//! `_record_memory_history`. NVML is loaded at runtime so the renderer does not need it to start.
+//! The CSV it writes (`time_us,used_bytes`) converts to a viewable snapshot with `snapviewer-renderer convert`.
+
+use crate::error::SnapError;
+use anyhow::{Context, Result, bail};
+use libloading::{Library, Symbol};
+use std::ffi::c_void;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const CSV_HEADER: &str = "time_us,used_bytes";
+
+#[cfg(windows)]
+const NVML_LIBRARY: &str = "nvml.dll";
+#[cfg(not(windows))]
+const NVML_LIBRARY: &str = "libnvidia-ml.so.1";
+
+const NVML_SUCCESS: i32 = 0;
+const NVML_ERROR_INSUFFICIENT_SIZE: i32 = 7;
+
+type Device = *mut c_void;
+
+/// `nvmlProcessInfo_t` as of the `_v3` functions
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct ProcessInfo {
+    pid: u32,
+    used_gpu_memory: u64,
+    gpu_instance_id: u32,
+    compute_instance_id: u32,
+}
+
+/// `nvmlMemory_t`
+#[repr(C)]
+#[derive(Debug, Default)]
+struct MemoryInfo {
+    total: u64,
+    free: u64,
+    used: u64,
+}
+
+/// The few NVML functions sampling needs
+struct Nvml {
+    lib: Library,
+    device: Device,
+}
+
+fn check(code: i32, what: &str) -> Result<()> {
+    if code != NVML_SUCCESS {
+        bail!("{} failed with NVML error {}", what, code);
+    }
+    Ok(())
+}
+
+impl Nvml {
+    fn open(device_index: u32) -> Result<Self> {
+        // SAFETY: loading NVML runs no initialization beyond the dynamic linker's
+        let lib = unsafe { Library::new(NVML_LIBRARY) }.with_context(|| {
+            format!(
+                "Could not load {}, is the NVIDIA driver installed?",
+                NVML_LIBRARY
+            )
+        })?;
+        let mut device: Device = std::ptr::null_mut();
+        // SAFETY: signatures from nvml.h
+        unsafe {
+            let init: Symbol<unsafe extern "C" fn() -> i32> = lib.get(b"nvmlInit_v2")?;
+            check(init(), "nvmlInit")?;
+            let get_handle: Symbol<unsafe extern "C" fn(u32, *mut Device) -> i32> =
+                lib.get(b"nvmlDeviceGetHandleByIndex_v2")?;
+            check(
+                get_handle(device_index, &mut device),
+                &format!("Opening device {}", device_index),
+            )?;
+        }
+        Ok(Self { lib, device })
+    }
+
+    /// Memory used by the whole device
+    fn device_used(&self) -> Result<u64> {
+        let mut info = MemoryInfo::default();
+        // SAFETY: signature from nvml.h, `info` outlives the call
+        unsafe {
+            let get_info: Symbol<unsafe extern "C" fn(Device, *mut MemoryInfo) -> i32> =
+                self.lib.get(b"nvmlDeviceGetMemoryInfo")?;
+            check(get_info(self.device, &mut info), "nvmlDeviceGetMemoryInfo")?;
+        }
+        Ok(info.used)
+    }
+
+    /// Memory used by process `pid`, `None` if it has no context on the device (anymore)
+    fn process_used(&self, pid: u32) -> Result<Option<u64>> {
+        let mut infos = vec![ProcessInfo::default(); 64];
+        // SAFETY: signature from nvml.h, NVML writes at most `count` entries
+        unsafe {
+            let get_processes: Symbol<
+                unsafe extern "C" fn(Device, *mut u32, *mut ProcessInfo) -> i32,
+            > = self.lib.get(b"nvmlDeviceGetComputeRunningProcesses_v3")?;
+            loop {
+                let mut count = infos.len() as u32;
+                match get_processes(self.device, &mut count, infos.as_mut_ptr()) {
+                    NVML_ERROR_INSUFFICIENT_SIZE => {
+                        infos.resize(count as usize * 2, ProcessInfo::default())
+                    }
+                    code => {
+                        check(code, "nvmlDeviceGetComputeRunningProcesses")?;
+                        infos.truncate(count as usize);
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(infos
+            .iter()
+            .find(|info| info.pid == pid)
+            .map(|info| info.used_gpu_memory))
+    }
+}
+
+impl Drop for Nvml {
+    fn drop(&mut self) {
+        // SAFETY: initialized in `open`
+        unsafe {
+            if let Ok(shutdown) = self
+                .lib
+                .get::<unsafe extern "C" fn() -> i32>(b"nvmlShutdown")
+            {
+                shutdown();
+            }
+        }
+    }
+}
+
+pub fn csv_line(time_us: u128, used_bytes: u64) -> String {
+    format!("{},{}", time_us, used_bytes)
+}
+
+/// Samples the memory of process `pid` (or the whole device) on `device` every `interval`
+/// into `out`, until the process exits or `duration` elapses. Lines are flushed as they are
+/// written so interrupting with Ctrl+C keeps everything so far.
+///
+/// ## Returns
+/// The number of samples written.
+pub fn record(
+    pid: Option<u32>,
+    device: u32,
+    interval: Duration,
+    duration: Option<Duration>,
+    out: &Path,
+) -> Result<usize> {
+    let nvml = Nvml::open(device).context(SnapError::GpuInit)?;
+    let mut file = std::fs::File::create(out)
+        .with_context(|| format!("Could not create {}", out.display()))?;
+    writeln!(file, "{}", CSV_HEADER)?;
+
+    let start = Instant::now();
+    let mut samples = 0;
+    let mut seen_process = false;
+    loop {
+        let used = match pid {
+            Some(pid) => match nvml.process_used(pid)? {
+                Some(used) => {
+                    seen_process = true;
+                    used
+                }
+                // not on the GPU yet
+                None if !seen_process => 0,
+                // finished
+                None => break,
+            },
+            None => nvml.device_used()?,
+        };
+        let now_us = SystemTime::now().duration_since(UNIX_EPOCH)?.as_micros();
+        writeln!(file, "{}", csv_line(now_us, used))?;
+        file.flush()?;
+        samples += 1;
+
+        if duration.is_some_and(|d| start.elapsed() >= d) {
+            break;
+        }
+        std::thread::sleep(interval);
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::record::{ProcessInfo, csv_line};
+
+    #[test]
+    fn test_record_format() {
+        assert_eq!(
+            csv_line(1_700_000_000_000_000, 1024),
+            "1700000000000000,1024"
+        );
+        // layout of nvmlProcessInfo_t v3
+        assert_eq!(std::mem::size_of::<ProcessInfo>(), 24);
+    }
+}
@@ -0,0 +1,108 @@
+//! Aggregate statistics of the allocations in a rectangle dragged with Shift, sent to the GUI as
+//! a `REGION_STATS_EVENT` message.
+
+use crate::allocation::Allocation;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// First part of the two-part IPC message carrying `RegionStats` as JSON in its second part.
+/// Messages of a single part are plain text for the message panel.
+pub const REGION_STATS_EVENT: &str = "region_stats";
+
+/// Allocations sharing a callstack within a region
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CallstackStats {
+    pub callstack: String,
+    pub count: usize,
+    pub bytes: u64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RegionStats {
+    /// Timestamps the region spans
+    pub time_range: (u64, u64),
+    /// Memory the region spans, bytes
+    pub memory_range: (u64, u64),
+    /// Allocations intersecting the region
+    pub count: usize,
+    /// Their total size
+    pub bytes: u64,
+    /// Callstacks holding the most bytes, most first
+    pub callstacks: Vec<CallstackStats>,
+}
+
+impl RegionStats {
+    /// Stats of `allocations[indices]`, whose callstacks are `callstacks` (same order), with the
+    /// `top` callstacks by bytes
+    pub fn new(
+        allocations: &[Allocation],
+        indices: &[usize],
+        callstacks: Vec<String>,
+        time_range: (u64, u64),
+        memory_range: (u64, u64),
+        top: usize,
+    ) -> Self {
+        let mut by_callstack: HashMap<String, CallstackStats> = HashMap::new();
+        for (&idx, callstack) in indices.iter().zip(callstacks) {
+            let entry = by_callstack
+                .entry(callstack)
+                .or_insert_with_key(|callstack| CallstackStats {
+                    callstack: callstack.clone(),
+                    count: 0,
+                    bytes: 0,
+                });
+            entry.count += 1;
+            entry.bytes += allocations[idx].size;
+        }
+        let mut callstacks: Vec<_> = by_callstack.into_values().collect();
+        callstacks.sort_by(|a, b| {
+            b.bytes
+                .cmp(&a.bytes)
+                .then_with(|| a.callstack.cmp(&b.callstack))
+        });
+        callstacks.truncate(top);
+
+        Self {
+            time_range,
+            memory_range,
+            count: indices.len(),
+            bytes: indices.iter().map(|&idx| allocations[idx].size).sum(),
+            callstacks,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("region stats serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::Allocation;
+    use crate::region::RegionStats;
+
+    #[test]
+    fn test_region_stats() {
+        let alloc = |size| Allocation {
+            timesteps: vec![0, 10].into(),
+            offsets: vec![0, 0].into(),
+            size,
+            peak_mem: 0,
+            peak_timestamps: vec![],
+            category: None,
+        };
+        let allocs = [alloc(100), alloc(200), alloc(50), alloc(400)];
+        let callstacks = ["a", "b", "a"].map(str::to_string).to_vec();
+
+        let stats = RegionStats::new(&allocs, &[0, 1, 2], callstacks, (0, 10), (0, 1024), 1);
+        assert_eq!((stats.count, stats.bytes), (3, 350));
+        // b holds 200, a 150
+        assert_eq!(stats.callstacks.len(), 1);
+        assert_eq!(stats.callstacks[0].callstack, "b");
+
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json["bytes"], 350);
+        assert_eq!(json["time_range"][1], 10);
+        assert_eq!(json["callstacks"][0]["count"], 1);
+    }
+}
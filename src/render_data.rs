@@ -1,60 +1,153 @@
-use crate::geometry::AllocationGeometry;
+use crate::constants::MARKER_WIDTH;
+use crate::geometry::{AllocationGeometry, YAxis};
 use indicatif::ProgressIterator;
 use log::info;
-use rand::Rng;
-use three_d::{CpuMesh, Srgba};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+use three_d::{CpuMesh, Srgba, Vec3};
 
-pub fn from_allocations<'a>(
-    allocations: impl ExactSizeIterator<Item = &'a AllocationGeometry>, // required for progress bar
-) -> (CpuMesh, Vec<Srgba>) {
-    info!("Converting geometries to render-able mesh...");
+/// Depth added per allocation so overlapping quads (e.g. the two traces in diff mode) do not
+/// z-fight. Just above the precision of a 24 bit depth buffer over the camera's 0~10 range.
+const Z_JITTER_STEP: f64 = 2e-6;
+/// Offsets repeat after this many allocations; the largest stays below the selection at 0.005
+const Z_JITTER_LEVELS: usize = 2048;
 
-    // pack a random color with each allocation
-    let mut rng = rand::rng();
-    let alloc_colors = allocations
-        .map(|alloc| {
-            let color = loop {
+/// Deterministic depth offset of the `index`th allocation, later allocations drawn on top
+fn z_jitter(index: usize) -> f64 {
+    (index % Z_JITTER_LEVELS) as f64 * Z_JITTER_STEP
+}
+
+/// Which colors allocations are randomly drawn from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorFamily {
+    Any,
+    /// Primary snapshot in diff mode
+    Blue,
+    /// Compared snapshot in diff mode
+    Orange,
+}
+
+impl ColorFamily {
+    fn random_color(self, rng: &mut StdRng) -> Srgba {
+        let (r, g, b) = match self {
+            ColorFamily::Any => loop {
                 let r: u32 = rng.random_range(0..=255);
                 let g: u32 = rng.random_range(0..=255);
                 let b: u32 = rng.random_range(0..=255);
 
                 // Reject colors that are too light or too dark
                 if 150 < r + g + b && r + g + b < 600 {
-                    break Srgba::new(r as u8, g as u8, b as u8, 30);
+                    break (r, g, b);
                 }
-            };
+            },
+            ColorFamily::Blue => (
+                rng.random_range(0..=80),
+                rng.random_range(60..=160),
+                rng.random_range(180..=255),
+            ),
+            ColorFamily::Orange => (
+                rng.random_range(200..=255),
+                rng.random_range(90..=170),
+                rng.random_range(0..=60),
+            ),
+        };
+        Srgba::new(r as u8, g as u8, b as u8, 30)
+    }
+}
 
-            (alloc, color)
-        })
+/// A random color of `ColorFamily::Any` picked by `seed`, the same in every run
+pub fn seeded_color(seed: u64) -> Srgba {
+    ColorFamily::Any.random_color(&mut StdRng::seed_from_u64(seed))
+}
+
+pub fn from_allocations<'a>(
+    allocations: impl ExactSizeIterator<Item = &'a AllocationGeometry>, // required for progress bar
+    chunk_width: f64,
+    color_seed: Option<u64>,
+    family: ColorFamily,
+    y_axis: YAxis,
+) -> (Vec<CpuMesh>, Vec<Srgba>) {
+    info!("Converting geometries to render-able mesh...");
+
+    // pack a random color with each allocation, reproducible if seeded
+    let mut rng = match color_seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    };
+    let alloc_colors = allocations
+        .map(|alloc| (alloc, family.random_color(&mut rng)))
         .progress();
 
-    from_allocations_with_z(alloc_colors, 0.0)
+    // the compared snapshot's allocations interleave with the primary's in depth, so the
+    // allocation both traces share (same index) does not z-fight either
+    let z = match family {
+        ColorFamily::Orange => Z_JITTER_STEP / 2.0,
+        _ => 0.0,
+    };
+    chunks_from_allocations_with_z(alloc_colors, z, chunk_width, y_axis)
 }
 
 pub fn from_allocations_with_z<'a>(
     alloc_zip_colors: impl Iterator<Item = (&'a AllocationGeometry, Srgba)>,
     z: f64,
+    y_axis: YAxis,
 ) -> (CpuMesh, Vec<Srgba>) {
-    // prepare containers for geometry
-    let mut verts = Vec::new();
-    let mut vert_colors = Vec::new();
+    // a single chunk spanning all time
+    let (chunks, alloc_colors) =
+        chunks_from_allocations_with_z(alloc_zip_colors, z, f64::INFINITY, y_axis);
+    let mesh = chunks
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| to_cpu_mesh(vec![], vec![]));
+
+    (mesh, alloc_colors)
+}
+
+/// Like `from_allocations_with_z`, but splits quads into meshes by time bucket of `chunk_width`
+/// world units, so chunks outside the viewport can be skipped when rendering.
+/// Empty chunks are omitted, as are allocations of no height (those `--max-allocs` leaves out).
+/// Edges are placed on the y axis by `y_axis`.
+pub fn chunks_from_allocations_with_z<'a>(
+    alloc_zip_colors: impl Iterator<Item = (&'a AllocationGeometry, Srgba)>,
+    z: f64,
+    chunk_width: f64,
+    y_axis: YAxis,
+) -> (Vec<CpuMesh>, Vec<Srgba>) {
+    // prepare containers for geometry, per chunk
+    let mut chunks: BTreeMap<i64, (Vec<Vec3>, Vec<Srgba>)> = BTreeMap::new();
     let mut alloc_colors = Vec::new();
 
-    for (alloc, color) in alloc_zip_colors {
+    for (index, (alloc, color)) in alloc_zip_colors.enumerate() {
         alloc_colors.push(color);
-        for ivert in 0..alloc.num_steps() - 1 {
+        if alloc.size == 0.0 {
+            continue;
+        }
+        let z = z + z_jitter(index);
+        let marker = alloc.is_marker();
+        for ivert in 0..alloc.num_steps().saturating_sub(1) {
             let this_time = alloc.timesteps[ivert];
-            let next_time = alloc.timesteps[ivert + 1];
+            let next_time = match marker {
+                true => this_time + MARKER_WIDTH,
+                false => alloc.timesteps[ivert + 1],
+            };
             let this_lo = alloc.offsets[ivert];
             let next_lo = alloc.offsets[ivert + 1];
-            let this_hi = this_lo + alloc.size;
-            let next_hi = next_lo + alloc.size;
+            let this_hi = y_axis.map(this_lo + alloc.size);
+            let next_hi = y_axis.map(next_lo + alloc.size);
+            let (this_lo, next_lo) = (y_axis.map(this_lo), y_axis.map(next_lo));
+
+            // a quad belongs to the chunk its left edge is in
+            let ichunk = (this_time / chunk_width).floor() as i64;
+            let (verts, vert_colors) = chunks.entry(ichunk).or_default();
 
             // vertices that make up the quad
-            let left_bot = three_d::Vector3::new(this_time, this_lo, z);
-            let left_top = three_d::Vector3::new(this_time, this_hi, z);
-            let right_bot = three_d::Vector3::new(next_time, next_lo, z);
-            let right_top = three_d::Vector3::new(next_time, next_hi, z);
+            // f32 suffices as coordinates are normalized to the resolution,
+            // see `TraceGeometry::check_f32_precision`
+            let left_bot = three_d::vec3(this_time as f32, this_lo as f32, z as f32);
+            let left_top = three_d::vec3(this_time as f32, this_hi as f32, z as f32);
+            let right_bot = three_d::vec3(next_time as f32, next_lo as f32, z as f32);
+            let right_top = three_d::vec3(next_time as f32, next_hi as f32, z as f32);
 
             // Triangle 1
             verts.push(left_bot);
@@ -73,21 +166,60 @@ pub fn from_allocations_with_z<'a>(
         }
     }
 
+    let meshes = chunks
+        .into_values()
+        .map(|(verts, vert_colors)| to_cpu_mesh(verts, vert_colors))
+        .collect();
+
+    (meshes, alloc_colors)
+}
+
+/// Axis-aligned rectangles `(x0, y0, x1, y1)` in world coords at depth `z`, in one color
+pub fn rects_mesh(
+    rects: impl Iterator<Item = (f64, f64, f64, f64)>,
+    color: Srgba,
+    z: f64,
+) -> CpuMesh {
+    colored_rects_mesh(rects.map(|rect| (rect, color)), z)
+}
+
+/// Like `rects_mesh`, each rectangle in its own color
+pub fn colored_rects_mesh(
+    rects: impl Iterator<Item = ((f64, f64, f64, f64), Srgba)>,
+    z: f64,
+) -> CpuMesh {
+    let z = z as f32;
+    let mut vert_colors = Vec::new();
+    let verts: Vec<Vec3> = rects
+        .flat_map(|((x0, y0, x1, y1), color)| {
+            vert_colors.extend([color; 6]);
+            let (x0, y0, x1, y1) = (x0 as f32, y0 as f32, x1 as f32, y1 as f32);
+            [
+                three_d::vec3(x0, y0, z),
+                three_d::vec3(x1, y0, z),
+                three_d::vec3(x0, y1, z),
+                three_d::vec3(x0, y1, z),
+                three_d::vec3(x1, y0, z),
+                three_d::vec3(x1, y1, z),
+            ]
+        })
+        .collect();
+    to_cpu_mesh(verts, vert_colors)
+}
+
+fn to_cpu_mesh(verts: Vec<Vec3>, vert_colors: Vec<Srgba>) -> CpuMesh {
     assert!(
-        verts.len() % 3 == 0,
+        verts.len().is_multiple_of(3),
         "Require 3 verts per triangle, got {}",
         verts.len()
     );
 
-    (
-        CpuMesh {
-            positions: three_d::Positions::F64(verts),
-            colors: Some(vert_colors),
-            indices: three_d::Indices::None,
-            normals: None,
-            tangents: None,
-            uvs: None,
-        },
-        alloc_colors,
-    )
+    CpuMesh {
+        positions: three_d::Positions::F32(verts),
+        colors: Some(vert_colors),
+        indices: three_d::Indices::None,
+        normals: None,
+        tangents: None,
+        uvs: None,
+    }
 }
@@ -1,8 +1,33 @@
 use crate::{
-    allocation::Allocation, database::sqlite::AllocationDatabase, geometry::TraceGeometry,
-    render_data, utils::memory_usage,
+    allocation::{Allocation, LaneData},
+    clock::Clock,
+    color_mode::{ColorMode, Legend},
+    constants::{HEATMAP_BIN_PX, LOD_COLUMN_WIDTHS, MESH_TIME_CHUNKS, SELECTION_HISTORY_LIMIT},
+    database::sqlite::AllocationDatabase,
+    diff::DiffInput,
+    downsample::{KeepBy, Others, downsample},
+    filter::AllocationFilter,
+    fragmentation::UnusedReserved,
+    geometry::{
+        AllocationGeometry, TraceGeometry, binned_by_column, find_peak_timestamps, stack_lanes,
+    },
+    groups::CallstackGroups,
+    heatmap::Heatmap,
+    history::History,
+    lining,
+    load_progress::LoadProgress,
+    raster::{self, Image},
+    render_data::{self, ColorFamily},
+    segments::SegmentBand,
+    svg,
+    theme::Theme,
+    utils::{format_bytes, memory_usage},
 };
+use log::info;
+use std::cell::OnceCell;
+use std::collections::{HashSet, VecDeque};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use three_d::{ColorMaterial, Context, CpuMesh, Gm, Mesh, Srgba};
 
 pub struct FpsTimer {
@@ -10,6 +35,12 @@ pub struct FpsTimer {
     pub frame: u64,
 }
 
+impl Default for FpsTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FpsTimer {
     pub fn new() -> Self {
         Self {
@@ -21,7 +52,7 @@ impl FpsTimer {
         self.frame += 1;
         let elapsed = self.timer.elapsed().as_secs_f64();
         if elapsed >= 1.0 {
-            log::trace!("FPS: {:.2}", self.frame as f64 / elapsed as f64);
+            log::trace!("FPS: {:.2}", self.frame as f64 / elapsed);
             self.timer = std::time::Instant::now();
             self.frame = 0;
         }
@@ -76,29 +107,323 @@ impl DecayingColor {
         self.material.color = color;
     }
 
+    /// Still animating towards the target color
+    pub fn is_fading(&self) -> bool {
+        self.time < self.fade_time
+    }
+
     pub fn material(&self) -> ColorMaterial {
         self.material.clone()
     }
 }
 
+/// Returns `None` for an empty mesh (e.g. everything filtered out), which has nothing to draw.
+pub fn upload_mesh(context: &Context, cpu_mesh: &CpuMesh) -> Option<Gm<Mesh, ColorMaterial>> {
+    if cpu_mesh.positions.is_empty() {
+        return None;
+    }
+
+    Some(Gm::new(
+        Mesh::new(context, cpu_mesh),
+        ColorMaterial {
+            color: Srgba::WHITE,
+            ..Default::default()
+        },
+    ))
+}
+
+/// The exact allocation mesh, moved to the GPU a few chunks per frame so the window stays
+/// responsive while it uploads. If the upload is cancelled, the finest LOD mesh stands in.
+pub struct ChunkedMesh {
+    pub uploaded: Vec<Gm<Mesh, ColorMaterial>>,
+    pending: VecDeque<CpuMesh>,
+    total: usize,
+    started: Instant,
+    pub cancelled: bool,
+}
+
+impl ChunkedMesh {
+    pub fn new(cpu_meshes: Vec<CpuMesh>) -> Self {
+        Self {
+            uploaded: Vec::new(),
+            total: cpu_meshes.len(),
+            pending: cpu_meshes.into(),
+            started: Instant::now(),
+            cancelled: false,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// (uploaded, total) chunk counts
+    pub fn progress(&self) -> (usize, usize) {
+        (self.total - self.pending.len(), self.total)
+    }
+
+    /// How long the upload has been running
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Upload pending chunks for about `budget`, at least one
+    pub fn upload_step(&mut self, context: &Context, budget: Duration) {
+        let start = Instant::now();
+        while let Some(cpu_mesh) = self.pending.pop_front() {
+            self.uploaded.extend(upload_mesh(context, &cpu_mesh));
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+
+    /// Stop uploading, dropping the chunks not on the GPU yet
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+        self.cancelled = true;
+    }
+}
+
+/// Decimated mesh used instead of the exact one when zoomed out
+pub struct LodMesh {
+    pub column_width: f64,
+    /// Built on first use; inner `None` means nothing is visible
+    pub mesh: OnceCell<Option<Gm<Mesh, ColorMaterial>>>,
+}
+
+/// Second snapshot overlaid in diff mode, drawn in another color family and filtered like the
+/// primary one, but not pickable
+pub struct DiffTrace {
+    pub name: String,
+    pub trace_geom: TraceGeometry,
+    pub alloc_colors: Vec<Srgba>,
+    pub only_primary: Vec<usize>,
+    pub only_diff: Vec<usize>,
+}
+
+/// Screen width of the selected allocation's border, in logical pixels
+const OUTLINE_WIDTH_PX: f32 = 2.0;
+
+/// At most this many allocations per side are listed by `--diff`
+const DIFF_REPORT_LIMIT: usize = 50;
+
+/// Fraction of the way to white that allocations outside the spotlight are faded
+const SPOTLIGHT_DIM: f32 = 0.85;
+/// Reserved-but-unused memory in fragmentation mode
+const FRAGMENTATION_COLOR: Srgba = Srgba::new(110, 110, 110, 70);
+/// Reserved segments, alternating so neighbouring segments stand apart
+const SEGMENT_COLORS: [Srgba; 2] = [Srgba::new(90, 120, 160, 28), Srgba::new(90, 120, 160, 52)];
+/// Behind every allocation
+const SEGMENTS_Z: f64 = -0.001;
+/// Allocations `--max-allocs` left out, aggregated into bands
+const OTHERS_COLOR: Srgba = Srgba::new(140, 140, 140, 110);
+/// Behind every allocation, over the segments
+const OTHERS_Z: f64 = -0.0005;
+/// Time columns the "other" bands are drawn in, each as high as its maximum
+const OTHERS_COLUMNS: usize = 8192;
+/// Opacity of the background-colored veil over the allocations in hover-dim mode, leaving about
+/// a fifth of their color
+const HOVER_VEIL_ALPHA: u8 = 204;
+/// Above the z jitter of every allocation, below the selection at 0.005
+const HOVER_VEIL_Z: f64 = 0.0045;
+/// The hovered allocation, over the veil
+const HOVERED_Z: f64 = 0.0047;
+
+fn dimmed(color: Srgba) -> Srgba {
+    let fade = |c: u8| c + ((255 - c) as f32 * SPOTLIGHT_DIM) as u8;
+    Srgba::new(fade(color.r), fade(color.g), fade(color.b), color.a)
+}
+
+/// Average of `colors`, for a level-of-detail rectangle standing in for several allocations
+fn mean_color(colors: &[Srgba]) -> Srgba {
+    let n = colors.len().max(1) as u32;
+    let mean = |channel: fn(&Srgba) -> u8| {
+        (colors.iter().map(|c| channel(c) as u32).sum::<u32>() / n) as u8
+    };
+    Srgba::new(mean(|c| c.r), mean(|c| c.g), mean(|c| c.b), mean(|c| c.a))
+}
+
+/// "1.00 GiB linear.py:125:forward": size and the first frame of `callstack` (as formatted by
+/// the converter), without the frame's directory
+pub fn top_label(size: u64, callstack: &str) -> String {
+    let size = format_bytes(size as i64);
+    let Some(frame) = callstack.lines().next() else {
+        return size;
+    };
+    let frame = frame.split_once(") ").map_or(frame, |(_, frame)| frame);
+    // file:line:name, where the file may contain colons itself (Windows drives)
+    let mut parts = frame.rsplitn(3, ':');
+    let (name, line, file) = (parts.next(), parts.next(), parts.next());
+    match (name, line, file) {
+        (Some(name), Some(line), Some(file)) => {
+            let file = file.rsplit(['/', '\\']).next().unwrap_or(file);
+            format!("{} {}:{}:{}", size, file, line, name)
+        }
+        _ => format!("{} {}", size, frame),
+    }
+}
+
+/// Geometry, color and raw data of each allocation in a trace
+fn with_colors<'a>(
+    trace_geom: &'a TraceGeometry,
+    alloc_colors: &'a [Srgba],
+) -> impl Iterator<Item = (&'a AllocationGeometry, Srgba, &'a Allocation)> {
+    trace_geom
+        .allocations
+        .iter()
+        .zip(alloc_colors.iter().copied())
+        .zip(trace_geom.raw_allocs.iter())
+        .map(|((alloc, color), raw)| (alloc, color, raw))
+}
+
 pub struct RenderLoop {
     pub trace_geom: TraceGeometry,
     pub resolution: (u32, u32),
     pub selected_mesh: Option<Gm<Mesh, ColorMaterial>>,
+    /// Index of the allocation `selected_mesh` highlights
+    pub selected: Option<usize>,
+    /// Border of the selected allocation, and the zoom it was built for
+    pub selected_outline: Option<(f32, Gm<Mesh, ColorMaterial>)>,
     pub decaying_color: DecayingColor,
     pub alloc_colors: Vec<Srgba>,
+    pub filter: AllocationFilter,
+    pub lod_meshes: Vec<LodMesh>,
+    pub diff: Option<DiffTrace>,
+    /// Allocations kept in their colors while the rest is dimmed, see `set_spotlight`
+    pub spotlight: Option<HashSet<usize>>,
+    /// Key to the allocation colors, unless they are random
+    pub legend: Option<Legend>,
+    /// Wall-clock time of timesteps, if the snapshot recorded it
+    pub clock: Option<Clock>,
+    /// Colors around the allocations, change with `set_theme`
+    pub theme: Theme,
+    /// Colors and legend replaced by grouping mode, see `set_grouped`
+    ungrouped: Option<(Vec<Srgba>, Option<Legend>)>,
+    /// Allocations selected so far, `None` before the first selection
+    selection_history: Option<History<usize>>,
+    /// Density heatmap and its legend, built on first use in heatmap mode; inner `None` means
+    /// nothing is visible
+    heatmap: OnceCell<(Option<Gm<Mesh, ColorMaterial>>, Legend)>,
+    /// Labels of the largest allocations, see `top_labels`
+    top_labels: OnceCell<Vec<(usize, String)>>,
+    /// Allocations left out by `--max-allocs`
+    pub others: Option<Others>,
+}
+
+/// Where an allocation has room for a label in the visible range, world coords
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LabelSpot {
+    /// Visible part of its time span, as (min, max)
+    pub x_range: (f32, f32),
+    /// Middle of its drawn height at the middle of `x_range`
+    pub y: f32,
+    /// Drawn height there
+    pub height: f32,
 }
 
 impl RenderLoop {
-    /// Executed at start
+    /// Executed at start.
+    /// With `lanes`, the plot is split into one lane per CUDA stream. With `max_allocs`, only
+    /// that many allocations are drawn, see `downsample`.
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize(
         allocations: Arc<[Allocation]>,
+        lanes: Option<Vec<LaneData>>,
+        diff: Option<DiffInput>,
         resolution: (u32, u32),
-    ) -> anyhow::Result<(Self, CpuMesh)> {
+        max_segments: usize,
+        color_seed: Option<u64>,
+        color_mode: ColorMode,
+        max_allocs: Option<(usize, KeepBy)>,
+        progress: &LoadProgress,
+    ) -> anyhow::Result<(Self, Vec<CpuMesh>)> {
+        progress.stage("building geometry", 40);
         println!("Memory before building geometry: {} MiB", memory_usage());
-        let trace_geom = TraceGeometry::from_allocations(Arc::clone(&allocations), resolution);
+        let streams: Vec<i64> = lanes.iter().flatten().map(|lane| lane.stream).collect();
+        let (allocations, lanes) = match lanes {
+            Some(lanes) => stack_lanes(&allocations, &lanes),
+            None => (allocations, Vec::new()),
+        };
+
+        // in diff mode both traces share axes
+        let mut extent = TraceGeometry::extent(&allocations);
+        if let Some(diff) = &diff {
+            let diff_extent = TraceGeometry::extent(&diff.allocations);
+            extent = (extent.0.max(diff_extent.0), extent.1.max(diff_extent.1));
+        }
+
+        let (allocations, others) = match max_allocs
+            .and_then(|(max, keep_by)| downsample(&allocations, max, keep_by, &streams, &lanes))
+        {
+            Some((kept, others)) => {
+                println!(
+                    "Drawing {} of {} allocations, the others in grey",
+                    kept.len() - others.count,
+                    kept.len()
+                );
+                let peaks = find_peak_timestamps(&allocations, extent.0);
+                (kept, Some((others, peaks)))
+            }
+            None => (allocations, None),
+        };
+
+        let mut trace_geom =
+            TraceGeometry::from_allocations_scaled(Arc::clone(&allocations), resolution, extent);
+        trace_geom.lanes = lanes;
+        // the peaks of all allocations, not just the kept ones
+        let others = others.map(|(others, peaks)| {
+            trace_geom.peak_timestamps = peaks;
+            others
+        });
+        if let Some(msg) = trace_geom.coarsen(max_segments) {
+            println!("{}", msg);
+        }
         println!("Memory after building geometry: {} MiB", memory_usage());
-        let (cpumesh, alloc_colors) = render_data::from_allocations(trace_geom.allocations.iter());
+        progress.stage("building meshes", 65);
+        let family = match diff {
+            Some(_) => ColorFamily::Blue,
+            None => ColorFamily::Any,
+        };
+        let (mut cpu_meshes, alloc_colors) = match color_mode.colors(&allocations) {
+            Some(colors) => render_data::chunks_from_allocations_with_z(
+                trace_geom.allocations.iter().zip(colors),
+                0.0,
+                Self::chunk_width(resolution),
+                trace_geom.y_axis,
+            ),
+            None => render_data::from_allocations(
+                trace_geom.allocations.iter(),
+                Self::chunk_width(resolution),
+                color_seed,
+                family,
+                trace_geom.y_axis,
+            ),
+        };
+        let legend = color_mode.legend(&allocations);
+
+        let diff = diff.map(|diff| {
+            let mut trace_geom =
+                TraceGeometry::from_allocations_scaled(diff.allocations, resolution, extent);
+            if let Some(msg) = trace_geom.coarsen(max_segments) {
+                println!("{}", msg);
+            }
+            let (diff_meshes, alloc_colors) = render_data::from_allocations(
+                trace_geom.allocations.iter(),
+                Self::chunk_width(resolution),
+                color_seed,
+                ColorFamily::Orange,
+                trace_geom.y_axis,
+            );
+            cpu_meshes.extend(diff_meshes);
+            DiffTrace {
+                name: diff.name,
+                trace_geom,
+                alloc_colors,
+                only_primary: diff.only_primary,
+                only_diff: diff.only_diff,
+            }
+        });
         println!("Memory after building render data: {} MiB", memory_usage());
 
         Ok((
@@ -106,41 +431,572 @@ impl RenderLoop {
                 trace_geom,
                 resolution,
                 selected_mesh: None,
+                selected: None,
+                selected_outline: None,
                 decaying_color: DecayingColor::new(0.8, Srgba::WHITE),
                 alloc_colors,
+                filter: AllocationFilter::default(),
+                lod_meshes: LOD_COLUMN_WIDTHS
+                    .iter()
+                    .map(|&column_width| LodMesh {
+                        column_width,
+                        mesh: OnceCell::new(),
+                    })
+                    .collect(),
+                diff,
+                spotlight: None,
+                legend,
+                clock: None,
+                theme: Theme::LIGHT,
+                ungrouped: None,
+                selection_history: None,
+                heatmap: OnceCell::new(),
+                top_labels: OnceCell::new(),
+                others,
             },
-            cpumesh,
+            cpu_meshes,
         ))
     }
 
+    /// Allocations accepted by the current filter, with their original colors, dimmed if
+    /// outside the spotlight. Includes the compared snapshot in diff mode.
+    fn visible_allocations(&self) -> impl Iterator<Item = (&AllocationGeometry, Srgba)> {
+        let spotlight = self.spotlight.as_ref();
+        let diff = self
+            .diff
+            .iter()
+            .flat_map(|diff| with_colors(&diff.trace_geom, &diff.alloc_colors))
+            .map(move |(alloc, color, raw)| match spotlight {
+                Some(_) => (alloc, dimmed(color), raw),
+                None => (alloc, color, raw),
+            });
+        with_colors(&self.trace_geom, &self.alloc_colors)
+            .enumerate()
+            .map(move |(i, (alloc, color, raw))| match spotlight {
+                Some(kept) if !kept.contains(&i) => (alloc, dimmed(color), raw),
+                _ => (alloc, color, raw),
+            })
+            .chain(diff)
+            .filter(|(_, _, raw)| self.filter.accepts(raw))
+            .map(|(alloc, color, _)| (alloc, color))
+    }
+
+    /// Dim everything but the `n` largest allocations alive in `start..=stop` and accepted by
+    /// the filter. Meshes need a rebuild afterwards.
+    pub fn set_spotlight(&mut self, n: usize, start: u64, stop: u64) {
+        let mut candidates: Vec<usize> = (0..self.trace_geom.raw_allocs.len())
+            .filter(|&i| {
+                let raw = &self.trace_geom.raw_allocs[i];
+                raw.is_alive_in_interval(start, stop) && self.filter.accepts(raw)
+            })
+            .collect();
+        candidates.sort_by_key(|&i| std::cmp::Reverse(self.trace_geom.raw_allocs[i].size));
+        self.spotlight = Some(candidates.into_iter().take(n).collect());
+    }
+
+    /// Lists allocations that only exist in one of the diffed snapshots, largest first
+    pub fn diff_report(&self) -> String {
+        let Some(diff) = &self.diff else {
+            return "Not in diff mode, start the renderer with --diff <dir>".to_string();
+        };
+
+        let mut report = String::new();
+        for (title, trace, indices) in [
+            ("primary snapshot", &self.trace_geom, &diff.only_primary),
+            (diff.name.as_str(), &diff.trace_geom, &diff.only_diff),
+        ] {
+            let total: u64 = indices.iter().map(|&i| trace.raw_allocs[i].size).sum();
+            report += &format!(
+                "Only in {}: {} allocations, {} total\n",
+                title,
+                indices.len(),
+                format_bytes(total as i64)
+            );
+
+            let mut largest = indices.clone();
+            largest.sort_by_key(|&i| std::cmp::Reverse(trace.raw_allocs[i].size));
+            for &i in largest.iter().take(DIFF_REPORT_LIMIT) {
+                let alloc = &trace.raw_allocs[i];
+                let (start, stop) = alloc.start_end_time();
+                report += &format!(
+                    "    #{}: {}, alive {}..{}\n",
+                    i,
+                    format_bytes(alloc.size as i64),
+                    start,
+                    stop
+                );
+            }
+            if largest.len() > DIFF_REPORT_LIMIT {
+                report += &format!("    ... {} more\n", largest.len() - DIFF_REPORT_LIMIT);
+            }
+        }
+        report
+    }
+
+    /// Width of a mesh chunk in world units
+    fn chunk_width(resolution: (u32, u32)) -> f64 {
+        resolution.0 as f64 / MESH_TIME_CHUNKS as f64
+    }
+
+    /// Rebuild the chunked allocation mesh from allocations accepted by the current filter,
+    /// keeping each allocation's original color.
+    pub fn build_filtered_meshes(&self) -> Vec<CpuMesh> {
+        let (cpu_meshes, _) = render_data::chunks_from_allocations_with_z(
+            self.visible_allocations(),
+            0.0,
+            Self::chunk_width(self.resolution),
+            self.trace_geom.y_axis,
+        );
+        cpu_meshes
+    }
+
+    /// The allocations drawn in the visible world rect as SVG, in their current colors (filter and
+    /// spotlight applied), and how many there are
+    pub fn export_svg(
+        &self,
+        x_range: (f32, f32),
+        y_range: (f32, f32),
+        size: (u32, u32),
+    ) -> (String, usize) {
+        svg::render(
+            &self.trace_geom,
+            self.visible_allocations(),
+            x_range,
+            y_range,
+            size,
+        )
+    }
+
+    /// The `n` largest allocations accepted by the filter, largest first, with labels of their
+    /// size and top stack frame. Built on first use, `n` is expected to stay the same.
+    pub fn top_labels(&self, db_ptr: u64, n: usize) -> &[(usize, String)] {
+        self.top_labels.get_or_init(|| {
+            // Terrible hack, see `allocation_info`
+            let db = unsafe { &*(db_ptr as *const AllocationDatabase) };
+            let raw = &self.trace_geom.raw_allocs;
+            let mut largest: Vec<usize> = (0..raw.len())
+                .filter(|&i| self.filter.accepts(&raw[i]))
+                .collect();
+            largest.sort_by_key(|&i| std::cmp::Reverse(raw[i].size));
+            largest.truncate(n);
+            largest
+                .into_iter()
+                .map(|i| {
+                    let callstack = db.read_callstack(i).unwrap_or_default();
+                    (i, top_label(raw[i].size, &callstack))
+                })
+                .collect()
+        })
+    }
+
+    /// Room for a label on allocation `idx` within the visible `x_range`, `None` if it is not
+    /// visible there
+    pub fn label_spot(&self, idx: usize, x_range: (f32, f32)) -> Option<LabelSpot> {
+        let alloc = &self.trace_geom.allocations[idx];
+        let start = (*alloc.timesteps.first()?).max(x_range.0 as f64);
+        let stop = (*alloc.timesteps.last()?).min(x_range.1 as f64);
+        if start >= stop {
+            return None;
+        }
+        let x = (start + stop) / 2.0;
+        let i = alloc
+            .timesteps
+            .partition_point(|&t| t <= x)
+            .clamp(1, alloc.num_steps() - 1);
+        let (t0, t1) = (alloc.timesteps[i - 1], alloc.timesteps[i]);
+        let (o0, o1) = (alloc.offsets[i - 1], alloc.offsets[i]);
+        let offset = if t1 > t0 {
+            o0 + (o1 - o0) * (x - t0) / (t1 - t0)
+        } else {
+            o0
+        };
+        let y_axis = self.trace_geom.y_axis;
+        let (low, high) = (y_axis.map(offset), y_axis.map(offset + alloc.size));
+        Some(LabelSpot {
+            x_range: (start as f32, stop as f32),
+            y: ((low + high) / 2.0) as f32,
+            height: (high - low) as f32,
+        })
+    }
+
+    /// Same as `export_svg`, drawn into an image on the CPU
+    pub fn rasterize(
+        &self,
+        x_range: (f32, f32),
+        y_range: (f32, f32),
+        size: (u32, u32),
+    ) -> (Image, usize) {
+        raster::render(
+            &self.trace_geom,
+            self.visible_allocations(),
+            x_range,
+            y_range,
+            size,
+        )
+    }
+
+    /// Drop cached LOD and heatmap meshes, e.g. after the filter changed
+    pub fn invalidate_lod(&mut self) {
+        for lod in self.lod_meshes.iter_mut() {
+            lod.mesh = OnceCell::new();
+        }
+        self.heatmap = OnceCell::new();
+        self.top_labels = OnceCell::new();
+    }
+
+    /// Allocations accepted by the filter as a time × memory density heatmap, with its legend
+    pub fn heatmap(&self, context: &Context) -> (Option<&Gm<Mesh, ColorMaterial>>, &Legend) {
+        let (mesh, legend) = self.heatmap.get_or_init(|| {
+            info!("Building density heatmap");
+            let heatmap = Heatmap::from_allocations(
+                self.visible_allocations().map(|(alloc, _)| alloc),
+                (self.resolution.0 as f64, self.resolution.1 as f64),
+                (
+                    (self.resolution.0 / HEATMAP_BIN_PX) as usize,
+                    (self.resolution.1 / HEATMAP_BIN_PX) as usize,
+                ),
+                self.trace_geom.y_axis,
+            );
+            (upload_mesh(context, &heatmap.mesh()), heatmap.legend())
+        });
+        (mesh.as_ref(), legend)
+    }
+
+    /// The coarsest LOD mesh whose columns are at most one pixel wide at `zoom`,
+    /// or `None` if zoomed in far enough to need exact geometry.
+    pub fn lod_mesh(
+        &self,
+        context: &Context,
+        zoom: f32,
+    ) -> Option<Option<&Gm<Mesh, ColorMaterial>>> {
+        let lod = self
+            .lod_meshes
+            .iter()
+            .find(|lod| lod.column_width * zoom as f64 <= 1.0)?;
+        Some(self.build_lod(context, lod))
+    }
+
+    /// The most detailed LOD mesh, drawn at any zoom if the exact mesh upload was cancelled
+    pub fn finest_lod_mesh(&self, context: &Context) -> Option<&Gm<Mesh, ColorMaterial>> {
+        self.lod_meshes
+            .last()
+            .and_then(|lod| self.build_lod(context, lod))
+    }
+
+    /// Build the coarsest LOD mesh not built yet, so zooming out later does not stall.
+    /// Meant for idle frames, one mesh per frame.
+    ///
+    /// ## Returns
+    /// Whether a mesh was built.
+    pub fn warm_up_lod(&self, context: &Context) -> bool {
+        match self.lod_meshes.iter().find(|lod| lod.mesh.get().is_none()) {
+            Some(lod) => {
+                self.build_lod(context, lod);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether every LOD mesh is built
+    pub fn lod_ready(&self) -> bool {
+        self.lod_meshes.iter().all(|lod| lod.mesh.get().is_some())
+    }
+
+    fn build_lod<'a>(
+        &'a self,
+        context: &Context,
+        lod: &'a LodMesh,
+    ) -> Option<&'a Gm<Mesh, ColorMaterial>> {
+        let mesh = lod.mesh.get_or_init(|| {
+            info!("Building LOD mesh, column width {}", lod.column_width);
+            let (mut decimated, rects) =
+                binned_by_column(self.visible_allocations(), lod.column_width);
+            decimated.extend(
+                rects
+                    .into_iter()
+                    .map(|(rect, colors)| (rect, mean_color(&colors))),
+            );
+            let (cpu_mesh, _) = render_data::from_allocations_with_z(
+                decimated.iter().map(|(alloc, color)| (alloc, *color)),
+                0.0,
+                self.trace_geom.y_axis,
+            );
+            upload_mesh(context, &cpu_mesh)
+        });
+
+        mesh.as_ref()
+    }
+
+    /// Grey bands of reserved-but-unused memory on top of the stacked allocations
+    pub fn fragmentation_mesh(
+        &self,
+        context: &Context,
+        pieces: &[UnusedReserved],
+    ) -> Option<Gm<Mesh, ColorMaterial>> {
+        let geom = &self.trace_geom;
+        let rects = pieces.iter().map(|p| {
+            (
+                geom.timestamp2xworld(p.start) as f64,
+                geom.memory2yworld(p.live as i64) as f64,
+                geom.timestamp2xworld(p.stop) as f64,
+                geom.memory2yworld(p.reserved as i64) as f64,
+            )
+        });
+        upload_mesh(
+            context,
+            &render_data::rects_mesh(rects, FRAGMENTATION_COLOR, 0.0),
+        )
+    }
+
+    /// Faint bands of the reserved segments behind the allocations
+    pub fn segments_mesh(
+        &self,
+        context: &Context,
+        bands: &[SegmentBand],
+    ) -> Option<Gm<Mesh, ColorMaterial>> {
+        let geom = &self.trace_geom;
+        let rects = bands.iter().map(|b| {
+            (
+                (
+                    geom.timestamp2xworld(b.start) as f64,
+                    geom.memory2yworld(b.offset as i64) as f64,
+                    geom.timestamp2xworld(b.stop) as f64,
+                    geom.memory2yworld((b.offset + b.size) as i64) as f64,
+                ),
+                SEGMENT_COLORS[b.segment % SEGMENT_COLORS.len()],
+            )
+        });
+        upload_mesh(context, &render_data::colored_rects_mesh(rects, SEGMENTS_Z))
+    }
+
+    /// Grey bands where the allocations left out by `--max-allocs` are, behind the kept ones
+    pub fn others_mesh(&self, context: &Context) -> Option<Gm<Mesh, ColorMaterial>> {
+        let others = self.others.as_ref()?;
+        let geom = &self.trace_geom;
+        let max_time = geom.max_time as u64;
+        let span = max_time as f64 + 1.0;
+        let column_start = |i: usize| (span * i as f64 / OTHERS_COLUMNS as f64) as u64;
+        let rects = others.bands.iter().flat_map(|(base, timeline)| {
+            let maxima = timeline.column_maxima(0, max_time, OTHERS_COLUMNS);
+            maxima
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, live)| live > 0)
+                .map(move |(i, live)| {
+                    (
+                        geom.timestamp2xworld(column_start(i)) as f64,
+                        geom.memory2yworld(*base as i64) as f64,
+                        geom.timestamp2xworld(column_start(i + 1)) as f64,
+                        geom.memory2yworld((base + live) as i64) as f64,
+                    )
+                })
+        });
+        upload_mesh(
+            context,
+            &render_data::rects_mesh(rects, OTHERS_COLOR, OTHERS_Z),
+        )
+    }
+
+    /// Hover-dim mode: a veil over the visible world coords `x_range` × `y_range`, with the
+    /// `hovered` allocation drawn over it in its own color
+    pub fn hover_meshes(
+        &self,
+        context: &Context,
+        hovered: Option<usize>,
+        x_range: (f32, f32),
+        y_range: (f32, f32),
+    ) -> Vec<Gm<Mesh, ColorMaterial>> {
+        let veil = render_data::rects_mesh(
+            std::iter::once((
+                x_range.0 as f64,
+                y_range.0 as f64,
+                x_range.1 as f64,
+                y_range.1 as f64,
+            )),
+            Srgba {
+                a: HOVER_VEIL_ALPHA,
+                ..self.theme.background
+            },
+            HOVER_VEIL_Z,
+        );
+        let hovered = hovered.map(|idx| {
+            render_data::from_allocations_with_z(
+                std::iter::once((&self.trace_geom.allocations[idx], self.alloc_colors[idx])),
+                HOVERED_Z,
+                self.trace_geom.y_axis,
+            )
+            .0
+        });
+        std::iter::once(veil)
+            .chain(hovered)
+            .filter_map(|cpu_mesh| upload_mesh(context, &cpu_mesh))
+            .collect()
+    }
+
     pub fn show_alloc(&mut self, context: &Context, idx: usize) {
         // animate allocated mesh
         let (cpu_mesh, _) = render_data::from_allocations_with_z(
             std::iter::once((&self.trace_geom.allocations[idx], Srgba::WHITE)),
             0.005,
+            self.trace_geom.y_axis,
         );
         let alloc_mesh = Gm::new(
-            Mesh::new(&context, &cpu_mesh),
+            Mesh::new(context, &cpu_mesh),
             self.decaying_color.material(),
         );
         self.selected_mesh = Some(alloc_mesh);
+        self.selected = Some(idx);
+        self.selected_outline = None;
 
         // The original color of the allocation
         let original_color = self.alloc_colors[idx];
         self.decaying_color.reset(original_color);
     }
 
+    /// Remember `idx` as the newest selection, dropping the ones gone back from
+    pub fn record_selection(&mut self, idx: usize) {
+        match &mut self.selection_history {
+            Some(history) => history.record(idx),
+            None => self.selection_history = Some(History::new(idx, SELECTION_HISTORY_LIMIT)),
+        }
+    }
+
+    /// The selection before the current one in the selection history, if any
+    pub fn selection_back(&mut self) -> Option<usize> {
+        self.selection_history.as_mut()?.undo().copied()
+    }
+
+    /// The selection after the current one, after going back
+    pub fn selection_forward(&mut self) -> Option<usize> {
+        self.selection_history.as_mut()?.redo().copied()
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selected_mesh = None;
+        self.selected = None;
+        self.selected_outline = None;
+    }
+
+    /// Rebuild the border around the selected allocation if the zoom changed,
+    /// so it stays `OUTLINE_WIDTH_PX` wide on screen.
+    pub fn update_selected_outline(&mut self, context: &Context, zoom: f32) {
+        let Some(idx) = self.selected else {
+            return;
+        };
+        if self
+            .selected_outline
+            .as_ref()
+            .is_none_or(|(z, _)| *z != zoom)
+        {
+            let points = lining::outline(&self.trace_geom.allocations[idx], self.trace_geom.y_axis);
+            let cpu_mesh = lining::line_loop_mesh(&points, OUTLINE_WIDTH_PX / zoom, 0.006);
+            let mesh = Gm::new(
+                Mesh::new(context, &cpu_mesh),
+                ColorMaterial {
+                    color: self.theme.selection,
+                    ..Default::default()
+                },
+            );
+            self.selected_outline = Some((zoom, mesh));
+        }
+    }
+
+    /// Recolor the background, text and selection outline
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.selected_outline = None;
+    }
+
+    /// Grouping mode: one color per callstack group with `Some`, the previous colors back with
+    /// `None`. Meshes need a rebuild afterwards.
+    pub fn set_grouped(&mut self, groups: Option<&CallstackGroups>) {
+        match groups {
+            Some(groups) => {
+                let colors = groups.colors();
+                let ungrouped = (
+                    std::mem::replace(&mut self.alloc_colors, colors),
+                    self.legend.take(),
+                );
+                self.ungrouped.get_or_insert(ungrouped);
+            }
+            None => {
+                if let Some((colors, legend)) = self.ungrouped.take() {
+                    self.alloc_colors = colors;
+                    self.legend = legend;
+                }
+            }
+        }
+    }
+
+    pub fn is_grouped(&self) -> bool {
+        self.ungrouped.is_some()
+    }
+
+    /// Switch the memory axis between linear and log scale. The meshes need a rebuild after.
+    pub fn set_log_y(&mut self, log: bool) {
+        self.trace_geom.y_axis.log = log;
+        if let Some(diff) = &mut self.diff {
+            diff.trace_geom.y_axis.log = log;
+        }
+    }
+
+    /// Time since the trace started at `timestep` (e.g. "3.05 s"), or the timestep itself if
+    /// the snapshot has no times
+    pub fn format_time(&self, timestep: u64) -> String {
+        match &self.clock {
+            Some(clock) => clock.format(timestep),
+            None => timestep.to_string(),
+        }
+    }
+
     pub fn allocation_info(&self, db_ptr: u64, idx: usize) -> String {
         // Terrible hack, but I did not find a better way.
         let db = unsafe { &mut *(db_ptr as *mut AllocationDatabase) };
-        let header = self.trace_geom.raw_allocs[idx].to_string();
+        let alloc = &self.trace_geom.raw_allocs[idx];
+        let mut header = alloc.to_string();
+        if self.clock.is_some()
+            && let (Some(start), Some(stop)) = (alloc.timesteps.first(), alloc.timesteps.last())
+        {
+            header.push_str(&format!(
+                "|- Time: {} .. {}\n",
+                self.format_time(*start),
+                self.format_time(*stop)
+            ));
+        }
+
+        if !db.has_elements().unwrap_or(false) {
+            return format!(
+                "{}|- callstack: unavailable, elements.db is missing\n",
+                header
+            );
+        }
 
         // Everybody told me not to use interpolated string, but this is not a security sensitive app.
         let query_result = db
             .execute(&format!("SELECT callstack FROM allocs WHERE idx = {}", idx))
             .unwrap();
-        let callstack = query_result.splitn(2, "callstack:").skip(1).next().unwrap();
+        let (_, callstack) = query_result.split_once("callstack:").unwrap();
 
         format!("{}|- callstack:\n{}", header, callstack)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::render_loop::top_label;
+
+    #[test]
+    fn test_top_label() {
+        let callstack = "(0) /venv/torch/nn/modules/linear.py:125:forward\n(1) train.py:10:main";
+        assert_eq!(
+            top_label(1 << 30, callstack),
+            "1.00 GiB linear.py:125:forward"
+        );
+        assert_eq!(
+            top_label(1024, r"(0) C:\code\train.py:7:step"),
+            "1.00 KiB train.py:7:step"
+        );
+        assert_eq!(top_label(1024, ""), "1.00 KiB");
+    }
+}
@@ -0,0 +1,179 @@
+//! Recording the window to a GIF or MP4 by piping frames to ffmpeg, which must be on PATH.
+
+use anyhow::{Context, bail};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Padding for frames smaller than the recording, e.g. after the window shrank
+const BACKGROUND: [u8; 4] = [255, 255, 255, 255];
+
+/// ffmpeg arguments encoding raw RGBA frames of `size` at `fps` from stdin into `out`,
+/// by the extension of `out`
+pub fn ffmpeg_args(out: &Path, size: (u32, u32), fps: u32) -> anyhow::Result<Vec<String>> {
+    let mut args: Vec<String> = [
+        "-y",
+        "-loglevel",
+        "error",
+        "-f",
+        "rawvideo",
+        "-pix_fmt",
+        "rgba",
+        "-s",
+        &format!("{}x{}", size.0, size.1),
+        "-r",
+        &fps.to_string(),
+        "-i",
+        "-",
+    ]
+    .map(str::to_string)
+    .to_vec();
+    let encoding: &[&str] = match out.extension().and_then(|ext| ext.to_str()) {
+        // one palette for the whole clip, allocation colors are few
+        Some("gif") => &["-vf", "split[a][b];[a]palettegen[p];[b][p]paletteuse"],
+        // yuv420p for players that support nothing else, which needs even sizes
+        Some("mp4") => &[
+            "-c:v",
+            "libx264",
+            "-pix_fmt",
+            "yuv420p",
+            "-vf",
+            "pad=ceil(iw/2)*2:ceil(ih/2)*2:color=white",
+        ],
+        _ => bail!(
+            "Cannot record to {}, expected a .gif or .mp4 file",
+            out.display()
+        ),
+    };
+    args.extend(encoding.iter().map(|arg| arg.to_string()));
+    args.push(out.display().to_string());
+    Ok(args)
+}
+
+/// `frame` (`from` pixels, rows top to bottom) cropped or padded to `to`, anchored top left
+pub fn fit_frame(frame: &[[u8; 4]], from: (u32, u32), to: (u32, u32)) -> Vec<[u8; 4]> {
+    if from == to {
+        return frame.to_vec();
+    }
+    let mut fitted = vec![BACKGROUND; to.0 as usize * to.1 as usize];
+    let width = from.0.min(to.0) as usize;
+    for y in 0..from.1.min(to.1) as usize {
+        let src = y * from.0 as usize;
+        let dst = y * to.0 as usize;
+        fitted[dst..dst + width].copy_from_slice(&frame[src..src + width]);
+    }
+    fitted
+}
+
+/// A recording in progress. Frames are written at a fixed rate: a frame that took longer to
+/// draw is repeated, so the clip plays back at the speed it was recorded.
+pub struct Screencast {
+    pub path: PathBuf,
+    size: (u32, u32),
+    frame_time: Duration,
+    started: Instant,
+    frames: u64,
+    ffmpeg: Child,
+    stdin: Option<ChildStdin>,
+}
+
+impl Screencast {
+    /// Starts ffmpeg recording frames of `size` pixels (the window's size when recording starts)
+    pub fn start(path: &Path, size: (u32, u32), fps: u32) -> anyhow::Result<Self> {
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(ffmpeg_args(path, size, fps)?)
+            .stdin(Stdio::piped())
+            .spawn()
+            .context("Could not start ffmpeg, is it installed and on PATH?")?;
+        let stdin = ffmpeg.stdin.take();
+        Ok(Self {
+            path: path.to_path_buf(),
+            size,
+            frame_time: Duration::from_secs_f64(1.0 / fps as f64),
+            started: Instant::now(),
+            frames: 0,
+            ffmpeg,
+            stdin,
+        })
+    }
+
+    /// Frames the clip should have by now
+    fn frames_due(&self) -> u64 {
+        (self.started.elapsed().as_secs_f64() / self.frame_time.as_secs_f64()) as u64 + 1
+    }
+
+    /// Whether the next frame should be captured, even if nothing changed on screen
+    pub fn due(&self) -> bool {
+        self.frames_due() > self.frames
+    }
+
+    /// Writes the window's pixels (`size` pixels, rows top to bottom) as many times as frames
+    /// are due
+    pub fn capture(&mut self, pixels: &[[u8; 4]], size: (u32, u32)) -> anyhow::Result<()> {
+        let due = self.frames_due().saturating_sub(self.frames);
+        if due == 0 {
+            return Ok(());
+        }
+        let frame = fit_frame(pixels, size, self.size);
+        let bytes: Vec<u8> = frame.into_iter().flatten().collect();
+        let stdin = self.stdin.as_mut().context("Recording already finished")?;
+        for _ in 0..due {
+            stdin
+                .write_all(&bytes)
+                .context("ffmpeg stopped accepting frames")?;
+        }
+        self.frames += due;
+        Ok(())
+    }
+
+    /// Closes the clip, waiting for ffmpeg to finish encoding
+    ///
+    /// ## Returns
+    /// A message saying where the clip was written.
+    pub fn finish(mut self) -> anyhow::Result<String> {
+        drop(self.stdin.take());
+        let status = self.ffmpeg.wait()?;
+        if !status.success() {
+            bail!("ffmpeg failed ({}) writing {}", status, self.path.display());
+        }
+        Ok(format!(
+            "Recorded {:.1} s ({} frames) to {}",
+            self.frames as f64 * self.frame_time.as_secs_f64(),
+            self.frames,
+            self.path.display()
+        ))
+    }
+}
+
+impl Drop for Screencast {
+    /// Still a playable clip if the window closes while recording
+    fn drop(&mut self) {
+        if self.stdin.take().is_some() {
+            let _ = self.ffmpeg.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::screencast::{ffmpeg_args, fit_frame};
+    use std::path::Path;
+
+    #[test]
+    fn test_screencast_frames() {
+        let args = ffmpeg_args(Path::new("out.gif"), (640, 480), 15).unwrap();
+        assert!(args.windows(2).any(|w| w == ["-s", "640x480"]));
+        assert!(args.windows(2).any(|w| w == ["-r", "15"]));
+        assert_eq!(args.last().unwrap(), "out.gif");
+        assert!(ffmpeg_args(Path::new("out.mp4"), (640, 480), 15).is_ok());
+        assert!(ffmpeg_args(Path::new("out.avi"), (640, 480), 15).is_err());
+
+        let (a, b) = ([1, 1, 1, 1], [2, 2, 2, 2]);
+        let white = [255; 4];
+        // 2x2 frame into 3x1: first row kept, padded on the right
+        assert_eq!(fit_frame(&[a, b, b, a], (2, 2), (3, 1)), [a, b, white]);
+        // into 1x3: first column kept, padded at the bottom
+        assert_eq!(fit_frame(&[a, b, b, a], (2, 2), (1, 3)), [a, b, white]);
+    }
+}
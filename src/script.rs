@@ -0,0 +1,199 @@
+use crate::constants::{
+    SCRIPT_MAX_ARRAY_SIZE, SCRIPT_MAX_CALL_LEVELS, SCRIPT_MAX_OPERATIONS, SCRIPT_MAX_STRING_SIZE,
+};
+use crate::{allocation::Allocation, database::sqlite::AllocationDatabase};
+use rhai::{Array, Dynamic, Engine, Map};
+use std::cell::{OnceCell, RefCell};
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Renderer commands queued by a script, applied after it finishes
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptAction {
+    /// `select(idx)`
+    Select(usize),
+    /// `view(t0, t1)`
+    View(u64, u64),
+    /// `filter("size > 1MiB")`, same syntax as the `--filter` command
+    Filter(String),
+}
+
+#[derive(Debug, Default)]
+pub struct ScriptOutput {
+    /// Everything the script passed to `print`
+    pub printed: String,
+    pub actions: Vec<ScriptAction>,
+}
+
+/// Runs a Rhai script against a loaded snapshot. The script can use
+/// - `allocations()`: array of `#{idx, size, start, stop, peak_mem}` maps
+/// - `allocation(idx)`: one such map
+/// - `sql(query)`: runs a query on elements.db, returns the formatted result
+/// - `select(idx)`, `view(t0, t1)`, `filter(cmd)`: renderer commands
+/// - `print(...)`: output shown in the REPL
+///
+/// Scripts that exceed the operation, call depth, string or array limits in `constants.rs` fail
+/// with a script error.
+pub fn run_script(
+    source: &str,
+    allocations: Arc<[Allocation]>,
+    db: &'static AllocationDatabase,
+) -> anyhow::Result<ScriptOutput> {
+    let output = Rc::new(RefCell::new(ScriptOutput::default()));
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(SCRIPT_MAX_OPERATIONS)
+        .set_max_call_levels(SCRIPT_MAX_CALL_LEVELS)
+        .set_max_string_size(SCRIPT_MAX_STRING_SIZE)
+        .set_max_array_size(SCRIPT_MAX_ARRAY_SIZE.max(allocations.len()));
+
+    let printed = Rc::clone(&output);
+    engine.on_print(move |text| {
+        let mut out = printed.borrow_mut();
+        out.printed.push_str(text);
+        out.printed.push('\n');
+    });
+
+    // built on the first call and copied afterwards, scripts often call it in a loop
+    let allocs = Arc::clone(&allocations);
+    let all = OnceCell::new();
+    engine.register_fn("allocations", move || -> Array {
+        all.get_or_init(|| -> Array {
+            allocs
+                .iter()
+                .enumerate()
+                .map(|(idx, alloc)| Dynamic::from_map(allocation_map(idx, alloc)))
+                .collect()
+        })
+        .clone()
+    });
+
+    let allocs = Arc::clone(&allocations);
+    engine.register_fn(
+        "allocation",
+        move |idx: i64| -> Result<Map, Box<rhai::EvalAltResult>> {
+            usize::try_from(idx)
+                .ok()
+                .and_then(|i| allocs.get(i).map(|alloc| allocation_map(i, alloc)))
+                .ok_or_else(|| format!("Allocation #{} does not exist", idx).into())
+        },
+    );
+
+    engine.register_fn(
+        "sql",
+        move |query: &str| -> Result<String, Box<rhai::EvalAltResult>> {
+            db.execute(query).map_err(|e| e.to_string().into())
+        },
+    );
+
+    let actions = Rc::clone(&output);
+    engine.register_fn("select", move |idx: i64| {
+        actions
+            .borrow_mut()
+            .actions
+            .push(ScriptAction::Select(idx.max(0) as usize));
+    });
+    let actions = Rc::clone(&output);
+    engine.register_fn("view", move |t0: i64, t1: i64| {
+        actions
+            .borrow_mut()
+            .actions
+            .push(ScriptAction::View(t0.max(0) as u64, t1.max(0) as u64));
+    });
+    let actions = Rc::clone(&output);
+    engine.register_fn("filter", move |command: &str| {
+        actions
+            .borrow_mut()
+            .actions
+            .push(ScriptAction::Filter(command.to_string()));
+    });
+
+    engine
+        .run(source)
+        .map_err(|e| anyhow::anyhow!("Script error: {}", e))?;
+    drop(engine);
+
+    let output = Rc::try_unwrap(output)
+        .map_err(|_| anyhow::anyhow!("Script output is still borrowed"))?
+        .into_inner();
+    Ok(output)
+}
+
+fn allocation_map(idx: usize, alloc: &Allocation) -> Map {
+    let (start, stop) = alloc.start_end_time();
+    let mut map = Map::new();
+    map.insert("idx".into(), (idx as i64).into());
+    map.insert("size".into(), (alloc.size as i64).into());
+    map.insert("start".into(), (start as i64).into());
+    map.insert("stop".into(), (stop as i64).into());
+    map.insert("peak_mem".into(), (alloc.peak_mem as i64).into());
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::sqlite::AllocationDatabase;
+
+    #[test]
+    fn test_script_queues_actions() {
+        let db = Box::leak(Box::new(AllocationDatabase {
+            conn: rusqlite::Connection::open_in_memory().unwrap(),
+        }));
+        let allocs: Arc<[Allocation]> = Arc::from(vec![
+            Allocation {
+                timesteps: vec![0, 10].into(),
+                offsets: vec![0, 0].into(),
+                size: 96,
+                peak_mem: 96,
+                peak_timestamps: vec![0],
+                category: None,
+            },
+            Allocation {
+                timesteps: vec![5, 20].into(),
+                offsets: vec![96, 96].into(),
+                size: 100,
+                peak_mem: 196,
+                peak_timestamps: vec![5],
+                category: None,
+            },
+        ]);
+
+        let output = run_script(
+            r#"
+            for a in allocations() {
+                if a.size % 32 == 0 {
+                    print(a.idx);
+                    select(a.idx);
+                }
+            }
+            view(0, allocation(1).stop);
+            "#,
+            allocs,
+            db,
+        )
+        .unwrap();
+
+        assert_eq!(output.printed, "0\n");
+        assert_eq!(
+            output.actions,
+            vec![ScriptAction::Select(0), ScriptAction::View(0, 20)]
+        );
+    }
+
+    #[test]
+    fn test_script_limits() {
+        let db = Box::leak(Box::new(AllocationDatabase {
+            conn: rusqlite::Connection::open_in_memory().unwrap(),
+        }));
+        let allocs: Arc<[Allocation]> = Arc::from(Vec::new());
+        for source in [
+            "fn deeper(n) { deeper(n + 1) } deeper(0);",
+            r#"let s = "x"; loop { s += s; }"#,
+            "let a = [0]; loop { a += a; }",
+        ] {
+            let result = run_script(source, Arc::clone(&allocs), db);
+            assert!(result.is_err(), "{} should exceed a limit", source);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Segments the caching allocator reserved, drawn as faint bands behind the allocations (the
+//! blocks handed out from them), so reserved-but-free memory stands apart from used memory.
+
+/// `size` bytes reserved over timesteps `start..stop`, as in the segments table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub start: u64,
+    pub stop: u64,
+    pub size: u64,
+}
+
+/// Part of a segment's band where it stays at one offset: `offset..offset + size` bytes over
+/// `start..stop`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentBand {
+    /// Index into the segments the band belongs to
+    pub segment: usize,
+    pub start: u64,
+    pub stop: u64,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Segments stacked the way the converter stacks allocations: in the order they were reserved,
+/// the ones above moving down when one is released.
+pub fn stack_segments(segments: &[Segment]) -> Vec<SegmentBand> {
+    let mut times: Vec<u64> = segments.iter().flat_map(|s| [s.start, s.stop]).collect();
+    times.sort_unstable();
+    times.dedup();
+
+    let mut order: Vec<usize> = (0..segments.len()).collect();
+    order.sort_by_key(|&i| (segments[i].start, i));
+    let mut pending = order.into_iter().peekable();
+
+    let mut live: Vec<usize> = Vec::new();
+    let mut bands: Vec<SegmentBand> = Vec::new();
+    // index into `bands` of each live segment's current band
+    let mut current: Vec<Option<usize>> = vec![None; segments.len()];
+    for (i, &t) in times.iter().enumerate() {
+        live.retain(|&s| segments[s].stop > t);
+        while let Some(s) = pending.next_if(|&s| segments[s].start <= t) {
+            if segments[s].stop > segments[s].start {
+                live.push(s);
+            }
+        }
+        let Some(&stop) = times.get(i + 1) else {
+            break;
+        };
+        let mut offset = 0;
+        for &s in &live {
+            match current[s].map(|b| &mut bands[b]) {
+                Some(band) if band.offset == offset && band.stop == t => band.stop = stop,
+                _ => {
+                    current[s] = Some(bands.len());
+                    bands.push(SegmentBand {
+                        segment: s,
+                        start: t,
+                        stop,
+                        offset,
+                        size: segments[s].size,
+                    });
+                }
+            }
+            offset += segments[s].size;
+        }
+    }
+    bands
+}
+
+/// Bytes reserved at `timestep`, the top of the bands there
+pub fn reserved_at(bands: &[SegmentBand], timestep: u64) -> u64 {
+    bands
+        .iter()
+        .filter(|b| b.start <= timestep && timestep < b.stop)
+        .map(|b| b.offset + b.size)
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::segments::{Segment, SegmentBand, reserved_at, stack_segments};
+
+    #[test]
+    fn test_stack_segments() {
+        let segment = |start, stop, size| Segment { start, stop, size };
+        // the second is released while the third sits on top of it
+        let segments = [
+            segment(0, 100, 10),
+            segment(10, 50, 20),
+            segment(20, 100, 30),
+        ];
+        let band = |segment, start, stop, offset, size| SegmentBand {
+            segment,
+            start,
+            stop,
+            offset,
+            size,
+        };
+        assert_eq!(
+            stack_segments(&segments),
+            [
+                band(0, 0, 100, 0, 10),
+                band(1, 10, 50, 10, 20),
+                band(2, 20, 50, 30, 30),
+                band(2, 50, 100, 10, 30),
+            ]
+        );
+
+        let bands = stack_segments(&segments);
+        assert_eq!(reserved_at(&bands, 5), 10);
+        assert_eq!(reserved_at(&bands, 30), 60);
+        assert_eq!(reserved_at(&bands, 60), 40);
+        assert_eq!(reserved_at(&bands, 100), 0);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{
+    geometry::{AllocationGeometry, TraceGeometry},
+    ticks::memory_ticks,
+    utils::format_bytes,
+};
+use std::fmt::Write;
+use three_d::Srgba;
+
+const TICK_FONT_SIZE: u32 = 12;
+const TICK_COLOR: &str = "#333333";
+const GRID_COLOR: &str = "#d7d7d7";
+
+/// The visible part of the plot as an SVG document, for reports and papers.
+///
+/// `x_range` and `y_range` are the visible world coords, `size` the document size in pixels.
+/// Allocations entirely outside the time range are left out, and each allocation's outline is
+/// decimated to one vertex per pixel column, so the file stays small for long traces.
+///
+/// ## Returns
+/// The document and the number of allocations in it.
+pub fn render<'a>(
+    geom: &TraceGeometry,
+    allocations: impl Iterator<Item = (&'a AllocationGeometry, Srgba)>,
+    x_range: (f32, f32),
+    y_range: (f32, f32),
+    size: (u32, u32),
+) -> (String, usize) {
+    let (width, height) = (size.0 as f64, size.1 as f64);
+    let (x0, x1) = (x_range.0 as f64, x_range.1 as f64);
+    let (y0, y1) = (y_range.0 as f64, y_range.1 as f64);
+    let sx = |x: f64| (x - x0) / (x1 - x0) * width;
+    let sy = |y: f64| (y1 - geom.y_axis.map(y)) / (y1 - y0) * height;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        size.0, size.1
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+
+    let ticks = memory_ticks(geom, y_range);
+    for &(_, ratio) in &ticks {
+        let y = (1.0 - ratio as f64) * height;
+        let _ = writeln!(
+            svg,
+            r#"<line x1="0" y1="{y:.2}" x2="{width}" y2="{y:.2}" stroke="{GRID_COLOR}" stroke-width="1"/>"#
+        );
+    }
+
+    let column_width = (x1 - x0) / width;
+    let mut count = 0;
+    for (alloc, color) in allocations {
+        let (start, stop) = alloc.drawn_time_range();
+        if stop < x0 || start > x1 {
+            continue;
+        }
+        let mut alloc = alloc.decimated(column_width);
+        alloc.widen_marker();
+        // top edge left to right, then bottom edge back
+        let top = alloc
+            .timesteps
+            .iter()
+            .zip(&alloc.offsets)
+            .map(|(&t, &o)| (sx(t), sy(o + alloc.size)));
+        let bottom = alloc
+            .timesteps
+            .iter()
+            .zip(&alloc.offsets)
+            .rev()
+            .map(|(&t, &o)| (sx(t), sy(o)));
+        let points: Vec<String> = top
+            .chain(bottom)
+            .map(|(x, y)| format!("{:.2},{:.2}", x, y))
+            .collect();
+        let _ = writeln!(
+            svg,
+            r#"<polygon points="{}" fill="rgb({},{},{})" fill-opacity="{:.3}"/>"#,
+            points.join(" "),
+            color.r,
+            color.g,
+            color.b,
+            color.a as f64 / 255.0
+        );
+        count += 1;
+    }
+
+    for &(bytes, ratio) in &ticks {
+        let y = (1.0 - ratio as f64) * height;
+        let _ = writeln!(
+            svg,
+            r#"<text x="4" y="{:.2}" font-family="monospace" font-size="{}" fill="{}">{}</text>"#,
+            y - 2.0,
+            TICK_FONT_SIZE,
+            TICK_COLOR,
+            format_bytes(bytes)
+        );
+    }
+    svg.push_str("</svg>\n");
+    (svg, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocation::Allocation;
+    use crate::geometry::TraceGeometry;
+    use crate::svg::render;
+    use std::sync::Arc;
+    use three_d::Srgba;
+
+    #[test]
+    fn test_render_svg() {
+        let alloc = |start, stop, offset| Allocation {
+            timesteps: vec![start, stop].into(),
+            offsets: vec![offset, offset].into(),
+            size: 1024,
+            peak_mem: 0,
+            peak_timestamps: vec![],
+            category: None,
+        };
+        let allocs: Arc<[Allocation]> = Arc::from(vec![alloc(0, 10, 0), alloc(50, 100, 1024)]);
+        let geom = TraceGeometry::from_allocations(allocs, (100, 100));
+        let color = Srgba::new(255, 0, 0, 51);
+        let colored = geom.allocations.iter().map(|a| (a, color));
+
+        // only the first allocation is in the first fifth of the time axis
+        let (svg, count) = render(&geom, colored, (0.0, 20.0), (0.0, 100.0), (200, 100));
+        assert_eq!(count, 1);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains(r#"fill="rgb(255,0,0)" fill-opacity="0.200""#));
+        // 0..10 of 0..20 spans half of the 200 pixels; 1 KiB of the 2 KiB peak is half the height
+        assert!(svg.contains(r#"points="0.00,50.00 100.00,50.00 100.00,100.00 0.00,100.00""#));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::palette::parse_hex;
+use anyhow::bail;
+use std::str::FromStr;
+use three_d::Srgba;
+
+/// Colors of everything the renderer draws around the allocations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub background: Srgba,
+    /// Tick labels and all other text
+    pub ticks: Srgba,
+    /// Outline of the selected allocation
+    pub selection: Srgba,
+    pub grid: Srgba,
+    /// Behind the legend and the controls overlay
+    pub panel: Srgba,
+    /// Behind the allocator event glyphs
+    pub strip: Srgba,
+}
+
+impl Theme {
+    pub const LIGHT: Self = Self {
+        background: Srgba::new(255, 255, 255, 255),
+        ticks: Srgba::new(0, 0, 0, 255),
+        selection: Srgba::new(0, 0, 0, 255),
+        grid: Srgba::new(215, 215, 215, 255),
+        panel: Srgba::new(250, 246, 225, 255),
+        strip: Srgba::new(235, 235, 235, 255),
+    };
+
+    /// The GUI's `night` palette
+    pub const DARK: Self = Self {
+        background: Srgba::new(0x12, 0x12, 0x12, 255),
+        ticks: Srgba::new(0xe0, 0xe0, 0xe0, 255),
+        selection: Srgba::new(0xad, 0x70, 0xf7, 255),
+        grid: Srgba::new(0x3a, 0x3a, 0x3a, 255),
+        panel: Srgba::new(0x2d, 0x2d, 0x2d, 255),
+        strip: Srgba::new(0x1e, 0x1e, 0x1e, 255),
+    };
+
+    /// The GUI's `cute` palette
+    pub const CUTE: Self = Self {
+        background: Srgba::new(0xff, 0xf5, 0xf8, 255),
+        ticks: Srgba::new(0x2d, 0x2d, 0x2d, 255),
+        selection: Srgba::new(0xe9, 0x1e, 0x63, 255),
+        grid: Srgba::new(0xf8, 0xbb, 0xdd, 255),
+        panel: Srgba::new(0xfc, 0xe4, 0xec, 255),
+        strip: Srgba::new(0xfc, 0xe4, 0xec, 255),
+    };
+
+    /// Built-in themes, in the order N cycles through them
+    pub const PRESETS: [(&str, Self); 3] = [
+        ("light", Self::LIGHT),
+        ("dark", Self::DARK),
+        ("cute", Self::CUTE),
+    ];
+
+    /// Name of the built-in theme this is, `None` after custom colors
+    pub fn name(&self) -> Option<&'static str> {
+        Self::PRESETS
+            .iter()
+            .find(|(_, theme)| theme == self)
+            .map(|&(name, _)| name)
+    }
+
+    /// The built-in theme after this one, the first one after custom colors
+    pub fn next(&self) -> Self {
+        let i = Self::PRESETS
+            .iter()
+            .position(|(_, theme)| theme == self)
+            .map_or(0, |i| i + 1);
+        Self::PRESETS[i % Self::PRESETS.len()].1
+    }
+
+    /// `ClearState` arguments for the background
+    pub fn clear_color(&self) -> (f32, f32, f32, f32) {
+        let channel = |c: u8| c as f32 / 255.0;
+        let bg = self.background;
+        (channel(bg.r), channel(bg.g), channel(bg.b), channel(bg.a))
+    }
+}
+
+impl FromStr for Theme {
+    type Err = anyhow::Error;
+
+    /// A built-in theme, by its name or the name of the GUI palette it matches
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "light" | "default" => Ok(Theme::LIGHT),
+            "dark" | "night" => Ok(Theme::DARK),
+            "cute" => Ok(Theme::CUTE),
+            _ => bail!("Unknown theme `{}`, expected light, dark or cute", s),
+        }
+    }
+}
+
+/// An opaque hex color like `#1f77b4`, for the `--background`, `--tick-color` and
+/// `--selection-color` flags
+pub fn parse_color(token: &str) -> anyhow::Result<Srgba> {
+    parse_hex(token, 255)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::theme::{Theme, parse_color};
+    use three_d::Srgba;
+
+    #[test]
+    fn test_theme() {
+        assert_eq!("night".parse::<Theme>().unwrap(), Theme::DARK);
+        assert!("solarized".parse::<Theme>().is_err());
+
+        assert_eq!(Theme::LIGHT.next(), Theme::DARK);
+        assert_eq!(Theme::CUTE.next(), Theme::LIGHT);
+        let custom = Theme {
+            background: parse_color("#002b36").unwrap(),
+            ..Theme::DARK
+        };
+        assert_eq!(custom.background, Srgba::new(0, 0x2b, 0x36, 255));
+        assert_eq!(custom.name(), None);
+        assert_eq!(custom.next(), Theme::LIGHT);
+        assert_eq!(Theme::LIGHT.clear_color(), (1.0, 1.0, 1.0, 1.0));
+    }
+}
@@ -1,19 +1,54 @@
-use crate::{constants::INTERVALS, utils::format_bytes_precision};
+use crate::{
+    color_mode::Legend, constants::INTERVALS, events::EventKind, geometry::TraceGeometry,
+    render_loop::LabelSpot, theme::Theme, utils::format_bytes_precision,
+};
 use nalgebra::Vector2;
+use std::collections::HashMap;
 use three_d::{
-    ColorMaterial, Context, Gm, Mat4, Mesh, Srgba, TextGenerator, TextLayoutOptions, Vector3,
+    ColorMaterial, Context, CpuMesh, Geometry, Gm, Mat4, Mesh, Positions, Srgba, TextGenerator,
+    TextLayoutOptions, Vec3, Vector3,
 };
 
 pub const TICKS_FLOAT_Z: f32 = 0.01;
+/// Above allocations, below tick labels
+pub const GRID_FLOAT_Z: f32 = TICKS_FLOAT_Z / 2.0;
+pub const PEAK_COLOR: Srgba = Srgba::new(220, 40, 40, 255);
+pub const ANNOTATION_COLOR: Srgba = Srgba::new(40, 90, 220, 255);
+/// Above allocations and their selection outline, below text
+pub const OVERLAY_FLOAT_Z: f32 = TICKS_FLOAT_Z * 0.8;
+/// Event strip band and glyphs, above allocations and below the overlay
+pub const EVENT_STRIP_Z: f32 = TICKS_FLOAT_Z * 0.7;
+pub const EVENT_GLYPH_Z: f32 = TICKS_FLOAT_Z * 0.75;
+pub const EVENT_STRIP_HEIGHT_PX: f32 = 18.0;
+pub const EVENT_GLYPH_SIZE_PX: f32 = 8.0;
+pub const LINKED_CURSOR_COLOR: Srgba = Srgba::new(230, 130, 0, 255);
+/// The time cursor placed with `--at`
+pub const AT_CURSOR_COLOR: Srgba = Srgba::new(0, 150, 136, 255);
+/// Fill and border of the rectangle dragged with Shift for region stats
+pub const REGION_FILL_COLOR: Srgba = Srgba::new(40, 90, 220, 40);
+pub const REGION_BORDER_COLOR: Srgba = Srgba::new(40, 90, 220, 255);
+pub const COUNT_CURVE_COLOR: Srgba = Srgba::new(150, 40, 170, 255);
+pub const LANE_SEPARATOR_COLOR: Srgba = Srgba::new(120, 120, 120, 255);
+/// Most labels on the log memory axis
+const LOG_MAX_TICKS: usize = 12;
+/// Legend gradient, above its panel (drawn at `OVERLAY_FLOAT_Z`) and below text
+pub const LEGEND_BAR_Z: f32 = TICKS_FLOAT_Z * 0.9;
+/// Legend panel in the bottom right corner, as fractions of the window
+const LEGEND_LEFT: f32 = 0.75;
+const LEGEND_RIGHT: f32 = 0.97;
+const LEGEND_BAR_BOTTOM: f32 = 0.1;
+const LEGEND_BAR_TOP: f32 = 0.13;
+const LEGEND_KEY_WIDTH: f32 = 0.015;
 
 pub struct TickGenerator<'a> {
     pub generator: TextGenerator<'a>,
     pub resolution: (u32, u32),
     pub fontsize_px: f32,
+    pub theme: Theme,
 }
 
 impl<'a> TickGenerator<'a> {
-    pub fn jbmono(resolution: (u32, u32), fontsize_px: f32) -> Self {
+    pub fn jbmono(resolution: (u32, u32), fontsize_px: f32, theme: Theme) -> Self {
         let generator = TextGenerator::new(
             include_bytes!("../assets/JetBrainsMono-Medium.ttf"),
             0,
@@ -25,30 +60,487 @@ impl<'a> TickGenerator<'a> {
             generator,
             resolution,
             fontsize_px,
+            theme,
         }
     }
 
-    pub fn generate_memory_ticks(
+    /// Follow window resizes, `resolution` is the window size in logical pixels
+    pub fn resize(&mut self, resolution: (u32, u32)) {
+        self.resolution = resolution;
+    }
+
+    /// Faint horizontal lines across the visible plot at each memory tick, see `memory_ticks`.
+    /// `x_range` and `y_range` are the visible world coords, as (min, max).
+    pub fn generate_memory_grid(
         &self,
-        low_bytes: i64,
-        high_bytes: i64,
+        ticks: &[(i64, f32)],
         scale: f32,
-        screen_center_world: Vector2<f32>, // world coords of the screen center
+        x_range: (f32, f32),
+        y_range: (f32, f32),
+        context: &'a Context,
+    ) -> Option<Gm<Mesh, ColorMaterial>> {
+        let ys = ticks
+            .iter()
+            .map(|&(_, y_ratio)| y_range.0 + y_ratio * (y_range.1 - y_range.0));
+
+        // 1 logical pixel thick
+        let lines = ys.map(|y| (x_range.0, y - scale / 2.0, x_range.1, y + scale / 2.0));
+        rects_mesh(lines, self.theme.grid, GRID_FLOAT_Z, context)
+    }
+
+    /// Red line and label at the global memory peak, and vertical lines at the times it is reached.
+    /// Only what falls inside the visible `x_range`/`y_range` is generated.
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_peak_markers(
+        &self,
+        peak_bytes: i64,
+        peak_y: f32,
+        peak_xs: &[f32],
+        scale: f32,
+        screen_center_world: Vector2<f32>,
+        x_range: (f32, f32),
+        y_range: (f32, f32),
         context: &'a Context,
     ) -> Vec<Gm<Mesh, ColorMaterial>> {
-        // 1. generate ticks as a list of u64
-        let ticks_bytes = generate_ticks(low_bytes, high_bytes);
+        let mut markers = Vec::new();
+        let half_width = 1.5 * scale;
+
+        let peak_visible = y_range.0 <= peak_y && peak_y <= y_range.1;
+        let horizontal = peak_visible.then_some((
+            x_range.0,
+            peak_y - half_width,
+            x_range.1,
+            peak_y + half_width,
+        ));
+        let verticals = peak_xs
+            .iter()
+            .filter(|&&x| x_range.0 <= x && x <= x_range.1)
+            .map(|&x| (x - half_width, y_range.0, x + half_width, y_range.1));
+        markers.extend(rects_mesh(
+            horizontal.into_iter().chain(verticals),
+            PEAK_COLOR,
+            GRID_FLOAT_Z,
+            context,
+        ));
+
+        if peak_visible {
+            let y_ratio = (peak_y - y_range.0) / (y_range.1 - y_range.0);
+            let text = format!("—— peak {}", format_bytes_precision(peak_bytes, 4));
+            let mut label =
+                self.generate_text_mesh(&text, y_ratio, scale, screen_center_world, context);
+            label.material.color = PEAK_COLOR;
+            markers.push(label);
+        }
+
+        markers
+    }
 
-        // 2. map ticks to meshes
-        ticks_bytes
+    /// Gray line at the bottom of each `(bottom y, top y, label)` lane but the lowest,
+    /// and the lane's label at the right, for lanes in `y_range`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_lane_separators(
+        &self,
+        lanes: &[(f32, f32, String)],
+        scale: f32,
+        screen_center_world: Vector2<f32>,
+        x_range: (f32, f32),
+        y_range: (f32, f32),
+        context: &'a Context,
+    ) -> Vec<Gm<Mesh, ColorMaterial>> {
+        let lines = lanes
+            .iter()
+            .skip(1)
+            .filter(|(bottom, _, _)| y_range.0 <= *bottom && *bottom <= y_range.1)
+            .map(|(bottom, _, _)| (x_range.0, bottom - scale, x_range.1, bottom + scale));
+        let mut separators: Vec<_> = rects_mesh(lines, LANE_SEPARATOR_COLOR, GRID_FLOAT_Z, context)
             .into_iter()
-            .map(|bytes| {
-                let y_ratio = (bytes - low_bytes) as f32 / (high_bytes - low_bytes) as f32;
-                let text = format!("—— {}", format_bytes_precision(bytes, 4));
-                self.generate_text_mesh(&text, y_ratio, scale, screen_center_world, context)
+            .collect();
+
+        for (bottom, top, label) in lanes {
+            // label the middle of the lane's visible part
+            let (bottom, top) = (bottom.max(y_range.0), top.min(y_range.1));
+            if bottom >= top {
+                continue;
+            }
+            let y_ratio = ((bottom + top) / 2.0 - y_range.0) / (y_range.1 - y_range.0);
+            let mut text = self.generate_text_mesh_at(
+                label,
+                0.85,
+                y_ratio,
+                scale,
+                screen_center_world,
+                context,
+            );
+            text.material.color = LANE_SEPARATOR_COLOR;
+            separators.push(text);
+        }
+        separators
+    }
+
+    /// Blue vertical line and label for each `(x world coord, label)` annotation in `x_range`.
+    /// Labels are staggered vertically so close annotations stay readable.
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_annotation_markers(
+        &self,
+        annotations: &[(f32, &str)],
+        scale: f32,
+        screen_center_world: Vector2<f32>,
+        x_range: (f32, f32),
+        y_range: (f32, f32),
+        context: &'a Context,
+    ) -> Vec<Gm<Mesh, ColorMaterial>> {
+        let visible: Vec<(usize, f32, &str)> = annotations
+            .iter()
+            .enumerate()
+            .filter(|(_, (x, _))| x_range.0 <= *x && *x <= x_range.1)
+            .map(|(i, (x, label))| (i, *x, *label))
+            .collect();
+
+        let half_width = scale;
+        let lines = visible
+            .iter()
+            .map(|&(_, x, _)| (x - half_width, y_range.0, x + half_width, y_range.1));
+        let mut markers: Vec<_> = rects_mesh(lines, ANNOTATION_COLOR, GRID_FLOAT_Z, context)
+            .into_iter()
+            .collect();
+
+        for (i, x, label) in visible {
+            let x_ratio = (x - x_range.0) / (x_range.1 - x_range.0);
+            let y_ratio = 0.95 - 0.04 * (i % 4) as f32;
+            let mut text = self.generate_text_mesh_at(
+                &format!(" {}", label),
+                x_ratio,
+                y_ratio,
+                scale,
+                screen_center_world,
+                context,
+            );
+            text.material.color = ANNOTATION_COLOR;
+            markers.push(text);
+        }
+        markers
+    }
+
+    /// Each label centered on its allocation's spot, left out where it does not fit on screen
+    pub fn generate_allocation_labels(
+        &self,
+        labels: &[(LabelSpot, &str)],
+        scale: f32,
+        context: &'a Context,
+    ) -> Vec<Gm<Mesh, ColorMaterial>> {
+        labels
+            .iter()
+            .filter(|(spot, _)| spot.height / scale >= self.fontsize_px)
+            .filter_map(|&(spot, label)| {
+                let mut text = self.text_mesh(label, context);
+                let width_px = text.aabb().size().x;
+                if (spot.x_range.1 - spot.x_range.0) / scale < width_px {
+                    return None;
+                }
+                let left = (spot.x_range.0 + spot.x_range.1) / 2.0 - width_px * scale / 2.0;
+                let bottom = spot.y - self.fontsize_px * scale / 2.0;
+                text.set_transformation(
+                    Mat4::from_translation(Vector3::new(left, bottom, TICKS_FLOAT_Z))
+                        * Mat4::from_scale(scale),
+                );
+                Some(text)
             })
             .collect()
     }
+
+    /// Vertical line at `x` (world coords), e.g. where the linked renderer's cursor is, if in
+    /// `x_range`
+    pub fn generate_cursor_line(
+        &self,
+        x: f32,
+        color: Srgba,
+        scale: f32,
+        x_range: (f32, f32),
+        y_range: (f32, f32),
+        context: &'a Context,
+    ) -> Option<Gm<Mesh, ColorMaterial>> {
+        if x < x_range.0 || x > x_range.1 {
+            return None;
+        }
+        rects_mesh(
+            std::iter::once((x - scale, y_range.0, x + scale, y_range.1)),
+            color,
+            GRID_FLOAT_Z,
+            context,
+        )
+    }
+
+    /// Translucent rectangle with a 1 px border between the corners `p0` and `p1` (world coords)
+    pub fn generate_region_box(
+        &self,
+        p0: Vector2<f32>,
+        p1: Vector2<f32>,
+        scale: f32,
+        context: &'a Context,
+    ) -> Vec<Gm<Mesh, ColorMaterial>> {
+        let (x0, x1) = (p0.x.min(p1.x), p0.x.max(p1.x));
+        let (y0, y1) = (p0.y.min(p1.y), p0.y.max(p1.y));
+        let border = [
+            (x0, y0, x1, y0 + scale),
+            (x0, y1 - scale, x1, y1),
+            (x0, y0, x0 + scale, y1),
+            (x1 - scale, y0, x1, y1),
+        ];
+        rects_mesh(
+            std::iter::once((x0, y0, x1, y1)),
+            REGION_FILL_COLOR,
+            GRID_FLOAT_Z,
+            context,
+        )
+        .into_iter()
+        .chain(rects_mesh(
+            border.into_iter(),
+            REGION_BORDER_COLOR,
+            GRID_FLOAT_Z,
+            context,
+        ))
+        .collect()
+    }
+
+    /// Step curve of `values`, one per equal slice of `x_range`, scaled so their maximum reaches
+    /// the top of `y_range`. Drawn over the allocations on its own vertical scale.
+    pub fn generate_count_curve(
+        &self,
+        values: &[u64],
+        scale: f32,
+        x_range: (f32, f32),
+        y_range: (f32, f32),
+        context: &'a Context,
+    ) -> Option<Gm<Mesh, ColorMaterial>> {
+        let max = values.iter().copied().max()?.max(1) as f32;
+        let width = (x_range.1 - x_range.0) / values.len() as f32;
+        let y = |v: u64| y_range.0 + (y_range.1 - y_range.0) * 0.95 * v as f32 / max;
+        let horizontal = values.iter().enumerate().map(|(i, &v)| {
+            let x = x_range.0 + width * i as f32;
+            (x, y(v) - scale, x + width, y(v) + scale)
+        });
+        let vertical = values.windows(2).enumerate().map(|(i, pair)| {
+            let x = x_range.0 + width * (i + 1) as f32;
+            let (lo, hi) = (y(pair[0].min(pair[1])), y(pair[0].max(pair[1])));
+            (x - scale, lo - scale, x + scale, hi + scale)
+        });
+        rects_mesh(
+            horizontal.chain(vertical),
+            COUNT_CURVE_COLOR,
+            GRID_FLOAT_Z,
+            context,
+        )
+    }
+
+    /// Band of `EVENT_STRIP_HEIGHT_PX` along the bottom of the view, with a square glyph for each
+    /// `(x world coord, kind)` event in `x_range`, colored by kind.
+    pub fn generate_event_strip(
+        &self,
+        events: &[(f32, EventKind)],
+        scale: f32,
+        x_range: (f32, f32),
+        y_bottom: f32,
+        context: &'a Context,
+    ) -> Vec<Gm<Mesh, ColorMaterial>> {
+        let y_top = y_bottom + EVENT_STRIP_HEIGHT_PX * scale;
+        let mut strip: Vec<_> = rects_mesh(
+            std::iter::once((x_range.0, y_bottom, x_range.1, y_top)),
+            self.theme.strip,
+            EVENT_STRIP_Z,
+            context,
+        )
+        .into_iter()
+        .collect();
+
+        let half = EVENT_GLYPH_SIZE_PX / 2.0 * scale;
+        let y_mid = (y_bottom + y_top) / 2.0;
+        for kind in [
+            EventKind::SegmentFree,
+            EventKind::Snapshot,
+            EventKind::OutOfMemory,
+        ] {
+            let glyphs = events
+                .iter()
+                .filter(|(x, k)| *k == kind && x_range.0 <= *x && *x <= x_range.1)
+                .map(|(x, _)| (x - half, y_mid - half, x + half, y_mid + half));
+            strip.extend(rects_mesh(
+                glyphs,
+                event_color(kind),
+                EVENT_GLYPH_Z,
+                context,
+            ));
+        }
+        strip
+    }
+
+    /// Centered panel listing `lines`, drawn over everything else in the window
+    pub fn generate_overlay(
+        &self,
+        lines: &[&str],
+        scale: f32,
+        screen_center_world: Vector2<f32>,
+        context: &'a Context,
+    ) -> Vec<Gm<Mesh, ColorMaterial>> {
+        let line_height = 0.05;
+        let top = 0.5 + line_height * lines.len() as f32 / 2.0;
+        let ratio2world = |x_ratio: f32, y_ratio: f32| {
+            screen_center_world
+                + scale
+                    * Vector2::new(
+                        (x_ratio - 0.5) * self.resolution.0 as f32,
+                        (y_ratio - 0.5) * self.resolution.1 as f32,
+                    )
+        };
+        let lo = ratio2world(0.15, 1.0 - top - line_height);
+        let hi = ratio2world(0.85, top + line_height);
+        let mut overlay: Vec<_> = rects_mesh(
+            std::iter::once((lo.x, lo.y, hi.x, hi.y)),
+            self.theme.panel,
+            OVERLAY_FLOAT_Z,
+            context,
+        )
+        .into_iter()
+        .collect();
+        overlay.extend(lines.iter().enumerate().map(|(i, line)| {
+            self.generate_text_mesh_at(
+                line,
+                0.18,
+                top - line_height * i as f32,
+                scale,
+                screen_center_world,
+                context,
+            )
+        }));
+        overlay
+    }
+
+    /// Gradient bar with the legend's title above and labels below, in the bottom right corner.
+    /// Legends with keys list a swatch and name per key instead, growing upwards.
+    pub fn generate_legend(
+        &self,
+        legend: &Legend,
+        scale: f32,
+        screen_center_world: Vector2<f32>,
+        context: &'a Context,
+    ) -> Vec<Gm<Mesh, ColorMaterial>> {
+        let ratio2world = |x_ratio: f32, y_ratio: f32| {
+            screen_center_world
+                + scale
+                    * Vector2::new(
+                        (x_ratio - 0.5) * self.resolution.0 as f32,
+                        (y_ratio - 0.5) * self.resolution.1 as f32,
+                    )
+        };
+        let line_height = 0.035;
+        let nkeys = legend.keys.len();
+        let (bottom, title_y) = if nkeys == 0 {
+            (
+                LEGEND_BAR_BOTTOM - 2.0 * line_height,
+                LEGEND_BAR_TOP + line_height,
+            )
+        } else {
+            (
+                LEGEND_BAR_BOTTOM - line_height,
+                LEGEND_BAR_BOTTOM + line_height * nkeys as f32,
+            )
+        };
+        let lo = ratio2world(LEGEND_LEFT - 0.01, bottom);
+        let hi = ratio2world(LEGEND_RIGHT + 0.01, title_y + line_height);
+        let mut meshes: Vec<_> = rects_mesh(
+            std::iter::once((lo.x, lo.y, hi.x, hi.y)),
+            self.theme.panel,
+            OVERLAY_FLOAT_Z,
+            context,
+        )
+        .into_iter()
+        .collect();
+
+        // one swatch per gradient color or key, colored per vertex
+        let width = (LEGEND_RIGHT - LEGEND_LEFT) / legend.gradient.len().max(1) as f32;
+        let gradient_swatches = legend.gradient.iter().enumerate().map(|(i, &color)| {
+            let x0 = LEGEND_LEFT + width * i as f32;
+            (x0, LEGEND_BAR_BOTTOM, x0 + width, LEGEND_BAR_TOP, color)
+        });
+        let key_y = |i: usize| LEGEND_BAR_BOTTOM + line_height * (nkeys - 1 - i) as f32;
+        let key_swatches = legend.keys.iter().enumerate().map(|(i, &(color, _))| {
+            let y0 = key_y(i);
+            let y1 = y0 + LEGEND_BAR_TOP - LEGEND_BAR_BOTTOM;
+            (LEGEND_LEFT, y0, LEGEND_LEFT + LEGEND_KEY_WIDTH, y1, color)
+        });
+        let mut verts = Vec::new();
+        let mut colors = Vec::new();
+        for (x0, y0, x1, y1, color) in gradient_swatches.chain(key_swatches) {
+            // opaque, the fill alpha is for overlapping allocations
+            let color = if nkeys == 0 {
+                color
+            } else {
+                Srgba { a: 255, ..color }
+            };
+            let p0 = ratio2world(x0, y0);
+            let p1 = ratio2world(x1, y1);
+            verts.extend([
+                Vec3::new(p0.x, p0.y, LEGEND_BAR_Z),
+                Vec3::new(p1.x, p0.y, LEGEND_BAR_Z),
+                Vec3::new(p1.x, p1.y, LEGEND_BAR_Z),
+                Vec3::new(p0.x, p0.y, LEGEND_BAR_Z),
+                Vec3::new(p1.x, p1.y, LEGEND_BAR_Z),
+                Vec3::new(p0.x, p1.y, LEGEND_BAR_Z),
+            ]);
+            colors.extend([color; 6]);
+        }
+        if !verts.is_empty() {
+            let cpumesh = CpuMesh {
+                positions: Positions::F32(verts),
+                colors: Some(colors),
+                ..Default::default()
+            };
+            meshes.push(Gm::new(
+                Mesh::new(context, &cpumesh),
+                ColorMaterial {
+                    color: Srgba::WHITE,
+                    ..Default::default()
+                },
+            ));
+        }
+
+        meshes.push(self.generate_text_mesh_at(
+            &legend.title,
+            LEGEND_LEFT,
+            title_y,
+            scale,
+            screen_center_world,
+            context,
+        ));
+        for (i, (_, name)) in legend.keys.iter().enumerate() {
+            meshes.push(self.generate_text_mesh_at(
+                name,
+                LEGEND_LEFT + LEGEND_KEY_WIDTH + 0.01,
+                key_y(i),
+                scale,
+                screen_center_world,
+                context,
+            ));
+        }
+        // labels start at their spot along the bar, the last one is pulled in to stay visible
+        let nlabels = legend.labels.len();
+        for (i, label) in legend.labels.iter().enumerate() {
+            let t = if nlabels > 1 {
+                i as f32 / (nlabels - 1) as f32
+            } else {
+                0.0
+            };
+            let x = (LEGEND_LEFT + (LEGEND_RIGHT - LEGEND_LEFT) * t).min(LEGEND_RIGHT - 0.06);
+            meshes.push(self.generate_text_mesh_at(
+                label,
+                x,
+                LEGEND_BAR_BOTTOM - line_height,
+                scale,
+                screen_center_world,
+                context,
+            ));
+        }
+        meshes
+    }
 }
 
 impl<'a> TickGenerator<'a> {
@@ -62,24 +554,53 @@ impl<'a> TickGenerator<'a> {
         screen_center_world: Vector2<f32>, // world coords of the screen center
         context: &'a Context,
     ) -> Gm<Mesh, ColorMaterial> {
-        let screen_pos_y_px = y_ratio * self.resolution.1 as f32 - self.fontsize_px / 2.0; // align font height center
+        self.generate_text_mesh_at(text, 0.0, y_ratio, scale, screen_center_world, context)
+    }
 
-        let center2pos_world = scale
-            * Vector2::new(
-                -((self.resolution.0 / 2) as f32),
-                screen_pos_y_px - (self.resolution.1 / 2) as f32,
-            );
+    /// Like `generate_text_mesh`, with the text starting at `x_ratio` (0~1, 0 for left) of the width
+    pub fn generate_text_mesh_at(
+        &self,
+        text: &str,
+        x_ratio: f32,
+        y_ratio: f32,
+        scale: f32,
+        screen_center_world: Vector2<f32>, // world coords of the screen center
+        context: &'a Context,
+    ) -> Gm<Mesh, ColorMaterial> {
+        let mut gm = self.text_mesh(text, context);
+        gm.set_transformation(self.text_transform(x_ratio, y_ratio, scale, screen_center_world));
+        gm
+    }
 
-        let font_pos_world = screen_center_world + center2pos_world;
+    /// Text at the origin, in font units
+    fn text_mesh(&self, text: &str, context: &Context) -> Gm<Mesh, ColorMaterial> {
         let cpumesh = self.generator.generate(text, TextLayoutOptions::default());
-
-        let mut gm = Gm::new(
+        Gm::new(
             Mesh::new(context, &cpumesh),
             ColorMaterial {
-                color: Srgba::BLACK,
+                color: self.theme.ticks,
                 ..Default::default()
             },
-        );
+        )
+    }
+
+    /// Places a `text_mesh` at `x_ratio`, `y_ratio` of the window, see `generate_text_mesh_at`
+    fn text_transform(
+        &self,
+        x_ratio: f32,
+        y_ratio: f32,
+        scale: f32,
+        screen_center_world: Vector2<f32>,
+    ) -> Mat4 {
+        let screen_pos_x_px = x_ratio * self.resolution.0 as f32;
+        let screen_pos_y_px = y_ratio * self.resolution.1 as f32 - self.fontsize_px / 2.0; // align font height center
+
+        let center2pos_world = scale
+            * Vector2::new(
+                screen_pos_x_px - (self.resolution.0 / 2) as f32,
+                screen_pos_y_px - (self.resolution.1 / 2) as f32,
+            );
+        let font_pos_world = screen_center_world + center2pos_world;
 
         let scale_transform = Mat4::from_scale(scale);
         let translate_transform = Mat4::from_translation(Vector3::new(
@@ -89,12 +610,111 @@ impl<'a> TickGenerator<'a> {
         ));
 
         // first scale, then translate
-        let transform = translate_transform * scale_transform;
+        translate_transform * scale_transform
+    }
+}
 
-        gm.set_transformation(transform);
+/// Memory tick labels kept across frames: a label's mesh is built once per (bytes, scale bucket)
+/// and only moved while panning, and nothing is touched while the view stays the same.
+#[derive(Default)]
+pub struct TickCache {
+    meshes: HashMap<(i64, i32), Gm<Mesh, ColorMaterial>>,
+    /// Keys of the ticks in the current view, bottom to top
+    visible: Vec<(i64, i32)>,
+    view: Option<TickView>,
+}
 
-        gm
+/// (ticks, scale, center, resolution) the tick labels were placed for
+type TickView = (Vec<(i64, u32)>, u32, [u32; 2], (u32, u32));
+
+impl TickCache {
+    /// Place labels for `ticks` (see `memory_ticks`), building missing meshes
+    pub fn update(
+        &mut self,
+        tickgen: &TickGenerator,
+        ticks: &[(i64, f32)],
+        scale: f32,
+        screen_center_world: Vector2<f32>, // world coords of the screen center
+        context: &Context,
+    ) {
+        let view = (
+            ticks
+                .iter()
+                .map(|&(bytes, y_ratio)| (bytes, y_ratio.to_bits()))
+                .collect(),
+            scale.to_bits(),
+            [
+                screen_center_world.x.to_bits(),
+                screen_center_world.y.to_bits(),
+            ],
+            tickgen.resolution,
+        );
+        if self.view.as_ref() == Some(&view) {
+            return;
+        }
+        self.view = Some(view);
+
+        let bucket = scale.log2().floor() as i32;
+        self.visible = ticks.iter().map(|&(bytes, _)| (bytes, bucket)).collect();
+        // drop labels that went off screen, so the cache stays small
+        self.meshes.retain(|key, _| self.visible.contains(key));
+
+        for &(bytes, y_ratio) in ticks {
+            let gm = self.meshes.entry((bytes, bucket)).or_insert_with(|| {
+                let text = format!("—— {}", format_bytes_precision(bytes, 4));
+                tickgen.text_mesh(&text, context)
+            });
+            gm.set_transformation(tickgen.text_transform(0.0, y_ratio, scale, screen_center_world));
+        }
+    }
+
+    pub fn meshes(&self) -> impl Iterator<Item = &Gm<Mesh, ColorMaterial>> {
+        self.visible.iter().filter_map(|key| self.meshes.get(key))
+    }
+}
+
+pub fn event_color(kind: EventKind) -> Srgba {
+    match kind {
+        EventKind::OutOfMemory => Srgba::new(220, 30, 30, 255),
+        EventKind::SegmentFree => Srgba::new(140, 60, 200, 255),
+        EventKind::Snapshot => Srgba::new(30, 150, 70, 255),
+    }
+}
+
+/// Axis-aligned rectangles `(x0, y0, x1, y1)` in world coords at depth `z`, as one flat colored mesh
+fn rects_mesh(
+    rects: impl Iterator<Item = (f32, f32, f32, f32)>,
+    color: Srgba,
+    z: f32,
+    context: &Context,
+) -> Option<Gm<Mesh, ColorMaterial>> {
+    let verts: Vec<Vec3> = rects
+        .flat_map(|(x0, y0, x1, y1)| {
+            [
+                Vec3::new(x0, y0, z),
+                Vec3::new(x1, y0, z),
+                Vec3::new(x1, y1, z),
+                Vec3::new(x0, y0, z),
+                Vec3::new(x1, y1, z),
+                Vec3::new(x0, y1, z),
+            ]
+        })
+        .collect();
+    if verts.is_empty() {
+        return None;
     }
+
+    let cpumesh = CpuMesh {
+        positions: Positions::F32(verts),
+        ..Default::default()
+    };
+    Some(Gm::new(
+        Mesh::new(context, &cpumesh),
+        ColorMaterial {
+            color,
+            ..Default::default()
+        },
+    ))
 }
 
 fn choose_interval(a: f64, b: f64, min_ticks: usize) -> f64 {
@@ -142,6 +762,46 @@ fn generate_ticks_f64(a: f64, b: f64, interval: f64) -> Vec<f64> {
     ticks
 }
 
+/// Powers of two between `low_bytes` and `high_bytes` (and 0), for the log memory axis.
+/// Exponents are skipped to keep at most `LOG_MAX_TICKS`; ranges too narrow to contain two
+/// powers (zoomed in) get linear ticks, as the log scale is nearly linear there.
+pub fn generate_log_ticks(low_bytes: i64, high_bytes: i64) -> Vec<i64> {
+    let mut ticks = Vec::new();
+    if low_bytes <= 0 && high_bytes >= 0 {
+        ticks.push(0);
+    }
+    if high_bytes >= 1 {
+        let lo_exp = (low_bytes.max(1) as f64).log2().ceil() as u32;
+        let hi_exp = (high_bytes as f64).log2().floor() as u32;
+        let count = (hi_exp + 1).saturating_sub(lo_exp) as usize;
+        let step = count.div_ceil(LOG_MAX_TICKS).max(1);
+        ticks.extend((lo_exp..=hi_exp).step_by(step).map(|exp| 1i64 << exp));
+    }
+    if ticks.len() < 2 {
+        return generate_ticks(low_bytes, high_bytes);
+    }
+    ticks
+}
+
+/// Ticks for the visible memory range `y_range` (world coords, as (min, max)), as
+/// (bytes, height ratio 0~1 from the bottom), on the trace's linear or log axis
+pub fn memory_ticks(geom: &TraceGeometry, y_range: (f32, f32)) -> Vec<(i64, f32)> {
+    let low_bytes = geom.yworld2memory(y_range.0);
+    let high_bytes = geom.yworld2memory(y_range.1);
+    let ticks = if geom.y_axis.log {
+        generate_log_ticks(low_bytes, high_bytes)
+    } else {
+        generate_ticks(low_bytes, high_bytes)
+    };
+    ticks
+        .into_iter()
+        .map(|bytes| {
+            let y = geom.memory2yworld(bytes);
+            (bytes, (y - y_range.0) / (y_range.1 - y_range.0))
+        })
+        .collect()
+}
+
 pub fn generate_ticks(low_bytes: i64, high_bytes: i64) -> Vec<i64> {
     let a = low_bytes as f64;
     let b = high_bytes as f64;
@@ -155,7 +815,7 @@ pub fn generate_ticks(low_bytes: i64, high_bytes: i64) -> Vec<i64> {
 
 #[cfg(test)]
 mod tests {
-    use crate::ticks::generate_ticks;
+    use crate::ticks::{generate_log_ticks, generate_ticks};
 
     #[test]
     fn test_ticks() {
@@ -179,4 +839,16 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_log_ticks() {
+        let ticks = generate_log_ticks(0, 1 << 30);
+        assert_eq!(ticks.len(), 12);
+        assert_eq!(ticks[..4], [0, 1, 8, 64]);
+        assert_eq!(ticks.last(), Some(&(1 << 30)));
+
+        // no power of two in range
+        let (low, high) = (1_200_000_000, 1_300_000_000);
+        assert_eq!(generate_log_ticks(low, high), generate_ticks(low, high));
+    }
 }
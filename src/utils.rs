@@ -1,8 +1,14 @@
-use crate::constants::UNITS;
+use crate::constants::{CONFIG_DIR_NAME, UNITS};
 use indicatif::{ProgressBar, ProgressStyle};
 use memory_stats::memory_stats;
+use std::path::PathBuf;
 use std::time::Duration;
 
+/// Per-user state directory, `None` if there is no home directory
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::home_dir().map(|home| home.join(CONFIG_DIR_NAME))
+}
+
 pub fn memory_usage() -> f64 {
     memory_stats().unwrap().virtual_mem as f64 / (1024.0 * 1024.0)
 }
@@ -98,3 +104,42 @@ pub fn get_spinner(message: &str) -> anyhow::Result<ProgressBar> {
 
     Ok(bar)
 }
+
+/// Inverse of `format_bytes`: parses `1024`, `512KiB`, `1.5 MiB` etc. into bytes.
+/// Units are binary and case insensitive, the trailing `B` is optional.
+pub fn parse_bytes(text: &str) -> anyhow::Result<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (num, unit) = text.split_at(split);
+
+    let num: f64 = num
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size '{}', expected e.g. `1MiB`", text))?;
+
+    let unit = unit.trim();
+    let unit = unit
+        .strip_suffix('B')
+        .or_else(|| unit.strip_suffix('b'))
+        .unwrap_or(unit);
+    let power = UNITS
+        .iter()
+        .position(|u| u.eq_ignore_ascii_case(unit))
+        .ok_or_else(|| anyhow::anyhow!("Unknown size unit in '{}'", text))?;
+
+    Ok((num * 1024_f64.powi(power as i32)) as u64)
+}
+
+/// Parses an inclusive timestamp range written as `<start>..<stop>`
+pub fn parse_time_range(text: &str) -> anyhow::Result<(u64, u64)> {
+    let (start, stop) = text.split_once("..").ok_or_else(|| {
+        anyhow::anyhow!("Invalid time range '{}', expected `<start>..<stop>`", text)
+    })?;
+    let start: u64 = start.parse()?;
+    let stop: u64 = stop.parse()?;
+    if start > stop {
+        anyhow::bail!("Empty time range: {} > {}", start, stop);
+    }
+    Ok((start, stop))
+}
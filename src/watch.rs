@@ -0,0 +1,105 @@
+//! `--watch`: noticing that the shown snapshot's converted directory changed, e.g. converted
+//! again after a training job dumped a new snapshot, so the renderer reloads it.
+
+use crate::{
+    constants::{MANIFEST_FILE_NAME, METADATA_FILE_NAME, WATCH_POLL_MS},
+    input::CONVERTED_FILES,
+    manifest::Manifest,
+};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// First part of the two-part IPC message sent once a reload (`--watch` or `--reload`) succeeded,
+/// the directory now shown in its second part, so gui.py can delete the cache entry it replaced
+pub const RELOADED_EVENT: &str = "reloaded";
+
+pub struct Watcher {
+    dir: PathBuf,
+    /// Newest modification time among the snapshot's files when it was loaded
+    loaded: Option<SystemTime>,
+    /// The same at the last poll; a change is reported once it holds still for a poll
+    seen: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl Watcher {
+    /// Watches `dir`, as it is loaded now
+    pub fn new(dir: &Path) -> Self {
+        let stamp = newest_modification(dir);
+        Self {
+            dir: dir.to_path_buf(),
+            loaded: stamp,
+            seen: stamp,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Whether the snapshot changed since it was loaded and is completely written, checked at
+    /// most every `WATCH_POLL_MS`. True once per change.
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < Duration::from_millis(WATCH_POLL_MS) {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let stamp = newest_modification(&self.dir);
+        let settled = stamp == self.seen;
+        self.seen = stamp;
+        if stamp.is_none() || !settled || stamp == self.loaded || !conversion_complete(&self.dir) {
+            return false;
+        }
+        self.loaded = stamp;
+        true
+    }
+}
+
+fn newest_modification(dir: &Path) -> Option<SystemTime> {
+    CONVERTED_FILES
+        .iter()
+        .chain([&METADATA_FILE_NAME])
+        .filter_map(|name| std::fs::metadata(dir.join(name)).ok()?.modified().ok())
+        .max()
+}
+
+/// Whether `convert` finished writing every snapshot file in `dir`: while it runs, the files it
+/// has not written yet are the previous conversion's. True for directories without a manifest.
+fn conversion_complete(dir: &Path) -> bool {
+    let Ok(bytes) = std::fs::read(dir.join(MANIFEST_FILE_NAME)) else {
+        return true;
+    };
+    let Ok(manifest) = serde_json::from_slice::<Manifest>(&bytes) else {
+        return false;
+    };
+    CONVERTED_FILES
+        .iter()
+        .chain([&METADATA_FILE_NAME])
+        .filter(|name| dir.join(name).exists())
+        .all(|name| manifest.is_done(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::Manifest;
+    use crate::watch::conversion_complete;
+
+    #[test]
+    fn test_conversion_complete() {
+        let dir = std::env::temp_dir().join(format!("snapviewer-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("elements.db"), b"").unwrap();
+        std::fs::write(dir.join("allocations.bin"), b"").unwrap();
+        let without_manifest = conversion_complete(&dir);
+
+        let input = dir.join("snap.pickle");
+        std::fs::write(&input, b"pickle").unwrap();
+        let mut manifest = Manifest::load(&dir, &input, None);
+        manifest.complete(&dir, "elements.db").unwrap();
+        let halfway = conversion_complete(&dir);
+        manifest.complete(&dir, "allocations.bin").unwrap();
+        let done = conversion_complete(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(without_manifest);
+        assert!(!halfway);
+        assert!(done);
+    }
+}
@@ -0,0 +1,147 @@
+//! three-d's `Window`, with the winit window kept at hand for what three-d does not expose:
+//! F11 switches fullscreen.
+
+use std::path::Path;
+use three_d::{
+    Context, FrameInput, FrameInputGenerator, FrameOutput, WindowError, WindowSettings,
+    WindowedContext,
+};
+use winit::{
+    dpi::LogicalSize,
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Fullscreen, Window, WindowBuilder},
+};
+
+pub struct ViewerWindow {
+    window: Window,
+    event_loop: EventLoop<()>,
+    gl: WindowedContext,
+}
+
+impl ViewerWindow {
+    /// A window as `three_d::Window::new` makes it, for the desktop
+    pub fn new(settings: WindowSettings) -> Result<Self, WindowError> {
+        let event_loop = EventLoop::new();
+        let mut builder = WindowBuilder::new()
+            .with_title(&settings.title)
+            .with_min_inner_size(LogicalSize::new(settings.min_size.0, settings.min_size.1))
+            .with_decorations(!settings.borderless);
+        builder = match settings.initial_size {
+            Some((width, height)) => {
+                builder.with_inner_size(LogicalSize::new(width as f64, height as f64))
+            }
+            None => builder.with_maximized(true),
+        };
+        let window = builder.build(&event_loop)?;
+        window.focus_window();
+
+        let mut surface_settings = settings.surface_settings;
+        let mut gl = WindowedContext::from_winit_window(&window, surface_settings);
+        if gl.is_err() {
+            surface_settings.multisamples = 0;
+            gl = WindowedContext::from_winit_window(&window, surface_settings);
+        }
+        Ok(Self {
+            window,
+            event_loop,
+            gl: gl?,
+        })
+    }
+
+    pub fn gl(&self) -> Context {
+        (*self.gl).clone()
+    }
+
+    /// Calls `callback` each frame, like `three_d::Window::render_loop`
+    pub fn render_loop<F: 'static + FnMut(FrameInput) -> FrameOutput>(self, mut callback: F) {
+        let Self {
+            window,
+            event_loop,
+            gl,
+        } = self;
+        let mut frame_input_generator = FrameInputGenerator::from_winit_window(&window);
+        event_loop.run(move |event, _, control_flow| match event {
+            Event::MainEventsCleared => window.request_redraw(),
+            Event::RedrawRequested(_) => {
+                let frame_input = frame_input_generator.generate(&gl);
+                let frame_output = callback(frame_input);
+                if frame_output.exit {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                if frame_output.swap_buffers {
+                    gl.swap_buffers().unwrap();
+                }
+                if frame_output.wait_next_event {
+                    *control_flow = ControlFlow::Wait;
+                } else {
+                    *control_flow = ControlFlow::Poll;
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent { ref event, .. } => {
+                frame_input_generator.handle_winit_window_event(event);
+                match event {
+                    WindowEvent::Resized(physical_size) => gl.resize(*physical_size),
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        gl.resize(**new_inner_size)
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::F11),
+                                ..
+                            },
+                        ..
+                    } => toggle_fullscreen(&window),
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    _ => (),
+                }
+            }
+            _ => (),
+        });
+    }
+}
+
+/// Borderless fullscreen on the window's monitor, or back to a window
+fn toggle_fullscreen(window: &Window) {
+    let fullscreen = match window.fullscreen() {
+        Some(_) => None,
+        None => Some(Fullscreen::Borderless(None)),
+    };
+    window.set_fullscreen(fullscreen);
+}
+
+/// "resnet50 - SnapViewer Renderer": the snapshot's name first, so viewers of different
+/// snapshots are told apart in the taskbar. `name` is e.g. the pickle's file name, the
+/// directory's name without it.
+pub fn window_title(dir: &str, name: Option<&str>) -> String {
+    let dir = Path::new(dir);
+    let name = name
+        .map(str::to_string)
+        .or_else(|| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| dir.display().to_string());
+    format!("{} - SnapViewer Renderer", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::window::window_title;
+
+    #[test]
+    fn test_window_title() {
+        assert_eq!(
+            window_title("/tmp/snapshots/resnet50/", None),
+            "resnet50 - SnapViewer Renderer"
+        );
+        assert_eq!(
+            window_title("/cache/3f2a", Some("step_100.pickle")),
+            "step_100.pickle - SnapViewer Renderer"
+        );
+    }
+}
@@ -1,13 +1,45 @@
 use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
 use three_d::{Camera, Viewport, vec3};
 
+/// Camera position independent of the window resolution: `center` is in fractions of the
+/// world size, so it can be restored in a renderer started with another `--res`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CameraState {
+    pub center: (f32, f32),
+    pub zoom: f32,
+}
+
+/// Camera move in progress, see `WindowTransform::animate_to_rect`
+#[derive(Debug, Clone, Copy)]
+struct Animation {
+    /// (center, zoom) at the start and the end
+    from: (Vector2<f32>, f32),
+    to: (Vector2<f32>, f32),
+    started: Instant,
+    duration: Duration,
+}
+
+impl Animation {
+    /// (center, zoom) at `t` (0~1) of the way, easing out. Zoom moves on a log scale so the
+    /// speed looks even.
+    fn at(&self, t: f32) -> (Vector2<f32>, f32) {
+        let eased = 1.0 - (1.0 - t.clamp(0.0, 1.0)).powi(3);
+        let center = self.from.0 + (self.to.0 - self.from.0) * eased;
+        let (z0, z1) = (self.from.1.ln(), self.to.1.ln());
+        (center, (z0 + (z1 - z0) * eased).exp())
+    }
+}
+
 #[derive(Debug)]
 pub struct WindowTransform {
     pub center: Vector2<f32>,
     pub zoom: f32,
 
-    resolution: (u32, u32),
     resolution_ratio: f64,
+    /// Current window size in logical pixels, follows resizes
+    screen_size: (u32, u32),
 
     min_zoom: f32,
     max_zoom: f32,
@@ -16,6 +48,8 @@ pub struct WindowTransform {
 
     zoom_step: f32,
     translate_step_multiplier: f32,
+
+    animation: Option<Animation>,
 }
 
 impl WindowTransform {
@@ -25,14 +59,15 @@ impl WindowTransform {
         Self {
             center: Vector2::new((resolution.0 / 2) as f32, (resolution.1 / 2) as f32),
             zoom: 1.0,
-            resolution,
             resolution_ratio,
+            screen_size: resolution,
             min_zoom: 0.75,
             max_zoom: 36.0, // TODO: update to max_timesteps / 100
             translate_max: Vector2::new(resolution.0 as f32, resolution.1 as f32),
             translate_min: Vector2::zeros(),
             zoom_step: 0.16, // everytime * (1.0 + zoom_step)
             translate_step_multiplier: 24.0,
+            animation: None,
         }
     }
 
@@ -47,6 +82,28 @@ impl WindowTransform {
         self.max_zoom = high;
     }
 
+    /// Physical pixels per logical pixel
+    pub fn set_resolution_ratio(&mut self, resolution_ratio: f64) {
+        self.resolution_ratio = resolution_ratio;
+    }
+
+    pub fn resolution_ratio(&self) -> f64 {
+        self.resolution_ratio
+    }
+
+    /// Track the window size; call every frame so resizes take effect
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        let ratio = self.resolution_ratio;
+        self.screen_size = (
+            (viewport.width as f64 / ratio) as u32,
+            (viewport.height as f64 / ratio) as u32,
+        );
+    }
+
+    pub fn screen_size(&self) -> (u32, u32) {
+        self.screen_size
+    }
+
     pub fn scale(&self) -> f32 {
         self.zoom.recip()
     }
@@ -56,11 +113,19 @@ impl WindowTransform {
     }
 
     pub fn ytop_world(&self) -> f32 {
-        self.center.y + (self.resolution.1 / 2) as f32 * self.scale()
+        self.center.y + (self.screen_size.1 / 2) as f32 * self.scale()
     }
 
     pub fn ybot_world(&self) -> f32 {
-        self.center.y - (self.resolution.1 / 2) as f32 * self.scale()
+        self.center.y - (self.screen_size.1 / 2) as f32 * self.scale()
+    }
+
+    pub fn xleft_world(&self) -> f32 {
+        self.center.x - (self.screen_size.0 / 2) as f32 * self.scale()
+    }
+
+    pub fn xright_world(&self) -> f32 {
+        self.center.x + (self.screen_size.0 / 2) as f32 * self.scale()
     }
 
     pub fn camera(&self, viewport: Viewport) -> Camera {
@@ -80,8 +145,8 @@ impl WindowTransform {
 
     pub fn screen2world(&self, cursor_pos_px: (f32, f32)) -> Vector2<f32> {
         let screen_center_px = Vector2::new(
-            (self.resolution.0 / 2) as f32,
-            (self.resolution.1 / 2) as f32,
+            (self.screen_size.0 / 2) as f32,
+            (self.screen_size.1 / 2) as f32,
         );
         let center2cursor_px: Vector2<f32> =
             Vector2::new(cursor_pos_px.0, cursor_pos_px.1) - screen_center_px;
@@ -144,6 +209,98 @@ impl WindowTransform {
         self.enforce_boundaries();
     }
 
+    /// Zoom and pan so that world x coords `x0..x1` fill the window width
+    pub fn show_x_range(&mut self, x0: f32, x1: f32) {
+        let width = (x1 - x0).max(f32::EPSILON);
+        self.zoom = (self.screen_size.0 as f32 / width).clamp(self.min_zoom, self.max_zoom);
+        self.center.x = (x0 + x1) / 2.0;
+        self.enforce_boundaries();
+    }
+
+    /// Center on the world rect `(x0, y0)..(x1, y1)` and zoom so its larger side spans `fill`
+    /// (0~1) of the window
+    pub fn show_rect(&mut self, (x0, y0): (f32, f32), (x1, y1): (f32, f32), fill: f32) {
+        let width = (x1 - x0).max(f32::EPSILON);
+        let height = (y1 - y0).max(f32::EPSILON);
+        let zoom =
+            fill * (self.screen_size.0 as f32 / width).min(self.screen_size.1 as f32 / height);
+        self.zoom = zoom.clamp(self.min_zoom, self.max_zoom);
+        self.center = Vector2::new((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+        self.enforce_boundaries();
+    }
+
+    /// Like `show_rect`, but moving there over `duration`; call `step_animation` every frame
+    pub fn animate_to_rect(
+        &mut self,
+        p0: (f32, f32),
+        p1: (f32, f32),
+        fill: f32,
+        duration: Duration,
+    ) {
+        let from = (self.center, self.zoom);
+        self.show_rect(p0, p1, fill);
+        let to = (self.center, self.zoom);
+        (self.center, self.zoom) = from;
+        self.animation = Some(Animation {
+            from,
+            to,
+            started: Instant::now(),
+            duration,
+        });
+    }
+
+    /// Moves the camera along the running animation, if any
+    ///
+    /// ## Returns
+    /// Whether the camera moved, i.e. the frame has to be drawn.
+    pub fn step_animation(&mut self) -> bool {
+        let Some(animation) = self.animation else {
+            return false;
+        };
+        let t = animation.started.elapsed().as_secs_f32() / animation.duration.as_secs_f32();
+        (self.center, self.zoom) = animation.at(t);
+        if t >= 1.0 {
+            self.animation = None;
+        }
+        true
+    }
+
+    /// Stop a running animation where it is, e.g. when the user takes over
+    pub fn cancel_animation(&mut self) {
+        self.animation = None;
+    }
+
+    pub fn camera_state(&self) -> CameraState {
+        CameraState {
+            center: (
+                self.center.x / self.translate_max.x,
+                self.center.y / self.translate_max.y,
+            ),
+            zoom: self.zoom,
+        }
+    }
+
+    pub fn set_camera_state(&mut self, state: &CameraState) {
+        self.center = Vector2::new(
+            state.center.0 * self.translate_max.x,
+            state.center.1 * self.translate_max.y,
+        );
+        self.zoom = state.zoom.clamp(self.min_zoom, self.max_zoom);
+        self.enforce_boundaries();
+    }
+
+    /// Pan by `dx_px` along the time axis, positive moves the view later in time
+    pub fn translate_x_by(&mut self, dx_px: f32) {
+        self.center.x += dx_px * self.scale();
+        self.enforce_boundaries();
+    }
+
+    /// Pan by `dy_px` along the memory axis, positive moves the view up
+    pub fn translate_y_by(&mut self, dy_px: f32) {
+        self.center.y += dy_px * self.scale();
+        self.enforce_boundaries();
+    }
+
     pub fn translate(&mut self, dir: TranslateDir) {
         match dir {
             TranslateDir::Left => self.center.x -= self.translate_step(),
@@ -155,3 +312,31 @@ impl WindowTransform {
         self.enforce_boundaries();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::window_transform::Animation;
+    use nalgebra::Vector2;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_animation_path() {
+        let animation = Animation {
+            from: (Vector2::new(0.0, 0.0), 1.0),
+            to: (Vector2::new(100.0, 50.0), 16.0),
+            started: Instant::now(),
+            duration: Duration::from_millis(300),
+        };
+        assert_eq!(animation.at(0.0), animation.from);
+        let (center, zoom) = animation.at(1.0);
+        assert_eq!(center, Vector2::new(100.0, 50.0));
+        assert!((zoom - 16.0).abs() < 1e-4);
+        // past the end stays at the end
+        assert_eq!(animation.at(2.0), animation.at(1.0));
+
+        // eased out: more than half way at half the time, zoom geometric
+        let (center, zoom) = animation.at(0.5);
+        assert!(center.x > 50.0);
+        assert!(zoom > 4.0 && zoom < 16.0);
+    }
+}